@@ -12,22 +12,45 @@ fn main() -> anyhow::Result<()> {
         "コマンド:\n",
         "  check <path>   シナリオの静的検査（ファイルまたはディレクトリ）\n",
         "      --format human|json|sarif  出力形式（既定: human）\n",
-        "      --no-assets                background / bgm の実在チェックを省略\n",
+        "      --no-assets                background / bgm / se の実在・中身のチェックを省略\n",
+        "      --canvas <幅>x<高さ>       background の画像サイズを画面サイズと照合（例: 1280x720）\n",
         "  trace <file>   シナリオを 1 経路ぶん自動実行して表示（SPEC 5.1）\n",
         "      --choices 1,3,1            選択肢で選ぶ番号（ブロック内の並び順、1 始まり）\n",
+        "      --random 2,1               ランダム分岐で抽選せずに選ぶ番号（1 始まり。尽きたら --seed で抽選）\n",
+        "      --seed <整数>              ランダム分岐の抽選のシード（既定: 0。同じシードなら同じ経路）\n",
         "      --format human|json        出力形式（既定: human）。--json は --format json と同じ\n",
-        "      --no-assets                background / bgm の実在チェックを省略\n",
-        "  routes <file>  全分岐を探索し到達可能性を報告（SPEC 5.2）\n",
+        "      --no-assets                background / bgm / se の実在チェックを省略\n",
+        "  routes <file>  全分岐を探索し到達可能性と経路ごとの長さを報告（SPEC 5.2）\n",
         "      --format human|json        出力形式（既定: human）\n",
-        "      --no-assets                background / bgm の実在チェックを省略\n",
+        "      --no-assets                background / bgm / se の実在チェックを省略\n",
+        "      --cpm <文字数>             読了目安の計算に使う 1 分あたりの文字数（既定: 500）\n",
+        "      --entry <file>             開始シーンを足す（複数可。DLC など本編からリンクされない入口）\n",
         "  fmt   <file>   よくある書き方を推測して v1 記法へ整形する（SPEC 7章）\n",
         "      --write                    整形結果をファイルに書き戻す（既定は表示のみ）\n",
         "      --format human|json        出力形式（既定: human）\n",
-        "  compile <file> --target web --output <path>\n",
+        "  stats <path>   分量（文字数・セリフ数・選択肢・エンディング・読了目安）をシーンごとと合計で集計する\n",
+        "      --format human|json        出力形式（既定: human）\n",
+        "      --cpm <文字数>             読了目安の計算に使う 1 分あたりの文字数（既定: 500）\n",
+        "  import <file.twee>             Twine の Twee 3 を v1 シーンに変換する（SPEC 7.2）\n",
+        "      --output <path>            書き出し先の .md（id はこのファイル名から補う。省略時は表示のみ）\n",
+        "  export script <file>           校正用の台本をプロジェクト全体から作る（開始シーンから深さ優先）\n",
+        "      --format text|html         出力形式（既定: text）\n",
+        "      --output <path>            書き出し先（省略時は表示のみ）\n",
+        "      --entry <file>             開始シーンを足す（複数可）\n",
+        "      --no-assets                background / bgm / se の実在チェックを省略\n",
+        "  compile <file> --target <target> --output <path>\n",
         "                 Markdown シナリオから StoryBundle JSON を生成する（#128）\n",
-        "      --target web               出力形式（現在は web のみ対応）\n",
-        "      --output <path>            StoryBundle JSON の書き出し先\n",
-        "      --no-assets                background / bgm の実在チェックを省略"
+        "      --target <target>          出力形式（web: StoryBundle JSON / html: 単体で開けるプレビュー /\n",
+        "                                 ink: Ink / twee: Twine の Twee 3 / yarn: Yarn Spinner /\n",
+        "                                 tyrano: TyranoScript、--output はディレクトリ）\n",
+        "      --output <path>            書き出し先\n",
+        "      --all-characters           登場しないキャラクターも characters に含める（--target web / html のみ）\n",
+        "      --assets-out <dir>         参照しているアセットを <dir> にコピーする\n",
+        "      --split                    --output をディレクトリとして、目次とシーンごとの JSON に分けて書き出す\n",
+        "      --source-map <path>        ステップとシナリオ上の位置の対応表を <path> に書き出す\n",
+        "      --strip-source             bundle から source（シナリオ上の位置）を省く（--target web / html のみ）\n",
+        "      --entry <file>             開始シーンを足す（複数可）\n",
+        "      --no-assets                background / bgm / se の実在チェックを省略"
     );
 
     if args.len() < 3 {
@@ -96,24 +119,165 @@ fn main() -> anyhow::Result<()> {
                 std::process::exit(1);
             }
         }
+        "stats" => {
+            let (json, options) = parse_stats_args(&args[3..], usage);
+            let result = scenario::stats_path(Path::new(file_path), &options);
+            let rendered = if json {
+                scenario::render_stats_json(&result)
+            } else {
+                scenario::render_stats_human(&result)
+            };
+            println!("{}", rendered);
+            if result.has_errors() {
+                std::process::exit(1);
+            }
+        }
+        "import" => {
+            let output = parse_import_args(&args[3..], usage);
+            let source = Path::new(file_path);
+            let target = match &output {
+                Some(output) => Path::new(output).to_path_buf(),
+                None => source.with_extension("md"),
+            };
+            let result = scenario::import_twee_path(source, &target);
+            let check = scenario::CheckResult {
+                files: vec![result.path.clone()],
+                diagnostics: result.diagnostics.clone(),
+            };
+            match &output {
+                // 表示のみ: stdout は Markdown だけにして、診断は stderr に出す
+                None => {
+                    if !check.diagnostics.is_empty() {
+                        eprintln!("{}", scenario::render_human(&check));
+                    }
+                    if !result.has_errors() {
+                        print!("{}", result.markdown);
+                    }
+                }
+                Some(output) => {
+                    if !check.diagnostics.is_empty() {
+                        println!("{}", scenario::render_human(&check));
+                    }
+                    if !result.has_errors() {
+                        fs::write(output, &result.markdown)
+                            .map_err(|e| anyhow::anyhow!("書き出せません '{}': {}", output, e))?;
+                        println!(
+                            "v1 シーンを書き出しました: {} ({} passages)",
+                            output, result.passages
+                        );
+                    }
+                }
+            }
+            if result.has_errors() {
+                std::process::exit(1);
+            }
+        }
+        "export" => {
+            if file_path != "script" || args.len() < 4 {
+                eprintln!(
+                    "export には script と開始シーンを指定してください\n{}",
+                    usage
+                );
+                std::process::exit(1);
+            }
+            let (html, output, options) = parse_export_args(&args[4..], usage);
+            let result = scenario::script_path(Path::new(&args[3]), &options);
+            // 表示のみのときは stdout を台本だけにして、診断は stderr に出す
+            if !result.check.diagnostics.is_empty() {
+                if output.is_some() {
+                    println!("{}", scenario::render_human(&result.check));
+                } else {
+                    eprintln!("{}", scenario::render_human(&result.check));
+                }
+            }
+            if result.has_errors() {
+                std::process::exit(1);
+            }
+            let document = result.document.expect("エラーがなければ document がある");
+            let rendered = if html {
+                scenario::render_script_html(&document)
+            } else {
+                scenario::render_script_text(&document)
+            };
+            match output {
+                Some(output) => {
+                    fs::write(&output, rendered)
+                        .map_err(|e| anyhow::anyhow!("書き出せません '{}': {}", output, e))?;
+                    println!(
+                        "台本を書き出しました: {} ({} sections, 付録 {})",
+                        output,
+                        document.units.len(),
+                        document.appendix.len()
+                    );
+                }
+                None => print!("{}", rendered),
+            }
+        }
         "compile" => {
-            let (target, output, options) = parse_compile_args(&args[3..], usage);
-            if target.is_empty() {
-                eprintln!("compile には --target web の指定が必要です\n{}", usage);
+            let args = parse_compile_args(&args[3..], usage);
+            if args.target.is_empty() {
+                eprintln!(
+                    "compile には --target web|html|ink|twee|yarn|tyrano の指定が必要です\n{}",
+                    usage
+                );
                 std::process::exit(1);
             }
-            if target != "web" {
+            let export = scenario::ExportTarget::from_name(&args.target);
+            if export.is_none() && !matches!(args.target.as_str(), "web" | "html") {
                 eprintln!(
-                    "--target には web のみ指定できます（指定: {}）\n{}",
-                    target, usage
+                    "--target には web / html / ink / twee / yarn / tyrano のいずれかを指定できます（指定: {}）\n{}",
+                    args.target, usage
                 );
                 std::process::exit(1);
             }
-            let Some(output) = output else {
+            if args.split && args.target != "web" {
+                eprintln!("--split は --target web でのみ使えます\n{}", usage);
+                std::process::exit(1);
+            }
+            let Some(output) = args.output else {
                 eprintln!("compile には --output <path> の指定が必要です\n{}", usage);
                 std::process::exit(1);
             };
-            let result = scenario::compile_path(Path::new(file_path), &options);
+            if let Some(target) = export {
+                if args.source_map.is_some()
+                    || args.assets_out.is_some()
+                    || args.options.all_characters
+                    || args.options.strip_source
+                {
+                    eprintln!(
+                        "--source-map / --assets-out / --all-characters / --strip-source は --target web / html でのみ使えます\n{}",
+                        usage
+                    );
+                    std::process::exit(1);
+                }
+                let options = scenario::ExportOptions {
+                    check_assets: args.options.check_assets,
+                    entries: args.options.entries,
+                };
+                let result = scenario::export_path(Path::new(file_path), target, &options);
+                if !result.check.diagnostics.is_empty() {
+                    println!("{}", scenario::render_human(&result.check));
+                }
+                if result.has_errors() {
+                    std::process::exit(1);
+                }
+                if target.writes_files() {
+                    write_export_files(&result.files, Path::new(&output))?;
+                    println!(
+                        "{} 形式で書き出しました: {} ({} files)",
+                        target.name(),
+                        output,
+                        result.files.len()
+                    );
+                    return Ok(());
+                }
+                let text = result.output.expect("エラーがなければ output がある");
+                fs::write(&output, text)
+                    .map_err(|e| anyhow::anyhow!("書き出せません '{}': {}", output, e))?;
+                println!("{} 形式で書き出しました: {}", target.name(), output);
+                return Ok(());
+            }
+            let result = scenario::compile_path(Path::new(file_path), &args.options);
             if !result.check.diagnostics.is_empty() {
                 println!("{}", scenario::render_human(&result.check));
             }
@@ -121,16 +285,63 @@ fn main() -> anyhow::Result<()> {
                 std::process::exit(1);
             }
             let bundle = result.bundle.expect("エラーがなければ bundle がある");
-            let json = serde_json::to_string_pretty(&bundle)
-                .map_err(|e| anyhow::anyhow!("StoryBundle をシリアライズできません: {}", e))?;
-            fs::write(&output, &json)
-                .map_err(|e| anyhow::anyhow!("StoryBundle を書き出せません '{}': {}", output, e))?;
-            println!(
-                "StoryBundle を書き出しました: {} ({} scenes, {} assets)",
-                output,
-                bundle.scenes.len(),
-                bundle.assets.len()
-            );
+            if let (Some(path), Some(map)) = (&args.source_map, &result.source_map) {
+                let json = serde_json::to_string_pretty(map)
+                    .map_err(|e| anyhow::anyhow!("ソースマップをシリアライズできません: {}", e))?;
+                fs::write(path, &json).map_err(|e| {
+                    anyhow::anyhow!("ソースマップを書き出せません '{}': {}", path, e)
+                })?;
+                println!("ソースマップを書き出しました: {}", path);
+            }
+            // html はコピー先のアセットを参照するので、先にコピーしておく
+            if let Some(dir) = &args.assets_out {
+                let copied = scenario::copy_assets(&bundle, Path::new(dir))
+                    .map_err(|e| anyhow::anyhow!("アセットをコピーできません '{}': {}", dir, e))?;
+                println!("アセットをコピーしました: {} ({} files)", dir, copied);
+            }
+            if args.target == "html" {
+                let output = Path::new(&output);
+                let html_dir = match output.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => dir,
+                    _ => Path::new("."),
+                };
+                let html = scenario::render_html(
+                    &bundle,
+                    html_dir,
+                    args.assets_out.as_deref().map(Path::new),
+                );
+                fs::write(output, html).map_err(|e| {
+                    anyhow::anyhow!("HTML を書き出せません '{}': {}", output.display(), e)
+                })?;
+                println!(
+                    "プレビュー用 HTML を書き出しました: {} ({} scenes, {} assets)",
+                    output.display(),
+                    bundle.scenes.len(),
+                    bundle.assets.len()
+                );
+            } else if args.split {
+                scenario::write_split_bundle(&bundle, Path::new(&output)).map_err(|e| {
+                    anyhow::anyhow!("StoryBundle を書き出せません '{}': {}", output, e)
+                })?;
+                println!(
+                    "StoryBundle をシーンごとに書き出しました: {} (index.json + {} scenes, {} assets)",
+                    output,
+                    bundle.scenes.len(),
+                    bundle.assets.len()
+                );
+            } else {
+                let json = serde_json::to_string_pretty(&bundle)
+                    .map_err(|e| anyhow::anyhow!("StoryBundle をシリアライズできません: {}", e))?;
+                fs::write(&output, &json).map_err(|e| {
+                    anyhow::anyhow!("StoryBundle を書き出せません '{}': {}", output, e)
+                })?;
+                println!(
+                    "StoryBundle を書き出しました: {} ({} scenes, {} assets)",
+                    output,
+                    bundle.scenes.len(),
+                    bundle.assets.len()
+                );
+            }
         }
         _ => {
             eprintln!("不明なコマンド: {}\n{}", command, usage);
@@ -168,6 +379,21 @@ fn parse_check_args(rest: &[String], usage: &str) -> (CheckFormat, scenario::Che
                 };
             }
             "--no-assets" => options.check_assets = false,
+            "--canvas" => {
+                let size = iter.next().and_then(|s| {
+                    let (w, h) = s.split_once(['x', 'X'])?;
+                    Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
+                });
+                match size {
+                    Some((w, h)) if w > 0 && h > 0 => options.canvas = Some((w, h)),
+                    _ => {
+                        eprintln!(
+                            "--canvas には画面サイズを <幅>x<高さ> で指定してください（例: --canvas 1280x720）"
+                        );
+                        std::process::exit(1);
+                    }
+                }
+            }
             other => {
                 eprintln!("不明なオプション: {}\n{}", other, usage);
                 std::process::exit(1);
@@ -205,6 +431,34 @@ fn parse_trace_args(rest: &[String], usage: &str) -> (bool, scenario::TraceOptio
                     })
                     .collect();
             }
+            "--random" => {
+                let Some(list) = iter.next() else {
+                    eprintln!(
+                        "--random には項目の番号をカンマ区切りで指定してください（例: --random 2,1）"
+                    );
+                    std::process::exit(1);
+                };
+                options.random = list
+                    .split(',')
+                    .map(|s| match s.trim().parse::<usize>() {
+                        Ok(n) if n >= 1 => n,
+                        _ => {
+                            eprintln!(
+                                "--random の「{}」が項目の番号として読めません。ランダム分岐内の並び順を 1 始まりの数字で指定してください（例: --random 2,1）",
+                                s.trim()
+                            );
+                            std::process::exit(1);
+                        }
+                    })
+                    .collect();
+            }
+            "--seed" => match iter.next().map(|s| s.parse::<u64>()) {
+                Some(Ok(seed)) => options.seed = seed,
+                _ => {
+                    eprintln!("--seed には 0 以上の整数を指定してください（例: --seed 42）");
+                    std::process::exit(1);
+                }
+            },
             "--json" => json = true,
             "--format" => match iter.next().map(String::as_str) {
                 Some("human") => json = false,
@@ -247,6 +501,25 @@ fn parse_routes_args(rest: &[String], usage: &str) -> (bool, scenario::RoutesOpt
                 }
             },
             "--no-assets" => options.check_assets = false,
+            "--cpm" => {
+                options.chars_per_minute = match iter.next().and_then(|v| v.parse().ok()) {
+                    Some(cpm) if cpm > 0 => cpm,
+                    _ => {
+                        eprintln!("--cpm には 1 以上の整数を指定してください\n{}", usage);
+                        std::process::exit(1);
+                    }
+                };
+            }
+            "--entry" => match iter.next() {
+                Some(v) => options.entries.push(v.into()),
+                None => {
+                    eprintln!(
+                        "--entry には開始シーンのファイルを指定してください\n{}",
+                        usage
+                    );
+                    std::process::exit(1);
+                }
+            },
             other => {
                 eprintln!("不明なオプション: {}\n{}", other, usage);
                 std::process::exit(1);
@@ -256,19 +529,33 @@ fn parse_routes_args(rest: &[String], usage: &str) -> (bool, scenario::RoutesOpt
     (json, options)
 }
 
-/// compile の引数を解釈する。返り値は (--target の値, --output の値, オプション)
-fn parse_compile_args(
-    rest: &[String],
-    usage: &str,
-) -> (String, Option<String>, scenario::CompileOptions) {
-    let mut target = String::new();
-    let mut output = None;
-    let mut options = scenario::CompileOptions::default();
+/// compile の引数
+struct CompileArgs {
+    target: String,
+    output: Option<String>,
+    /// `--assets-out` のコピー先
+    assets_out: Option<String>,
+    /// `--split`: output をディレクトリとして index.json とシーンごとの JSON を書く
+    split: bool,
+    /// `--source-map` の書き出し先
+    source_map: Option<String>,
+    options: scenario::CompileOptions,
+}
+
+fn parse_compile_args(rest: &[String], usage: &str) -> CompileArgs {
+    let mut args = CompileArgs {
+        target: String::new(),
+        output: None,
+        assets_out: None,
+        split: false,
+        source_map: None,
+        options: scenario::CompileOptions::default(),
+    };
     let mut iter = rest.iter();
     while let Some(arg) = iter.next() {
         match arg.as_str() {
             "--target" => {
-                target = match iter.next() {
+                args.target = match iter.next() {
                     Some(v) => v.clone(),
                     None => {
                         eprintln!("--target には値を指定してください\n{}", usage);
@@ -276,6 +563,97 @@ fn parse_compile_args(
                     }
                 };
             }
+            "--output" => {
+                args.output = match iter.next() {
+                    Some(v) => Some(v.clone()),
+                    None => {
+                        eprintln!("--output には出力先パスを指定してください\n{}", usage);
+                        std::process::exit(1);
+                    }
+                };
+            }
+            "--assets-out" => {
+                args.assets_out = match iter.next() {
+                    Some(v) => Some(v.clone()),
+                    None => {
+                        eprintln!(
+                            "--assets-out にはコピー先ディレクトリを指定してください\n{}",
+                            usage
+                        );
+                        std::process::exit(1);
+                    }
+                };
+            }
+            "--split" => args.split = true,
+            "--source-map" => {
+                args.source_map = match iter.next() {
+                    Some(v) => Some(v.clone()),
+                    None => {
+                        eprintln!(
+                            "--source-map には書き出し先パスを指定してください\n{}",
+                            usage
+                        );
+                        std::process::exit(1);
+                    }
+                };
+            }
+            "--strip-source" => args.options.strip_source = true,
+            "--no-assets" => args.options.check_assets = false,
+            "--all-characters" => args.options.all_characters = true,
+            "--entry" => match iter.next() {
+                Some(v) => args.options.entries.push(v.into()),
+                None => {
+                    eprintln!(
+                        "--entry には開始シーンのファイルを指定してください\n{}",
+                        usage
+                    );
+                    std::process::exit(1);
+                }
+            },
+            other => {
+                eprintln!("不明なオプション: {}\n{}", other, usage);
+                std::process::exit(1);
+            }
+        }
+    }
+    args
+}
+
+/// 複数ファイルの書き出し（`--target tyrano`）。`dir` はなければ作る
+fn write_export_files(files: &[scenario::ExportFile], dir: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)
+        .map_err(|e| anyhow::anyhow!("ディレクトリを作れません '{}': {}", dir.display(), e))?;
+    for file in files {
+        let path = dir.join(&file.name);
+        fs::write(&path, &file.text)
+            .map_err(|e| anyhow::anyhow!("書き出せません '{}': {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// export script の引数を解釈する。返り値は (HTML か, --output, 検査オプション)
+fn parse_export_args(
+    rest: &[String],
+    usage: &str,
+) -> (bool, Option<String>, scenario::ExportOptions) {
+    let mut html = false;
+    let mut output = None;
+    let mut options = scenario::ExportOptions::default();
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => match iter.next().map(String::as_str) {
+                Some("text") => html = false,
+                Some("html") => html = true,
+                other => {
+                    eprintln!(
+                        "--format には text / html を指定できます（指定: {}）\n{}",
+                        other.unwrap_or(""),
+                        usage
+                    );
+                    std::process::exit(1);
+                }
+            },
             "--output" => {
                 output = match iter.next() {
                     Some(v) => Some(v.clone()),
@@ -286,13 +664,84 @@ fn parse_compile_args(
                 };
             }
             "--no-assets" => options.check_assets = false,
+            "--entry" => match iter.next() {
+                Some(v) => options.entries.push(v.into()),
+                None => {
+                    eprintln!(
+                        "--entry には開始シーンのファイルを指定してください\n{}",
+                        usage
+                    );
+                    std::process::exit(1);
+                }
+            },
+            other => {
+                eprintln!("不明なオプション: {}\n{}", other, usage);
+                std::process::exit(1);
+            }
+        }
+    }
+    (html, output, options)
+}
+
+/// stats の引数を解釈する。返り値は (JSON 出力か, 集計オプション)
+fn parse_stats_args(rest: &[String], usage: &str) -> (bool, scenario::StatsOptions) {
+    let mut json = false;
+    let mut options = scenario::StatsOptions::default();
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => match iter.next().map(String::as_str) {
+                Some("human") => json = false,
+                Some("json") => json = true,
+                other => {
+                    eprintln!(
+                        "--format には human / json を指定できます（指定: {}）\n{}",
+                        other.unwrap_or(""),
+                        usage
+                    );
+                    std::process::exit(1);
+                }
+            },
+            "--cpm" => {
+                options.chars_per_minute = match iter.next().and_then(|v| v.parse().ok()) {
+                    Some(cpm) if cpm > 0 => cpm,
+                    _ => {
+                        eprintln!("--cpm には 1 以上の整数を指定してください\n{}", usage);
+                        std::process::exit(1);
+                    }
+                };
+            }
+            other => {
+                eprintln!("不明なオプション: {}\n{}", other, usage);
+                std::process::exit(1);
+            }
+        }
+    }
+    (json, options)
+}
+
+/// import の `--output`
+fn parse_import_args(rest: &[String], usage: &str) -> Option<String> {
+    let mut output = None;
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--output" => {
+                output = match iter.next() {
+                    Some(v) => Some(v.clone()),
+                    None => {
+                        eprintln!("--output には出力先パスを指定してください\n{}", usage);
+                        std::process::exit(1);
+                    }
+                };
+            }
             other => {
                 eprintln!("不明なオプション: {}\n{}", other, usage);
                 std::process::exit(1);
             }
         }
     }
-    (target, output, options)
+    output
 }
 
 /// fmt の引数を解釈する。返り値は (JSON 出力か, --write が指定されたか)
@@ -0,0 +1,341 @@
+//! achievements.yaml の探索・読み込みと、条件の参照解決（SPEC 2.4）
+//!
+//! 実績（アチーブメント）の宣言ファイル。endings.yaml と同じく、シナリオ
+//! ファイルと同じディレクトリ、またはその祖先ディレクトリの最も近いものが
+//! 使われ、置かなくてもよい。
+//!
+//! 実績の条件は 2 種類:
+//! - `when`: 1 回のプレイ（1 経路）ですべて満たす条件。エンディング・通った
+//!   セクション・選んだ選択肢を参照する
+//! - `endings`: 何回かのプレイで、列挙したエンディングをすべて見る
+//!
+//! 参照先の実在は check が（`broken-achievement-ref`）、達成できるかどうかは
+//! routes の全分岐探索が（`unattainable-achievement`）確かめる。
+
+use super::Block;
+use super::characters::key_line;
+use super::project::{LoadedScene, find_nearest};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// 読み込んだ実績定義
+#[derive(Debug, Clone, PartialEq)]
+pub struct Achievements {
+    /// 定義ファイルのパス
+    pub path: PathBuf,
+    /// 実績 id → 定義
+    pub entries: BTreeMap<String, Achievement>,
+    /// 実績 id → achievements.yaml 内の行番号（見つけられた id だけ）
+    pub lines: BTreeMap<String, usize>,
+}
+
+/// 1 つの実績
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Achievement {
+    /// 表示名（`title:`）
+    pub title: Option<String>,
+    /// 説明（`description:`）
+    pub description: Option<String>,
+    /// 1 回のプレイですべて満たす条件（`when:`）
+    pub when: Vec<Condition>,
+    /// 何回かのプレイですべて見るエンディング（`endings:`）
+    pub endings: Vec<String>,
+}
+
+/// `when:` の 1 条件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Condition {
+    /// `ending: id` — このエンディングで終わる
+    Ending { id: String },
+    /// `section: シーンID#アンカー` — このセクションを通る
+    Section { scene: String, anchor: String },
+    /// `choice: ラベル`（`scene: シーンID` で絞れる）— この選択肢を選ぶ
+    Choice {
+        label: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        scene: Option<String>,
+    },
+}
+
+impl Achievements {
+    pub fn contains(&self, id: &str) -> bool {
+        self.entries.contains_key(id)
+    }
+}
+
+/// シナリオファイルから最も近い achievements.yaml
+pub fn find_achievements_file(scene_path: &Path) -> Option<PathBuf> {
+    find_nearest(scene_path, "achievements.yaml")
+}
+
+/// achievements.yaml を読み込む。形式エラーはメッセージ文字列で返す
+pub fn load_achievements(path: &Path) -> Result<Achievements, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("{} を読み込めません: {}", path.display(), e))?;
+    parse_achievements(&source, path)
+}
+
+fn parse_achievements(source: &str, path: &Path) -> Result<Achievements, String> {
+    let value: serde_yaml::Value = serde_yaml::from_str(source)
+        .map_err(|e| format!("{} の YAML が解析できません: {}", path.display(), e))?;
+    let mapping = value
+        .get("achievements")
+        .and_then(|v| v.as_mapping())
+        .ok_or_else(|| {
+            format!(
+                "{} に `achievements:` マッピングがありません。`achievements:` の下に実績 id を並べてください",
+                path.display()
+            )
+        })?;
+    let mut entries = BTreeMap::new();
+    let mut lines = BTreeMap::new();
+    for (key, val) in mapping {
+        let id = key
+            .as_str()
+            .ok_or_else(|| format!("{} の実績 id が文字列ではありません", path.display()))?;
+        if let Some(line) = key_line(source, 1, id) {
+            lines.insert(id.to_string(), line);
+        }
+        let achievement =
+            parse_achievement(val).map_err(|e| format!("{} の「{id}」の {e}", path.display()))?;
+        entries.insert(id.to_string(), achievement);
+    }
+    Ok(Achievements {
+        path: path.to_path_buf(),
+        entries,
+        lines,
+    })
+}
+
+fn parse_achievement(value: &serde_yaml::Value) -> Result<Achievement, String> {
+    if !value.is_mapping() {
+        return Err(
+            "宣言は `title` / `description` と、`when` か `endings` のマッピングで書いてください"
+                .to_string(),
+        );
+    }
+    let text = |key: &str| -> Result<Option<String>, String> {
+        match value.get(key) {
+            None => Ok(None),
+            Some(v) => v
+                .as_str()
+                .map(|s| Some(s.to_string()))
+                .ok_or_else(|| format!("{key} は文字列で書いてください")),
+        }
+    };
+    let when = match value.get("when") {
+        None => Vec::new(),
+        Some(v) => v
+            .as_sequence()
+            .ok_or_else(|| {
+                "when は条件のリストで書いてください（例: `when: [{ ending: true_end }]`）"
+                    .to_string()
+            })?
+            .iter()
+            .map(parse_condition)
+            .collect::<Result<_, _>>()?,
+    };
+    let endings = match value.get("endings") {
+        None => Vec::new(),
+        Some(v) => {
+            let invalid = || {
+                "endings は ending id のリストで書いてください（例: `endings: [a_end, b_end]`）"
+                    .to_string()
+            };
+            v.as_sequence()
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|e| e.as_str().map(String::from).ok_or_else(invalid))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    match (when.is_empty(), endings.is_empty()) {
+        (true, true) => {
+            return Err(
+                "条件がありません。`when` か `endings` のどちらかを書いてください".to_string(),
+            );
+        }
+        (false, false) => {
+            return Err(
+                "`when`（1 回のプレイ）と `endings`（何回かのプレイ）は同じ実績に書けません。別の実績に分けてください"
+                    .to_string(),
+            );
+        }
+        _ => {}
+    }
+    Ok(Achievement {
+        title: text("title")?,
+        description: text("description")?,
+        when,
+        endings,
+    })
+}
+
+fn parse_condition(value: &serde_yaml::Value) -> Result<Condition, String> {
+    let text = |key: &str| value.get(key).and_then(|v| v.as_str());
+    if let Some(id) = text("ending") {
+        return Ok(Condition::Ending { id: id.to_string() });
+    }
+    if let Some(target) = text("section") {
+        return match target.split_once('#') {
+            Some((scene, anchor)) if !scene.is_empty() && !anchor.is_empty() => {
+                Ok(Condition::Section {
+                    scene: scene.to_string(),
+                    anchor: anchor.to_string(),
+                })
+            }
+            _ => Err(format!(
+                "section「{target}」は `シーンID#アンカー` の形で書いてください（例: `spring_002#after-school`）"
+            )),
+        };
+    }
+    if let Some(label) = text("choice") {
+        return Ok(Condition::Choice {
+            label: label.to_string(),
+            scene: text("scene").map(String::from),
+        });
+    }
+    Err("when の条件は `ending:` / `section:` / `choice:` のいずれかで書いてください".to_string())
+}
+
+// ------------------------------------------------------------ 参照の解決
+
+/// 読み込んだシーンに解決した条件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Resolved {
+    Ending(String),
+    /// (シーン, セクション) の scenes・sections 内インデックス
+    Section(usize, usize),
+    /// 選択肢のラベルと、絞り込むシーン
+    Choice(String, Option<usize>),
+}
+
+/// 条件の参照先をシーンから引く。見つからなければ理由を返す
+pub(super) fn resolve(scenes: &[LoadedScene], condition: &Condition) -> Result<Resolved, String> {
+    match condition {
+        Condition::Ending { id } => {
+            let found = scenes
+                .iter()
+                .any(|s| blocks(s).any(|b| matches!(b, Block::Ending { id: e, .. } if e == id)));
+            if found {
+                Ok(Resolved::Ending(id.clone()))
+            } else {
+                Err(format!(
+                    "エンディング「{id}」はどのシーンにもありません（`<!-- ending: {id} -->`）"
+                ))
+            }
+        }
+        Condition::Section { scene, anchor } => {
+            let i = scene_index(scenes, scene)?;
+            scenes[i]
+                .parsed
+                .scene
+                .sections
+                .iter()
+                .position(|s| s.anchor == *anchor)
+                .map(|j| Resolved::Section(i, j))
+                .ok_or_else(|| format!("シーン「{scene}」にセクション「#{anchor}」がありません"))
+        }
+        Condition::Choice { label, scene } => {
+            let scene_idx = scene
+                .as_deref()
+                .map(|s| scene_index(scenes, s))
+                .transpose()?;
+            let found = scenes
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| scene_idx.is_none_or(|s| s == *i))
+                .any(|(_, s)| {
+                    blocks(s).any(|b| match b {
+                        Block::Choices { items, .. } => items.iter().any(|it| it.label == *label),
+                        _ => false,
+                    })
+                });
+            if found {
+                Ok(Resolved::Choice(label.clone(), scene_idx))
+            } else {
+                Err(match scene {
+                    Some(s) => format!("シーン「{s}」に選択肢「{label}」がありません"),
+                    None => format!("どのシーンにも選択肢「{label}」がありません"),
+                })
+            }
+        }
+    }
+}
+
+/// `endings:` の ending id がシーンにあるか
+pub(super) fn resolve_ending(scenes: &[LoadedScene], id: &str) -> Result<(), String> {
+    resolve(scenes, &Condition::Ending { id: id.to_string() }).map(|_| ())
+}
+
+fn scene_index(scenes: &[LoadedScene], id: &str) -> Result<usize, String> {
+    scenes
+        .iter()
+        .position(|s| s.parsed.scene.id.as_deref() == Some(id))
+        .ok_or_else(|| format!("シーン「{id}」が見つかりません（front matter の id で指定します）"))
+}
+
+fn blocks(scene: &LoadedScene) -> impl Iterator<Item = &Block> {
+    let scene = &scene.parsed.scene;
+    scene
+        .lead
+        .iter()
+        .chain(scene.sections.iter().flat_map(|s| &s.blocks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn achievements_yamlを解析できる() {
+        let src = "achievements:\n  run:\n    title: 全力疾走\n    when:\n      - choice: 一緒に走る\n        scene: spring_001\n      - section: spring_002#after-school\n      - ending: calm_route\n  all:\n    endings: [a_end, b_end]\n";
+        let achievements = parse_achievements(src, Path::new("achievements.yaml")).unwrap();
+        let run = &achievements.entries["run"];
+        assert_eq!(run.title.as_deref(), Some("全力疾走"));
+        assert_eq!(
+            run.when,
+            vec![
+                Condition::Choice {
+                    label: "一緒に走る".to_string(),
+                    scene: Some("spring_001".to_string()),
+                },
+                Condition::Section {
+                    scene: "spring_002".to_string(),
+                    anchor: "after-school".to_string(),
+                },
+                Condition::Ending {
+                    id: "calm_route".to_string(),
+                },
+            ]
+        );
+        assert_eq!(achievements.entries["all"].endings, vec!["a_end", "b_end"]);
+        assert_eq!(achievements.lines["all"], 9);
+    }
+
+    #[test]
+    fn 条件のない実績と両方を書いた実績はエラーになる() {
+        let err = parse_achievements("achievements:\n  a:\n    title: x\n", Path::new("a.yaml"))
+            .unwrap_err();
+        assert!(err.contains("条件がありません"), "{err}");
+        let src = "achievements:\n  a:\n    when: [{ ending: x }]\n    endings: [y]\n";
+        let err = parse_achievements(src, Path::new("a.yaml")).unwrap_err();
+        assert!(err.contains("同じ実績に書けません"), "{err}");
+    }
+
+    #[test]
+    fn シーンidのないsectionはエラーになる() {
+        let src = "achievements:\n  a:\n    when:\n      - section: \"#after-school\"\n";
+        let err = parse_achievements(src, Path::new("a.yaml")).unwrap_err();
+        assert!(err.contains("シーンID#アンカー"), "{err}");
+    }
+
+    #[test]
+    fn 未知の条件はエラーになる() {
+        let src = "achievements:\n  a:\n    when:\n      - flag: x\n";
+        let err = parse_achievements(src, Path::new("a.yaml")).unwrap_err();
+        assert!(err.contains("ending:"), "{err}");
+    }
+}
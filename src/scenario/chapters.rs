@@ -0,0 +1,292 @@
+//! chapters.yaml の探索・読み込みと、章のシーンの解決（SPEC 2.5）
+//!
+//! 章立てと読む順の宣言ファイル。プロジェクトはふつう「開始ファイルと
+//! リンクで辿れる閉包」か「ディレクトリ配下のすべての .md」で、ファイル名
+//! 以上の順序を持たない。chapters.yaml は章の並び・各章のシーンファイル・
+//! 章の入口を宣言し、チャプターセレクト画面のように章の入口から遊び始める
+//! 作品を扱えるようにする。endings.yaml と同じく、シナリオファイルと同じ
+//! ディレクトリ、またはその祖先ディレクトリの最も近いものが使われ、置かなくてもよい。
+//!
+//! - 章の並びは `chapters:` に書いた順
+//! - シーンのパスは chapters.yaml からの相対パス
+//! - `entry` を省くと、章の最初のシーンが入口になる
+//!
+//! 章のシーンはリンクで辿れなくてもプロジェクトに読み込まれる
+//! （[`load_project`](super::project::load_project)）。ファイルの実在と
+//! 章の入口からの到達は check が（`broken-chapter-ref` /
+//! `unreachable-chapter-scene`）、章ごとの経路は routes が確かめる。
+
+use super::characters::key_line;
+use super::include::is_fragment;
+use super::project::{LoadedScene, find_nearest, resolve_sibling};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// 読み込んだ章立て
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapters {
+    /// 定義ファイルのパス
+    pub path: PathBuf,
+    /// 章（書いた順）
+    pub chapters: Vec<Chapter>,
+    /// 章 id → chapters.yaml 内の行番号（見つけられた id だけ）
+    pub lines: BTreeMap<String, usize>,
+}
+
+/// 1 つの章
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub id: String,
+    /// 章の表示名（`title:`）
+    pub title: Option<String>,
+    /// 章の入口のシーン（`entry:`、省略時は `scenes` の先頭）
+    pub entry: String,
+    /// 章のシーンファイル（`scenes:`、chapters.yaml からの相対パス）
+    pub scenes: Vec<String>,
+    /// `scenes` と同じ並びの、chapters.yaml 内の行番号（見つけられたものだけ）
+    pub scene_lines: Vec<Option<usize>>,
+}
+
+impl Chapters {
+    /// 章のシーンのパス（chapters.yaml からの相対パス）を解決する
+    pub fn resolve_path(&self, relative: &str) -> Option<PathBuf> {
+        resolve_sibling(&self.path, relative)
+    }
+}
+
+/// シナリオファイルから最も近い chapters.yaml
+pub fn find_chapters_file(scene_path: &Path) -> Option<PathBuf> {
+    find_nearest(scene_path, "chapters.yaml")
+}
+
+/// chapters.yaml を読み込む。形式エラーはメッセージ文字列で返す
+pub fn load_chapters(path: &Path) -> Result<Chapters, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("{} を読み込めません: {}", path.display(), e))?;
+    parse_chapters(&source, path)
+}
+
+fn parse_chapters(source: &str, path: &Path) -> Result<Chapters, String> {
+    let value: serde_yaml::Value = serde_yaml::from_str(source)
+        .map_err(|e| format!("{} の YAML が解析できません: {}", path.display(), e))?;
+    let mapping = value
+        .get("chapters")
+        .and_then(|v| v.as_mapping())
+        .ok_or_else(|| {
+            format!(
+                "{} に `chapters:` マッピングがありません。`chapters:` の下に章 id を読む順に並べてください",
+                path.display()
+            )
+        })?;
+    let mut chapters = Vec::new();
+    let mut lines = BTreeMap::new();
+    // 同じシーンを 2 つの章に入れると、章の境目が決まらない
+    let mut owner: BTreeMap<String, String> = BTreeMap::new();
+    for (key, val) in mapping {
+        let id = key
+            .as_str()
+            .ok_or_else(|| format!("{} の章 id が文字列ではありません", path.display()))?;
+        let line = key_line(source, 1, id);
+        if let Some(line) = line {
+            lines.insert(id.to_string(), line);
+        }
+        let chapter = parse_chapter(id, val, source, line.unwrap_or(1))
+            .map_err(|e| format!("{} の「{id}」の {e}", path.display()))?;
+        for scene in &chapter.scenes {
+            let normalized = normalize(scene);
+            if let Some(other) = owner.insert(normalized, id.to_string()) {
+                return Err(format!(
+                    "{} の「{scene}」が章「{other}」と「{id}」の両方に入っています。シーンはどれか 1 つの章に入れてください",
+                    path.display()
+                ));
+            }
+        }
+        chapters.push(chapter);
+    }
+    Ok(Chapters {
+        path: path.to_path_buf(),
+        chapters,
+        lines,
+    })
+}
+
+/// 1 章の `title` / `entry` / `scenes` を読む。`from` は章 id の行
+fn parse_chapter(
+    id: &str,
+    value: &serde_yaml::Value,
+    source: &str,
+    from: usize,
+) -> Result<Chapter, String> {
+    if !value.is_mapping() {
+        return Err("宣言は `title` / `entry` / `scenes` のマッピングで書いてください".to_string());
+    }
+    let text = |key: &str| -> Result<Option<String>, String> {
+        match value.get(key) {
+            None => Ok(None),
+            Some(v) => v
+                .as_str()
+                .map(|s| Some(s.to_string()))
+                .ok_or_else(|| format!("{key} は文字列で書いてください")),
+        }
+    };
+    let invalid = || {
+        "scenes はシーンファイルのリストで書いてください（例: `scenes: [scenario/ch1.md]`）"
+            .to_string()
+    };
+    let scenes: Vec<String> = value
+        .get("scenes")
+        .and_then(|v| v.as_sequence())
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|s| s.as_str().map(String::from).ok_or_else(invalid))
+        .collect::<Result<_, _>>()?;
+    if scenes.is_empty() {
+        return Err(
+            "scenes が空です。章に入れるシーンファイルを 1 つ以上書いてください".to_string(),
+        );
+    }
+    let entry = match text("entry")? {
+        Some(entry) => {
+            if !scenes.iter().any(|s| normalize(s) == normalize(&entry)) {
+                return Err(format!(
+                    "entry「{entry}」が scenes にありません。章の入口も scenes に並べてください"
+                ));
+            }
+            entry
+        }
+        None => scenes[0].clone(),
+    };
+    let scene_lines = scenes
+        .iter()
+        .map(|scene| item_line(source, from, scene))
+        .collect();
+    Ok(Chapter {
+        id: id.to_string(),
+        title: text("title")?,
+        entry,
+        scenes,
+        scene_lines,
+    })
+}
+
+/// 比較用に `./` と `\` 区切りの違いをならす
+fn normalize(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    path.trim_start_matches("./").to_string()
+}
+
+/// `from` 行以降で最初に `- value` か、フロー形式のリストで value が現れる行
+fn item_line(source: &str, from: usize, value: &str) -> Option<usize> {
+    source
+        .lines()
+        .enumerate()
+        .skip(from.saturating_sub(1))
+        .find(|(_, l)| {
+            let l = l.trim();
+            let item = l.strip_prefix('-').map(str::trim);
+            item.is_some_and(|i| i.trim_matches(['"', '\'']) == value)
+                || (l.starts_with("scenes:") && l.contains(value))
+        })
+        .map(|(i, _)| i + 1)
+}
+
+// ------------------------------------------------------------ シーンの解決
+
+/// 読み込んだシーンに解決した章
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct ResolvedChapter {
+    pub(super) id: String,
+    pub(super) title: Option<String>,
+    /// 入口のシーンの scenes 内インデックス
+    pub(super) entry: usize,
+    /// 章のシーンの scenes 内インデックス（書いた順）
+    pub(super) scenes: Vec<usize>,
+}
+
+/// chapters.yaml からの相対パスを、読み込んだシーンのインデックスに引く
+pub(super) fn scene_index(
+    chapters: &Chapters,
+    scenes: &[LoadedScene],
+    relative: &str,
+) -> Option<usize> {
+    let canon = chapters.resolve_path(relative)?.canonicalize().ok()?;
+    scenes.iter().position(|s| s.canon == canon)
+}
+
+/// 章のシーンとして読み込める `.md` か（フラグメントは章に入れられない）
+pub(super) fn is_chapter_scene(path: &Path) -> bool {
+    path.is_file() && path.extension().and_then(|e| e.to_str()) == Some("md") && !is_fragment(path)
+}
+
+/// プロジェクト（最初のシーン）に適用される chapters.yaml の章を解決する。
+/// ファイルがない・読み込めない場合は空。入口が解決できない章は飛ばす
+/// （壊れた参照は check が `broken-chapter-ref` で報告する）
+pub(super) fn project_chapters(scenes: &[LoadedScene]) -> Vec<ResolvedChapter> {
+    let Some(file) = scenes
+        .first()
+        .and_then(|s| find_chapters_file(&s.path))
+        .and_then(|path| load_chapters(&path).ok())
+    else {
+        return Vec::new();
+    };
+    file.chapters
+        .iter()
+        .filter_map(|chapter| {
+            let entry = scene_index(&file, scenes, &chapter.entry)?;
+            let mut seen = HashSet::new();
+            let members = chapter
+                .scenes
+                .iter()
+                .filter_map(|s| scene_index(&file, scenes, s))
+                .filter(|i| seen.insert(*i))
+                .collect();
+            Some(ResolvedChapter {
+                id: chapter.id.clone(),
+                title: chapter.title.clone(),
+                entry,
+                scenes: members,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chapters_yamlを解析できる() {
+        let src = "chapters:\n  spring:\n    title: 春\n    scenes:\n      - scenario/a.md\n      - scenario/b.md\n  summer:\n    entry: scenario/d.md\n    scenes: [scenario/c.md, scenario/d.md]\n";
+        let chapters = parse_chapters(src, Path::new("chapters.yaml")).unwrap();
+        let ids: Vec<&str> = chapters.chapters.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["spring", "summer"]);
+        let spring = &chapters.chapters[0];
+        assert_eq!(spring.title.as_deref(), Some("春"));
+        assert_eq!(spring.entry, "scenario/a.md");
+        assert_eq!(spring.scene_lines, vec![Some(5), Some(6)]);
+        assert_eq!(chapters.chapters[1].entry, "scenario/d.md");
+        assert_eq!(chapters.chapters[1].scene_lines, vec![Some(9), Some(9)]);
+        assert_eq!(chapters.lines["summer"], 7);
+    }
+
+    #[test]
+    fn scenesにないentryはエラーになる() {
+        let src = "chapters:\n  a:\n    entry: x.md\n    scenes: [y.md]\n";
+        let err = parse_chapters(src, Path::new("c.yaml")).unwrap_err();
+        assert!(err.contains("entry「x.md」"), "{err}");
+    }
+
+    #[test]
+    fn 同じシーンを2つの章に入れるとエラーになる() {
+        let src = "chapters:\n  a:\n    scenes: [x.md]\n  b:\n    scenes: [./x.md]\n";
+        let err = parse_chapters(src, Path::new("c.yaml")).unwrap_err();
+        assert!(err.contains("「a」と「b」"), "{err}");
+    }
+
+    #[test]
+    fn 空のscenesはエラーになる() {
+        let err =
+            parse_chapters("chapters:\n  a:\n    scenes: []\n", Path::new("c.yaml")).unwrap_err();
+        assert!(err.contains("scenes が空"), "{err}");
+    }
+}
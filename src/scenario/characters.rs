@@ -3,6 +3,7 @@
 //! キャラクター（話者）の事前宣言ファイル。シナリオファイルと同じ
 //! ディレクトリ、またはその祖先ディレクトリに置かれ、最も近いものが使われる。
 
+use super::project::find_nearest;
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
@@ -13,27 +14,56 @@ pub struct Characters {
     pub path: PathBuf,
     /// 話者名 → メタデータ（tsumugai は中身を解釈せず compile 先へ引き渡す）
     pub entries: BTreeMap<String, serde_yaml::Value>,
+    /// 話者名 → 表情名 → 立ち絵（`expressions:`、SPEC 2.1）。
+    /// 表情を宣言していない話者は含まない
+    pub expressions: BTreeMap<String, BTreeMap<String, Expression>>,
+    /// 別名 → 話者名（`aliases:`、SPEC 2.1）
+    pub aliases: BTreeMap<String, String>,
+    /// 話者名 → characters.yaml 内の行番号（見つけられた話者だけ）
+    pub lines: BTreeMap<String, usize>,
+}
+
+/// `expressions:` に宣言された 1 つの表情
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    /// 立ち絵画像。characters.yaml からの相対パス（書かれたまま）
+    pub path: String,
+    /// characters.yaml 内の行番号（見つけられなければ None）
+    pub line: Option<usize>,
 }
 
 impl Characters {
     pub fn contains(&self, speaker: &str) -> bool {
         self.entries.contains_key(speaker)
     }
+
+    /// 話者名または別名から、宣言済みの話者名を引く。どちらでもなければ None
+    pub fn canonical<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        if self.entries.contains_key(name) {
+            return Some(name);
+        }
+        self.aliases.get(name).map(String::as_str)
+    }
+
+    /// 話者の表示名（`display_name:`）。宣言がなければ話者名そのもの
+    pub fn display_name<'a>(&'a self, speaker: &'a str) -> &'a str {
+        self.entries
+            .get(speaker)
+            .and_then(|v| v.get("display_name"))
+            .and_then(|v| v.as_str())
+            .unwrap_or(speaker)
+    }
+
+    /// 話者の表情を引く。話者・表情のどちらかが未宣言なら None
+    pub fn expression(&self, speaker: &str, expression: &str) -> Option<&Expression> {
+        self.expressions.get(speaker)?.get(expression)
+    }
 }
 
 /// シナリオファイルの位置から characters.yaml を探す。
 /// 同階層 → 祖先ディレクトリの順で最も近いものを返す。
 pub fn find_characters_file(scene_path: &Path) -> Option<PathBuf> {
-    let start = scene_path.parent()?;
-    let mut dir = Some(start);
-    while let Some(d) = dir {
-        let candidate = d.join("characters.yaml");
-        if candidate.is_file() {
-            return Some(candidate);
-        }
-        dir = d.parent();
-    }
-    None
+    find_nearest(scene_path, "characters.yaml")
 }
 
 /// characters.yaml を読み込む。
@@ -59,18 +89,118 @@ fn parse_characters(source: &str, path: &Path) -> Result<Characters, String> {
             )
         })?;
     let mut entries = BTreeMap::new();
+    let mut expressions = BTreeMap::new();
+    let mut aliases: BTreeMap<String, String> = BTreeMap::new();
+    let mut lines = BTreeMap::new();
     for (key, val) in mapping {
         let name = key
             .as_str()
             .ok_or_else(|| format!("{} の話者名が文字列ではありません", path.display()))?;
+        if let Some(line) = key_line(source, 1, name) {
+            lines.insert(name.to_string(), line);
+        }
+        if val.get("display_name").is_some_and(|v| !v.is_string()) {
+            return Err(format!(
+                "{} の「{name}」の display_name は文字列で書いてください",
+                path.display()
+            ));
+        }
+        for alias in parse_aliases(val)
+            .map_err(|e| format!("{} の「{name}」の aliases: {e}", path.display()))?
+        {
+            if let Some(other) = aliases.insert(alias.clone(), name.to_string()) {
+                return Err(format!(
+                    "{} の別名「{alias}」が「{other}」と「{name}」の両方に宣言されています",
+                    path.display()
+                ));
+            }
+        }
+        if let Some(declared) = val.get("expressions") {
+            let parsed = parse_expressions(source, name, declared)
+                .map_err(|e| format!("{} の「{name}」の expressions: {e}", path.display()))?;
+            expressions.insert(name.to_string(), parsed);
+        }
         entries.insert(name.to_string(), val.clone());
     }
+    if let Some((alias, name)) = aliases
+        .iter()
+        .find(|(alias, _)| entries.contains_key(*alias))
+    {
+        return Err(format!(
+            "{} の「{name}」の別名「{alias}」は、同じ名前の話者が宣言されています。別名は話者名と重ならないようにしてください",
+            path.display()
+        ));
+    }
     Ok(Characters {
         path: path.to_path_buf(),
         entries,
+        expressions,
+        aliases,
+        lines,
     })
 }
 
+/// `aliases:` を文字列のリストとして読む（なければ空）
+fn parse_aliases(entry: &serde_yaml::Value) -> Result<Vec<String>, String> {
+    let Some(value) = entry.get("aliases") else {
+        return Ok(Vec::new());
+    };
+    let invalid = || "別名の文字列のリストで書いてください（例: `aliases: [葵]`）".to_string();
+    value
+        .as_sequence()
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|v| v.as_str().map(String::from).ok_or_else(invalid))
+        .collect()
+}
+
+/// `expressions:` を「表情名: 画像パス」のマッピングとして読む
+fn parse_expressions(
+    source: &str,
+    speaker: &str,
+    value: &serde_yaml::Value,
+) -> Result<BTreeMap<String, Expression>, String> {
+    let mapping = value.as_mapping().ok_or_else(|| {
+        "「表情名: 画像パス」のマッピングで書いてください（例: `笑顔: sprites/smile.png`）"
+            .to_string()
+    })?;
+    let speaker_line = key_line(source, 1, speaker);
+    let mut out = BTreeMap::new();
+    for (key, val) in mapping {
+        let (Some(name), Some(path)) = (key.as_str(), val.as_str()) else {
+            return Err("表情名と画像パスはどちらも文字列で書いてください".to_string());
+        };
+        out.insert(
+            name.to_string(),
+            Expression {
+                path: path.to_string(),
+                line: speaker_line.and_then(|from| key_line(source, from, name)),
+            },
+        );
+    }
+    Ok(out)
+}
+
+/// `from` 行以降で最初に `key:` が現れる行（1-origin）。
+/// serde_yaml は位置情報を返さないため、Diagnostic の span 用に行を探す
+pub(super) fn key_line(source: &str, from: usize, key: &str) -> Option<usize> {
+    source
+        .lines()
+        .enumerate()
+        .skip(from.saturating_sub(1))
+        .find(|(_, l)| {
+            let l = l.trim_start();
+            [
+                format!("{key}:"),
+                format!("\"{key}\":"),
+                format!("'{key}':"),
+            ]
+            .iter()
+            .any(|k| l.starts_with(k.as_str()))
+        })
+        .map(|(i, _)| i + 1)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -84,6 +214,44 @@ mod tests {
         assert!(!chars.contains("先生"));
     }
 
+    #[test]
+    fn expressionsを表情名と画像パスとして読む() {
+        let src = "characters:\n  幼なじみ:\n    color: \"#ff9999\"\n    expressions:\n      笑顔: sprites/smile.png\n      怒り: sprites/angry.png\n  主人公: {}\n";
+        let chars = parse_characters(src, Path::new("characters.yaml")).unwrap();
+        let smile = chars.expression("幼なじみ", "笑顔").unwrap();
+        assert_eq!(smile.path, "sprites/smile.png");
+        assert_eq!(smile.line, Some(5));
+        assert_eq!(chars.expression("幼なじみ", "怒り").unwrap().line, Some(6));
+        assert!(chars.expression("幼なじみ", "泣き").is_none());
+        assert!(!chars.expressions.contains_key("主人公"));
+    }
+
+    #[test]
+    fn 別名から話者名と表示名を引ける() {
+        let src = "characters:\n  葵:\n    display_name: 春日葵\n    aliases: [あおい, アオイ]\n  主人公: {}\n";
+        let chars = parse_characters(src, Path::new("characters.yaml")).unwrap();
+        assert_eq!(chars.canonical("あおい"), Some("葵"));
+        assert_eq!(chars.canonical("葵"), Some("葵"));
+        assert_eq!(chars.canonical("先生"), None);
+        assert_eq!(chars.display_name("葵"), "春日葵");
+        assert_eq!(chars.display_name("主人公"), "主人公");
+        assert_eq!(chars.lines["主人公"], 5);
+    }
+
+    #[test]
+    fn 話者名と重なる別名はエラーになる() {
+        let src = "characters:\n  葵:\n    aliases: [主人公]\n  主人公: {}\n";
+        let err = parse_characters(src, Path::new("c.yaml")).unwrap_err();
+        assert!(err.contains("別名「主人公」"), "{err}");
+    }
+
+    #[test]
+    fn マッピングでないexpressionsはエラーになる() {
+        let src = "characters:\n  幼なじみ:\n    expressions: smile.png\n";
+        let err = parse_characters(src, Path::new("c.yaml")).unwrap_err();
+        assert!(err.contains("expressions"), "{err}");
+    }
+
     #[test]
     fn charactersキーがないとエラーになる() {
         let err = parse_characters("cast:\n  A: {}\n", Path::new("c.yaml")).unwrap_err();
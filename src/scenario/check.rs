@@ -5,23 +5,45 @@
 //!
 //! - `broken-link`: リンク先のファイル・アンカー（H2）の実在解決
 //! - `duplicate-scene-id`: シーン ID のファイル横断の一意性
-//! - `missing-asset`: front matter の background / bgm の実在
+//! - `missing-asset`: front matter と制御コメントの background / bgm / se、
+//!   characters.yaml の立ち絵（`expressions:`）の実在
+//! - `path-case-mismatch` / `non-portable-path`: 参照パスが大文字小文字を区別する
+//!   環境（Web の本番環境）でもそのまま解決できるか
+//! - `empty-asset` / `asset-kind-mismatch` / `asset-format-mismatch` /
+//!   `unsupported-asset-format` / `canvas-size-mismatch`: 実在するアセットの中身
+//!   （ヘッダを読んで判定する。[`media`](super::media)）
 //! - `undefined-character` / `missing-characters-file` / `invalid-characters-file`
+//! - `undefined-expression`: セリフ・`show` の表情が characters.yaml に宣言されているか
+//! - `undefined-ending` / `invalid-endings-file`: endings.yaml があるとき、
+//!   シナリオの ending id が宣言されているか
+//! - `broken-achievement-ref` / `invalid-achievements-file`: achievements.yaml の
+//!   条件が参照するエンディング・セクション・選択肢の実在
+//! - `broken-chapter-ref` / `invalid-chapters-file`: chapters.yaml の章のシーンの実在
+//! - `unreachable-chapter-scene`: 章のシーンが章の入口からリンクで辿れるか
 //! - `implicit-fallthrough` / `unreachable-section`: 実行フロー
+//! - `stray-return` / `recursive-call`: 呼び出しと戻り（SPEC 4.9）。routes の
+//!   `return-without-call` / `unbounded-recursion` を、経路を辿らずに静的に見る
 //! - `too-many-choices`: 1 つの選択肢ブロックの項目数が多すぎないか（#151）
+//! - `unused-character` / `unused-ending` / `unused-asset`: 宣言・配置されているのに
+//!   使われていないキャラクター・エンディングとアセット（ディレクトリを渡したときだけ）
 //!
 //! SPEC 6.1「Diagnostic は学習教材である」に従い、最初のエラーで止まらず
 //! 検出できたすべての Diagnostic を返す。[`check_path`] は入出力エラーでも
 //! 失敗せず、`io-error` の Diagnostic として報告する（JSON / SARIF 出力の
 //! 形式を崩さないため）。
 
+use super::achievements::{self, Achievements, find_achievements_file, load_achievements};
+use super::chapters::{self, Chapters, find_chapters_file, is_chapter_scene, load_chapters};
 use super::characters::{Characters, find_characters_file, load_characters};
 use super::diagnostic::{Diagnostic, Severity, Span};
+use super::endings::{Endings, find_endings_file, load_endings};
+use super::media::{self, MediaKind};
 use super::project::{
-    LoadedScene, collect_md_files, file_level, load_project, resolve_sibling, scene_links,
+    LoadedScene, actual_spelling, common_root, file_level, load_project, resolve_sibling,
+    scene_assets, scene_characters, scene_links, seed_files,
 };
 use super::{Block, LinkTarget, slugify};
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 /// 選択肢ブロックの項目数がこれを超えたら `too-many-choices` で警告する既定値
@@ -35,10 +57,13 @@ const DEFAULT_MAX_CHOICE_ITEMS: usize = 6;
 /// check の動作オプション
 #[derive(Debug, Clone)]
 pub struct CheckOptions {
-    /// background / bgm の実在チェック（`--no-assets` で false）
+    /// background / bgm / se の実在チェック（`--no-assets` で false）
     pub check_assets: bool,
     /// 選択肢ブロックの項目数がこれを超えたら `too-many-choices` で警告する
     pub max_choice_items: usize,
+    /// 想定する画面サイズ（幅, 高さ）。指定すると background の画像サイズが
+    /// これと違うときに `canvas-size-mismatch` で警告する（`--canvas 1280x720`）
+    pub canvas: Option<(u32, u32)>,
 }
 
 impl Default for CheckOptions {
@@ -46,6 +71,7 @@ impl Default for CheckOptions {
         Self {
             check_assets: true,
             max_choice_items: DEFAULT_MAX_CHOICE_ITEMS,
+            canvas: None,
         }
     }
 }
@@ -86,37 +112,17 @@ impl CheckResult {
 /// パスが存在しない・読めない場合も panic や Err にせず、`io-error` の
 /// Diagnostic を持つ [`CheckResult`] を返す。
 pub fn check_path(path: &Path, options: &CheckOptions) -> CheckResult {
-    let mut diagnostics = Vec::new();
-    let seeds: Vec<PathBuf> = if path.is_dir() {
-        let mut files = Vec::new();
-        collect_md_files(path, &mut files);
-        if files.is_empty() {
-            diagnostics.push(file_level(
-                "io-error",
-                Severity::Error,
-                path,
-                format!(
-                    "{} に .md ファイルがありません。シナリオファイルのあるディレクトリか、ファイルそのものを指定してください",
-                    path.display()
-                ),
-            ));
-        }
-        files
-    } else if path.is_file() {
-        vec![path.to_path_buf()]
-    } else {
-        diagnostics.push(file_level(
-            "io-error",
-            Severity::Error,
-            path,
-            format!(
-                "{} が見つかりません。パスを確認してください",
-                path.display()
-            ),
-        ));
-        Vec::new()
-    };
+    check_paths(&[path.to_path_buf()], options)
+}
 
+/// 複数のファイル（またはディレクトリ）を 1 つのプロジェクトとして検査する。
+/// routes / compile の `--entry` で開始シーンが複数あるときに使う
+pub(super) fn check_paths(paths: &[PathBuf], options: &CheckOptions) -> CheckResult {
+    let mut diagnostics = Vec::new();
+    let mut seeds = Vec::new();
+    for path in paths {
+        seeds.extend(seed_files(path, &mut diagnostics));
+    }
     let scenes = load_project(seeds, &mut diagnostics);
     for scene in &scenes {
         diagnostics.extend(scene.parsed.diagnostics.iter().cloned());
@@ -124,20 +130,51 @@ pub fn check_path(path: &Path, options: &CheckOptions) -> CheckResult {
     check_duplicate_scene_ids(&scenes, &mut diagnostics);
     check_links(&scenes, &mut diagnostics);
     if options.check_assets {
-        check_assets(&scenes, &mut diagnostics);
+        check_assets(&scenes, options.canvas, &mut diagnostics);
+    }
+    let characters = check_characters(&scenes, options.check_assets, &mut diagnostics);
+    let endings = check_endings(&scenes, &mut diagnostics);
+    check_achievements(&scenes, &mut diagnostics);
+    check_chapters(&scenes, &mut diagnostics);
+    // 使われていない宣言・ファイルは、プロジェクト全体を渡されたときだけ判定できる
+    if paths.iter().any(|p| p.is_dir()) {
+        check_unused_characters(&scenes, &characters, &mut diagnostics);
+        check_unused_endings(&scenes, &endings, &mut diagnostics);
+        if options.check_assets {
+            check_unused_assets(&scenes, &characters, &mut diagnostics);
+        }
     }
-    check_characters(&scenes, &mut diagnostics);
     for scene in &scenes {
         check_fallthrough(scene, &mut diagnostics);
         check_choice_branch_count(scene, options.max_choice_items, &mut diagnostics);
     }
     check_unreachable(&scenes, &mut diagnostics);
+    check_calls(&scenes, paths, &mut diagnostics);
+
+    // 取り込んだフラグメントの行への指摘は、フラグメントのファイルと行に直す。
+    // 複数のシーンが同じフラグメントを取り込むと同じ指摘が重なるので 1 つにする
+    let mut unique: Vec<Diagnostic> = Vec::new();
+    for mut diag in diagnostics {
+        relocate(&mut diag, &scenes);
+        if !unique.contains(&diag) {
+            unique.push(diag);
+        }
+    }
+    let mut diagnostics = unique;
+
+    // 検査したファイル: シーン → 取り込まれたフラグメントの順
+    let mut files: Vec<PathBuf> = scenes.iter().map(|s| s.path.clone()).collect();
+    for fragment in scenes.iter().flat_map(|s| &s.parsed.fragments) {
+        if !files.contains(&fragment.path) {
+            files.push(fragment.path.clone());
+        }
+    }
 
     // ファイル順 → 行順に並べ、入力との対応を追いやすくする
-    let order: HashMap<&Path, usize> = scenes
+    let order: HashMap<&Path, usize> = files
         .iter()
         .enumerate()
-        .map(|(i, s)| (s.path.as_path(), i))
+        .map(|(i, f)| (f.as_path(), i))
         .collect();
     diagnostics.sort_by_key(|d| {
         (
@@ -146,10 +183,28 @@ pub fn check_path(path: &Path, options: &CheckOptions) -> CheckResult {
         )
     });
 
-    CheckResult {
-        files: scenes.iter().map(|s| s.path.clone()).collect(),
-        diagnostics,
-    }
+    CheckResult { files, diagnostics }
+}
+
+/// シーンの行への Diagnostic が取り込んだフラグメントの行なら、フラグメントの
+/// ファイルと行に付け替える（SPEC 4.8）。別のファイルになる関連行は外す
+fn relocate(diag: &mut Diagnostic, scenes: &[LoadedScene]) {
+    let Some(scene) = scenes.iter().find(|s| s.path == diag.file) else {
+        return;
+    };
+    let Some(span) = diag.span.as_mut() else {
+        return;
+    };
+    let parsed = &scene.parsed;
+    let (file, line) = parsed.locate(span.line);
+    span.line = line;
+    let file = file.to_path_buf();
+    diag.related_spans.retain_mut(|related| {
+        let (related_file, line) = parsed.locate(related.line);
+        related.line = line;
+        related_file == file
+    });
+    diag.file = file;
 }
 
 // ------------------------------------------------------- duplicate-scene-id
@@ -193,14 +248,20 @@ fn check_links(scenes: &[LoadedScene], diagnostics: &mut Vec<Diagnostic>) {
     }
 }
 
+/// `label` はリンクのラベル。None は `<!-- call: -->`（SPEC 4.9）で、
+/// 修正案も directive の形で出す
 fn check_one_link(
     scene: &LoadedScene,
-    label: &str,
+    label: Option<&str>,
     target: &LinkTarget,
     line: usize,
     by_canon: &HashMap<&Path, &LoadedScene>,
     diagnostics: &mut Vec<Diagnostic>,
 ) {
+    let written = |href: &str| match label {
+        Some(label) => format!("[{label}]({href})"),
+        None => format!("<!-- call: {href} -->"),
+    };
     // 1) ファイルの解決
     let target_scene: &LoadedScene = match &target.file {
         None => scene,
@@ -216,6 +277,14 @@ fn check_one_link(
                 ));
                 return;
             };
+            let at = |rule_id, severity, message| match severity {
+                Severity::Error => Diagnostic::error(rule_id, &scene.path, line, message),
+                Severity::Warning => Diagnostic::warning(rule_id, &scene.path, line, message),
+            };
+            let rewrite = |fixed: &str| written(&with_anchor(fixed, target.anchor.as_deref()));
+            let what = format!("リンク先「{file}」");
+            let file = &check_portable_path(&scene.path, file, &what, at, rewrite, diagnostics);
+            let resolved = resolve_sibling(&scene.path, file).unwrap_or(resolved);
             if !resolved.is_file() {
                 let mut diag = Diagnostic::error(
                     "broken-link",
@@ -230,10 +299,8 @@ fn check_one_link(
                 if let Some(similar) = closest_md_in(&resolved) {
                     diag.message
                         .push_str(&format!("。よく似た「{similar}」があります"));
-                    diag.suggestion = Some(format!(
-                        "[{label}]({})",
-                        with_anchor(&similar, target.anchor.as_deref())
-                    ));
+                    diag.suggestion =
+                        Some(written(&with_anchor(&similar, target.anchor.as_deref())));
                 }
                 diagnostics.push(diag);
                 return;
@@ -292,7 +359,7 @@ fn check_one_link(
             "H1 タイトルはアンカーにならず、分岐先にできるのは H2 セクションだけです。ファイルの先頭から始めたい場合は、アンカーなしでファイル名だけをリンクしてください",
         );
         if let Some(file) = &target.file {
-            diag.suggestion = Some(format!("[{label}]({file})"));
+            diag.suggestion = Some(written(file));
         }
     } else if let Some(similar) = closest(anchor, &anchors) {
         let section = target_md
@@ -302,10 +369,11 @@ fn check_one_link(
             .expect("closest はセクション由来");
         let fixed = with_anchor_target(target.file.as_deref(), similar);
         diag.message.push_str(&format!(
-            "よく似た「## {}」があります。`[{label}]({fixed})` の間違いではありませんか？",
-            section.heading
+            "よく似た「## {}」があります。`{}` の間違いではありませんか？",
+            section.heading,
+            written(&fixed)
         ));
-        diag.suggestion = Some(format!("[{label}]({fixed})"));
+        diag.suggestion = Some(written(&fixed));
         if target.file.is_none() {
             diag.related_spans.push(Span {
                 line: section.line,
@@ -357,35 +425,48 @@ fn closest_md_in(missing: &Path) -> Option<String> {
 
 // ------------------------------------------------------------ missing-asset
 
-fn check_assets(scenes: &[LoadedScene], diagnostics: &mut Vec<Diagnostic>) {
+fn check_assets(
+    scenes: &[LoadedScene],
+    canvas: Option<(u32, u32)>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
     for scene in scenes {
-        let md = &scene.parsed.scene;
-        let spans = &scene.parsed.front_matter_spans;
-        let entries = [
-            ("background", &md.background, spans.background),
-            ("bgm", &md.bgm, spans.bgm),
-        ];
-        for (key, value, span) in entries {
-            let Some(value) = value else { continue };
-            let Some(resolved) = resolve_sibling(&scene.path, value) else {
-                diagnostics.push(Diagnostic::error(
+        for asset in scene_assets(&scene.parsed) {
+            let (key, value) = (asset.key, asset.path);
+            let at = |rule_id, severity, message| match severity {
+                Severity::Error => Diagnostic::error(rule_id, &scene.path, asset.line, message),
+                Severity::Warning => Diagnostic::warning(rule_id, &scene.path, asset.line, message),
+            };
+            if resolve_sibling(&scene.path, value).is_none() {
+                diagnostics.push(at(
                     "missing-asset",
-                    &scene.path,
-                    span.unwrap_or(1),
+                    Severity::Error,
                     format!(
                         "{key} の「{value}」は絶対パスです。{} からの相対パスで書いてください（SPEC 2章）",
                         scene.path.display()
                     ),
                 ));
                 continue;
+            }
+            let what = format!("{key} の「{value}」");
+            let rewrite = |fixed: &str| asset.rewrite(fixed);
+            let value = &check_portable_path(&scene.path, value, &what, at, rewrite, diagnostics);
+            let Some(resolved) = resolve_sibling(&scene.path, value) else {
+                continue;
             };
             if resolved.is_file() {
+                let content = AssetContent {
+                    what,
+                    value,
+                    expected: MediaKind::expected_for(key),
+                    canvas: canvas.filter(|_| key == "background"),
+                };
+                content.check(&resolved, at, rewrite, diagnostics);
                 continue;
             }
-            let mut diag = Diagnostic::error(
+            let mut diag = at(
                 "missing-asset",
-                &scene.path,
-                span.unwrap_or(1),
+                Severity::Error,
                 format!(
                     "{key} のファイル「{value}」が見つかりません（{} からの相対パス {} を探しました）",
                     scene.path.display(),
@@ -397,7 +478,7 @@ fn check_assets(scenes: &[LoadedScene], diagnostics: &mut Vec<Diagnostic>) {
                 let fixed = dir.join(&similar).to_string_lossy().replace('\\', "/");
                 diag.message
                     .push_str(&format!("。よく似た「{fixed}」があります"));
-                diag.suggestion = Some(format!("{key}: {fixed}"));
+                diag.suggestion = Some(asset.rewrite(&fixed));
             }
             diagnostics.push(diag);
         }
@@ -420,9 +501,203 @@ fn closest_file_in(missing: &Path) -> Option<String> {
     closest(name, &refs).map(String::from)
 }
 
+// ------------------------------------------ path-case-mismatch / non-portable-path
+
+/// 参照パスの移植性を検査し、以降の解決に使うパス（`/` 区切り・実際の綴り）を返す。
+///
+/// 作業環境（macOS・Windows）はファイル名の大文字小文字を区別しないことが多く、
+/// Web の本番環境（Linux）で初めて読み込めないことが分かる。`at` は参照位置に
+/// Diagnostic を作り、`rewrite` は修正後のパスから参照の書き換え例を作る
+fn check_portable_path(
+    scene_path: &Path,
+    value: &str,
+    what: &str,
+    at: impl Fn(&'static str, Severity, String) -> Diagnostic,
+    rewrite: impl Fn(&str) -> String,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let mut path = value.to_string();
+    if value.contains('\\') {
+        path = value.replace('\\', "/");
+        diagnostics.push(
+            at(
+                "non-portable-path",
+                Severity::Warning,
+                format!(
+                    "{what}の区切りに `\\` が使われています。Windows 以外（Web の本番環境を含む）では区切りとして扱われないため、`/` で書いてください"
+                ),
+            )
+            .with_suggestion(rewrite(&path)),
+        );
+    }
+    if let Some(actual) = actual_spelling(scene_path, &path)
+        && actual != path
+    {
+        diagnostics.push(
+            at(
+                "path-case-mismatch",
+                Severity::Error,
+                format!(
+                    "{what}は実際のファイル名「{actual}」と大文字・小文字が違います。macOS・Windows では読み込めても、大文字小文字を区別する環境（Linux の Web サーバーなど）では見つかりません"
+                ),
+            )
+            .with_suggestion(rewrite(&actual)),
+        );
+        path = actual;
+    }
+    let non_ascii: Vec<&str> = path
+        .split('/')
+        .filter(|segment| !segment.is_ascii())
+        .collect();
+    if !non_ascii.is_empty() {
+        diagnostics.push(at(
+            "non-portable-path",
+            Severity::Warning,
+            format!(
+                "{what}に ASCII 以外の文字を含む名前（「{}」）があります。Web では URL エンコードが必要になり、環境によって濁点の表し方（macOS の NFD など）が変わって名前が一致しなくなることがあるため、英数字と `-` `_` の名前にしてください",
+                non_ascii.join("」「")
+            ),
+        ));
+    }
+    path
+}
+
+// ------------------------------------------------------- アセットの中身
+
+/// 中身の判定に読む先頭のバイト数。JPEG は SOF までのセグメントが長いことがある
+const SNIFF_LIMIT: u64 = 256 * 1024;
+
+/// 実在するアセット 1 件の中身の検査（SPEC 6章 `empty-asset` ほか）
+struct AssetContent<'a> {
+    /// メッセージの主語（「bgm の「…」」など）
+    what: String,
+    /// 書かれたままのパス
+    value: &'a str,
+    /// 参照キーから期待される種類
+    expected: MediaKind,
+    /// background のときだけ、設定されたキャンバスサイズ
+    canvas: Option<(u32, u32)>,
+}
+
+impl AssetContent<'_> {
+    /// `at` は参照位置に Diagnostic を作り、`rewrite` は修正後のパスから
+    /// 参照の書き換え例（suggestion）を作る
+    fn check(
+        &self,
+        resolved: &Path,
+        at: impl Fn(&'static str, Severity, String) -> Diagnostic,
+        rewrite: impl Fn(&str) -> String,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        use std::io::Read;
+        let what = &self.what;
+        let mut bytes = Vec::new();
+        let read =
+            std::fs::File::open(resolved).and_then(|f| f.take(SNIFF_LIMIT).read_to_end(&mut bytes));
+        if read.is_err() {
+            // 実在は確認済み。読めない理由は環境依存なのでここでは報告しない
+            return;
+        }
+        if bytes.is_empty() {
+            diagnostics.push(at(
+                "empty-asset",
+                Severity::Error,
+                format!(
+                    "{what}は 0 バイトの空ファイルです（{}）。書き出しに失敗していないか確認し、中身のあるファイルに差し替えてください",
+                    resolved.display()
+                ),
+            ));
+            return;
+        }
+        let expected = self.expected.label();
+        let Some(format) = media::sniff(&bytes) else {
+            diagnostics.push(at(
+                "unsupported-asset-format",
+                Severity::Warning,
+                format!(
+                    "{what}の形式を判別できません。Web で再生できる{expected}形式（{}）で書き出し直してください",
+                    web_formats(self.expected)
+                ),
+            ));
+            return;
+        };
+        let (name, actual) = (format.name(), format.kind().label());
+        if format.kind() != self.expected {
+            diagnostics.push(at(
+                "asset-kind-mismatch",
+                Severity::Error,
+                format!(
+                    "{what}は{actual}ファイル（{name}）です。ここには{expected}ファイルを指定してください"
+                ),
+            ));
+            return;
+        }
+        let extension = Path::new(self.value)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        if !extension
+            .as_deref()
+            .is_some_and(|e| format.extensions().contains(&e))
+        {
+            let fixed = Path::new(self.value)
+                .with_extension(format.extensions()[0])
+                .to_string_lossy()
+                .replace('\\', "/");
+            diagnostics.push(
+                at(
+                    "asset-format-mismatch",
+                    Severity::Warning,
+                    format!(
+                        "{what}の中身は {name} です。拡張子で形式を判断する環境では読み込めないため、ファイル名を「{fixed}」に変えて参照も直してください"
+                    ),
+                )
+                .with_suggestion(rewrite(&fixed)),
+            );
+        }
+        if !format.web_supported() {
+            diagnostics.push(at(
+                "unsupported-asset-format",
+                Severity::Warning,
+                format!(
+                    "{what}は {name} 形式です。Web（compile --target web）では再生できないため、{} のいずれかで書き出し直してください",
+                    web_formats(self.expected)
+                ),
+            ));
+        }
+        if let (Some((width, height)), Some(size)) =
+            (self.canvas, media::image_size(format, &bytes))
+            && size != (width, height)
+        {
+            diagnostics.push(at(
+                "canvas-size-mismatch",
+                Severity::Warning,
+                format!(
+                    "{what}は {}x{} ですが、キャンバスは {width}x{height} です。表示時に拡大縮小されたり余白が出たりするため、画面サイズに合わせて書き出し直してください",
+                    size.0, size.1
+                ),
+            ));
+        }
+    }
+}
+
+/// メッセージ用の、Web で扱える形式の一覧
+fn web_formats(kind: MediaKind) -> &'static str {
+    match kind {
+        MediaKind::Image => "PNG / JPEG / WebP / GIF",
+        MediaKind::Audio => "Ogg / MP3 / WAV / M4A",
+    }
+}
+
 // ------------------------------------------- characters.yaml と話者の検査
 
-fn check_characters(scenes: &[LoadedScene], diagnostics: &mut Vec<Diagnostic>) {
+/// 話者・表情を検査し、読み込んだ characters.yaml（パス → 内容。読み込みに
+/// 失敗したものは None）を返す
+fn check_characters(
+    scenes: &[LoadedScene],
+    check_assets: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> HashMap<PathBuf, Option<Characters>> {
     // 同じ characters.yaml を何度も読まない。None は読み込み失敗（報告済み）
     let mut cache: HashMap<PathBuf, Option<Characters>> = HashMap::new();
     let mut missing_reported = false;
@@ -449,7 +724,12 @@ fn check_characters(scenes: &[LoadedScene], diagnostics: &mut Vec<Diagnostic>) {
         };
         if !cache.contains_key(&chars_path) {
             let loaded = match load_characters(&chars_path) {
-                Ok(c) => Some(c),
+                Ok(c) => {
+                    if check_assets {
+                        check_sprites(&c, diagnostics);
+                    }
+                    Some(c)
+                }
                 Err(e) => {
                     diagnostics.push(
                         file_level(
@@ -471,14 +751,632 @@ fn check_characters(scenes: &[LoadedScene], diagnostics: &mut Vec<Diagnostic>) {
             continue;
         };
         check_speakers(scene, chars, diagnostics);
+        check_expressions(scene, chars, diagnostics);
+    }
+    cache
+}
+
+/// characters.yaml に宣言されているのに、どのシーンにも登場しないキャラクター
+/// （`unused-character`）。別名で登場していれば読み込み時に話者名へ揃っている
+fn check_unused_characters(
+    scenes: &[LoadedScene],
+    characters: &HashMap<PathBuf, Option<Characters>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut used: HashMap<PathBuf, HashSet<&str>> = HashMap::new();
+    for scene in scenes {
+        if let Some(chars_path) = find_characters_file(&scene.path) {
+            used.entry(chars_path)
+                .or_default()
+                .extend(scene_characters(&scene.parsed.scene));
+        }
+    }
+    let mut files: Vec<&Characters> = characters.values().flatten().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    for chars in files {
+        let used = used.get(&chars.path);
+        for name in chars.entries.keys() {
+            if used.is_some_and(|u| u.contains(name.as_str())) {
+                continue;
+            }
+            let message = format!(
+                "「{name}」は {} に宣言されていますが、どのシーンにも登場しません。書き間違いで別名義になっていないか確認し、不要なら宣言を削除してください",
+                chars.path.display()
+            );
+            diagnostics.push(match chars.lines.get(name) {
+                Some(&line) => Diagnostic::warning("unused-character", &chars.path, line, message),
+                None => file_level("unused-character", Severity::Warning, &chars.path, message),
+            });
+        }
+    }
+}
+
+// ------------------------------------------------------ endings.yaml の検査
+
+/// シナリオの ending id が endings.yaml に宣言されているかを検査し、読み込んだ
+/// endings.yaml（パス → 内容。読み込みに失敗したものは None）を返す。
+/// endings.yaml は任意なので、見つからないシーンは検査しない（SPEC 2.3）
+fn check_endings(
+    scenes: &[LoadedScene],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> HashMap<PathBuf, Option<Endings>> {
+    let mut cache: HashMap<PathBuf, Option<Endings>> = HashMap::new();
+    for scene in scenes {
+        let Some(endings_path) = find_endings_file(&scene.path) else {
+            continue;
+        };
+        let endings = cache.entry(endings_path).or_insert_with_key(|path| {
+            load_endings(path)
+                .map_err(|e| {
+                    diagnostics.push(
+                        file_level(
+                            "invalid-endings-file",
+                            Severity::Error,
+                            path,
+                            format!(
+                                "{e}。修正するまで ending id の検査（undefined-ending）は行いません"
+                            ),
+                        )
+                        .with_suggestion("endings:\n  ending_id:\n    title: タイトル\n    type: good\n    order: 1".to_string()),
+                    );
+                })
+                .ok()
+        });
+        let Some(endings) = endings.as_ref() else {
+            continue;
+        };
+        let declared: Vec<&str> = endings.entries.keys().map(String::as_str).collect();
+        for (id, line) in scene_endings(scene) {
+            if endings.contains(id) {
+                continue;
+            }
+            let mut message = format!(
+                "エンディング「{id}」は {} に宣言されていません。",
+                endings.path.display()
+            );
+            let suggestion = match closest(id, &declared) {
+                Some(similar) => {
+                    message.push_str(&format!(
+                        "宣言済みの「{similar}」の書き間違いではありませんか？新しいエンディングなら endings.yaml に追加してください"
+                    ));
+                    format!("<!-- ending: {similar} -->")
+                }
+                None => {
+                    message.push_str(&format!(
+                        "ギャラリーに載せるため、endings.yaml にタイトル・種類・解放順を追加してください（宣言済み: {}）",
+                        if declared.is_empty() {
+                            "なし".to_string()
+                        } else {
+                            declared.join("、")
+                        }
+                    ));
+                    format!("{id}:\n  title: タイトル")
+                }
+            };
+            let diag = Diagnostic::warning("undefined-ending", &scene.path, line, message)
+                .with_suggestion(suggestion);
+            diagnostics.push(diag);
+        }
+    }
+    cache
+}
+
+/// シーン内の `<!-- ending: id -->`（id と行）
+fn scene_endings(scene: &LoadedScene) -> Vec<(&str, usize)> {
+    let scene = &scene.parsed.scene;
+    scene
+        .lead
+        .iter()
+        .chain(scene.sections.iter().flat_map(|s| &s.blocks))
+        .filter_map(|block| match block {
+            Block::Ending { id, line } => Some((id.as_str(), *line)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// endings.yaml に宣言されているのに、どのシーンからも到達を宣言されていない
+/// エンディング（`unused-ending`）
+fn check_unused_endings(
+    scenes: &[LoadedScene],
+    endings: &HashMap<PathBuf, Option<Endings>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut used: HashMap<PathBuf, HashSet<&str>> = HashMap::new();
+    for scene in scenes {
+        if let Some(endings_path) = find_endings_file(&scene.path) {
+            used.entry(endings_path)
+                .or_default()
+                .extend(scene_endings(scene).into_iter().map(|(id, _)| id));
+        }
+    }
+    let mut files: Vec<&Endings> = endings.values().flatten().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    for file in files {
+        let used = used.get(&file.path);
+        for id in file.entries.keys() {
+            if used.is_some_and(|u| u.contains(id.as_str())) {
+                continue;
+            }
+            let message = format!(
+                "エンディング「{id}」は {} に宣言されていますが、どのシーンにも `<!-- ending: {id} -->` がありません。書き間違いがないか確認し、不要なら宣言を削除してください",
+                file.path.display()
+            );
+            diagnostics.push(match file.lines.get(id) {
+                Some(&line) => Diagnostic::warning("unused-ending", &file.path, line, message),
+                None => file_level("unused-ending", Severity::Warning, &file.path, message),
+            });
+        }
+    }
+}
+
+// -------------------------------------------------- achievements.yaml の検査
+
+/// achievements.yaml の条件が参照するエンディング・セクション・選択肢が、
+/// 検査対象のシーンにあるか（`broken-achievement-ref`、SPEC 2.4）。
+/// 達成できるかどうかは routes が確かめる
+fn check_achievements(scenes: &[LoadedScene], diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    for scene in scenes {
+        let Some(path) = find_achievements_file(&scene.path) else {
+            continue;
+        };
+        if !seen.insert(path.clone()) {
+            continue;
+        }
+        let achievements = match load_achievements(&path) {
+            Ok(a) => a,
+            Err(e) => {
+                diagnostics.push(
+                    file_level(
+                        "invalid-achievements-file",
+                        Severity::Error,
+                        &path,
+                        format!("{e}。修正するまで実績の条件の検査は行いません"),
+                    )
+                    .with_suggestion(
+                        "achievements:\n  achievement_id:\n    title: タイトル\n    when:\n      - ending: ending_id"
+                            .to_string(),
+                    ),
+                );
+                continue;
+            }
+        };
+        check_achievement_refs(scenes, &achievements, diagnostics);
+    }
+}
+
+fn check_achievement_refs(
+    scenes: &[LoadedScene],
+    achievements: &Achievements,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for (id, achievement) in &achievements.entries {
+        let errors = achievement
+            .when
+            .iter()
+            .filter_map(|c| achievements::resolve(scenes, c).err())
+            .chain(
+                achievement
+                    .endings
+                    .iter()
+                    .filter_map(|e| achievements::resolve_ending(scenes, e).err()),
+            );
+        for error in errors {
+            let message = format!(
+                "実績「{id}」の条件が解決できません: {error}。書き間違いがないか確認してください"
+            );
+            diagnostics.push(match achievements.lines.get(id) {
+                Some(&line) => {
+                    Diagnostic::error("broken-achievement-ref", &achievements.path, line, message)
+                }
+                None => file_level(
+                    "broken-achievement-ref",
+                    Severity::Error,
+                    &achievements.path,
+                    message,
+                ),
+            });
+        }
+    }
+}
+
+// ------------------------------------------------------ chapters.yaml の検査
+
+/// chapters.yaml の章のシーンが実在するか（`broken-chapter-ref`）と、章の
+/// 入口からリンクで辿れるか（`unreachable-chapter-scene`）を確かめる（SPEC 2.5）
+fn check_chapters(scenes: &[LoadedScene], diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    for scene in scenes {
+        let Some(path) = find_chapters_file(&scene.path) else {
+            continue;
+        };
+        if !seen.insert(path.clone()) {
+            continue;
+        }
+        let file = match load_chapters(&path) {
+            Ok(c) => c,
+            Err(e) => {
+                diagnostics.push(
+                    file_level(
+                        "invalid-chapters-file",
+                        Severity::Error,
+                        &path,
+                        format!("{e}。修正するまで章の検査は行いません"),
+                    )
+                    .with_suggestion(
+                        "chapters:\n  chapter_id:\n    title: タイトル\n    scenes:\n      - scenario/scene.md"
+                            .to_string(),
+                    ),
+                );
+                continue;
+            }
+        };
+        check_chapter_scenes(scenes, &file, diagnostics);
+    }
+}
+
+fn check_chapter_scenes(
+    scenes: &[LoadedScene],
+    file: &Chapters,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let at = |line: Option<usize>, rule: &'static str, severity: Severity, message: String| match (
+        line, severity,
+    ) {
+        (Some(line), Severity::Error) => Diagnostic::error(rule, &file.path, line, message),
+        (Some(line), _) => Diagnostic::warning(rule, &file.path, line, message),
+        (None, _) => file_level(rule, severity, &file.path, message),
+    };
+    // シーン → 入っている章（chapters.yaml の並び）
+    let mut owner: HashMap<usize, usize> = HashMap::new();
+    let mut members: Vec<Vec<(usize, Option<usize>)>> = Vec::new();
+    for (c, chapter) in file.chapters.iter().enumerate() {
+        let mut resolved = Vec::new();
+        for (relative, line) in chapter.scenes.iter().zip(&chapter.scene_lines) {
+            let found = file
+                .resolve_path(relative)
+                .filter(|p| is_chapter_scene(p))
+                .and_then(|_| chapters::scene_index(file, scenes, relative));
+            match found {
+                Some(i) => {
+                    owner.insert(i, c);
+                    resolved.push((i, *line));
+                }
+                None => diagnostics.push(at(
+                    *line,
+                    "broken-chapter-ref",
+                    Severity::Error,
+                    format!(
+                        "章「{}」のシーン「{relative}」が見つかりません。chapters.yaml からの相対パスで、実在するシーンファイル（.md、名前が `_` で始まらないもの）を指定してください",
+                        chapter.id
+                    ),
+                )),
+            }
+        }
+        members.push(resolved);
+    }
+
+    for (c, chapter) in file.chapters.iter().enumerate() {
+        let Some(entry) = chapters::scene_index(file, scenes, &chapter.entry) else {
+            continue; // 入口の broken-chapter-ref は報告済み
+        };
+        // 章の入口からリンクを辿る。他の章のシーンに入ったらそこで止める
+        let mut reached: HashSet<usize> = HashSet::from([entry]);
+        let mut queue = vec![entry];
+        while let Some(i) = queue.pop() {
+            for next in linked_scenes(scenes, i) {
+                if owner.get(&next).is_none_or(|&o| o == c) && reached.insert(next) {
+                    queue.push(next);
+                }
+            }
+        }
+        for &(i, line) in &members[c] {
+            if reached.contains(&i) {
+                continue;
+            }
+            diagnostics.push(at(
+                line,
+                "unreachable-chapter-scene",
+                Severity::Warning,
+                format!(
+                    "{} は章「{}」に入っていますが、章の入口 {} からリンクで辿れません。章の中のシーンからリンクするか、別の章に移してください",
+                    scenes[i].path.display(),
+                    chapter.id,
+                    scenes[entry].path.display()
+                ),
+            ));
+        }
+    }
+}
+
+/// シーンのリンク（ジャンプ・選択肢・call）の飛び先シーン
+fn linked_scenes(scenes: &[LoadedScene], i: usize) -> Vec<usize> {
+    scene_links(&scenes[i].parsed.scene)
+        .into_iter()
+        .filter_map(|(_, target, _)| {
+            let Some(file) = &target.file else {
+                return Some(i);
+            };
+            let canon = resolve_sibling(&scenes[i].path, file)?
+                .canonicalize()
+                .ok()?;
+            scenes.iter().position(|s| s.canon == canon)
+        })
+        .collect()
+}
+
+// ------------------------------------------------------------- unused-asset
+
+/// アセットのディレクトリにあるのに、どこからも参照されていない画像・音声
+/// （`unused-asset`）。
+///
+/// アセットのディレクトリは、プロジェクトのルート（シーンと参照されている
+/// アセットの共通の祖先）直下で参照されているアセットを含むディレクトリ
+/// （`assets/` 等）で、その配下を再帰的に見る。参照が 1 つもない下位の
+/// ディレクトリ（`assets/se/` 等）も対象になる。シーン・yaml 等の素材以外の
+/// ファイルは数えない
+fn check_unused_assets(
+    scenes: &[LoadedScene],
+    characters: &HashMap<PathBuf, Option<Characters>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut referenced: Vec<PathBuf> = Vec::new();
+    for scene in scenes {
+        for asset in scene_assets(&scene.parsed) {
+            referenced.extend(resolve_sibling(&scene.path, asset.path));
+        }
+    }
+    for chars in characters.values().flatten() {
+        for expressions in chars.expressions.values() {
+            for expression in expressions.values() {
+                referenced.extend(resolve_sibling(&chars.path, &expression.path));
+            }
+        }
+    }
+    // 実在しない参照は missing-asset が報告済み
+    let referenced: Vec<PathBuf> = referenced
+        .iter()
+        .filter(|path| path.is_file())
+        .map(|path| normalize(path))
+        .collect();
+    let used: HashSet<PathBuf> = referenced
+        .iter()
+        .filter_map(|path| path.canonicalize().ok())
+        .collect();
+    let scene_dirs: Vec<PathBuf> = scenes
+        .iter()
+        .filter_map(|s| Some(normalize(s.path.parent()?)))
+        .collect();
+    let Some(root) = common_root(
+        scene_dirs
+            .iter()
+            .map(PathBuf::as_path)
+            .chain(referenced.iter().filter_map(|p| p.parent())),
+    ) else {
+        return;
+    };
+    let asset_dirs: BTreeSet<PathBuf> = referenced
+        .iter()
+        .filter_map(|path| {
+            let relative = path.strip_prefix(&root).ok()?;
+            let mut components = relative.components();
+            let top = components.next()?;
+            // ルート直下に置かれたアセットはルートそのものを見る
+            Some(match components.next() {
+                Some(_) => root.join(top),
+                None => root.clone(),
+            })
+        })
+        .collect();
+    let mut files = Vec::new();
+    for dir in &asset_dirs {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir.as_path()
+        };
+        collect_media_files(dir, &mut files);
+    }
+    let mut seen = HashSet::new();
+    for file in files {
+        let Ok(canon) = file.canonicalize() else {
+            continue;
+        };
+        if used.contains(&canon) || !seen.insert(canon) {
+            continue;
+        }
+        diagnostics.push(file_level(
+            "unused-asset",
+            Severity::Warning,
+            &file,
+            format!(
+                "{} はどのシーン・characters.yaml からも参照されていません。参照し忘れていないか確認し、不要なら配布前に削除してください",
+                file.display()
+            ),
+        ));
+    }
+}
+
+/// ディレクトリ配下の画像・音声を再帰的に集める（名前順、隠しファイルは除く）
+fn collect_media_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            collect_media_files(&path, out);
+        } else if media::is_media_path(&path) {
+            out.push(path);
+        }
+    }
+}
+
+/// `.` と `..` を字面の上で畳んだパス。`scenario/../assets/bg.png` のような
+/// 参照を、シーンのディレクトリと比べられる形（`assets/bg.png`）にする
+fn normalize(path: &Path) -> PathBuf {
+    use std::path::Component;
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(out.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// characters.yaml の `expressions:` に書かれた立ち絵の実在（`missing-asset`）
+fn check_sprites(chars: &Characters, diagnostics: &mut Vec<Diagnostic>) {
+    for (speaker, expressions) in &chars.expressions {
+        for (name, expression) in expressions {
+            let value = expression.path.as_str();
+            let at = |rule_id, severity, message| match expression.line {
+                Some(line) if severity == Severity::Error => {
+                    Diagnostic::error(rule_id, &chars.path, line, message)
+                }
+                Some(line) => Diagnostic::warning(rule_id, &chars.path, line, message),
+                None => file_level(rule_id, severity, &chars.path, message),
+            };
+            if resolve_sibling(&chars.path, value).is_none() {
+                diagnostics.push(at(
+                    "missing-asset",
+                    Severity::Error,
+                    format!(
+                        "「{speaker}」の表情「{name}」の立ち絵「{value}」は絶対パスです。{} からの相対パスで書いてください（SPEC 2.1）",
+                        chars.path.display()
+                    ),
+                ));
+                continue;
+            }
+            let what = format!("「{speaker}」の表情「{name}」の立ち絵「{value}」");
+            let rewrite = |fixed: &str| format!("{name}: {fixed}");
+            let value = &check_portable_path(&chars.path, value, &what, at, rewrite, diagnostics);
+            let Some(resolved) = resolve_sibling(&chars.path, value) else {
+                continue;
+            };
+            if resolved.is_file() {
+                let content = AssetContent {
+                    what,
+                    value,
+                    expected: MediaKind::Image,
+                    canvas: None,
+                };
+                content.check(&resolved, at, rewrite, diagnostics);
+                continue;
+            }
+            let mut diag = at(
+                "missing-asset",
+                Severity::Error,
+                format!(
+                    "「{speaker}」の表情「{name}」の立ち絵「{value}」が見つかりません（{} からの相対パス {} を探しました）",
+                    chars.path.display(),
+                    resolved.display()
+                ),
+            );
+            if let Some(similar) = closest_file_in(&resolved) {
+                let dir = Path::new(value).parent().unwrap_or_else(|| Path::new(""));
+                let fixed = dir.join(&similar).to_string_lossy().replace('\\', "/");
+                diag.message
+                    .push_str(&format!("。よく似た「{fixed}」があります"));
+                diag.suggestion = Some(rewrite(&fixed));
+            }
+            diagnostics.push(diag);
+        }
+    }
+}
+
+/// セリフ・`show` の表情が characters.yaml に宣言されているか（`undefined-expression`）。
+/// 話者そのものが未宣言なら undefined-character に任せ、ここでは報告しない
+fn check_expressions(scene: &LoadedScene, chars: &Characters, diagnostics: &mut Vec<Diagnostic>) {
+    let md = &scene.parsed.scene;
+    let blocks = md
+        .lead
+        .iter()
+        .chain(md.sections.iter().flat_map(|s| s.blocks.iter()));
+    for block in blocks {
+        let (speaker, expression, line) = match block {
+            Block::Dialogue {
+                speaker,
+                expression: Some(expression),
+                line,
+                ..
+            } => (speaker, expression, *line),
+            Block::Show {
+                character,
+                expression: Some(expression),
+                line,
+            } => (character, expression, *line),
+            _ => continue,
+        };
+        if !chars.contains(speaker) || chars.expression(speaker, expression).is_some() {
+            continue;
+        }
+        let declared: Vec<&str> = chars
+            .expressions
+            .get(speaker.as_str())
+            .map(|e| e.keys().map(String::as_str).collect())
+            .unwrap_or_default();
+        let mut message = format!(
+            "「{speaker}」の表情「{expression}」は {} に宣言されていません。",
+            chars.path.display()
+        );
+        let mut suggestion = None;
+        if declared.is_empty() {
+            message.push_str(&format!(
+                "「{speaker}」の expressions: に表情名と立ち絵のパスを追加してください"
+            ));
+            suggestion = Some(format!(
+                "{speaker}:\n  expressions:\n    {expression}: sprites/..."
+            ));
+        } else if let Some(similar) = closest(expression, &declared) {
+            message.push_str(&format!(
+                "宣言済みの「{similar}」の書き間違いではありませんか？"
+            ));
+            suggestion = Some(rewrite_speaker(block, speaker, Some(similar)));
+        } else {
+            message.push_str(&format!(
+                "宣言済みの表情から選ぶか、characters.yaml に追加してください（宣言済み: {}）",
+                declared.join("、")
+            ));
+        }
+        let mut diag = Diagnostic::error("undefined-expression", &scene.path, line, message);
+        diag.suggestion = suggestion;
+        diagnostics.push(diag);
+    }
+}
+
+/// 話者名・表情を差し替えたブロックの書き換え例（suggestion 用）
+fn rewrite_speaker(block: &Block, speaker: &str, expression: Option<&str>) -> String {
+    let name = match expression {
+        Some(expression) => format!("{speaker}({expression})"),
+        None => speaker.to_string(),
+    };
+    match block {
+        Block::Dialogue { text, .. } => {
+            format!("{name}: {}", text.lines().next().unwrap_or(""))
+        }
+        Block::Hide { .. } => format!("<!-- hide: {speaker} -->"),
+        _ => format!("<!-- show: {name} -->"),
     }
 }
 
 fn check_speakers(scene: &LoadedScene, chars: &Characters, diagnostics: &mut Vec<Diagnostic>) {
     /// 未宣言話者の出現箇所（最初の 1 件に warning、以降は related_spans）
-    struct Occurrence {
+    struct Occurrence<'a> {
         first_line: usize,
-        first_text: String,
+        first: &'a Block,
         rest: Vec<usize>,
     }
     let md = &scene.parsed.scene;
@@ -488,23 +1386,24 @@ fn check_speakers(scene: &LoadedScene, chars: &Characters, diagnostics: &mut Vec
         .iter()
         .chain(md.sections.iter().flat_map(|s| s.blocks.iter()));
     for block in blocks {
-        let Block::Dialogue {
-            speaker,
-            text,
-            line,
-        } = block
-        else {
-            continue;
+        // セリフの話者と、立ち絵の show / hide で指定したキャラクター
+        let (speaker, line) = match block {
+            Block::Dialogue { speaker, line, .. } => (speaker, *line),
+            Block::Show {
+                character, line, ..
+            }
+            | Block::Hide { character, line } => (character, *line),
+            _ => continue,
         };
         if chars.contains(speaker) {
             continue;
         }
         undefined
             .entry(speaker.as_str())
-            .and_modify(|o| o.rest.push(*line))
+            .and_modify(|o| o.rest.push(line))
             .or_insert_with(|| Occurrence {
-                first_line: *line,
-                first_text: text.lines().next().unwrap_or("").to_string(),
+                first_line: line,
+                first: block,
                 rest: Vec::new(),
             });
     }
@@ -519,7 +1418,13 @@ fn check_speakers(scene: &LoadedScene, chars: &Characters, diagnostics: &mut Vec
             message.push_str(&format!(
                 "宣言済みの「{similar}」の書き間違いではありませんか？新しい登場人物なら characters.yaml に追加してください"
             ));
-            suggestion = Some(format!("{similar}: {}", occ.first_text));
+            let expression = match occ.first {
+                Block::Dialogue { expression, .. } | Block::Show { expression, .. } => {
+                    expression.as_deref()
+                }
+                _ => None,
+            };
+            suggestion = Some(rewrite_speaker(occ.first, similar, expression));
         } else {
             let list: Vec<&str> = declared.iter().take(8).copied().collect();
             message.push_str(&format!(
@@ -537,6 +1442,29 @@ fn check_speakers(scene: &LoadedScene, chars: &Characters, diagnostics: &mut Vec
             .collect();
         diagnostics.push(diag);
     }
+
+    // front matter の display_names で表示名を上書きする話者（SPEC 3.1）
+    let line = scene.parsed.front_matter_spans.display_names.unwrap_or(1);
+    for name in md.display_names.keys() {
+        if chars.canonical(name).is_some() {
+            continue;
+        }
+        let mut diag = Diagnostic::warning(
+            "undefined-character",
+            &scene.path,
+            line,
+            format!(
+                "display_names の「{name}」は {} に宣言されていません。表示名を上書きできるのは宣言済みの話者（または別名）だけです",
+                chars.path.display()
+            ),
+        );
+        if let Some(similar) = closest(name, &declared) {
+            diag.message.push_str(&format!(
+                "。宣言済みの「{similar}」の書き間違いではありませんか？"
+            ));
+        }
+        diagnostics.push(diag);
+    }
 }
 
 // ---------------------------------------------------- implicit-fallthrough
@@ -555,7 +1483,7 @@ fn check_fallthrough(scene: &LoadedScene, diagnostics: &mut Vec<Diagnostic>) {
                 &scene.path,
                 line,
                 format!(
-                    "セクション「{}」の末尾が ending・ジャンプ・選択肢のいずれでもないため、実行は次のセクション「{}」（{} 行目）に流れ込みます。意図した合流でなければ、`<!-- ending: id -->` で終えるか、ジャンプで飛び先を明示してください",
+                    "セクション「{}」の末尾が ending・ジャンプ・選択肢・return のいずれでもないため、実行は次のセクション「{}」（{} 行目）に流れ込みます。意図した合流でなければ、`<!-- ending: id -->` で終えるか、ジャンプで飛び先を明示してください",
                     cur.heading, next.heading, next.line
                 ),
             )
@@ -607,6 +1535,235 @@ fn check_choice_branch_count(
     }
 }
 
+// -------------------------------------------- stray-return / recursive-call
+
+/// ブロックの位置（シーン, セクション, ブロック）。セクションが None ならリード部
+type Point = (usize, Option<usize>, usize);
+
+/// 呼び出し先の要約。呼び出し先の先頭から（呼び出し中として）辿れる範囲の性質
+#[derive(Debug, Clone, Default, PartialEq)]
+struct CallSummary {
+    /// `return` で呼び出し元へ戻れる
+    returns: bool,
+    /// ending・ファイル末尾で実行が終わる
+    ends: bool,
+    /// 途中で（入れ子の呼び出し先の中も含めて）実行する `call`
+    calls: BTreeSet<Point>,
+}
+
+/// 呼び出しと戻りを静的に検査する（SPEC 4.9）。
+///
+/// 呼び出し先ごとに「戻れるか・終われるか・どの call を実行するか」を不動点
+/// まで求め、それを使って
+/// - 開始シーンから呼び出しを経ずに辿れる `return` を `stray-return`
+/// - 呼び出し先から戻りも終わりもせず、同じ call にまた着くものを `recursive-call`
+///
+/// として報告する。開始シーンは、引数に渡したファイルと、他のシーンから
+/// リンク・呼び出しされていないシーン
+fn check_calls(scenes: &[LoadedScene], paths: &[PathBuf], diagnostics: &mut Vec<Diagnostic>) {
+    let mut calls: Vec<(Point, (usize, Option<usize>))> = Vec::new();
+    let mut referenced = HashSet::new();
+    for (i, scene) in scenes.iter().enumerate() {
+        for (point, block) in scene_points(scene, i) {
+            let targets: Vec<&LinkTarget> = match block {
+                Block::Jump { target, .. } | Block::Call { target, .. } => vec![target],
+                Block::Choices { items, .. } => items.iter().map(|item| &item.target).collect(),
+                Block::Random { items, .. } => items.iter().map(|item| &item.target).collect(),
+                _ => continue,
+            };
+            for target in targets {
+                let Some(key) = try_resolve(scenes, i, target) else {
+                    continue;
+                };
+                if key.0 != i {
+                    referenced.insert(key.0);
+                }
+                if matches!(block, Block::Call { .. }) {
+                    calls.push((point, key));
+                }
+            }
+        }
+    }
+
+    let mut summaries: HashMap<(usize, Option<usize>), CallSummary> = HashMap::new();
+    loop {
+        let mut changed = false;
+        for &(_, key) in &calls {
+            let summary = walk_calls(scenes, vec![(key.0, key.1, 0)], &summaries, |_| {});
+            if summaries.get(&key) != Some(&summary) {
+                summaries.insert(key, summary);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let seeds: HashSet<PathBuf> = paths
+        .iter()
+        .filter(|p| p.is_file())
+        .filter_map(|p| p.canonicalize().ok())
+        .collect();
+    let roots: Vec<Point> = (0..scenes.len())
+        .filter(|&i| seeds.contains(&scenes[i].canon) || !referenced.contains(&i))
+        .map(|i| (i, None, 0))
+        .collect();
+    walk_calls(scenes, roots, &summaries, |(i, section, index)| {
+        let line = block_line(&segment(&scenes[i], section)[index]);
+        diagnostics.push(Diagnostic::warning(
+            "stray-return",
+            &scenes[i].path,
+            line,
+            "この `<!-- return -->` には、呼び出し（`<!-- call: -->`）を経ずにジャンプやフォールスルーで到達する経路があります。実行すると戻り先がなく return-without-call になります。呼び出し先のセクションへは call だけで入るよう、直前のセクションを ending・ジャンプ・選択肢で終えてください".to_string(),
+        ));
+    });
+
+    for (point, key) in &calls {
+        let summary = &summaries[key];
+        if summary.calls.contains(point) && !summary.returns && !summary.ends {
+            let (i, section, index) = *point;
+            let Block::Call { target, line } = &segment(&scenes[i], section)[index] else {
+                continue;
+            };
+            diagnostics.push(Diagnostic::warning(
+                "recursive-call",
+                &scenes[i].path,
+                *line,
+                format!(
+                    "呼び出し先「{}」からは、return で戻ることも ending で終わることもないまま、この呼び出しにもう一度到達します。v1 には変数がないため呼び出しが止まりません（unbounded-recursion）。呼び出し先のどこかで return か ending に進めるようにしてください",
+                    link_text(target)
+                ),
+            ));
+        }
+    }
+}
+
+/// 呼び出し中でない実行として `starts` から辿る。入れ子の call は、呼び出し先が
+/// 戻れるときだけその次へ進む。`return` に着くたびに `on_return` を呼ぶ
+fn walk_calls(
+    scenes: &[LoadedScene],
+    starts: Vec<Point>,
+    summaries: &HashMap<(usize, Option<usize>), CallSummary>,
+    mut on_return: impl FnMut(Point),
+) -> CallSummary {
+    let mut summary = CallSummary::default();
+    let mut seen = HashSet::new();
+    let mut stack = starts;
+    while let Some(point) = stack.pop() {
+        if !seen.insert(point) {
+            continue;
+        }
+        let (i, section, index) = point;
+        let scene = &scenes[i];
+        let Some(block) = segment(scene, section).get(index) else {
+            // 末尾まで来たら次のセクションへフォールスルーし、なければ終わる
+            let next = section.map_or(0, |j| j + 1);
+            if next < scene.parsed.scene.sections.len() {
+                stack.push((i, Some(next), 0));
+            } else {
+                summary.ends = true;
+            }
+            continue;
+        };
+        let mut go = |target: &LinkTarget| {
+            if let Some((j, section)) = try_resolve(scenes, i, target) {
+                stack.push((j, section, 0));
+            }
+        };
+        match block {
+            Block::Return { .. } => {
+                summary.returns = true;
+                on_return(point);
+            }
+            Block::Ending { .. } => summary.ends = true,
+            Block::Jump { target, .. } => go(target),
+            Block::Choices { items, .. } => items.iter().for_each(|item| go(&item.target)),
+            Block::Random { items, .. } => items.iter().for_each(|item| go(&item.target)),
+            Block::Call { target, .. } => {
+                summary.calls.insert(point);
+                let Some(key) = try_resolve(scenes, i, target) else {
+                    // 呼び出し先が解決できない（broken-link 報告済み）なら素通りする
+                    stack.push((i, section, index + 1));
+                    continue;
+                };
+                // 要約がまだない呼び出し先は、戻れるとも終わるとも分かっていない
+                if let Some(callee) = summaries.get(&key) {
+                    summary.ends |= callee.ends;
+                    summary.calls.extend(callee.calls.iter().copied());
+                    if callee.returns {
+                        stack.push((i, section, index + 1));
+                    }
+                }
+            }
+            _ => stack.push((i, section, index + 1)),
+        }
+    }
+    summary
+}
+
+/// シーンのブロックを位置つきで並べる
+fn scene_points(scene: &LoadedScene, i: usize) -> Vec<(Point, &Block)> {
+    let md = &scene.parsed.scene;
+    let lead = md.lead.iter().enumerate().map(|(k, b)| ((i, None, k), b));
+    let sections = md.sections.iter().enumerate().flat_map(|(j, section)| {
+        section
+            .blocks
+            .iter()
+            .enumerate()
+            .map(move |(k, b)| ((i, Some(j), k), b))
+    });
+    lead.chain(sections).collect()
+}
+
+/// リード部（None）またはセクションのブロック列
+fn segment(scene: &LoadedScene, section: Option<usize>) -> &[Block] {
+    match section {
+        Some(j) => &scene.parsed.scene.sections[j].blocks,
+        None => &scene.parsed.scene.lead,
+    }
+}
+
+/// リンク先を（シーン, セクション）に解決する。解決できなければ None
+/// （broken-link として報告済み）
+fn try_resolve(
+    scenes: &[LoadedScene],
+    current: usize,
+    target: &LinkTarget,
+) -> Option<(usize, Option<usize>)> {
+    let scene = match &target.file {
+        None => current,
+        Some(file) => {
+            let canon = resolve_sibling(&scenes[current].path, file)?
+                .canonicalize()
+                .ok()?;
+            scenes.iter().position(|s| s.canon == canon)?
+        }
+    };
+    let section = match &target.anchor {
+        None => None,
+        Some(anchor) => Some(
+            scenes[scene]
+                .parsed
+                .scene
+                .sections
+                .iter()
+                .position(|s| s.anchor == *anchor)?,
+        ),
+    };
+    Some((scene, section))
+}
+
+/// リンク先を書いたとおりに表示する（`file.md#anchor` / `#anchor` / `file.md`）
+fn link_text(target: &LinkTarget) -> String {
+    match (&target.file, &target.anchor) {
+        (Some(file), Some(anchor)) => format!("{file}#{anchor}"),
+        (Some(file), None) => file.clone(),
+        (None, Some(anchor)) => format!("#{anchor}"),
+        (None, None) => String::new(),
+    }
+}
+
 // ----------------------------------------------------- unreachable-section
 
 fn check_unreachable(scenes: &[LoadedScene], diagnostics: &mut Vec<Diagnostic>) {
@@ -656,11 +1813,17 @@ fn check_unreachable(scenes: &[LoadedScene], diagnostics: &mut Vec<Diagnostic>)
     }
 }
 
-/// 実行がここで必ず終わる・飛ぶブロックか（SPEC 5章のフォールスルー判定）
+/// 実行がここで必ず終わる・飛ぶ・戻るブロックか（SPEC 5章のフォールスルー判定）
 fn ends_with_terminator(last: Option<&Block>) -> bool {
     matches!(
         last,
-        Some(Block::Ending { .. } | Block::Jump { .. } | Block::Choices { .. })
+        Some(
+            Block::Ending { .. }
+                | Block::Jump { .. }
+                | Block::Choices { .. }
+                | Block::Random { .. }
+                | Block::Return { .. }
+        )
     )
 }
 
@@ -669,8 +1832,17 @@ fn block_line(block: &Block) -> usize {
         Block::Narration { line, .. }
         | Block::Dialogue { line, .. }
         | Block::Choices { line, .. }
+        | Block::Random { line, .. }
         | Block::Jump { line, .. }
-        | Block::Ending { line, .. } => *line,
+        | Block::Call { line, .. }
+        | Block::Return { line }
+        | Block::Ending { line, .. }
+        | Block::Background { line, .. }
+        | Block::Bgm { line, .. }
+        | Block::BgmStop { line }
+        | Block::Se { line, .. }
+        | Block::Show { line, .. }
+        | Block::Hide { line, .. } => *line,
     }
 }
 
@@ -18,14 +18,50 @@
 //!   `{ scene_id, step_index }` に解決済みの形で持たせる。これは
 //!   `check_path` を通過済み（broken-link なし）という前提で解決できる
 //! - 現行の v1 記法には変数（`set_variable`）に相当する構文がないため、
-//!   このステップ種別は実装しない（narration / dialogue / choice / jump /
-//!   ending の 5 種のみ）。構文が追加された時点で対応する
+//!   このステップ種別は実装しない。構文が追加された時点で対応する
+//! - 背景・BGM・効果音の切り替え（SPEC 4.6）は `background` / `bgm` /
+//!   `bgm_stop` / `se` ステップとして出現位置に置く。`assets` には front matter
+//!   と制御コメントの両方から参照されたパスを重複なく並べる
+//! - 立ち絵（`show` / `hide`、セリフの表情）は表情名のまま step に載せ、
+//!   画像パスは `assets` の `sprite` で引けるようにする。登場するキャラクター
+//!   の表情は、シナリオで使われていないものも含めて全部載せる（`show` で表情を
+//!   省略したときにどの立ち絵を出すかは compile 先が決めるため）
+//! - `assets` の各要素には、プロジェクトのルートからの相対パス `file`・サイズ・
+//!   内容のハッシュを載せる。ハッシュは `story_build_id` の計算にも含まれるため、
+//!   シナリオを変えずにアセットだけ差し替えても別の build id になる
+//! - 各ステップの `source` はブロックの行・列範囲と属するセクションのアンカーを
+//!   持つ。同じ位置を見出しつきの [`SourceMap`] にも書き出せるので、本番用の
+//!   bundle は `--strip-source` で `source` を省き、対応表だけを手元に残せる
+//! - `endings` には endings.yaml の宣言（SPEC 2.3）と、宣言のないままシナリオで
+//!   使われた ending を解放順（`order`）→ id の順に並べる。ギャラリー画面は
+//!   この表だけで一覧を作れる
+//! - `achievements` には achievements.yaml の実績（SPEC 2.4）を id 順に載せる。
+//!   セクション条件はリンクと同じく `{ sceneId, stepIndex }` に解決するので、
+//!   player は実行中の位置と突き合わせるだけで解除を判定できる
+//! - `chapters` には chapters.yaml の章（SPEC 2.5）を章の順に載せる。入口は
+//!   リンクと同じ `{ sceneId, stepIndex }` なので、チャプターセレクト画面は
+//!   そこから再生を始めればよい
+//! - `entrySceneIds` には開始シーンと `--entry` で足した開始シーンを並べる。
+//!   本編からリンクされない DLC やおまけシナリオも、player はここから
+//!   始められる（`entrySceneId` は常に先頭と同じ）
+//! - `--split` では目次（[`BundleIndex`]）とシーンごとのファイルに分ける。
+//!   bundle の組み立ては変えず、出来上がった [`StoryBundle`] を
+//!   [`split_bundle`] で分けるだけにして、1 ファイル出力と中身を揃える
 
+use super::achievements::{self, Resolved, find_achievements_file, load_achievements};
+use super::chapters::project_chapters;
+use super::characters::{Characters, find_characters_file, load_characters};
 use super::check::CheckResult;
-use super::project::{LoadedScene, load_checked_project, resolve_sibling};
+use super::endings::{EndingKind, find_endings_file, load_endings};
+use super::parse::SourceRange;
+use super::project::{
+    CheckedProject, LoadedScene, common_root, load_checked_project, resolve_sibling, scene_assets,
+    scene_characters,
+};
 use super::routes::{RoutesOptions, routes_path};
 use super::{Block, LinkTarget, Scene};
 use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
 
 const SCHEMA_VERSION: &str = "1";
@@ -33,13 +69,26 @@ const SCHEMA_VERSION: &str = "1";
 /// compile の動作オプション
 #[derive(Debug, Clone)]
 pub struct CompileOptions {
-    /// background / bgm の実在チェック（`--no-assets` で false）
+    /// background / bgm / se の実在チェック（`--no-assets` で false）
     pub check_assets: bool,
+    /// bundle の `characters` に、登場しないキャラクターも含めて characters.yaml
+    /// の宣言をすべて載せる（`--all-characters` で true）
+    pub all_characters: bool,
+    /// bundle から `source`（シナリオ上の位置）を除く（`--strip-source` で true）。
+    /// 位置は [`CompileResult::source_map`] で別ファイルにできる
+    pub strip_source: bool,
+    /// 追加の開始シーン（`--entry`、複数可）
+    pub entries: Vec<PathBuf>,
 }
 
 impl Default for CompileOptions {
     fn default() -> Self {
-        Self { check_assets: true }
+        Self {
+            check_assets: true,
+            all_characters: false,
+            strip_source: false,
+            entries: Vec::new(),
+        }
     }
 }
 
@@ -52,6 +101,8 @@ pub struct CompileResult {
     pub check: CheckResult,
     /// 生成した StoryBundle。check が error のときは None
     pub bundle: Option<StoryBundle>,
+    /// bundle の各ステップからシナリオ Markdown への対応。bundle と同時に作られる
+    pub source_map: Option<SourceMap>,
 }
 
 impl CompileResult {
@@ -70,15 +121,189 @@ pub struct StoryBundle {
     pub story_build_id: String,
     pub title: String,
     pub entry_scene_id: String,
+    /// 開始シーンの ID（`entry_scene_id` → `--entry` の順、重複なし）
+    pub entry_scene_ids: Vec<String>,
     pub scenes: Vec<BundleScene>,
     pub assets: Vec<BundleAsset>,
+    /// characters.yaml で宣言されたキャラクター（名前順）
+    pub characters: Vec<BundleCharacter>,
+    /// エンディングの一覧（解放順 → id の順、SPEC 2.3）
+    pub endings: Vec<BundleEnding>,
+    /// 実績の一覧（id 順、SPEC 2.4）
+    pub achievements: Vec<BundleAchievement>,
+    /// 章の一覧（chapters.yaml の順、SPEC 2.5）
+    pub chapters: Vec<BundleChapter>,
+    /// シーン ID → 書かれたままの参照（[`BundleAssetKind::ref_key`]）→
+    /// 解決先の [`BundleAsset::identity`]（bundle には出力しない）。
+    /// 別々のディレクトリのシーンが同じ `bg.png` を書いても取り違えない
+    #[serde(skip)]
+    pub asset_refs: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// エンディング 1 つ（ギャラリー画面用）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleEnding {
+    /// `<!-- ending: id -->` の id
+    pub id: String,
+    /// endings.yaml の `title`。なければ id と同じ
+    pub title: String,
+    /// endings.yaml の `type`
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<EndingKind>,
+    /// endings.yaml の `order`（解放順）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<u32>,
+    /// endings.yaml に宣言されているか
+    pub declared: bool,
+}
+
+/// 章 1 つ（チャプターセレクト画面用）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleChapter {
+    /// chapters.yaml のキー
+    pub id: String,
+    /// chapters.yaml の `title`。なければ id と同じ
+    pub title: String,
+    /// 章の入口（入口のシーンの先頭 step）
+    pub entry: StepTarget,
+    /// 章のシーン ID（chapters.yaml に書いた順）
+    pub scene_ids: Vec<String>,
+}
+
+/// 実績 1 つ。`conditions` と `endings` はどちらか一方だけが空でない
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleAchievement {
+    /// achievements.yaml のキー
+    pub id: String,
+    /// achievements.yaml の `title`。なければ id と同じ
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 1 回のプレイですべて満たす条件（`when:`）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<BundleCondition>,
+    /// すべて見れば解除されるエンディング（`endings:`）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub endings: Vec<String>,
+}
+
+/// 実績の条件 1 つ。参照は解決済み
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BundleCondition {
+    /// このエンディングで終わる
+    Ending { id: String },
+    /// このセクションの先頭 step を通る
+    Section { target: StepTarget },
+    /// このラベルの選択肢を選ぶ（`sceneId` があればそのシーンの選択肢だけ）
+    Choice {
+        label: String,
+        #[serde(rename = "sceneId", skip_serializing_if = "Option::is_none")]
+        scene_id: Option<String>,
+    },
 }
 
-/// シナリオ Markdown 上の位置（arikoi 側のデバッグ表示用）
+/// characters.yaml の 1 キャラクター（SPEC 2.1）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleCharacter {
+    /// characters.yaml のキー（セリフの話者名）
+    pub name: String,
+    /// 表示名。`display_name` がなければ `name` と同じ
+    pub display_name: String,
+    /// 別名（`aliases`）。シナリオ中の別名は `name` に揃えて出力する
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// 上記と `expressions`（立ち絵は `assets` の `sprite`）以外のメタデータ。
+    /// tsumugai は中身を解釈せず JSON にしてそのまま渡す
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+impl BundleCharacter {
+    fn from_entry(name: &str, entry: &serde_yaml::Value) -> Self {
+        let text = |key: &str| entry.get(key).and_then(|v| v.as_str()).map(String::from);
+        let mut metadata = serde_json::Map::new();
+        if let Some(mapping) = entry.as_mapping() {
+            for (key, value) in mapping {
+                let Some(key) = key.as_str() else {
+                    continue;
+                };
+                if matches!(key, "display_name" | "color" | "expressions" | "aliases") {
+                    continue;
+                }
+                // YAML の値は JSON にできないもの（非文字列キー等）だけ落とす
+                if let Ok(value) = serde_json::to_value(value) {
+                    metadata.insert(key.to_string(), value);
+                }
+            }
+        }
+        Self {
+            name: name.to_string(),
+            display_name: text("display_name").unwrap_or_else(|| name.to_string()),
+            aliases: entry
+                .get("aliases")
+                .and_then(|v| v.as_sequence())
+                .map(|seq| {
+                    seq.iter()
+                        .filter_map(|v| v.as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            color: text("color"),
+            metadata,
+        }
+    }
+}
+
+/// シナリオ Markdown 上の位置（arikoi 側のデバッグ表示・プレイヤーからの報告用）
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SourceLocation {
     pub file: String,
     pub line: usize,
+    /// 開始列（1-origin、文字単位）。シーン自体の位置では None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    /// 範囲の最後の文字の列（その文字を含む）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_column: Option<usize>,
+    /// ステップが属するセクション（H2）のアンカー。リード部では None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<String>,
+}
+
+/// bundle のステップからシナリオ Markdown への対応表（`--source-map`）。
+/// 本番用の bundle から `source` を除いても、プレイヤーからの報告にある
+/// `(scene_id, step_index)` をこの表で `file:line:column` と見出しに戻せる
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceMap {
+    pub schema_version: String,
+    /// 対応する bundle の `story_build_id`
+    pub story_build_id: String,
+    /// シーン ID → そのシーンの `steps` と同じ並びの位置
+    pub scenes: BTreeMap<String, Vec<SourceMapStep>>,
+}
+
+/// 1 ステップぶんの位置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceMapStep {
+    #[serde(flatten)]
+    pub location: SourceLocation,
+    /// 属するセクションの見出し。リード部では None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heading: Option<String>,
+    /// 選択肢・ランダム分岐の各項目の位置（その 2 つのステップのみ）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<SourceLocation>,
 }
 
 /// 1 ファイル = 1 シーン（[`super::Scene`] に対応）
@@ -86,7 +311,16 @@ pub struct SourceLocation {
 pub struct BundleScene {
     pub id: String,
     pub title: Option<String>,
-    pub source: SourceLocation,
+    /// front matter の `background`（シーン開始時の背景。書かれたままのパスで、
+    /// `assets` の `background` と同じ表記）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    /// front matter の `bgm`（シーン開始時の BGM）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bgm: Option<String>,
+    /// `--strip-source` では None（出力しない）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<SourceLocation>,
     pub steps: Vec<BundleStep>,
 }
 
@@ -103,42 +337,201 @@ pub struct StepTarget {
 pub struct ChoiceOption {
     pub label: String,
     pub target: StepTarget,
-    pub source: SourceLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<SourceLocation>,
+}
+
+/// ランダム分岐の 1 項目（SPEC 4.10）
+#[derive(Debug, Clone, Serialize)]
+pub struct RandomOption {
+    pub label: String,
+    /// 重み。player は `weight / 全項目の weight の合計` の確率でこの項目へ進む
+    pub weight: u32,
+    pub target: StepTarget,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<SourceLocation>,
 }
 
 /// 1 ステップ（SPEC 4章のブロックに対応。set_variable は現行記法に無いため未実装）
 #[derive(Debug, Clone, Serialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
 pub enum BundleStep {
     Narration {
         text: String,
-        source: SourceLocation,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
     },
     Dialogue {
+        /// characters.yaml の話者名（別名は話者名に揃える）
         speaker: String,
+        /// このシーンでの表示名（`display_name` / front matter の `display_names`）
+        display_name: String,
+        /// `名前(表情): 本文` の表情名。画像は `assets` の `sprite` で引く
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expression: Option<String>,
         text: String,
-        source: SourceLocation,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
     },
     Choice {
         items: Vec<ChoiceOption>,
-        source: SourceLocation,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
+    },
+    /// `<!-- random -->` の直後のリスト（SPEC 4.10）。入力を待たずに、重みに
+    /// 比例した確率で項目を 1 つ選んで `target` へ移る
+    Random {
+        items: Vec<RandomOption>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
     },
     Jump {
         target: StepTarget,
-        source: SourceLocation,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
+    },
+    /// `<!-- call: -->`（SPEC 4.9）。次のステップを戻り先として積み、`target` へ移る
+    Call {
+        target: StepTarget,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
+    },
+    /// `<!-- return -->`。最後に積んだ戻り先へ移る
+    Return {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
     },
     Ending {
         id: String,
-        source: SourceLocation,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
+    },
+    /// `<!-- background: path -->`（SPEC 4.6）
+    Background {
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
+    },
+    /// `<!-- bgm: path -->`
+    Bgm {
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
+    },
+    /// `<!-- bgm: stop -->`
+    BgmStop {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
     },
+    /// `<!-- se: path -->`
+    Se {
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
+    },
+    /// `<!-- show: 名前(表情) -->`
+    Show {
+        character: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expression: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
+    },
+    /// `<!-- hide: 名前 -->`
+    Hide {
+        character: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<SourceLocation>,
+    },
+}
+
+/// アセット参照（front matter と制御コメントの background / bgm / se、
+/// characters.yaml の立ち絵）と、配布・キャッシュ判定のためのファイル情報
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleAsset {
+    #[serde(flatten)]
+    pub kind: BundleAssetKind,
+    /// プロジェクトのルート（シーンとアセットの共通の祖先ディレクトリ）からの
+    /// `/` 区切りの相対パス。`--assets-out` の書き出し先もこの配置になる
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// ファイルサイズ（バイト）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// 内容のハッシュ（FNV-1a 64bit の 16 進）。player のキャッシュ更新に使う
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// 読み込み元のファイル（bundle には出力しない）。`--no-assets` で
+    /// 実在しないファイルを参照しているときは None
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
 }
 
-/// アセット参照（現行記法にある background / bgm のみ）
+/// アセットの種類と、参照元に書かれたままのパス
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 #[serde(tag = "kind", rename_all = "snake_case")]
-pub enum BundleAsset {
-    Background { path: String },
-    Bgm { path: String },
+pub enum BundleAssetKind {
+    Background {
+        path: String,
+    },
+    Bgm {
+        path: String,
+    },
+    Se {
+        path: String,
+    },
+    /// 立ち絵。`path` は characters.yaml からの相対パス
+    Sprite {
+        character: String,
+        expression: String,
+        path: String,
+    },
+}
+
+impl BundleAsset {
+    /// 参照元ファイル `from` からの相対パスを解決して作る。ファイル情報は
+    /// プロジェクトのルートが決まってから [`fill_asset_files`] で埋める
+    fn new(kind: BundleAssetKind, from: &Path, path: &str) -> Self {
+        let source = resolve_sibling(from, path).and_then(|p| p.canonicalize().ok());
+        Self {
+            kind,
+            file: None,
+            size: None,
+            hash: None,
+            source: source.filter(|p| p.is_file()),
+        }
+    }
+
+    /// 解決先のファイルで決まるアセットの同一性。`file` があればそれ、
+    /// なければ読み込み元のパス。実在しないアセットは None
+    pub fn identity(&self) -> Option<String> {
+        self.file.clone().or_else(|| {
+            self.source
+                .as_ref()
+                .map(|p| p.to_string_lossy().into_owned())
+        })
+    }
+}
+
+impl BundleAssetKind {
+    /// シーンに書かれたままの参照のキー（`background:bg.png`、`sprite:キャラ(表情)`）
+    pub fn ref_key(&self) -> String {
+        match self {
+            Self::Background { path } => format!("background:{path}"),
+            Self::Bgm { path } => format!("bgm:{path}"),
+            Self::Se { path } => format!("se:{path}"),
+            Self::Sprite {
+                character,
+                expression,
+                ..
+            } => format!("sprite:{character}({expression})"),
+        }
+    }
 }
 
 /// シーンファイルを実行前検査してから StoryBundle を生成する（#128）。
@@ -151,44 +544,77 @@ pub enum BundleAsset {
 /// パスが存在しない・ディレクトリ・検査 error の場合も panic や Err にせず、
 /// Diagnostic 入りの [`CompileResult`] を返す（bundle は None になる）。
 pub fn compile_path(path: &Path, options: &CompileOptions) -> CompileResult {
-    let mut project = match load_checked_project(path, "compile", options.check_assets) {
+    let project = match load_compilable(path, &options.entries, options.check_assets) {
         Ok(project) => project,
         Err(check) => {
             return CompileResult {
                 file: path.to_path_buf(),
                 check,
                 bundle: None,
+                source_map: None,
             };
         }
     };
 
-    // check だけでは分からない循環・到達不能を routes の全分岐探索で検出する
+    let mut entries = vec![path.to_path_buf()];
+    entries.extend(options.entries.iter().cloned());
+    let (bundle, source_map) = build_bundle(&project.scenes, &entries, options);
+    CompileResult {
+        file: path.to_path_buf(),
+        check: project.check,
+        bundle: Some(bundle),
+        source_map: Some(source_map),
+    }
+}
+
+/// compile 系（StoryBundle と他エンジン向けの書き出し）共通の実行前検証。
+/// check の error に加え、check だけでは分からない循環・到達不能を routes の
+/// 全分岐探索で検出する。error があれば診断だけを返す。
+/// `entries` は `--entry` で足した開始シーン
+pub(super) fn load_compilable(
+    path: &Path,
+    entries: &[PathBuf],
+    check_assets: bool,
+) -> Result<CheckedProject, CheckResult> {
+    let mut project = load_checked_project(path, entries, "compile", check_assets)?;
     let routes_options = RoutesOptions {
-        check_assets: options.check_assets,
+        check_assets,
+        entries: entries.to_vec(),
         ..RoutesOptions::default()
     };
     if let Some(report) = routes_path(path, &routes_options).report {
         project.check.diagnostics.extend(report.diagnostics);
     }
     if project.check.has_errors() {
-        return CompileResult {
-            file: path.to_path_buf(),
-            check: project.check,
-            bundle: None,
-        };
+        return Err(project.check);
     }
+    Ok(project)
+}
 
-    let bundle = build_bundle(&project.scenes, path);
-    CompileResult {
-        file: path.to_path_buf(),
-        check: project.check,
-        bundle: Some(bundle),
+/// 開始シーンのパスを `scenes` の位置にする（指定順、重複なし）
+pub(super) fn entry_indices(scenes: &[LoadedScene], entries: &[PathBuf]) -> Vec<usize> {
+    let mut indices: Vec<usize> = Vec::new();
+    for entry in entries {
+        let canon = entry.canonicalize().expect("check済みなので実在する");
+        let idx = scenes
+            .iter()
+            .position(|s| s.canon == canon)
+            .expect("entry はロード済み");
+        if !indices.contains(&idx) {
+            indices.push(idx);
+        }
     }
+    indices
 }
 
 // -------------------------------------------------------------- bundle構築
 
-fn build_bundle(scenes: &[LoadedScene], entry: &Path) -> StoryBundle {
+/// `entries` は開始シーン（先頭が `entrySceneId`）
+fn build_bundle(
+    scenes: &[LoadedScene],
+    entries: &[PathBuf],
+    options: &CompileOptions,
+) -> (StoryBundle, SourceMap) {
     let scene_ids: Vec<String> = scenes
         .iter()
         .map(|s| s.parsed.scene.id.clone().expect("check済みなのでidがある"))
@@ -198,34 +624,46 @@ fn build_bundle(scenes: &[LoadedScene], entry: &Path) -> StoryBundle {
         .map(|s| segment_offsets(&s.parsed.scene))
         .collect();
 
-    let entry_canon = entry.canonicalize().expect("check済みなので実在する");
-    let entry_idx = scenes
-        .iter()
-        .position(|s| s.canon == entry_canon)
-        .expect("entry はロード済み");
+    let entry_indices = entry_indices(scenes, entries);
+    let entry_idx = entry_indices[0];
 
-    let bundle_scenes: Vec<BundleScene> = scenes
-        .iter()
-        .enumerate()
-        .map(|(i, loaded)| build_scene(i, loaded, scenes, &scene_ids, &layouts))
-        .collect();
+    let mut bundle_scenes = Vec::with_capacity(scenes.len());
+    let mut map_scenes = BTreeMap::new();
+    for (i, loaded) in scenes.iter().enumerate() {
+        let (scene, map) = build_scene(
+            i,
+            loaded,
+            scenes,
+            &scene_ids,
+            &layouts,
+            options.strip_source,
+        );
+        map_scenes.insert(scene.id.clone(), map);
+        bundle_scenes.push(scene);
+    }
 
-    let mut assets: Vec<BundleAsset> = scenes
+    // (シーン, そのシーンから見たアセット)。同じパスでもシーンの置き場所で解決先が変わる
+    let mut referenced: Vec<(usize, BundleAsset)> = scenes
         .iter()
-        .flat_map(|s| {
-            let md = &s.parsed.scene;
-            let mut a = Vec::new();
-            if let Some(bg) = &md.background {
-                a.push(BundleAsset::Background { path: bg.clone() });
-            }
-            if let Some(bgm) = &md.bgm {
-                a.push(BundleAsset::Bgm { path: bgm.clone() });
-            }
-            a
+        .enumerate()
+        .flat_map(|(i, s)| scene_assets(&s.parsed).into_iter().map(move |a| (i, s, a)))
+        .map(|(i, scene, asset)| {
+            let path = asset.path.to_string();
+            let kind = match asset.key {
+                "background" => BundleAssetKind::Background { path },
+                "bgm" => BundleAssetKind::Bgm { path },
+                _ => BundleAssetKind::Se { path },
+            };
+            (i, BundleAsset::new(kind, &scene.path, asset.path))
         })
         .collect();
+    let characters = SceneCharacters::load(scenes);
+    referenced.extend(sprite_assets(scenes, &characters));
+    let mut assets: Vec<BundleAsset> = referenced.iter().map(|(_, a)| a.clone()).collect();
     assets.sort();
     assets.dedup();
+    fill_asset_files(scenes, &mut assets);
+    let asset_refs = asset_refs(&scene_ids, &referenced, &assets);
 
     let title = scenes[entry_idx]
         .parsed
@@ -239,11 +677,309 @@ fn build_bundle(scenes: &[LoadedScene], entry: &Path) -> StoryBundle {
         story_build_id: String::new(),
         title,
         entry_scene_id: scene_ids[entry_idx].clone(),
+        entry_scene_ids: entry_indices
+            .iter()
+            .map(|&i| scene_ids[i].clone())
+            .collect(),
         scenes: bundle_scenes,
         assets,
+        characters: bundle_characters(scenes, &characters, options.all_characters),
+        endings: bundle_endings(scenes),
+        achievements: bundle_achievements(scenes, &scene_ids, &layouts),
+        chapters: bundle_chapters(scenes, &scene_ids),
+        asset_refs,
     };
     bundle.story_build_id = compute_build_id(&bundle);
-    bundle
+    let source_map = SourceMap {
+        schema_version: SCHEMA_VERSION.to_string(),
+        story_build_id: bundle.story_build_id.clone(),
+        scenes: map_scenes,
+    };
+    (bundle, source_map)
+}
+
+/// 各シーンに適用される characters.yaml（SPEC 2.1: 最も近いもの）
+struct SceneCharacters {
+    /// 読み込んだ characters.yaml（重複なし、最初に使われた順）
+    files: Vec<Characters>,
+    /// シーン i が使う `files` のインデックス。見つからなければ None
+    of_scene: Vec<Option<usize>>,
+}
+
+impl SceneCharacters {
+    fn load(scenes: &[LoadedScene]) -> Self {
+        // check 済みなので読み込みに失敗した characters.yaml はない（見つからないことはある）
+        let mut index: HashMap<PathBuf, Option<usize>> = HashMap::new();
+        let mut files = Vec::new();
+        let of_scene = scenes
+            .iter()
+            .map(|scene| {
+                let path = find_characters_file(&scene.path)?;
+                *index.entry(path).or_insert_with_key(|p| {
+                    let chars = load_characters(p).ok()?;
+                    files.push(chars);
+                    Some(files.len() - 1)
+                })
+            })
+            .collect();
+        Self { files, of_scene }
+    }
+
+    fn of(&self, scene: usize) -> Option<&Characters> {
+        self.of_scene[scene].map(|i| &self.files[i])
+    }
+}
+
+/// シナリオに登場するキャラクター（話者・show / hide）の立ち絵を、
+/// 参照するシーンの番号とともにすべて集める
+fn sprite_assets(
+    scenes: &[LoadedScene],
+    characters: &SceneCharacters,
+) -> Vec<(usize, BundleAsset)> {
+    let mut out = Vec::new();
+    for (i, scene) in scenes.iter().enumerate() {
+        let Some(chars) = characters.of(i) else {
+            continue;
+        };
+        for character in scene_characters(&scene.parsed.scene) {
+            let Some(expressions) = chars.expressions.get(character) else {
+                continue;
+            };
+            for (expression, sprite) in expressions {
+                let kind = BundleAssetKind::Sprite {
+                    character: character.to_string(),
+                    expression: expression.clone(),
+                    path: sprite.path.clone(),
+                };
+                out.push((i, BundleAsset::new(kind, &chars.path, &sprite.path)));
+            }
+        }
+    }
+    out
+}
+
+/// シーンごとに、書かれたままの参照から解決先のアセットの同一性を引く表を作る。
+/// `assets` は `referenced` を重複除去して `file` を埋めたもの
+fn asset_refs(
+    scene_ids: &[String],
+    referenced: &[(usize, BundleAsset)],
+    assets: &[BundleAsset],
+) -> BTreeMap<String, BTreeMap<String, String>> {
+    let mut refs: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+    for (i, asset) in referenced {
+        let Some(identity) = assets
+            .iter()
+            .find(|a| a.kind == asset.kind && a.source == asset.source)
+            .and_then(BundleAsset::identity)
+        else {
+            continue;
+        };
+        refs.entry(scene_ids[*i].clone())
+            .or_default()
+            .insert(asset.kind.ref_key(), identity);
+    }
+    refs
+}
+
+/// 実在するアセットに、プロジェクトのルートからの相対パス・サイズ・ハッシュを
+/// 埋める。ルートはシーンとアセットの共通の祖先ディレクトリなので、
+/// プロジェクトを置く場所が変わっても `file` は変わらない
+fn fill_asset_files(scenes: &[LoadedScene], assets: &mut [BundleAsset]) {
+    let dirs = scenes
+        .iter()
+        .filter_map(|s| s.canon.parent())
+        .chain(assets.iter().filter_map(|a| a.source.as_deref()?.parent()));
+    let Some(root) = common_root(dirs) else {
+        return;
+    };
+    for asset in assets {
+        let Some(source) = &asset.source else {
+            continue;
+        };
+        let Ok(bytes) = std::fs::read(source) else {
+            continue;
+        };
+        asset.file = source
+            .strip_prefix(&root)
+            .ok()
+            .map(|p| p.to_string_lossy().replace('\\', "/"));
+        asset.size = Some(bytes.len() as u64);
+        asset.hash = Some(format!("{:016x}", fnv1a64(&bytes)));
+    }
+}
+
+/// bundle の `assets` を `out_dir` の下に `file` の配置でコピーし、コピーした
+/// ファイル数を返す（`--assets-out`）。同じファイルを指す参照は 1 回だけ書く。
+/// コピー先が素材そのもの（`out_dir` がプロジェクトの根元と重なる）なら書かない。
+/// 自分自身へのコピーは素材を空にしてしまう
+pub fn copy_assets(bundle: &StoryBundle, out_dir: &Path) -> std::io::Result<usize> {
+    let mut written = std::collections::BTreeSet::new();
+    for asset in &bundle.assets {
+        let (Some(source), Some(file)) = (&asset.source, &asset.file) else {
+            continue;
+        };
+        if !written.insert(file.as_str()) {
+            continue;
+        }
+        let dest = out_dir.join(file);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if let (Ok(from), Ok(to)) = (Path::new(source).canonicalize(), dest.canonicalize())
+            && from == to
+        {
+            continue;
+        }
+        std::fs::copy(source, &dest)?;
+    }
+    Ok(written.len())
+}
+
+/// characters.yaml の宣言を bundle の `characters` にする（SPEC 2.1）。
+///
+/// `all` が false なら登場するキャラクターだけ、true なら宣言済みの全員を
+/// 名前順に並べる。同じ名前が複数の characters.yaml にあれば先に使われた方を採る
+fn bundle_characters(
+    scenes: &[LoadedScene],
+    characters: &SceneCharacters,
+    all: bool,
+) -> Vec<BundleCharacter> {
+    let mut out: BTreeMap<String, BundleCharacter> = BTreeMap::new();
+    for (i, scene) in scenes.iter().enumerate() {
+        let Some(chars) = characters.of(i) else {
+            continue;
+        };
+        let names: Vec<&str> = if all {
+            chars.entries.keys().map(String::as_str).collect()
+        } else {
+            scene_characters(&scene.parsed.scene)
+                .into_iter()
+                .filter(|name| chars.contains(name))
+                .collect()
+        };
+        for name in names {
+            if out.contains_key(name) {
+                continue;
+            }
+            out.insert(
+                name.to_string(),
+                BundleCharacter::from_entry(name, &chars.entries[name]),
+            );
+        }
+    }
+    out.into_values().collect()
+}
+
+/// endings.yaml の宣言とシナリオで使われた ending を bundle の `endings` にする
+/// （SPEC 2.3）。同じ id が複数の endings.yaml にあれば先に使われた方を採る
+fn bundle_endings(scenes: &[LoadedScene]) -> Vec<BundleEnding> {
+    // check 済みなので読み込みに失敗した endings.yaml はない（見つからないことはある）
+    let mut files = Vec::new();
+    let mut seen_files = std::collections::HashSet::new();
+    let mut out: BTreeMap<String, BundleEnding> = BTreeMap::new();
+    for scene in scenes {
+        if let Some(path) = find_endings_file(&scene.path)
+            && seen_files.insert(path.clone())
+            && let Ok(endings) = load_endings(&path)
+        {
+            files.push(endings);
+        }
+    }
+    for endings in &files {
+        for (id, meta) in &endings.entries {
+            out.entry(id.clone()).or_insert_with(|| BundleEnding {
+                id: id.clone(),
+                title: meta.title.clone().unwrap_or_else(|| id.clone()),
+                kind: meta.kind,
+                order: meta.order,
+                declared: true,
+            });
+        }
+    }
+    for scene in scenes {
+        let md = &scene.parsed.scene;
+        let blocks = md
+            .lead
+            .iter()
+            .chain(md.sections.iter().flat_map(|s| &s.blocks));
+        for block in blocks {
+            if let Block::Ending { id, .. } = block {
+                out.entry(id.clone()).or_insert_with(|| BundleEnding {
+                    id: id.clone(),
+                    title: id.clone(),
+                    kind: None,
+                    order: None,
+                    declared: false,
+                });
+            }
+        }
+    }
+    let mut endings: Vec<BundleEnding> = out.into_values().collect();
+    // order のないものは最後（id 順は BTreeMap の順を安定ソートで保つ）
+    endings.sort_by_key(|e| e.order.unwrap_or(u32::MAX));
+    endings
+}
+
+/// 開始シーンに適用される achievements.yaml の実績を bundle の
+/// `achievements` にする（SPEC 2.4）
+fn bundle_achievements(
+    scenes: &[LoadedScene],
+    scene_ids: &[String],
+    layouts: &[Vec<usize>],
+) -> Vec<BundleAchievement> {
+    // check 済みなので読み込めない・参照が壊れた achievements.yaml はない
+    let Some(file) =
+        find_achievements_file(&scenes[0].path).and_then(|path| load_achievements(&path).ok())
+    else {
+        return Vec::new();
+    };
+    file.entries
+        .into_iter()
+        .map(|(id, achievement)| BundleAchievement {
+            title: achievement.title.unwrap_or_else(|| id.clone()),
+            id,
+            description: achievement.description,
+            conditions: achievement
+                .when
+                .iter()
+                .filter_map(|c| achievements::resolve(scenes, c).ok())
+                .map(|resolved| match resolved {
+                    Resolved::Ending(id) => BundleCondition::Ending { id },
+                    Resolved::Section(scene, section) => BundleCondition::Section {
+                        target: StepTarget {
+                            scene_id: scene_ids[scene].clone(),
+                            step_index: layouts[scene][section + 1],
+                        },
+                    },
+                    Resolved::Choice(label, scene) => BundleCondition::Choice {
+                        label,
+                        scene_id: scene.map(|s| scene_ids[s].clone()),
+                    },
+                })
+                .collect(),
+            endings: achievement.endings,
+        })
+        .collect()
+}
+
+/// 開始シーンに適用される chapters.yaml の章を bundle の `chapters` にする（SPEC 2.5）
+fn bundle_chapters(scenes: &[LoadedScene], scene_ids: &[String]) -> Vec<BundleChapter> {
+    project_chapters(scenes)
+        .into_iter()
+        .map(|chapter| BundleChapter {
+            title: chapter.title.unwrap_or_else(|| chapter.id.clone()),
+            id: chapter.id,
+            entry: StepTarget {
+                scene_id: scene_ids[chapter.entry].clone(),
+                step_index: 0,
+            },
+            scene_ids: chapter
+                .scenes
+                .iter()
+                .map(|&i| scene_ids[i].clone())
+                .collect(),
+        })
+        .collect()
 }
 
 /// セグメント（`seg` 0 = リード部、`seg` n = `sections[n-1]`）の開始 step
@@ -266,72 +1002,195 @@ fn build_scene(
     scenes: &[LoadedScene],
     scene_ids: &[String],
     layouts: &[Vec<usize>],
-) -> BundleScene {
+    strip_source: bool,
+) -> (BundleScene, Vec<SourceMapStep>) {
     let md = &loaded.parsed.scene;
     let file = loaded.path.display().to_string();
-    let blocks = md
-        .lead
-        .iter()
-        .chain(md.sections.iter().flat_map(|s| s.blocks.iter()));
-    let steps = blocks
-        .map(|block| build_step(block, &file, scenes, scene_ids, layouts, idx))
-        .collect();
+    // (ブロック, 属するセクション)。リード部は None
+    let blocks = md.lead.iter().map(|b| (b, None)).chain(
+        md.sections
+            .iter()
+            .flat_map(|s| s.blocks.iter().map(move |b| (b, Some(s)))),
+    );
+    let mut steps = Vec::new();
+    let mut map = Vec::new();
+    for (i, (block, section)) in blocks.enumerate() {
+        let anchor = section.map(|s| s.anchor.clone());
+        // 取り込んだフラグメントの行はフラグメントのファイルと行で示す（SPEC 4.8）
+        let locate = |line: usize, range: Option<&SourceRange>| {
+            let (actual, actual_line) = loaded.parsed.locate(line);
+            SourceLocation {
+                file: if actual == loaded.path {
+                    file.clone()
+                } else {
+                    actual.display().to_string()
+                },
+                line: actual_line,
+                column: range.map(|r| r.column),
+                end_line: range.map(|r| loaded.parsed.locate(r.end_line).1),
+                end_column: range.map(|r| r.end_column),
+                anchor: anchor.clone(),
+            }
+        };
+        let location = locate(block_line(block), loaded.parsed.block_ranges.get(i));
+        let items: Vec<SourceLocation> = match block {
+            Block::Choices { items, .. } => items
+                .iter()
+                .map(|item| locate(item.line, loaded.parsed.item_ranges.get(&item.line)))
+                .collect(),
+            Block::Random { items, .. } => items
+                .iter()
+                .map(|item| locate(item.line, loaded.parsed.item_ranges.get(&item.line)))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let keep = |l: &SourceLocation| (!strip_source).then(|| l.clone());
+        let item_sources: Vec<Option<SourceLocation>> = items.iter().map(keep).collect();
+        steps.push(build_step(
+            block,
+            keep(&location),
+            &item_sources,
+            scenes,
+            scene_ids,
+            layouts,
+            idx,
+        ));
+        map.push(SourceMapStep {
+            location,
+            heading: section.map(|s| s.heading.clone()),
+            items,
+        });
+    }
 
-    BundleScene {
+    let scene = BundleScene {
         id: scene_ids[idx].clone(),
         title: md.title.clone(),
-        source: SourceLocation {
+        background: md.background.clone(),
+        bgm: md.bgm.clone(),
+        source: (!strip_source).then(|| SourceLocation {
             file: file.clone(),
             line: 1,
-        },
+            column: None,
+            end_line: None,
+            end_column: None,
+            anchor: None,
+        }),
         steps,
+    };
+    (scene, map)
+}
+
+/// ブロックの開始行
+fn block_line(block: &Block) -> usize {
+    match block {
+        Block::Narration { line, .. }
+        | Block::Dialogue { line, .. }
+        | Block::Choices { line, .. }
+        | Block::Random { line, .. }
+        | Block::Jump { line, .. }
+        | Block::Call { line, .. }
+        | Block::Return { line }
+        | Block::Ending { line, .. }
+        | Block::Background { line, .. }
+        | Block::Bgm { line, .. }
+        | Block::BgmStop { line }
+        | Block::Se { line, .. }
+        | Block::Show { line, .. }
+        | Block::Hide { line, .. } => *line,
     }
 }
 
+/// `source` はブロックの位置、`item_sources` は選択肢・ランダム分岐の各項目の位置
+/// （`--strip-source` ではどちらも None）
 fn build_step(
     block: &Block,
-    file: &str,
+    source: Option<SourceLocation>,
+    item_sources: &[Option<SourceLocation>],
     scenes: &[LoadedScene],
     scene_ids: &[String],
     layouts: &[Vec<usize>],
     current: usize,
 ) -> BundleStep {
-    let src = |line: usize| SourceLocation {
-        file: file.to_string(),
-        line,
-    };
     match block {
-        Block::Narration { text, line } => BundleStep::Narration {
+        Block::Narration { text, .. } => BundleStep::Narration {
             text: text.clone(),
-            source: src(*line),
+            source,
         },
         Block::Dialogue {
             speaker,
+            expression,
             text,
-            line,
+            ..
         } => BundleStep::Dialogue {
             speaker: speaker.clone(),
+            display_name: scenes[current].display_name(speaker).to_string(),
+            expression: expression.clone(),
             text: text.clone(),
-            source: src(*line),
+            source,
         },
-        Block::Ending { id, line } => BundleStep::Ending {
+        Block::Show {
+            character,
+            expression,
+            ..
+        } => BundleStep::Show {
+            character: character.clone(),
+            expression: expression.clone(),
+            source,
+        },
+        Block::Hide { character, .. } => BundleStep::Hide {
+            character: character.clone(),
+            source,
+        },
+        Block::Ending { id, .. } => BundleStep::Ending {
             id: id.clone(),
-            source: src(*line),
+            source,
+        },
+        Block::Background { path, .. } => BundleStep::Background {
+            path: path.clone(),
+            source,
+        },
+        Block::Bgm { path, .. } => BundleStep::Bgm {
+            path: path.clone(),
+            source,
         },
-        Block::Jump { target, line, .. } => BundleStep::Jump {
+        Block::BgmStop { .. } => BundleStep::BgmStop { source },
+        Block::Se { path, .. } => BundleStep::Se {
+            path: path.clone(),
+            source,
+        },
+        Block::Jump { target, .. } => BundleStep::Jump {
+            target: resolve_target(scenes, scene_ids, layouts, current, target),
+            source,
+        },
+        Block::Call { target, .. } => BundleStep::Call {
             target: resolve_target(scenes, scene_ids, layouts, current, target),
-            source: src(*line),
+            source,
+        },
+        Block::Return { .. } => BundleStep::Return { source },
+        Block::Choices { items, .. } => BundleStep::Choice {
+            items: items
+                .iter()
+                .zip(item_sources)
+                .map(|(item, item_source)| ChoiceOption {
+                    label: item.label.clone(),
+                    target: resolve_target(scenes, scene_ids, layouts, current, &item.target),
+                    source: item_source.clone(),
+                })
+                .collect(),
+            source,
         },
-        Block::Choices { items, line } => BundleStep::Choice {
+        Block::Random { items, .. } => BundleStep::Random {
             items: items
                 .iter()
-                .map(|item| ChoiceOption {
+                .zip(item_sources)
+                .map(|(item, item_source)| RandomOption {
                     label: item.label.clone(),
+                    weight: item.weight,
                     target: resolve_target(scenes, scene_ids, layouts, current, &item.target),
-                    source: src(item.line),
+                    source: item_source.clone(),
                 })
                 .collect(),
-            source: src(*line),
+            source,
         },
     }
 }
@@ -345,6 +1204,24 @@ fn resolve_target(
     current: usize,
     target: &LinkTarget,
 ) -> StepTarget {
+    let (scene_idx, section_idx) = resolve_link(scenes, current, target);
+    let step_index = match section_idx {
+        None => 0,
+        Some(section_idx) => layouts[scene_idx][section_idx + 1],
+    };
+    StepTarget {
+        scene_id: scene_ids[scene_idx].clone(),
+        step_index,
+    }
+}
+
+/// リンク先を（シーンの添字, セクションの添字）に解決する。セクションが
+/// None ならリード部の先頭。check_path を通過済みという前提でのみ呼ばれる
+pub(super) fn resolve_link(
+    scenes: &[LoadedScene],
+    current: usize,
+    target: &LinkTarget,
+) -> (usize, Option<usize>) {
     let scene_idx = match &target.file {
         None => current,
         Some(file) => {
@@ -357,23 +1234,169 @@ fn resolve_target(
                 .expect("check済みのリンク先ファイルは読み込み済み")
         }
     };
-    let step_index = match &target.anchor {
-        None => 0,
-        Some(anchor) => {
-            let section_idx = scenes[scene_idx]
-                .parsed
-                .scene
-                .sections
-                .iter()
-                .position(|s| s.anchor == *anchor)
-                .expect("check済みのアンカーは解決できる");
-            layouts[scene_idx][section_idx + 1]
-        }
+    let section_idx = target.anchor.as_ref().map(|anchor| {
+        scenes[scene_idx]
+            .parsed
+            .scene
+            .sections
+            .iter()
+            .position(|s| s.anchor == *anchor)
+            .expect("check済みのアンカーは解決できる")
+    });
+    (scene_idx, section_idx)
+}
+
+// -------------------------------------------------------------- 分割出力（--split）
+
+/// シーンごとに分割した StoryBundle（`--split`）。長い作品でも player が
+/// 最初に読むのは小さな [`BundleIndex`] だけで済み、各シーンは
+/// [`StepTarget::scene_id`] から引いた `file` を必要になったときに読む
+#[derive(Debug, Clone)]
+pub struct SplitBundle {
+    pub index: BundleIndex,
+    pub chunks: Vec<SceneChunk>,
+}
+
+/// 分割出力の目次（`index.json`）。`scenes` 以外は [`StoryBundle`] と同じ
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleIndex {
+    pub schema_version: String,
+    pub story_build_id: String,
+    pub title: String,
+    pub entry_scene_id: String,
+    pub entry_scene_ids: Vec<String>,
+    /// シーン ID と、そのシーンを収めたファイル（出現順）
+    pub scenes: Vec<BundleSceneRef>,
+    pub assets: Vec<BundleAsset>,
+    pub characters: Vec<BundleCharacter>,
+    pub endings: Vec<BundleEnding>,
+    pub achievements: Vec<BundleAchievement>,
+    pub chapters: Vec<BundleChapter>,
+}
+
+/// 目次の 1 シーン
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BundleSceneRef {
+    pub id: String,
+    /// index.json からの相対パス（`scenes/<シーン ID>-<内容のハッシュ>.json`）
+    pub file: String,
+}
+
+/// 1 シーンぶんのファイル。中身は [`BundleScene`] の JSON そのもの
+#[derive(Debug, Clone)]
+pub struct SceneChunk {
+    pub file: String,
+    pub scene: BundleScene,
+}
+
+/// StoryBundle をシーンごとに分割する。
+///
+/// ファイル名はシーン ID と、そのシーンの内容から計算したハッシュで決まる。
+/// 中身が変わらないシーンは別のビルドでも同じ名前になるため、player 側で
+/// ファイル単位にキャッシュできる（`story_build_id` はファイル名に含めない）
+pub fn split_bundle(bundle: &StoryBundle) -> SplitBundle {
+    let chunks: Vec<SceneChunk> = bundle
+        .scenes
+        .iter()
+        .map(|scene| {
+            let bytes = serde_json::to_vec(scene).expect("シリアライズに失敗しない");
+            let hash = format!("{:016x}", fnv1a64(&bytes));
+            SceneChunk {
+                file: format!("scenes/{}-{}.json", file_safe(&scene.id), &hash[..8]),
+                scene: scene.clone(),
+            }
+        })
+        .collect();
+    let index = BundleIndex {
+        schema_version: bundle.schema_version.clone(),
+        story_build_id: bundle.story_build_id.clone(),
+        title: bundle.title.clone(),
+        entry_scene_id: bundle.entry_scene_id.clone(),
+        entry_scene_ids: bundle.entry_scene_ids.clone(),
+        scenes: chunks
+            .iter()
+            .map(|c| BundleSceneRef {
+                id: c.scene.id.clone(),
+                file: c.file.clone(),
+            })
+            .collect(),
+        assets: bundle.assets.clone(),
+        characters: bundle.characters.clone(),
+        endings: bundle.endings.clone(),
+        achievements: bundle.achievements.clone(),
+        chapters: bundle.chapters.clone(),
     };
-    StepTarget {
-        scene_id: scene_ids[scene_idx].clone(),
-        step_index,
+    SplitBundle { index, chunks }
+}
+
+/// bundle を分割して `dir` に `index.json` とシーンごとの JSON を書き出す
+/// （`--split`）。`dir` はなければ作る。
+///
+/// 前回の `index.json` が載せていたシーンのファイルのうち、今回使わないものは
+/// 消す。ファイル名にハッシュが入るため、消さないと編集のたびに古いシーンが
+/// 溜まっていく。目次にないファイルには触れない
+pub fn write_split_bundle(bundle: &StoryBundle, dir: &Path) -> std::io::Result<SplitBundle> {
+    let split = split_bundle(bundle);
+    let index_path = dir.join("index.json");
+    let current: BTreeSet<&str> = split.chunks.iter().map(|c| c.file.as_str()).collect();
+    for file in previous_split_files(&index_path) {
+        if !current.contains(file.as_str()) {
+            match std::fs::remove_file(dir.join(&file)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
     }
+
+    for chunk in &split.chunks {
+        let path = dir.join(&chunk.file);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(&chunk.scene).expect("シリアライズに失敗しない");
+        std::fs::write(path, json)?;
+    }
+    let json = serde_json::to_string_pretty(&split.index).expect("シリアライズに失敗しない");
+    std::fs::write(index_path, json)?;
+    Ok(split)
+}
+
+/// 前回の `index.json` が載せていたシーンのファイル。読めなければ空。
+/// 書き出し先の外を消さないよう、`scenes/` 直下の `.json` だけを返す
+fn previous_split_files(index_path: &Path) -> Vec<String> {
+    let Ok(text) = std::fs::read_to_string(index_path) else {
+        return Vec::new();
+    };
+    let Ok(index) = serde_json::from_str::<serde_json::Value>(&text) else {
+        return Vec::new();
+    };
+    index["scenes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|scene| scene["file"].as_str())
+        .filter(|file| {
+            file.strip_prefix("scenes/").is_some_and(|name| {
+                name.ends_with(".json") && !name.contains(['/', '\\']) && !name.starts_with('.')
+            })
+        })
+        .map(String::from)
+        .collect()
+}
+
+/// シーン ID をファイル名に使える形にする。英数字・`-`・`_` 以外は `_` に
+/// 置き換える（衝突してもハッシュで区別される）
+fn file_safe(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
 }
 
 // -------------------------------------------------------------- story_build_id
@@ -386,14 +1409,19 @@ fn compute_build_id(bundle: &StoryBundle) -> String {
         "schemaVersion": bundle.schema_version,
         "title": bundle.title,
         "entrySceneId": bundle.entry_scene_id,
+        "entrySceneIds": bundle.entry_scene_ids,
         "scenes": bundle.scenes,
         "assets": bundle.assets,
+        "characters": bundle.characters,
+        "endings": bundle.endings,
+        "achievements": bundle.achievements,
+        "chapters": bundle.chapters,
     });
     let bytes = serde_json::to_vec(&payload).expect("シリアライズに失敗しない");
     format!("{:016x}", fnv1a64(&bytes))
 }
 
-fn fnv1a64(bytes: &[u8]) -> u64 {
+pub(super) fn fnv1a64(bytes: &[u8]) -> u64 {
     let mut hash: u64 = 0xcbf29ce484222325;
     for &b in bytes {
         hash ^= u64::from(b);
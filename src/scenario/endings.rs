@@ -0,0 +1,189 @@
+//! endings.yaml の探索と読み込み（SPEC 2.3）
+//!
+//! エンディングのメタデータ（ギャラリー画面のタイトル・種類・解放順）の宣言
+//! ファイル。characters.yaml と同じく、シナリオファイルと同じディレクトリ、
+//! またはその祖先ディレクトリに置かれ、最も近いものが使われる。
+//! characters.yaml と違って置かなくてもよい（見つからなくても警告しない）。
+
+use super::characters::key_line;
+use super::project::find_nearest;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// 読み込んだエンディング定義
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endings {
+    /// 定義ファイルのパス
+    pub path: PathBuf,
+    /// ending id → メタデータ
+    pub entries: BTreeMap<String, EndingMeta>,
+    /// ending id → endings.yaml 内の行番号（見つけられた id だけ）
+    pub lines: BTreeMap<String, usize>,
+}
+
+/// 1 つのエンディングのメタデータ
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EndingMeta {
+    /// ギャラリーに出すタイトル（`title:`）
+    pub title: Option<String>,
+    /// エンディングの種類（`type:`）
+    pub kind: Option<EndingKind>,
+    /// ギャラリーでの解放順（`order:`、小さいほど先）
+    pub order: Option<u32>,
+}
+
+/// エンディングの種類（`type: good | bad | true | normal`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EndingKind {
+    Good,
+    Bad,
+    True,
+    Normal,
+}
+
+impl EndingKind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "good" => Some(Self::Good),
+            "bad" => Some(Self::Bad),
+            "true" => Some(Self::True),
+            "normal" => Some(Self::Normal),
+            _ => None,
+        }
+    }
+}
+
+impl Endings {
+    pub fn contains(&self, id: &str) -> bool {
+        self.entries.contains_key(id)
+    }
+}
+
+/// シナリオファイルから最も近い endings.yaml（探し方は characters.yaml と同じ）
+pub fn find_endings_file(scene_path: &Path) -> Option<PathBuf> {
+    find_nearest(scene_path, "endings.yaml")
+}
+
+/// endings.yaml を読み込む。形式エラーはメッセージ文字列で返す
+pub fn load_endings(path: &Path) -> Result<Endings, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("{} を読み込めません: {}", path.display(), e))?;
+    parse_endings(&source, path)
+}
+
+fn parse_endings(source: &str, path: &Path) -> Result<Endings, String> {
+    let value: serde_yaml::Value = serde_yaml::from_str(source)
+        .map_err(|e| format!("{} の YAML が解析できません: {}", path.display(), e))?;
+    let mapping = value
+        .get("endings")
+        .and_then(|v| v.as_mapping())
+        .ok_or_else(|| {
+            format!(
+                "{} に `endings:` マッピングがありません。`endings:` の下に ending id を並べてください",
+                path.display()
+            )
+        })?;
+    let mut entries = BTreeMap::new();
+    let mut lines = BTreeMap::new();
+    for (key, val) in mapping {
+        let id = key
+            .as_str()
+            .ok_or_else(|| format!("{} の ending id が文字列ではありません", path.display()))?;
+        if let Some(line) = key_line(source, 1, id) {
+            lines.insert(id.to_string(), line);
+        }
+        let meta = parse_meta(val).map_err(|e| format!("{} の「{id}」の {e}", path.display()))?;
+        entries.insert(id.to_string(), meta);
+    }
+    Ok(Endings {
+        path: path.to_path_buf(),
+        entries,
+        lines,
+    })
+}
+
+/// 1 エンディングの `title` / `type` / `order` を読む。値のない宣言（`id: {}`）も許す
+fn parse_meta(value: &serde_yaml::Value) -> Result<EndingMeta, String> {
+    if value.is_null() {
+        return Ok(EndingMeta::default());
+    }
+    if !value.is_mapping() {
+        return Err("宣言は `title` / `type` / `order` のマッピングで書いてください".to_string());
+    }
+    let title = match value.get("title") {
+        None => None,
+        Some(v) => Some(
+            v.as_str()
+                .ok_or_else(|| "title は文字列で書いてください".to_string())?
+                .to_string(),
+        ),
+    };
+    let kind = match value.get("type") {
+        None => None,
+        // YAML では引用符なしの `true` が真偽値になるため、それも true エンディングとして読む
+        Some(v) => Some(
+            v.as_str()
+                .and_then(EndingKind::from_name)
+                .or_else(|| (v.as_bool() == Some(true)).then_some(EndingKind::True))
+                .ok_or_else(|| {
+                    "type は good / bad / true / normal のいずれかで書いてください".to_string()
+                })?,
+        ),
+    };
+    let order = match value.get("order") {
+        None => None,
+        Some(v) => Some(
+            v.as_u64()
+                .and_then(|n| u32::try_from(n).ok())
+                .ok_or_else(|| "order は 0 以上の整数で書いてください".to_string())?,
+        ),
+    };
+    Ok(EndingMeta { title, kind, order })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endings_yamlを解析できる() {
+        let src = "endings:\n  childhood_route:\n    title: 幼なじみとの約束\n    type: good\n    order: 1\n  calm_route: {}\n";
+        let endings = parse_endings(src, Path::new("endings.yaml")).unwrap();
+        let childhood = &endings.entries["childhood_route"];
+        assert_eq!(childhood.title.as_deref(), Some("幼なじみとの約束"));
+        assert_eq!(childhood.kind, Some(EndingKind::Good));
+        assert_eq!(childhood.order, Some(1));
+        assert_eq!(endings.entries["calm_route"], EndingMeta::default());
+        assert_eq!(endings.lines["calm_route"], 6);
+        assert!(!endings.contains("sprint_route"));
+    }
+
+    #[test]
+    fn 引用符なしのtrueもtrueエンディングとして読む() {
+        let src = "endings:\n  a:\n    type: true\n  b:\n    type: \"true\"\n";
+        let endings = parse_endings(src, Path::new("e.yaml")).unwrap();
+        assert_eq!(endings.entries["a"].kind, Some(EndingKind::True));
+        assert_eq!(endings.entries["b"].kind, Some(EndingKind::True));
+    }
+
+    #[test]
+    fn 未知のtypeはエラーになる() {
+        let src = "endings:\n  a:\n    type: happy\n";
+        let err = parse_endings(src, Path::new("e.yaml")).unwrap_err();
+        assert!(err.contains("「a」の type"), "{err}");
+    }
+
+    #[test]
+    fn 負のorderはエラーになる() {
+        let src = "endings:\n  a:\n    order: -1\n";
+        let err = parse_endings(src, Path::new("e.yaml")).unwrap_err();
+        assert!(err.contains("order"), "{err}");
+    }
+
+    #[test]
+    fn endingsキーがないとエラーになる() {
+        let err = parse_endings("routes:\n  a: {}\n", Path::new("e.yaml")).unwrap_err();
+        assert!(err.contains("endings"));
+    }
+}
@@ -5,6 +5,10 @@
 //! 進んだ結果を**どう記録するか**（trace は 1 経路の逐次ログ、routes は
 //! 選択肢ごとに枝分かれした探索木）だけなので、進み方そのものはここに
 //! 共通化する。
+//!
+//! `<!-- call: -->` / `<!-- return -->`（SPEC 4.9）の戻り先は、呼び出し側が
+//! 持つ呼び出しスタック（`Vec<Cursor>`）に積む。ランダム分岐（SPEC 4.10）の
+//! 抽選も、同じシードなら同じ結果になるようここで決める。
 
 use super::project::{LoadedScene, resolve_sibling};
 use super::{Block, LinkTarget, Scene};
@@ -67,6 +71,46 @@ pub(super) fn goto(cursor: &mut Cursor, scenes: &[LoadedScene], target: &LinkTar
     }
 }
 
+/// [`call`] の結果
+pub(super) enum CallResult {
+    /// 戻り先を積んで呼び出し先へ移った
+    Entered(GotoResult),
+    /// 同じ `call` が戻る前にもう一度実行された。変数を持たない v1 では
+    /// 同じ呼び出しを繰り返し続けるため、移動せずに止める（unbounded-recursion）
+    Recursion,
+}
+
+/// `cursor` が指す `<!-- call: -->` を実行する。戻り先（このブロックの次）を
+/// `stack` に積んでから呼び出し先へ移る
+pub(super) fn call(
+    cursor: &mut Cursor,
+    stack: &mut Vec<Cursor>,
+    scenes: &[LoadedScene],
+    target: &LinkTarget,
+) -> CallResult {
+    let back = Cursor {
+        block: cursor.block + 1,
+        ..*cursor
+    };
+    if stack.contains(&back) {
+        return CallResult::Recursion;
+    }
+    stack.push(back);
+    CallResult::Entered(goto(cursor, scenes, target))
+}
+
+/// `<!-- return -->` を実行する。直近の戻り先へ移り、呼び出し中でなければ
+/// （スタックが空なら）false
+pub(super) fn ret(cursor: &mut Cursor, stack: &mut Vec<Cursor>) -> bool {
+    match stack.pop() {
+        Some(back) => {
+            *cursor = back;
+            true
+        }
+        None => false,
+    }
+}
+
 /// カーソルが指すセグメント（リード部 or セクション）のブロック列
 pub(super) fn segment_blocks(scene: &Scene, seg: usize) -> &[Block] {
     if seg == 0 {
@@ -94,3 +138,34 @@ pub(super) fn format_choices(choices: &[usize]) -> String {
         .collect::<Vec<_>>()
         .join(",")
 }
+
+/// ランダム分岐（SPEC 4.10）の抽選に使う擬似乱数（SplitMix64）。
+/// 同じシードからは常に同じ列を返すので、trace の `--seed` で結果を再現できる
+pub(super) struct Rng(u64);
+
+impl Rng {
+    pub(super) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// 重みに比例した確率で項目を 1 つ選ぶ（0 始まり）。`weights` は空でない
+    pub(super) fn pick(&mut self, weights: &[u32]) -> usize {
+        let total: u64 = weights.iter().map(|&w| u64::from(w)).sum();
+        let mut rest = self.next_u64() % total.max(1);
+        for (i, &weight) in weights.iter().enumerate() {
+            if rest < u64::from(weight) {
+                return i;
+            }
+            rest -= u64::from(weight);
+        }
+        weights.len() - 1
+    }
+}
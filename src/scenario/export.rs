@@ -0,0 +1,134 @@
+//! `compile --target ink` / `twee` / `yarn` / `tyrano`: 他エンジンの記法への書き出し
+//!
+//! StoryBundle（[`compile_path`](super::compile_path)）と同じ実行前検証を
+//! 通ったプロジェクトを、他のエンジンのソースにする。ink / twee / yarn は
+//! 1 ファイルのテキスト、tyrano はシーンごとのファイルになる。bundle は
+//! セクションを平坦化してしまうため、書き出しは bundle ではなく読み込んだ
+//! シーン（H2 セクションつき）から行う。各エンジンの記法への対応は backend
+//! ごとのモジュール（`ink.rs` / `twee.rs` / `yarn.rs` / `tyrano.rs`）が持つ。
+
+use super::check::CheckResult;
+use super::compile::load_compilable;
+use super::{ink, twee, tyrano, yarn};
+use std::path::{Path, PathBuf};
+
+/// 書き出し先のエンジン
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportTarget {
+    /// inkle の Ink（`.ink`）
+    Ink,
+    /// Twine の Twee 3（`.twee`）
+    Twee,
+    /// Yarn Spinner（`.yarn`）
+    Yarn,
+    /// TyranoScript（シーンごとの `.ks`）
+    Tyrano,
+}
+
+impl ExportTarget {
+    /// `--target` の名前から引く
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ink" => Some(Self::Ink),
+            "twee" => Some(Self::Twee),
+            "yarn" => Some(Self::Yarn),
+            "tyrano" => Some(Self::Tyrano),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ink => "ink",
+            Self::Twee => "twee",
+            Self::Yarn => "yarn",
+            Self::Tyrano => "tyrano",
+        }
+    }
+
+    /// 複数のファイルに書き出すか（`--output` はディレクトリになる）
+    pub fn writes_files(self) -> bool {
+        matches!(self, Self::Tyrano)
+    }
+}
+
+/// 複数ファイルに書き出す target の 1 ファイル
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportFile {
+    /// 出力先ディレクトリからの相対パス
+    pub name: String,
+    pub text: String,
+}
+
+/// 書き出しの動作オプション
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// background / bgm / se の実在チェック（`--no-assets` で false）
+    pub check_assets: bool,
+    /// 足す開始シーン（`--entry`）。リンクの閉包ごと書き出すが、書き出し先の
+    /// 開始位置は `path` のまま
+    pub entries: Vec<PathBuf>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            check_assets: true,
+            entries: Vec::new(),
+        }
+    }
+}
+
+/// 書き出しの結果。実行前検査（compile と同じ規則）の結果を必ず含む
+#[derive(Debug)]
+pub struct ExportResult {
+    /// 開始シーンとして指定されたパス
+    pub file: PathBuf,
+    pub target: ExportTarget,
+    /// 実行前検査の結果
+    pub check: CheckResult,
+    /// 書き出したソース。check が error のとき、または
+    /// [`writes_files`](ExportTarget::writes_files) の target では None
+    pub output: Option<String>,
+    /// 複数ファイルに書き出す target の出力（先頭が開始シーン）。それ以外は空
+    pub files: Vec<ExportFile>,
+}
+
+impl ExportResult {
+    /// exit code を 1 にすべきか（check エラー）
+    pub fn has_errors(&self) -> bool {
+        self.check.has_errors()
+    }
+}
+
+/// シナリオを `target` の記法に書き出す。
+///
+/// [`compile_path`](super::compile_path) と同じく infallible で、検査 error の
+/// ときは Diagnostic 入りの [`ExportResult`] を返す（output は None になる）。
+pub fn export_path(path: &Path, target: ExportTarget, options: &ExportOptions) -> ExportResult {
+    let project = match load_compilable(path, &options.entries, options.check_assets) {
+        Ok(project) => project,
+        Err(check) => {
+            return ExportResult {
+                file: path.to_path_buf(),
+                target,
+                check,
+                output: None,
+                files: Vec::new(),
+            };
+        }
+    };
+    let (output, files) = match target {
+        ExportTarget::Ink => (Some(ink::render(&project.scenes)), Vec::new()),
+        ExportTarget::Twee => (Some(twee::render(&project.scenes)), Vec::new()),
+        ExportTarget::Yarn => (Some(yarn::render(&project.scenes)), Vec::new()),
+        ExportTarget::Tyrano => (None, tyrano::render(&project.scenes)),
+    };
+    ExportResult {
+        file: path.to_path_buf(),
+        target,
+        check: project.check,
+        output,
+        files,
+    }
+}
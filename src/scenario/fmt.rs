@@ -10,6 +10,8 @@
 //!
 //! 認識するパターン（SPEC 7.1）:
 //! - `fmt-missing-frontmatter`: front matter がなければファイル名から補う
+//!   （フラグメント（SPEC 4.8、`_` 始まりのファイル）は front matter を
+//!   持たないので補わない）
 //! - `fmt-legacy`: 旧記法の一部（SAY / LABEL / JUMP / WAIT / `[c]` /
 //!   ENDING・END / BRANCH）を確定的に変換する。それ以外の旧記法
 //!   （SET / MODIFY / JUMP_IF / SHOW_IMAGE / PLAY_* / CLEAR_LAYER /
@@ -22,6 +24,7 @@
 
 use super::characters::{Characters, find_characters_file, load_characters};
 use super::diagnostic::{Diagnostic, Severity};
+use super::include::is_fragment;
 use super::parse;
 use super::project::file_level;
 use super::slugify;
@@ -113,6 +116,7 @@ pub fn fmt_str(source: &str, path: &Path, characters: Option<&Characters>) -> Fm
 
     let scan_start = match front_matter_end(&lines) {
         Some(end) => end,
+        None if is_fragment(path) => 0,
         None => {
             edits.push(Edit {
                 start: 0,
@@ -309,7 +313,7 @@ fn front_matter_end(lines: &[&str]) -> Option<usize> {
 }
 
 /// ファイル名（拡張子なし）からシーン ID を補う
-fn derive_id(path: &Path) -> String {
+pub(super) fn derive_id(path: &Path) -> String {
     path.file_stem()
         .and_then(|s| s.to_str())
         .filter(|s| !s.is_empty())
@@ -621,6 +625,13 @@ mod tests {
         assert!(result.formatted.ends_with("# タイトル\n\n本文。\n"));
     }
 
+    #[test]
+    fn フラグメントにはfront_matterを補わない() {
+        let result = fmt_str("あゆみ「おはよう。」\n", Path::new("_morning.md"), None);
+        assert_eq!(rule_ids(&result), vec!["fmt-kagi-dialogue"]);
+        assert_eq!(result.formatted, "あゆみ: おはよう。\n");
+    }
+
     // ------------------------------------------------------- fmt-kagi-dialogue
 
     #[test]
@@ -641,6 +652,9 @@ mod tests {
         let characters = Characters {
             path: PathBuf::from("characters.yaml"),
             entries,
+            expressions: std::collections::BTreeMap::new(),
+            aliases: std::collections::BTreeMap::new(),
+            lines: std::collections::BTreeMap::new(),
         };
         let result = fmt_str(
             "---\nid: t\n---\n\n# t\n\nあゆみ（少し照れている）\n",
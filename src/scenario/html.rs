@@ -0,0 +1,127 @@
+//! `compile --target html`: 単体で開けるプレビュー用 HTML
+//!
+//! arikoi の環境がなくても書いた話をクリックして確かめられるよう、
+//! [`StoryBundle`] をそのまま埋め込んだ 1 ファイルの HTML を生成する。
+//! プレイヤーは依存なしの素の JavaScript（`html/player.html`）で、
+//! ナレーション・セリフ（話者名は characters.yaml の `color`）・選択肢・
+//! 背景・BGM・効果音・立ち絵と、「戻る」「最初から」だけを扱う。
+//!
+//! # 設計メモ
+//! - bundle の JSON は変えずに埋め込む。プレイヤーは web target と同じ
+//!   契約（`steps` と解決済みの飛び先）で動くので、ここで実行モデルを
+//!   二重に持たない
+//! - アセットは埋め込まず、HTML の置き場所からの相対 URL で参照する。
+//!   ローカルで開いたときにシナリオの横にある画像・音声がそのまま読める
+//! - `</script>` で埋め込みが途切れないよう、JSON 中の `<` は `\u003c` にする
+//! - プレースホルダは 1 回の走査で埋め、埋めた値の中の `{{…}}` は置き換えない
+
+use super::compile::StoryBundle;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const PLAYER: &str = include_str!("html/player.html");
+
+/// bundle を埋め込んだ HTML を返す。
+///
+/// `html_dir` は HTML を置くディレクトリで、アセットの URL はここからの
+/// 相対パスになる。`assets_dir` を渡すと（`--assets-out`）、元のファイル
+/// ではなくコピー先の `file` を参照する。相対パスにできないアセット
+/// （別ドライブ等）と実在しないアセットは表示しない。
+///
+/// URL は書かれたままのパスではなく解決先のアセット（`file`）ごとに持ち、
+/// シーンごとの参照 → アセットの表（`refs`）と一緒に埋め込む。別々の
+/// ディレクトリのシーンがどちらも `bg.png` と書いても、それぞれの画像になる
+pub fn render_html(bundle: &StoryBundle, html_dir: &Path, assets_dir: Option<&Path>) -> String {
+    let mut urls = BTreeMap::new();
+    for asset in &bundle.assets {
+        let target = match (assets_dir, &asset.file, &asset.source) {
+            (Some(dir), Some(file), _) => dir.join(file),
+            (None, _, Some(source)) => source.clone(),
+            _ => continue,
+        };
+        let Some(url) = relative_url(html_dir, &target) else {
+            continue;
+        };
+        if let Some(identity) = asset.identity() {
+            urls.insert(identity, url);
+        }
+    }
+    let asset_urls = serde_json::json!({ "urls": urls, "refs": bundle.asset_refs });
+
+    fill(
+        PLAYER,
+        &[
+            ("{{TITLE}}", escape_html(&bundle.title)),
+            ("{{ASSET_URLS}}", script_json(&asset_urls)),
+            ("{{BUNDLE}}", script_json(bundle)),
+        ],
+    )
+}
+
+/// テンプレートのプレースホルダを 1 回の走査で埋める。埋めた値は読み返さない
+/// ので、タイトルやパスに `{{BUNDLE}}` 等が含まれていてもそのまま残る
+fn fill(template: &str, values: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match values.iter().find(|(key, _)| rest.starts_with(key)) {
+            Some((key, value)) => {
+                out.push_str(value);
+                rest = &rest[key.len()..];
+            }
+            None => {
+                out.push_str("{{");
+                rest = &rest[2..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `<script type="application/json">` に埋め込める JSON
+fn script_json<T: serde::Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value)
+        .expect("bundle は常に JSON にできる")
+        .replace('<', "\\u003c")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `from` ディレクトリから `to` への `/` 区切りの相対 URL。
+/// どちらも実在する必要がある（シンボリックリンクや `..` を解決して比べる）
+fn relative_url(from: &Path, to: &Path) -> Option<String> {
+    let from = from.canonicalize().ok()?;
+    let to = to.canonicalize().ok()?;
+    // 別ドライブ（Windows）なら共通の祖先がない
+    let common = from.ancestors().find(|a| to.starts_with(a))?;
+    let ups = from.strip_prefix(common).ok()?.components().count();
+    let rest = to.strip_prefix(common).ok()?;
+    let mut parts: Vec<String> = std::iter::repeat_n("..".to_string(), ups).collect();
+    parts.extend(
+        rest.components()
+            .map(|c| percent_encode(&c.as_os_str().to_string_lossy())),
+    );
+    Some(parts.join("/"))
+}
+
+/// URL のパス 1 区間として使えない文字をエンコードする（日本語のファイル名も通す）
+fn percent_encode(segment: &str) -> String {
+    let mut out = String::new();
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
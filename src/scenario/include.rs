@@ -0,0 +1,280 @@
+//! `<!-- include: path -->` によるフラグメントの取り込み（SPEC 4.8）
+//!
+//! 毎朝の支度や共通のエピローグのように、複数のシーンで繰り返す文章を
+//! フラグメント（front matter のない Markdown、ファイル名は `_` 始まり）に
+//! まとめ、読み込み時にブロック単位で差し込む。差し込んだ後の Scene は
+//! 普通のシーンと区別がつかないため、check / trace / routes / compile は
+//! フラグメントを意識しない。
+//!
+//! 差し込んだブロックの行番号は、取り込む側のファイルの行数より後ろに
+//! 振り直す（行番号がブロックや選択肢項目の識別に使われているため、
+//! 取り込む側の行と重ならないようにする）。Diagnostic・trace・ソースマップに
+//! 出すときは [`Parsed::locate`] でフラグメントのファイルと行に戻す。
+//!
+//! - 取り込むパスは、directive を書いたファイルからの相対パス
+//! - フラグメント内のリンクとアセットのパスは、取り込んだシーンに書いたもの
+//!   として解決する（差し込んだ後はシーンのブロックになるため）
+//! - フラグメントからさらにフラグメントを取り込める。取り込みが循環したら
+//!   `circular-include`
+
+use super::Block;
+use super::diagnostic::Diagnostic;
+use super::parse::{FragmentLines, Include, Parsed, SourceRange, parse_fragment};
+use super::project::resolve_sibling;
+use std::path::{Path, PathBuf};
+
+/// フラグメントのファイルか（ファイル名が `_` で始まる `.md`）。
+/// ディレクトリを検査するときシーンとして集めない
+pub(super) fn is_fragment(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with('_'))
+}
+
+/// `parsed` の `<!-- include: -->` をすべて展開する。
+/// 取り込みの問題とフラグメント自体の Diagnostic は `parsed.diagnostics` に積む
+pub(super) fn expand_includes(parsed: &mut Parsed) {
+    let root = parsed
+        .scene
+        .path
+        .canonicalize()
+        .unwrap_or_else(|_| parsed.scene.path.clone());
+    expand(parsed, &mut vec![root]);
+}
+
+/// `stack` は取り込み中のファイル（正規化パス、外側から順）
+fn expand(parsed: &mut Parsed, stack: &mut Vec<PathBuf>) {
+    let includes = std::mem::take(&mut parsed.includes);
+    // 行番号は書かれた順に振る
+    let mut base = parsed.line_extent();
+    let mut loaded = Vec::new();
+    for include in &includes {
+        if let Some(mut fragment) = load_fragment(parsed, include, stack) {
+            parsed.diagnostics.append(&mut fragment.diagnostics);
+            let lines = fragment.line_extent();
+            loaded.push((include, fragment, base));
+            base += lines;
+        }
+    }
+    // 後ろから差し込み、前の directive の位置がずれないようにする
+    for (include, mut fragment, base) in loaded.into_iter().rev() {
+        splice(parsed, include, &mut fragment, base);
+    }
+    parsed.fragments.sort_by_key(|f| f.base);
+}
+
+/// 取り込むフラグメントを読み、その中の include も展開する。
+/// 読めない・循環している場合は Diagnostic を積んで None
+fn load_fragment(
+    parsed: &mut Parsed,
+    include: &Include,
+    stack: &mut Vec<PathBuf>,
+) -> Option<Parsed> {
+    let from = parsed.scene.path.clone();
+    let path = resolve_sibling(&from, &include.path)
+        .filter(|p| p.is_file() && p.extension().and_then(|e| e.to_str()) == Some("md"));
+    let Some((path, canon)) = path.and_then(|p| p.canonicalize().ok().map(|c| (p, c))) else {
+        parsed.diagnostics.push(Diagnostic::error(
+            "broken-include",
+            &from,
+            include.line,
+            format!(
+                "取り込むファイル「{}」が見つかりません。このファイルからの相対パスで、実在する .md ファイルを指定してください",
+                include.path
+            ),
+        ));
+        return None;
+    };
+    if let Some(first) = stack.iter().position(|p| *p == canon) {
+        let cycle: Vec<String> = stack[first..]
+            .iter()
+            .chain(std::iter::once(&canon))
+            .map(|p| file_name(p))
+            .collect();
+        parsed.diagnostics.push(Diagnostic::error(
+            "circular-include",
+            &from,
+            include.line,
+            format!(
+                "「{}」の取り込みが循環しています（{}）。どこかの `<!-- include: -->` を外してください",
+                include.path,
+                cycle.join(" → ")
+            ),
+        ));
+        return None;
+    }
+    if !is_fragment(&path) {
+        parsed.diagnostics.push(
+            Diagnostic::error(
+                "invalid-fragment",
+                &from,
+                include.line,
+                format!(
+                    "取り込むファイル「{}」の名前が `_` で始まっていません。ディレクトリを検査したときシーンとして扱われないよう、`_{}` のような名前にしてください",
+                    include.path,
+                    file_name(&path)
+                ),
+            )
+            .with_suggestion(format!("<!-- include: {} -->", underscored(&include.path))),
+        );
+    }
+    let source = match std::fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) => {
+            parsed.diagnostics.push(Diagnostic::error(
+                "broken-include",
+                &from,
+                include.line,
+                format!("{} を読み込めません: {}", path.display(), e),
+            ));
+            return None;
+        }
+    };
+    let mut fragment = parse_fragment(&source, &path);
+    stack.push(canon);
+    expand(&mut fragment, stack);
+    stack.pop();
+    Some(fragment)
+}
+
+/// フラグメントのブロックを `include` の位置に差し込む。行番号は `base` だけずらす
+fn splice(parsed: &mut Parsed, include: &Include, fragment: &mut Parsed, base: usize) {
+    let blocks: Vec<Block> = std::mem::take(&mut fragment.scene.lead)
+        .into_iter()
+        .map(|mut block| {
+            shift_lines(&mut block, base);
+            block
+        })
+        .collect();
+    let segment = match include.segment {
+        0 => &mut parsed.scene.lead,
+        n => &mut parsed.scene.sections[n - 1].blocks,
+    };
+    segment.splice(include.position..include.position, blocks);
+
+    let shift = |r: &SourceRange| SourceRange {
+        line: r.line + base,
+        end_line: r.end_line + base,
+        ..*r
+    };
+    parsed.block_ranges.splice(
+        include.block_index..include.block_index,
+        fragment.block_ranges.iter().map(shift),
+    );
+    parsed.item_ranges.extend(
+        fragment
+            .item_ranges
+            .iter()
+            .map(|(line, range)| (line + base, shift(range))),
+    );
+    parsed.fragments.push(FragmentLines {
+        path: fragment.scene.path.clone(),
+        base,
+        lines: fragment.line_count,
+    });
+    parsed
+        .fragments
+        .extend(fragment.fragments.iter().map(|f| FragmentLines {
+            path: f.path.clone(),
+            base: f.base + base,
+            lines: f.lines,
+        }));
+}
+
+fn shift_lines(block: &mut Block, by: usize) {
+    match block {
+        Block::Choices { items, line } => {
+            *line += by;
+            for item in items {
+                item.line += by;
+            }
+        }
+        Block::Random { items, line } => {
+            *line += by;
+            for item in items {
+                item.line += by;
+            }
+        }
+        Block::Narration { line, .. }
+        | Block::Dialogue { line, .. }
+        | Block::Jump { line, .. }
+        | Block::Call { line, .. }
+        | Block::Return { line }
+        | Block::Ending { line, .. }
+        | Block::Background { line, .. }
+        | Block::Bgm { line, .. }
+        | Block::BgmStop { line }
+        | Block::Se { line, .. }
+        | Block::Show { line, .. }
+        | Block::Hide { line, .. } => *line += by,
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// パスの最後の区切りのファイル名に `_` を付ける
+fn underscored(path: &str) -> String {
+    match path.rsplit_once('/') {
+        Some((dir, name)) => format!("{dir}/_{name}"),
+        None => format!("_{path}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::parse_str;
+
+    #[test]
+    fn フラグメントかどうかはファイル名で決まる() {
+        assert!(is_fragment(Path::new("scenario/_morning.md")));
+        assert!(!is_fragment(Path::new("scenario/morning.md")));
+    }
+
+    #[test]
+    fn 取り込んだブロックの行はフラグメントの行に戻せる() {
+        let mut parsed = parse_str(
+            "---\nid: a\n---\n\n# 朝\n\n<!-- include: _x.md -->\n\n主人公: 行こう。\n",
+            Path::new("a.md"),
+        );
+        let mut fragment =
+            parse_fragment("母: 起きなさい。\n\n主人公: はーい。\n", Path::new("_x.md"));
+        let include = parsed.includes.remove(0);
+        assert_eq!((include.segment, include.position), (0, 0));
+        let base = parsed.line_extent();
+        splice(&mut parsed, &include, &mut fragment, base);
+
+        let lines: Vec<usize> = parsed
+            .scene
+            .lead
+            .iter()
+            .map(|b| match b {
+                Block::Dialogue { line, .. } => *line,
+                _ => 0,
+            })
+            .collect();
+        assert_eq!(lines, vec![10, 12, 9]);
+        assert_eq!(parsed.block_ranges.len(), 3);
+        assert_eq!(parsed.locate(12), (Path::new("_x.md"), 3));
+        assert_eq!(parsed.locate(9), (Path::new("a.md"), 9));
+    }
+
+    #[test]
+    fn フラグメントの見出しとfront_matterはinvalid_fragmentになる() {
+        let parsed = parse_fragment("---\nid: x\n---\n\n## 朝\n\n本文。\n", Path::new("_x.md"));
+        let rules: Vec<&str> = parsed.diagnostics.iter().map(|d| d.rule_id).collect();
+        assert_eq!(rules, vec!["invalid-fragment", "invalid-fragment"]);
+        assert!(parsed.scene.sections.is_empty());
+        assert_eq!(parsed.scene.lead.len(), 1);
+    }
+
+    #[test]
+    fn 取り込むパスの名前に下線を足す() {
+        assert_eq!(underscored("common/morning.md"), "common/_morning.md");
+        assert_eq!(underscored("morning.md"), "_morning.md");
+    }
+}
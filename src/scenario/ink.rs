@@ -0,0 +1,359 @@
+//! `compile --target ink`: inkle の Ink への書き出し
+//!
+//! 対応は次のとおり。
+//! - シーン（1 ファイル）→ knot、H2 セクション → stitch。名前は scene_id /
+//!   アンカーの `-` を `_` にしたもので、Ink の識別子にできないもの（日本語の
+//!   見出し等）は `section_2` のような連番にし、元の見出しをコメントで残す
+//! - 選択肢 → sticky な `+ [ラベル] -> 飛び先`。v1 記法の選択肢は何度通っても
+//!   同じ項目が出る（一度選んだら消える `*` に相当する構文はない）ため `+` にする
+//! - ジャンプ → `-> 飛び先`、エンディング → `# ending: id` のタグと `-> END`
+//! - 呼び出し（SPEC 4.9）→ トンネル `-> 飛び先 ->`、`<!-- return -->` → `->->`
+//! - セリフ → `表示名: 本文` の行に `# speaker:` / `# expression:` のタグ
+//! - 演出（SPEC 4.6）→ 次の行に付く `# background: path` 等のタグ行
+//! - Ink は stitch の末尾から次の stitch へ進まないため、フォールスルー
+//!   （SPEC 5章）は明示の `->` にする。最後のセクションの末尾は `-> END`
+//!
+//! 終端（選択肢・ジャンプ・エンディング）より後ろのブロックは到達不能
+//! （check の warning）なので書き出さない。Ink では選択肢の後ろの行が
+//! 最後の選択肢の中身になってしまうため。
+
+use super::Block;
+use super::compile::resolve_link;
+use super::project::LoadedScene;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Ink の識別子として使えない予約語
+const RESERVED: &[&str] = &[
+    "END", "DONE", "INCLUDE", "EXTERNAL", "VAR", "CONST", "LIST", "function", "return", "temp",
+    "else", "not", "and", "or", "true", "false", "has", "hasnt", "mod",
+];
+
+/// シーンを Ink のソースにする（先頭のシーンから開始する）
+pub(super) fn render(scenes: &[LoadedScene]) -> String {
+    let names = Names::new(scenes);
+    let mut out = String::new();
+    out.push_str("// tsumugai compile --target ink が生成したファイルです\n");
+    let _ = writeln!(out, "-> {}", names.knots[0]);
+
+    for (i, loaded) in scenes.iter().enumerate() {
+        let scene = &loaded.parsed.scene;
+        let file = loaded
+            .path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        out.push('\n');
+        match &scene.title {
+            Some(title) => {
+                let _ = writeln!(out, "// {}「{}」", file, title);
+            }
+            None => {
+                let _ = writeln!(out, "// {}", file);
+            }
+        }
+        let _ = writeln!(out, "=== {} ===", names.knots[i]);
+        if let Some(path) = &scene.background {
+            let _ = writeln!(out, "# background: {}", tag_value(path));
+        }
+        if let Some(bgm) = &scene.bgm {
+            let _ = writeln!(out, "# bgm: {}", tag_value(bgm));
+        }
+
+        let writer = BlockWriter {
+            scenes,
+            names: &names,
+            current: i,
+        };
+        let after_lead = match names.stitches[i].first() {
+            Some(first) => format!("{}.{}", names.knots[i], first),
+            None => "END".to_string(),
+        };
+        writer.blocks(&mut out, &scene.lead, &after_lead);
+
+        for (j, section) in scene.sections.iter().enumerate() {
+            let stitch = &names.stitches[i][j];
+            out.push('\n');
+            if ident(&section.anchor).as_ref() == Some(stitch) {
+                let _ = writeln!(out, "= {}", stitch);
+            } else {
+                let _ = writeln!(out, "= {}  // {}", stitch, section.heading);
+            }
+            let next = match names.stitches[i].get(j + 1) {
+                Some(next) => format!("{}.{}", names.knots[i], next),
+                None => "END".to_string(),
+            };
+            writer.blocks(&mut out, &section.blocks, &next);
+        }
+    }
+    out
+}
+
+/// knot / stitch の名前
+struct Names {
+    knots: Vec<String>,
+    /// シーンごとの、セクションの並びと同じ順の stitch 名
+    stitches: Vec<Vec<String>>,
+}
+
+impl Names {
+    fn new(scenes: &[LoadedScene]) -> Self {
+        let mut used = HashSet::new();
+        let mut knots = Vec::new();
+        let mut stitches = Vec::new();
+        for (i, loaded) in scenes.iter().enumerate() {
+            let scene = &loaded.parsed.scene;
+            let knot = scene
+                .id
+                .as_deref()
+                .and_then(ident)
+                .unwrap_or_else(|| format!("scene_{}", i + 1));
+            knots.push(unique(knot, &mut used));
+
+            let mut local = HashSet::new();
+            stitches.push(
+                scene
+                    .sections
+                    .iter()
+                    .enumerate()
+                    .map(|(j, section)| {
+                        let name =
+                            ident(&section.anchor).unwrap_or_else(|| format!("section_{}", j + 1));
+                        unique(name, &mut local)
+                    })
+                    .collect(),
+            );
+        }
+        Self { knots, stitches }
+    }
+}
+
+/// Ink の識別子にする。ASCII の英数字と `_` にできなければ None
+pub(super) fn ident(raw: &str) -> Option<String> {
+    let name: String = raw
+        .chars()
+        .map(|c| if matches!(c, '-' | '.' | ' ') { '_' } else { c })
+        .collect();
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED.contains(&name.as_str());
+    if !valid {
+        return None;
+    }
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        return Some(format!("_{}", name));
+    }
+    Some(name)
+}
+
+/// 置き換えで同じ名前になったら `_2` 等を付けて区別する
+pub(super) fn unique(name: String, used: &mut HashSet<String>) -> String {
+    let mut candidate = name.clone();
+    let mut n = 2;
+    while !used.insert(candidate.clone()) {
+        candidate = format!("{}_{}", name, n);
+        n += 1;
+    }
+    candidate
+}
+
+struct BlockWriter<'a> {
+    scenes: &'a [LoadedScene],
+    names: &'a Names,
+    current: usize,
+}
+
+impl BlockWriter<'_> {
+    /// ブロック列を書き、終端がなければ `next` へ進む
+    fn blocks(&self, out: &mut String, blocks: &[Block], next: &str) {
+        for block in blocks {
+            if self.block(out, block) {
+                return;
+            }
+        }
+        let _ = writeln!(out, "-> {}", next);
+    }
+
+    /// 1 ブロックを書く。実行がここで終わる（先へ進まない）なら true
+    fn block(&self, out: &mut String, block: &Block) -> bool {
+        match block {
+            Block::Narration { text, .. } => {
+                let _ = writeln!(out, "{}", escape(text));
+            }
+            Block::Dialogue {
+                speaker,
+                expression,
+                text,
+                ..
+            } => {
+                let display = self.scenes[self.current].display_name(speaker);
+                let _ = write!(
+                    out,
+                    "{}: {} # speaker: {}",
+                    escape(display),
+                    escape(text),
+                    tag_value(speaker)
+                );
+                if let Some(expression) = expression {
+                    let _ = write!(out, " # expression: {}", tag_value(expression));
+                }
+                out.push('\n');
+            }
+            Block::Choices { items, .. } => {
+                for item in items {
+                    let _ = writeln!(
+                        out,
+                        "+ [{}] -> {}",
+                        escape(&item.label),
+                        self.divert(&item.target)
+                    );
+                }
+                return true;
+            }
+            Block::Random { items, .. } => {
+                // 重みの累積値で `RANDOM` の出目を振り分ける。最後の項目は無条件で進む
+                let total: u32 = items.iter().map(|item| item.weight).sum();
+                let _ = writeln!(out, "~ temp roll = RANDOM(1, {})", total);
+                let mut upper = 0;
+                for (i, item) in items.iter().enumerate() {
+                    upper += item.weight;
+                    if i + 1 == items.len() {
+                        let _ = writeln!(out, "-> {}", self.divert(&item.target));
+                    } else {
+                        let _ = writeln!(
+                            out,
+                            "{{ roll <= {}: -> {} }}",
+                            upper,
+                            self.divert(&item.target)
+                        );
+                    }
+                }
+                return true;
+            }
+            Block::Jump { target, .. } => {
+                let _ = writeln!(out, "-> {}", self.divert(target));
+                return true;
+            }
+            Block::Call { target, .. } => {
+                let _ = writeln!(out, "-> {} ->", self.divert(target));
+            }
+            Block::Return { .. } => {
+                out.push_str("->->\n");
+                return true;
+            }
+            Block::Ending { id, .. } => {
+                let _ = writeln!(out, "# ending: {}", tag_value(id));
+                out.push_str("-> END\n");
+                return true;
+            }
+            Block::Background { path, .. } => {
+                let _ = writeln!(out, "# background: {}", tag_value(path));
+            }
+            Block::Bgm { path, .. } => {
+                let _ = writeln!(out, "# bgm: {}", tag_value(path));
+            }
+            Block::BgmStop { .. } => out.push_str("# bgm: stop\n"),
+            Block::Se { path, .. } => {
+                let _ = writeln!(out, "# se: {}", tag_value(path));
+            }
+            Block::Show {
+                character,
+                expression,
+                ..
+            } => match expression {
+                Some(expression) => {
+                    let _ = writeln!(
+                        out,
+                        "# show: {}({})",
+                        tag_value(character),
+                        tag_value(expression)
+                    );
+                }
+                None => {
+                    let _ = writeln!(out, "# show: {}", tag_value(character));
+                }
+            },
+            Block::Hide { character, .. } => {
+                let _ = writeln!(out, "# hide: {}", tag_value(character));
+            }
+        }
+        false
+    }
+
+    /// リンク先の `knot` / `knot.stitch`
+    fn divert(&self, target: &super::LinkTarget) -> String {
+        let (scene, section) = resolve_link(self.scenes, self.current, target);
+        let knot = &self.names.knots[scene];
+        match section {
+            Some(section) => format!("{}.{}", knot, self.names.stitches[scene][section]),
+            None => knot.clone(),
+        }
+    }
+}
+
+/// 本文を Ink のテキストとして書けるようにする。記法として解釈される文字
+/// （タグ・分岐・ロジック・コメント・行頭の選択肢記号等）を `\` でエスケープする。
+/// 行頭の判定は先頭だけでなく改行の後（インデントを含む）にも行う
+fn escape(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut line_start = true;
+    for (i, &c) in chars.iter().enumerate() {
+        let next = chars.get(i + 1).copied();
+        let special = match c {
+            '\\' | '#' | '{' | '}' | '[' | ']' | '|' => true,
+            // 行頭の `-` は gather になる
+            '-' => line_start || next == Some('>'),
+            '<' => matches!(next, Some('>' | '-')),
+            '/' => matches!(next, Some('/' | '*')),
+            '*' | '+' | '=' | '~' => line_start,
+            _ => false,
+        };
+        if special {
+            out.push('\\');
+        }
+        out.push(c);
+        line_start = c == '\n' || (line_start && matches!(c, ' ' | '\t'));
+    }
+    out
+}
+
+/// タグの値。`#` は次のタグの始まりになるため全角にする
+fn tag_value(value: &str) -> String {
+    value.replace('#', "＃")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn 記法として解釈される文字をエスケープする() {
+        assert_eq!(escape("普通の文"), "普通の文");
+        assert_eq!(escape("* 行頭"), "\\* 行頭");
+        assert_eq!(escape("- 行頭"), "\\- 行頭");
+        assert_eq!(escape("a -> b"), "a \\-> b");
+        assert_eq!(escape("#1 {x}"), "\\#1 \\{x\\}");
+        assert_eq!(escape("http://example.com"), "http:\\//example.com");
+    }
+
+    #[test]
+    fn 改行の後の行頭記号もエスケープする() {
+        assert_eq!(escape("一行目\n* 二行目"), "一行目\n\\* 二行目");
+        assert_eq!(escape("a\n- b\n  + c"), "a\n\\- b\n  \\+ c");
+        assert_eq!(escape("a\n= b\n~ c"), "a\n\\= b\n\\~ c");
+        assert_eq!(escape("a\n// b"), "a\n\\// b");
+        assert_eq!(escape("a * b = c"), "a * b = c");
+    }
+
+    #[test]
+    fn 識別子にできない名前は連番にする() {
+        assert_eq!(ident("run-together").as_deref(), Some("run_together"));
+        assert_eq!(ident("001").as_deref(), Some("_001"));
+        assert_eq!(ident("選択肢"), None);
+        assert_eq!(ident("END"), None);
+        let mut used = HashSet::new();
+        assert_eq!(unique("a_b".to_string(), &mut used), "a_b");
+        assert_eq!(unique("a_b".to_string(), &mut used), "a_b_2");
+    }
+}
@@ -0,0 +1,327 @@
+//! アセットファイルの形式判定（ヘッダの sniff）
+//!
+//! check がアセットの中身を検査する（SPEC 6章 `empty-asset` /
+//! `asset-kind-mismatch` / `asset-format-mismatch` / `unsupported-asset-format` /
+//! `canvas-size-mismatch`）ための最小限の判定だけを持つ。デコードはせず、
+//! 先頭のマジックナンバーと、画像ならサイズが書かれたヘッダだけを読む。
+
+/// アセットの種類。参照しているキーから期待される種類と照合する
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum MediaKind {
+    Image,
+    Audio,
+}
+
+impl MediaKind {
+    /// 参照キー（`background` / `bgm` / `se` / 立ち絵）が期待する種類
+    pub(super) fn expected_for(key: &str) -> Self {
+        match key {
+            "bgm" | "se" => Self::Audio,
+            _ => Self::Image,
+        }
+    }
+
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            Self::Image => "画像",
+            Self::Audio => "音声",
+        }
+    }
+}
+
+/// ヘッダから判定したファイル形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum MediaFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Bmp,
+    Tiff,
+    Psd,
+    Avif,
+    Heic,
+    Ogg,
+    Mp3,
+    Wav,
+    Flac,
+    M4a,
+    Aiff,
+    Midi,
+}
+
+impl MediaFormat {
+    const ALL: [Self; 16] = [
+        Self::Png,
+        Self::Jpeg,
+        Self::Gif,
+        Self::Webp,
+        Self::Bmp,
+        Self::Tiff,
+        Self::Psd,
+        Self::Avif,
+        Self::Heic,
+        Self::Ogg,
+        Self::Mp3,
+        Self::Wav,
+        Self::Flac,
+        Self::M4a,
+        Self::Aiff,
+        Self::Midi,
+    ];
+
+    pub(super) fn kind(self) -> MediaKind {
+        match self {
+            Self::Png
+            | Self::Jpeg
+            | Self::Gif
+            | Self::Webp
+            | Self::Bmp
+            | Self::Tiff
+            | Self::Psd
+            | Self::Avif
+            | Self::Heic => MediaKind::Image,
+            _ => MediaKind::Audio,
+        }
+    }
+
+    pub(super) fn name(self) -> &'static str {
+        match self {
+            Self::Png => "PNG",
+            Self::Jpeg => "JPEG",
+            Self::Gif => "GIF",
+            Self::Webp => "WebP",
+            Self::Bmp => "BMP",
+            Self::Tiff => "TIFF",
+            Self::Psd => "PSD",
+            Self::Avif => "AVIF",
+            Self::Heic => "HEIC",
+            Self::Ogg => "Ogg",
+            Self::Mp3 => "MP3",
+            Self::Wav => "WAV",
+            Self::Flac => "FLAC",
+            Self::M4a => "M4A（AAC）",
+            Self::Aiff => "AIFF",
+            Self::Midi => "MIDI",
+        }
+    }
+
+    /// この形式として正しい拡張子（小文字、先頭が推奨）
+    pub(super) fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Png => &["png"],
+            Self::Jpeg => &["jpg", "jpeg"],
+            Self::Gif => &["gif"],
+            Self::Webp => &["webp"],
+            Self::Bmp => &["bmp"],
+            Self::Tiff => &["tif", "tiff"],
+            Self::Psd => &["psd"],
+            Self::Avif => &["avif"],
+            Self::Heic => &["heic", "heif"],
+            Self::Ogg => &["ogg", "oga", "opus"],
+            Self::Mp3 => &["mp3"],
+            Self::Wav => &["wav"],
+            Self::Flac => &["flac"],
+            Self::M4a => &["m4a", "aac", "mp4"],
+            Self::Aiff => &["aif", "aiff"],
+            Self::Midi => &["mid", "midi"],
+        }
+    }
+
+    /// Web ブラウザ（compile --target web の再生先）で扱えるか
+    pub(super) fn web_supported(self) -> bool {
+        !matches!(
+            self,
+            Self::Tiff | Self::Psd | Self::Heic | Self::Aiff | Self::Midi
+        )
+    }
+}
+
+/// 拡張子がいずれかの画像・音声形式のものか（大文字小文字は区別しない）。
+/// `unused-asset` が素材以外のファイル（シーン・yaml 等）を数えないために使う
+pub(super) fn is_media_path(path: &std::path::Path) -> bool {
+    let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    let ext = ext.to_ascii_lowercase();
+    MediaFormat::ALL
+        .iter()
+        .any(|format| format.extensions().contains(&ext.as_str()))
+}
+
+/// 先頭のマジックナンバーから形式を判定する。分からなければ None
+pub(super) fn sniff(bytes: &[u8]) -> Option<MediaFormat> {
+    let at = |offset: usize, magic: &[u8]| bytes.get(offset..offset + magic.len()) == Some(magic);
+    let format = if at(0, b"\x89PNG\r\n\x1a\n") {
+        MediaFormat::Png
+    } else if at(0, b"\xff\xd8\xff") {
+        MediaFormat::Jpeg
+    } else if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        MediaFormat::Gif
+    } else if at(0, b"RIFF") && at(8, b"WEBP") {
+        MediaFormat::Webp
+    } else if at(0, b"RIFF") && at(8, b"WAVE") {
+        MediaFormat::Wav
+    } else if at(0, b"BM") && bytes.len() >= 26 {
+        MediaFormat::Bmp
+    } else if at(0, b"II*\0") || at(0, b"MM\0*") {
+        MediaFormat::Tiff
+    } else if at(0, b"8BPS") {
+        MediaFormat::Psd
+    } else if at(0, b"OggS") {
+        MediaFormat::Ogg
+    } else if at(0, b"fLaC") {
+        MediaFormat::Flac
+    } else if at(0, b"ID3") || (bytes.len() >= 2 && bytes[0] == 0xff && bytes[1] & 0xe0 == 0xe0) {
+        MediaFormat::Mp3
+    } else if at(4, b"ftyp") {
+        // ISO-BMFF は画像（AVIF / HEIC）と音声（M4A）で共通なので、major brand で分ける
+        match bytes.get(8..12)? {
+            b"avif" | b"avis" => MediaFormat::Avif,
+            b"heic" | b"heix" | b"mif1" => MediaFormat::Heic,
+            b"M4A " | b"M4B " | b"mp42" | b"isom" => MediaFormat::M4a,
+            _ => return None,
+        }
+    } else if at(0, b"FORM") && (at(8, b"AIFF") || at(8, b"AIFC")) {
+        MediaFormat::Aiff
+    } else if at(0, b"MThd") {
+        MediaFormat::Midi
+    } else {
+        return None;
+    };
+    Some(format)
+}
+
+/// 画像のピクセルサイズ（幅, 高さ）。ヘッダから読めなければ None
+pub(super) fn image_size(format: MediaFormat, bytes: &[u8]) -> Option<(u32, u32)> {
+    let be32 = |o: usize| Some(u32::from_be_bytes(bytes.get(o..o + 4)?.try_into().ok()?));
+    let be16 = |o: usize| {
+        Some(u32::from(u16::from_be_bytes(
+            bytes.get(o..o + 2)?.try_into().ok()?,
+        )))
+    };
+    let le16 = |o: usize| {
+        Some(u32::from(u16::from_le_bytes(
+            bytes.get(o..o + 2)?.try_into().ok()?,
+        )))
+    };
+    let le24 = |o: usize| {
+        let b = bytes.get(o..o + 3)?;
+        Some(u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16)
+    };
+    match format {
+        // IHDR は必ず先頭チャンク
+        MediaFormat::Png => Some((be32(16)?, be32(20)?)),
+        MediaFormat::Gif => Some((le16(6)?, le16(8)?)),
+        MediaFormat::Bmp => {
+            let width = i32::from_le_bytes(bytes.get(18..22)?.try_into().ok()?);
+            let height = i32::from_le_bytes(bytes.get(22..26)?.try_into().ok()?);
+            Some((width.unsigned_abs(), height.unsigned_abs()))
+        }
+        MediaFormat::Webp => match bytes.get(12..16)? {
+            b"VP8 " => Some((le16(26)? & 0x3fff, le16(28)? & 0x3fff)),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            b"VP8X" => Some((le24(24)? + 1, le24(27)? + 1)),
+            _ => None,
+        },
+        MediaFormat::Jpeg => {
+            // SOFn マーカーまでセグメントを辿る
+            let mut i = 2;
+            while i + 4 <= bytes.len() {
+                if bytes[i] != 0xff {
+                    return None;
+                }
+                let marker = bytes[i + 1];
+                if marker == 0xff {
+                    i += 1;
+                    continue;
+                }
+                let len = be16(i + 2)? as usize;
+                let is_sof = matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc);
+                if is_sof {
+                    return Some((be16(i + 7)?, be16(i + 5)?));
+                }
+                i += 2 + len;
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        bytes.extend(width.to_be_bytes());
+        bytes.extend(height.to_be_bytes());
+        bytes.extend([8, 2, 0, 0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn ヘッダから形式と種類を判定できる() {
+        assert_eq!(sniff(&png(1, 1)), Some(MediaFormat::Png));
+        assert_eq!(
+            sniff(b"\xff\xd8\xff\xe0\0\x10JFIF"),
+            Some(MediaFormat::Jpeg)
+        );
+        assert_eq!(sniff(b"OggS\0\x02"), Some(MediaFormat::Ogg));
+        assert_eq!(sniff(b"RIFF\0\0\0\0WAVEfmt "), Some(MediaFormat::Wav));
+        assert_eq!(sniff(b"ID3\x04\0"), Some(MediaFormat::Mp3));
+        assert_eq!(sniff(b"hello"), None);
+        assert_eq!(MediaFormat::M4a.kind(), MediaKind::Audio);
+        assert!(MediaFormat::Avif.web_supported());
+        assert!(!MediaFormat::Heic.web_supported());
+    }
+
+    #[test]
+    fn iso_bmffはmajor_brandで画像と音声を分ける() {
+        let ftyp = |brand: &[u8]| [b"\0\0\0\x1cftyp".as_slice(), brand, b"\0\0\0\0"].concat();
+        for (brand, format) in [
+            (b"avif", MediaFormat::Avif),
+            (b"avis", MediaFormat::Avif),
+            (b"heic", MediaFormat::Heic),
+            (b"heix", MediaFormat::Heic),
+            (b"mif1", MediaFormat::Heic),
+            (b"M4A ", MediaFormat::M4a),
+            (b"M4B ", MediaFormat::M4a),
+            (b"mp42", MediaFormat::M4a),
+            (b"isom", MediaFormat::M4a),
+        ] {
+            assert_eq!(sniff(&ftyp(brand)), Some(format), "{:?}", brand);
+        }
+        assert_eq!(MediaFormat::Avif.kind(), MediaKind::Image);
+        // 知らない brand は音声と決めつけない
+        assert_eq!(sniff(&ftyp(b"qt  ")), None);
+        assert_eq!(sniff(b"\0\0\0\x1cftyp"), None);
+        assert_eq!(MediaFormat::Ogg.kind(), MediaKind::Audio);
+        assert!(!MediaFormat::Midi.web_supported());
+    }
+
+    #[test]
+    fn 画像と音声の拡張子だけを素材のファイルとみなす() {
+        use std::path::Path;
+        assert!(is_media_path(Path::new("assets/bg/gate.PNG")));
+        assert!(is_media_path(Path::new("bgm/rain.ogg")));
+        assert!(!is_media_path(Path::new("scene.md")));
+        assert!(!is_media_path(Path::new("characters.yaml")));
+        assert!(!is_media_path(Path::new("README")));
+    }
+
+    #[test]
+    fn pngとjpegの画像サイズを読める() {
+        assert_eq!(
+            image_size(MediaFormat::Png, &png(1280, 720)),
+            Some((1280, 720))
+        );
+        // SOI → APP0（長さ 4）→ SOF0（高さ 600, 幅 800）
+        let jpeg = b"\xff\xd8\xff\xe0\0\x04\0\0\xff\xc0\0\x11\x08\x02\x58\x03\x20\x03";
+        assert_eq!(image_size(MediaFormat::Jpeg, jpeg), Some((800, 600)));
+    }
+}
@@ -3,7 +3,8 @@
 //! 一般 Markdown 準拠のシナリオ記法 v1 を解析して [`Scene`] を構築し
 //! （[`parse_str`] / [`parse_file`]）、プロジェクト全体の意味論検査
 //! （[`check_path`]）、経路検証（[`trace_path`] / [`routes_path`]）、
-//! 整形（[`fmt_path`]）、StoryBundle 生成（[`compile_path`]）と、
+//! 整形（[`fmt_path`]）、StoryBundle 生成（[`compile_path`]）とプレビュー HTML
+//! （[`render_html`]）、他エンジンの記法への書き出し（[`export_path`]）と、
 //! 各結果の出力（[`render_human`] / [`render_json`] / [`render_sarif`] 等）を提供する。
 //!
 //! # 設計方針（SPEC 6.1）
@@ -21,39 +22,82 @@
 //! assert_eq!(parsed.scene.id.as_deref(), Some("demo"));
 //! ```
 
+mod achievements;
 mod anchor;
+mod chapters;
 mod characters;
 mod check;
 mod compile;
 mod diagnostic;
+mod endings;
 mod exec;
+mod export;
 mod fmt;
+mod html;
+mod include;
+mod ink;
+mod media;
 mod parse;
 mod project;
 mod report;
 mod routes;
+mod script;
+mod stats;
 #[cfg(test)]
 mod tests;
 mod trace;
+mod twee;
+mod tyrano;
+mod yarn;
 
+pub use achievements::{
+    Achievement, Achievements, Condition as AchievementCondition, find_achievements_file,
+    load_achievements,
+};
 pub use anchor::{percent_decode, slugify};
-pub use characters::{Characters, find_characters_file, load_characters};
+pub use chapters::{Chapter, Chapters, find_chapters_file, load_chapters};
+pub use characters::{Characters, Expression, find_characters_file, load_characters};
 pub use check::{CheckOptions, CheckResult, check_path};
 pub use compile::{
-    BundleAsset, BundleScene, BundleStep, ChoiceOption as BundleChoiceOption, CompileOptions,
-    CompileResult, SourceLocation, StepTarget, StoryBundle, compile_path,
+    BundleAchievement, BundleAsset, BundleAssetKind, BundleChapter, BundleCharacter,
+    BundleCondition, BundleEnding, BundleIndex, BundleScene, BundleSceneRef, BundleStep,
+    ChoiceOption as BundleChoiceOption, CompileOptions, CompileResult,
+    RandomOption as BundleRandomOption, SceneChunk, SourceLocation, SourceMap, SourceMapStep,
+    SplitBundle, StepTarget, StoryBundle, compile_path, copy_assets, split_bundle,
+    write_split_bundle,
 };
 pub use diagnostic::{Diagnostic, Severity, Span};
+pub use endings::{EndingKind, EndingMeta, Endings, find_endings_file, load_endings};
+pub use export::{ExportFile, ExportOptions, ExportResult, ExportTarget, export_path};
 pub use fmt::{FmtChange, FmtResult, fmt_path, fmt_str};
-pub use parse::{FrontMatterSpans, Parsed, parse_file, parse_str};
+pub use html::render_html;
+pub use parse::{
+    FragmentLines, FrontMatterSpans, Include, Parsed, SourceRange, parse_file, parse_str,
+};
 pub use report::{
     render_fmt_human, render_fmt_json, render_human, render_json, render_routes_human,
-    render_routes_json, render_sarif, render_trace_human, render_trace_json,
+    render_routes_json, render_sarif, render_stats_human, render_stats_json, render_trace_human,
+    render_trace_json,
+};
+pub use routes::{
+    AchievementReport, ChapterReport, EndingLengths, RouteEnd, RouteLength, RouteRecord,
+    RoutesOptions, RoutesReport, RoutesResult, routes_path,
+};
+pub use script::{
+    ScriptDocument, ScriptLine, ScriptRef, ScriptResult, ScriptUnit, render_script_html,
+    render_script_text, script_path,
 };
-pub use routes::{RouteEnd, RouteRecord, RoutesOptions, RoutesReport, RoutesResult, routes_path};
-pub use trace::{Trace, TraceChoice, TraceEnd, TraceOptions, TraceResult, TraceStep, trace_path};
+pub use stats::{
+    ChapterStats, DEFAULT_CHARS_PER_MINUTE, SceneStats, SpeakerStats, StatsCounts, StatsOptions,
+    StatsReport, StatsResult, stats_path,
+};
+pub use trace::{
+    Trace, TraceChoice, TraceEnd, TraceOptions, TraceRandom, TraceResult, TraceStep, trace_path,
+};
+pub use twee::{ImportResult, import_twee_path, import_twee_str};
 
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 /// 1 ファイル = 1 シーン（SPEC 3章）
@@ -69,6 +113,8 @@ pub struct Scene {
     pub background: Option<String>,
     /// front matter の `bgm`（ファイルからの相対パス）
     pub bgm: Option<String>,
+    /// front matter の `display_names`（話者名 → このシーンでの表示名、SPEC 3.1）
+    pub display_names: BTreeMap<String, String>,
     /// front matter 直後から最初の H2 までのリード部
     pub lead: Vec<Block>,
     /// H2 セクション（分岐先）
@@ -96,19 +142,46 @@ pub enum Block {
     /// `名前: 本文` 形式の段落（SPEC 4.2）
     Dialogue {
         speaker: String,
+        /// `名前(表情): 本文` の表情名（SPEC 4.2）
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expression: Option<String>,
         text: String,
         line: usize,
     },
     /// リンクのみを項目とするリスト（SPEC 4.3）。ここで入力待ちになる
     Choices { items: Vec<ChoiceItem>, line: usize },
+    /// `<!-- random -->` の直後のリンクのリスト（SPEC 4.10）。項目のどれかへ
+    /// 重みに比例した確率で進む。入力待ちにはならない
+    Random { items: Vec<RandomItem>, line: usize },
     /// リンク 1 つだけの段落（SPEC 4.4）
     Jump {
         label: String,
         target: LinkTarget,
         line: usize,
     },
+    /// `<!-- call: 飛び先 -->`（SPEC 4.9）。飛び先を実行し、`return` で
+    /// このブロックの次へ戻る
+    Call { target: LinkTarget, line: usize },
+    /// `<!-- return -->`（SPEC 4.9）。直近の `call` の次へ戻る
+    Return { line: usize },
     /// `<!-- ending: id -->`（SPEC 4.5）。ここで実行終了
     Ending { id: String, line: usize },
+    /// `<!-- background: path -->`（SPEC 4.6）。シーン途中で背景を切り替える
+    Background { path: String, line: usize },
+    /// `<!-- bgm: path -->`（SPEC 4.6）。シーン途中で BGM を切り替える
+    Bgm { path: String, line: usize },
+    /// `<!-- bgm: stop -->`（SPEC 4.6）。BGM を止める
+    BgmStop { line: usize },
+    /// `<!-- se: path -->`（SPEC 4.6）。効果音を 1 回鳴らす
+    Se { path: String, line: usize },
+    /// `<!-- show: 名前(表情) -->`（SPEC 4.6）。立ち絵を表示する
+    Show {
+        character: String,
+        expression: Option<String>,
+        line: usize,
+    },
+    /// `<!-- hide: 名前 -->`（SPEC 4.6）。立ち絵を消す
+    Hide { character: String, line: usize },
 }
 
 /// 選択肢 1 項目
@@ -119,6 +192,16 @@ pub struct ChoiceItem {
     pub line: usize,
 }
 
+/// ランダム分岐の 1 項目（SPEC 4.10）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RandomItem {
+    pub label: String,
+    pub target: LinkTarget,
+    /// 項目に書いた `<!-- weight: N -->`。省略時は 1
+    pub weight: u32,
+    pub line: usize,
+}
+
 /// 選択肢・ジャンプの飛び先（SPEC 4.3）
 ///
 /// `#anchor` / `file.md` / `file.md#anchor` の 3 形式。
@@ -3,13 +3,32 @@
 //! pulldown-cmark のイベント列を歩いて [`Scene`] を構築する。
 //! SPEC 6.1 に従い、エラーで中断せず解釈できた範囲の Scene と
 //! すべての Diagnostic を返す。
+//!
+//! `<!-- include: path -->`（SPEC 4.8）は位置を [`Parsed::includes`] に記録する
+//! だけで、取り込み（ファイルの読み込みと展開）は読み込み時に
+//! [`include`](super::include) が行う。パーサーはファイルを読まない。
 
 use super::anchor::{percent_decode, slugify};
 use super::diagnostic::Diagnostic;
-use super::{Block, ChoiceItem, LinkTarget, Scene, Section};
+use super::{Block, ChoiceItem, LinkTarget, RandomItem, Scene, Section};
 use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::collections::BTreeMap;
 use std::ops::Range;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// `<!-- key: value -->` 形式で意味を持つ制御キー（SPEC 4.5 / 4.6 / 4.8 / 4.9 / 4.10）
+const DIRECTIVE_KEYS: [&str; 10] = [
+    "ending",
+    "background",
+    "bgm",
+    "se",
+    "show",
+    "hide",
+    "include",
+    "call",
+    "return",
+    "random",
+];
 
 /// パース結果。Scene と Diagnostic は常に両方返る
 #[derive(Debug)]
@@ -19,6 +38,73 @@ pub struct Parsed {
     /// front matter の各キーの行番号。check が missing-asset /
     /// duplicate-scene-id の span を付けるのに使う
     pub front_matter_spans: FrontMatterSpans,
+    /// 本文ブロックのソース上の範囲。`scene.lead` → `scene.sections[].blocks`
+    /// の順に 1 ブロック 1 要素で並ぶ（compile のソースマップ用）
+    pub block_ranges: Vec<SourceRange>,
+    /// 選択肢の各項目のソース上の範囲（項目の行 → 範囲）
+    pub item_ranges: BTreeMap<usize, SourceRange>,
+    /// `<!-- include: path -->` の位置（SPEC 4.8）。読み込み時に展開すると空になる
+    pub includes: Vec<Include>,
+    /// 展開して取り込んだフラグメントの行。取り込んだブロックの行番号は
+    /// このファイルの行数より後ろに振り直してあり、[`Parsed::locate`] で
+    /// フラグメントのファイルと行に戻せる
+    pub fragments: Vec<FragmentLines>,
+    /// ソースの行数
+    pub line_count: usize,
+}
+
+/// `<!-- include: path -->` 1 つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Include {
+    /// 書かれたとおりのパス（このファイルからの相対パス）
+    pub path: String,
+    pub line: usize,
+    /// 取り込む先のセグメント（0 = リード部、n = `sections[n-1]`）
+    pub segment: usize,
+    /// セグメント内のブロック位置
+    pub position: usize,
+    /// [`Parsed::block_ranges`] での位置
+    pub block_index: usize,
+}
+
+/// 取り込んだフラグメント 1 つの行の対応。`base + 1` 〜 `base + lines` 行目が
+/// フラグメントの 1 〜 `lines` 行目にあたる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FragmentLines {
+    pub path: PathBuf,
+    pub base: usize,
+    pub lines: usize,
+}
+
+impl Parsed {
+    /// 行番号を、実際に書かれているファイルと行に直す。取り込んだフラグメントの
+    /// 行ならフラグメントのファイル、そうでなければこのファイルのまま
+    pub fn locate(&self, line: usize) -> (&Path, usize) {
+        self.fragments
+            .iter()
+            .find(|f| line > f.base && line <= f.base + f.lines)
+            .map_or((self.scene.path.as_path(), line), |f| {
+                (f.path.as_path(), line - f.base)
+            })
+    }
+
+    /// 行番号の使われている範囲の末尾（取り込んだフラグメントの行を含む）
+    pub(super) fn line_extent(&self) -> usize {
+        self.fragments
+            .iter()
+            .map(|f| f.base + f.lines)
+            .fold(self.line_count, usize::max)
+    }
+}
+
+/// ソース上の範囲（1-origin、ファイル全体での行）。列は文字単位で数え、
+/// `end_column` は範囲の最後の文字の列（その文字を含む）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceRange {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
 }
 
 /// front matter のキーごとの行番号（1-origin、ファイル全体での行）
@@ -27,6 +113,7 @@ pub struct FrontMatterSpans {
     pub id: Option<usize>,
     pub background: Option<usize>,
     pub bgm: Option<usize>,
+    pub display_names: Option<usize>,
 }
 
 /// ファイルを読み込んでパースする
@@ -38,13 +125,14 @@ pub fn parse_file(path: &Path) -> Result<Parsed, String> {
 
 /// 文字列をパースする。`path` は Diagnostic とリンク解決の基準に使う
 pub fn parse_str(source: &str, path: &Path) -> Parsed {
-    let mut p = SceneParser::new(source, path);
-    p.run();
-    Parsed {
-        scene: p.scene,
-        diagnostics: p.diagnostics,
-        front_matter_spans: p.fm_spans,
-    }
+    SceneParser::new(source, path, false).finish()
+}
+
+/// フラグメント（SPEC 4.8、`<!-- include: -->` で取り込むファイル）をパースする。
+/// ブロックはすべて `scene.lead` に入る。front matter と H1 / H2 は書けず
+/// `invalid-fragment`、シーンにだけ要る front matter・タイトルの欠落は報告しない
+pub(super) fn parse_fragment(source: &str, path: &Path) -> Parsed {
+    SceneParser::new(source, path, true).finish()
 }
 
 /// front matter の解析結果
@@ -67,10 +155,19 @@ struct SceneParser<'a> {
     /// H1 を見た行（invalid-h1 判定用）
     title_line: Option<usize>,
     fm_spans: FrontMatterSpans,
+    /// いま読んでいる要素の body 内の範囲。push_block がブロックの範囲にする
+    current_range: Range<usize>,
+    block_ranges: Vec<SourceRange>,
+    item_ranges: BTreeMap<usize, SourceRange>,
+    includes: Vec<Include>,
+    /// フラグメントとしてパースしているか
+    fragment: bool,
+    /// 読んだ `<!-- random -->` の行。直後のリストをランダム分岐にする（SPEC 4.10）
+    pending_random: Option<usize>,
 }
 
 impl<'a> SceneParser<'a> {
-    fn new(source: &'a str, path: &'a Path) -> Self {
+    fn new(source: &'a str, path: &'a Path, fragment: bool) -> Self {
         let mut line_starts = vec![0];
         for (i, b) in source.bytes().enumerate() {
             if b == b'\n' {
@@ -88,6 +185,7 @@ impl<'a> SceneParser<'a> {
                 title: None,
                 background: None,
                 bgm: None,
+                display_names: BTreeMap::new(),
                 lead: Vec::new(),
                 sections: Vec::new(),
             },
@@ -95,6 +193,26 @@ impl<'a> SceneParser<'a> {
             current_section: None,
             title_line: None,
             fm_spans: FrontMatterSpans::default(),
+            current_range: 0..0,
+            block_ranges: Vec::new(),
+            item_ranges: BTreeMap::new(),
+            includes: Vec::new(),
+            fragment,
+            pending_random: None,
+        }
+    }
+
+    fn finish(mut self) -> Parsed {
+        self.run();
+        Parsed {
+            line_count: self.source.lines().count(),
+            scene: self.scene,
+            diagnostics: self.diagnostics,
+            front_matter_spans: self.fm_spans,
+            block_ranges: self.block_ranges,
+            item_ranges: self.item_ranges,
+            includes: self.includes,
+            fragments: Vec::new(),
         }
     }
 
@@ -111,6 +229,23 @@ impl<'a> SceneParser<'a> {
         self.line_at(self.body_offset + range.start)
     }
 
+    /// body 内の range → source 全体での範囲。末尾の空白・改行は含めない
+    fn range_of(&self, range: &Range<usize>) -> SourceRange {
+        let start = self.body_offset + range.start;
+        let text = self.source[start..self.body_offset + range.end].trim_end();
+        let last = text.char_indices().last().map_or(start, |(i, _)| start + i);
+        let column_at = |offset: usize| {
+            let line_start = self.line_starts[self.line_at(offset) - 1];
+            self.source[line_start..offset].chars().count() + 1
+        };
+        SourceRange {
+            line: self.line_at(start),
+            column: column_at(start),
+            end_line: self.line_at(last),
+            end_column: column_at(last),
+        }
+    }
+
     fn error(&mut self, rule_id: &'static str, line: usize, message: String) -> &mut Diagnostic {
         self.diagnostics
             .push(Diagnostic::error(rule_id, self.path, line, message));
@@ -124,6 +259,10 @@ impl<'a> SceneParser<'a> {
     }
 
     fn push_block(&mut self, block: Block) {
+        if !matches!(block, Block::Random { .. }) {
+            self.drop_pending_random();
+        }
+        self.block_ranges.push(self.range_of(&self.current_range));
         match &mut self.current_section {
             Some(section) => section.blocks.push(block),
             None => self.scene.lead.push(block),
@@ -131,7 +270,11 @@ impl<'a> SceneParser<'a> {
     }
 
     fn run(&mut self) {
-        let fm = self.parse_front_matter();
+        let fm = if self.fragment {
+            self.skip_fragment_front_matter()
+        } else {
+            self.parse_front_matter()
+        };
         self.body_offset = fm.body_offset;
         // self への可変借用と衝突しないよう、'a の参照を取り出してから使う
         let source: &'a str = self.source;
@@ -142,12 +285,13 @@ impl<'a> SceneParser<'a> {
         let events: Vec<(Event, Range<usize>)> =
             Parser::new_ext(body, options).into_offset_iter().collect();
         self.walk(&events);
+        self.drop_pending_random();
 
         // 開きっぱなしのセクションを確定する
         if let Some(section) = self.current_section.take() {
             self.scene.sections.push(section);
         }
-        if self.scene.title.is_none() {
+        if self.scene.title.is_none() && !self.fragment {
             let line = fm
                 .present
                 .map(|_| self.line_at(self.body_offset))
@@ -210,6 +354,39 @@ impl<'a> SceneParser<'a> {
         }
     }
 
+    /// フラグメントの front matter は読まずに飛ばし、`invalid-fragment` にする
+    fn skip_fragment_front_matter(&mut self) -> FrontMatter {
+        let close_line = (self.source.lines().next().map(str::trim_end) == Some("---"))
+            .then(|| {
+                self.source
+                    .lines()
+                    .enumerate()
+                    .skip(1)
+                    .find(|(_, l)| l.trim_end() == "---")
+                    .map(|(i, _)| i + 1)
+            })
+            .flatten();
+        let Some(close_line) = close_line else {
+            return FrontMatter {
+                body_offset: 0,
+                present: None,
+            };
+        };
+        self.error(
+            "invalid-fragment",
+            1,
+            "フラグメントには front matter を書けません（シーン ID や背景は取り込む側のシーンに書きます）".to_string(),
+        );
+        FrontMatter {
+            body_offset: self
+                .line_starts
+                .get(close_line)
+                .copied()
+                .unwrap_or(self.source.len()),
+            present: Some(1),
+        }
+    }
+
     fn read_front_matter_yaml(&mut self, yaml: &str) {
         // 各トップレベルキーの行番号（front matter は 1 行目の `---` から
         // 始まるので、yaml の i 行目 = ファイルの 2 + i 行目）
@@ -290,12 +467,32 @@ impl<'a> SceneParser<'a> {
                         }
                     }
                 }
+                "display_names" => {
+                    let line = line_of_key(key);
+                    self.fm_spans.display_names = Some(line);
+                    let names = val.as_mapping().and_then(|m| {
+                        m.iter()
+                            .map(|(k, v)| Some((k.as_str()?.to_string(), v.as_str()?.to_string())))
+                            .collect::<Option<BTreeMap<_, _>>>()
+                    });
+                    match names {
+                        Some(names) => self.scene.display_names = names,
+                        None => {
+                            self.error(
+                                "invalid-frontmatter",
+                                line,
+                                "front matter の `display_names` は「話者名: 表示名」のマッピングで書いてください".to_string(),
+                            )
+                            .suggestion = Some("display_names:\n  葵: \"???\"".to_string());
+                        }
+                    }
+                }
                 unknown => {
                     self.warning(
                         "unknown-frontmatter-key",
                         line_of_key(unknown),
                         format!(
-                            "front matter の `{unknown}` は v1 では定義されていないキーです（使えるのは id / background / bgm）"
+                            "front matter の `{unknown}` は v1 では定義されていないキーです（使えるのは id / background / bgm / display_names）"
                         ),
                     );
                 }
@@ -318,6 +515,7 @@ impl<'a> SceneParser<'a> {
         let mut i = 0;
         while i < events.len() {
             let (event, range) = &events[i];
+            self.current_range = range.clone();
             match event {
                 Event::Start(Tag::Heading { level, .. }) => {
                     i = self.consume_heading(events, i, *level, range);
@@ -329,20 +527,20 @@ impl<'a> SceneParser<'a> {
                     i = self.consume_list(events, i, range);
                 }
                 Event::Html(html) | Event::InlineHtml(html) => {
-                    let line = self.line_of(range);
-                    self.consume_html(html, line);
+                    self.consume_html(html, range);
                     i += 1;
                 }
                 Event::Start(Tag::HtmlBlock) | Event::End(TagEnd::HtmlBlock) => {
                     i += 1;
                 }
                 Event::Rule => {
-                    // 空行を挟んだ水平線は区切りとして無視する（SPEC 4.6）
+                    // 空行を挟んだ水平線は区切りとして無視する（SPEC 4.7）
                     i += 1;
                 }
                 Event::Start(tag) => {
                     // 引用・コードブロック・テーブル・画像など v1 で
-                    // 意味を定義していない要素（SPEC 4.6）
+                    // 意味を定義していない要素（SPEC 4.7）
+                    self.drop_pending_random();
                     let line = self.line_of(range);
                     self.warning(
                         "unsupported-element",
@@ -369,6 +567,7 @@ impl<'a> SceneParser<'a> {
         level: HeadingLevel,
         range: &Range<usize>,
     ) -> usize {
+        self.drop_pending_random();
         let line = self.line_of(range);
         let (text, next) = collect_text(events, start, TagEnd::Heading(level));
 
@@ -389,6 +588,16 @@ impl<'a> SceneParser<'a> {
             return next;
         }
 
+        if self.fragment && matches!(level, HeadingLevel::H1 | HeadingLevel::H2) {
+            self.error(
+                "invalid-fragment",
+                line,
+                format!(
+                    "フラグメントには見出し「{text}」を書けません（無視します）。タイトルやセクションは取り込む側のシーンに書いてください"
+                ),
+            );
+            return next;
+        }
         match level {
             HeadingLevel::H1 => {
                 if self.scene.title.is_some() {
@@ -503,15 +712,14 @@ impl<'a> SceneParser<'a> {
                     }
                 }
                 Event::InlineHtml(html) => {
-                    let l = self.line_of(range);
-                    self.consume_html(html, l);
+                    self.consume_html(html, range);
                 }
                 Event::Start(Tag::Image { .. }) => {
                     let l = self.line_of(range);
                     self.warning(
                         "unsupported-element",
                         l,
-                        "画像は v1 では解釈されません（無視します）。背景は front matter の background に書いてください".to_string(),
+                        "画像は v1 では解釈されません（無視します）。背景は front matter の background か `<!-- background: パス -->` で書いてください".to_string(),
                     );
                     i = skip_to_end(events, i);
                     continue;
@@ -523,6 +731,9 @@ impl<'a> SceneParser<'a> {
             i += 1;
         }
 
+        // 段落内の制御コメントが範囲を書き換えているので段落全体に戻す
+        self.current_range = events[start].1.clone();
+
         // リンク 1 つだけの段落はジャンプ（SPEC 4.4）
         let non_link_text: String = {
             let mut t = text.clone();
@@ -567,17 +778,33 @@ impl<'a> SceneParser<'a> {
             return i;
         }
         match split_dialogue(&text) {
-            Some((speaker, body)) => self.push_block(Block::Dialogue {
-                speaker,
-                text: body,
-                line,
-            }),
+            Some((speaker, body)) => {
+                let (speaker, expression) = split_expression(&speaker);
+                self.push_block(Block::Dialogue {
+                    speaker: speaker.to_string(),
+                    expression: expression.map(String::from),
+                    text: body,
+                    line,
+                })
+            }
             None => self.push_block(Block::Narration { text, line }),
         }
         i
     }
 
-    /// リストを読み切り、選択肢ブロックに分類する（SPEC 4.3）
+    /// `<!-- random -->` の後にリストが続かなかった。directive は無視する
+    fn drop_pending_random(&mut self) {
+        if let Some(line) = self.pending_random.take() {
+            self.warning(
+                "unknown-directive",
+                line,
+                "`<!-- random -->` の直後にリンクのリストがありません（無視します）。ランダムに進む先を `- [ラベル](#飛び先)` のリストで続けて書いてください".to_string(),
+            );
+        }
+    }
+
+    /// リストを読み切り、選択肢ブロック（`<!-- random -->` の直後ならランダム
+    /// 分岐）に分類する（SPEC 4.3 / 4.10）
     fn consume_list(
         &mut self,
         events: &[(Event, Range<usize>)],
@@ -585,10 +812,14 @@ impl<'a> SceneParser<'a> {
         range: &Range<usize>,
     ) -> usize {
         let list_line = self.line_of(range);
+        let random = self.pending_random.take().is_some();
         struct Item {
             line: usize,
+            range: Range<usize>,
             links: Vec<(String, String)>, // (label, href)
             extra_text: String,
+            /// 項目の `<!-- weight: N -->` の値（書かれたまま）
+            weight: Option<String>,
         }
         let mut items: Vec<Item> = Vec::new();
         let mut in_link: Option<(String, String)> = None;
@@ -615,8 +846,10 @@ impl<'a> SceneParser<'a> {
                 Event::Start(Tag::Item) => {
                     items.push(Item {
                         line: self.line_of(ev_range),
+                        range: ev_range.clone(),
                         links: Vec::new(),
                         extra_text: String::new(),
+                        weight: None,
                     });
                 }
                 Event::Start(Tag::Link { dest_url, .. }) => {
@@ -634,6 +867,16 @@ impl<'a> SceneParser<'a> {
                         item.extra_text.push_str(t);
                     }
                 }
+                Event::InlineHtml(html) if in_link.is_none() => {
+                    let weight = html
+                        .trim()
+                        .strip_prefix("<!--")
+                        .and_then(|c| c.strip_suffix("-->"))
+                        .and_then(|c| c.trim().strip_prefix("weight:"));
+                    if let (Some(weight), Some(item)) = (weight, items.last_mut()) {
+                        item.weight = Some(weight.trim().to_string());
+                    }
+                }
                 _ => {}
             }
             i += 1;
@@ -649,6 +892,7 @@ impl<'a> SceneParser<'a> {
         }
 
         let mut choice_items: Vec<ChoiceItem> = Vec::new();
+        let mut weights: Vec<u32> = Vec::new();
         for item in items {
             if item.links.len() != 1 || !item.extra_text.trim().is_empty() {
                 self.error(
@@ -668,26 +912,74 @@ impl<'a> SceneParser<'a> {
                 );
                 continue;
             }
+            let weight = match (&item.weight, random) {
+                (None, _) => 1,
+                (Some(weight), true) => match weight.parse::<u32>() {
+                    Ok(weight) if weight > 0 => weight,
+                    _ => {
+                        self.warning(
+                            "unknown-directive",
+                            item.line,
+                            format!(
+                                "重み「{weight}」は 1 以上の整数で書いてください（この項目は重み 1 として扱います）"
+                            ),
+                        )
+                        .suggestion = Some("<!-- weight: 2 -->".to_string());
+                        1
+                    }
+                },
+                (Some(_), false) => {
+                    self.warning(
+                        "unknown-directive",
+                        item.line,
+                        "`<!-- weight: N -->` はランダム分岐（`<!-- random -->` の直後のリスト）の項目にだけ書けます（無視します）".to_string(),
+                    );
+                    1
+                }
+            };
             if let Some(target) = self.parse_link_target(&href, item.line) {
+                self.item_ranges
+                    .insert(item.line, self.range_of(&item.range));
                 choice_items.push(ChoiceItem {
                     label: label.trim().to_string(),
                     target,
                     line: item.line,
                 });
+                weights.push(weight);
             }
         }
         if !choice_items.is_empty() {
-            self.push_block(Block::Choices {
-                items: choice_items,
-                line: list_line,
-            });
+            self.current_range = range.clone();
+            let block = if random {
+                Block::Random {
+                    items: choice_items
+                        .into_iter()
+                        .zip(weights)
+                        .map(|(item, weight)| RandomItem {
+                            label: item.label,
+                            target: item.target,
+                            weight,
+                            line: item.line,
+                        })
+                        .collect(),
+                    line: list_line,
+                }
+            } else {
+                Block::Choices {
+                    items: choice_items,
+                    line: list_line,
+                }
+            };
+            self.push_block(block);
         }
         i
     }
 
     /// HTML コメントから ending 等の制御情報を読む（SPEC 4.5）
-    fn consume_html(&mut self, html: &str, line: usize) {
+    fn consume_html(&mut self, html: &str, range: &Range<usize>) {
+        let line = self.line_of(range);
         let mut rest = html;
+        let mut consumed = 0; // html 内で読み終えたバイト数
         let mut saw_comment = false;
         while let Some(open) = rest.find("<!--") {
             let Some(close) = rest[open..].find("-->") else {
@@ -695,7 +987,10 @@ impl<'a> SceneParser<'a> {
             };
             saw_comment = true;
             let inner = rest[open + 4..open + close].trim();
-            self.consume_comment(inner, line);
+            let start = range.start + consumed + open;
+            self.current_range = start..start + close + 3;
+            self.consume_comment(inner, self.line_of(&self.current_range.clone()));
+            consumed += open + close + 3;
             rest = &rest[open + close + 3..];
         }
         if !saw_comment && !html.trim().is_empty() {
@@ -708,6 +1003,17 @@ impl<'a> SceneParser<'a> {
     }
 
     fn consume_comment(&mut self, inner: &str, line: usize) {
+        // 値を取らない `<!-- return -->`（SPEC 4.9）
+        if inner == "return" {
+            self.push_block(Block::Return { line });
+            return;
+        }
+        // 値を取らない `<!-- random -->`（SPEC 4.10）。直後のリストで使う
+        if inner == "random" {
+            self.drop_pending_random();
+            self.pending_random = Some(line);
+            return;
+        }
         // `key: value` 形式だけが制御情報。それ以外はメモとして無視する
         let Some((key, value)) = inner.split_once(':') else {
             return;
@@ -721,33 +1027,156 @@ impl<'a> SceneParser<'a> {
             return; // 自由文のメモ
         }
         let value = value.trim();
-        if key == "ending" {
-            if !value.is_empty()
-                && value
-                    .chars()
-                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
-            {
-                self.push_block(Block::Ending {
-                    id: value.to_string(),
+        match key {
+            "ending" => {
+                if !value.is_empty()
+                    && value
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                {
+                    self.push_block(Block::Ending {
+                        id: value.to_string(),
+                        line,
+                    });
+                } else {
+                    self.warning(
+                        "unknown-directive",
+                        line,
+                        format!(
+                            "ending id「{value}」に使えるのは英数字・ハイフン・アンダースコアだけです"
+                        ),
+                    );
+                }
+            }
+            "background" | "bgm" | "se" => {
+                if value.is_empty() {
+                    self.warning(
+                        "unknown-directive",
+                        line,
+                        format!(
+                            "`<!-- {key}: -->` にファイルのパスがありません。このファイルからの相対パスを書いてください"
+                        ),
+                    )
+                    .suggestion = Some(format!("<!-- {key}: ../assets/... -->"));
+                    return;
+                }
+                let path = value.to_string();
+                let block = match key {
+                    "background" => Block::Background { path, line },
+                    "bgm" if value == "stop" => Block::BgmStop { line },
+                    "bgm" => Block::Bgm { path, line },
+                    _ => Block::Se { path, line },
+                };
+                self.push_block(block);
+            }
+            "include" => {
+                if value.is_empty() {
+                    self.warning(
+                        "unknown-directive",
+                        line,
+                        "`<!-- include: -->` に取り込むファイルのパスがありません。このファイルからの相対パスを書いてください".to_string(),
+                    )
+                    .suggestion = Some("<!-- include: _fragment.md -->".to_string());
+                    return;
+                }
+                let (segment, position) = match &self.current_section {
+                    Some(section) => (self.scene.sections.len() + 1, section.blocks.len()),
+                    None => (0, self.scene.lead.len()),
+                };
+                self.includes.push(Include {
+                    path: value.to_string(),
                     line,
+                    segment,
+                    position,
+                    block_index: self.block_ranges.len(),
                 });
-            } else {
+            }
+            "call" => {
+                if value.is_empty() {
+                    self.warning(
+                        "unknown-directive",
+                        line,
+                        "`<!-- call: -->` に呼び出す先がありません。ジャンプと同じく `#見出し` / `ファイル.md` / `ファイル.md#見出し` を書いてください".to_string(),
+                    )
+                    .suggestion = Some("<!-- call: file.md#anchor -->".to_string());
+                    return;
+                }
+                if let Some(target) = self.parse_link_target(value, line) {
+                    self.push_block(Block::Call { target, line });
+                }
+            }
+            "return" => {
+                if value.is_empty() {
+                    self.push_block(Block::Return { line });
+                    return;
+                }
                 self.warning(
                     "unknown-directive",
                     line,
                     format!(
-                        "ending id「{value}」に使えるのは英数字・ハイフン・アンダースコアだけです"
+                        "`<!-- return -->` は値を取りません（「{value}」は無視します）。戻り先は直近の `<!-- call: -->` で決まります"
+                    ),
+                )
+                .suggestion = Some("<!-- return -->".to_string());
+            }
+            "random" => {
+                if value.is_empty() {
+                    self.drop_pending_random();
+                    self.pending_random = Some(line);
+                    return;
+                }
+                self.warning(
+                    "unknown-directive",
+                    line,
+                    format!(
+                        "`<!-- random -->` は値を取りません（「{value}」は無視します）。項目の重みは各項目に `<!-- weight: N -->` で書いてください"
+                    ),
+                )
+                .suggestion = Some("<!-- random -->".to_string());
+            }
+            "show" | "hide" => {
+                let (character, expression) = split_expression(value);
+                if character.is_empty() || character.chars().any(char::is_whitespace) {
+                    self.warning(
+                        "unknown-directive",
+                        line,
+                        format!(
+                            "`<!-- {key}: ... -->` には空白を含まないキャラクター名を書いてください"
+                        ),
+                    )
+                    .suggestion = Some(format!("<!-- {key}: 名前 -->"));
+                    return;
+                }
+                let character = character.to_string();
+                let block = match (key, expression) {
+                    ("show", expression) => Block::Show {
+                        character,
+                        expression: expression.map(String::from),
+                        line,
+                    },
+                    (_, None) => Block::Hide { character, line },
+                    (_, Some(_)) => {
+                        self.warning(
+                            "unknown-directive",
+                            line,
+                            "`<!-- hide: ... -->` に表情は書けません。キャラクター名だけを書いてください".to_string(),
+                        )
+                        .suggestion = Some(format!("<!-- hide: {character} -->"));
+                        return;
+                    }
+                };
+                self.push_block(block);
+            }
+            _ => {
+                self.warning(
+                    "unknown-directive",
+                    line,
+                    format!(
+                        "`<!-- {key}: ... -->` は v1 では定義されていない制御情報です（使えるのは {}）",
+                        DIRECTIVE_KEYS.join(" / ")
                     ),
                 );
             }
-        } else {
-            self.warning(
-                "unknown-directive",
-                line,
-                format!(
-                    "`<!-- {key}: ... -->` は v1 では定義されていない制御情報です（使えるのは ending のみ）"
-                ),
-            );
         }
     }
 
@@ -839,6 +1268,24 @@ fn split_dialogue(text: &str) -> Option<(String, String)> {
     Some((speaker.to_string(), rest))
 }
 
+/// `名前(表情)` / `名前（表情）` を名前と表情に分ける（SPEC 4.2 / 4.6）。
+/// 括弧がない・中身が空なら表情なし
+fn split_expression(name: &str) -> (&str, Option<&str>) {
+    let name = name.trim();
+    let Some(inner) = name.strip_suffix(')').or_else(|| name.strip_suffix('）')) else {
+        return (name, None);
+    };
+    let Some(open) = inner.rfind(['(', '（']) else {
+        return (name, None);
+    };
+    let expression = inner[open..].trim_start_matches(['(', '（']).trim();
+    let character = inner[..open].trim_end();
+    if expression.is_empty() || character.is_empty() {
+        return (name, None);
+    }
+    (character, Some(expression))
+}
+
 /// 旧記法（v0）の検出と書き換え案内（SPEC 6 `legacy-command`、11.1 対応表）
 ///
 /// fmt（[`super::fmt`]）が「確定的に変換できない旧記法」を同じ文言で
@@ -874,7 +1321,7 @@ pub(super) fn legacy_command(text: &str) -> Option<(String, String)> {
         "LABEL" => "分岐先は H2 見出しで書いてください（例: `## 飛び先`）".to_string(),
         "JUMP" => "ジャンプはリンクだけの段落で書いてください（例: `[次へ](#飛び先)`）".to_string(),
         "SHOW_IMAGE" | "PLAY_MUSIC" | "PLAY_BGM" | "PLAY_SE" => {
-            "背景・BGM は front matter に書いてください（例: `background: ../assets/bg/xxx.png`）"
+            "背景・BGM・効果音は front matter か制御コメントで書いてください（例: `<!-- background: ../assets/bg/xxx.png -->` / `<!-- se: ../assets/se/xxx.ogg -->`）"
                 .to_string()
         }
         "WAIT" => {
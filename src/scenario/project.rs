@@ -2,15 +2,20 @@
 //!
 //! check（意味論検査）と trace（実行トレース）が同じ読み込み規則を共有する:
 //!
-//! - ディレクトリ: 配下のすべての `.md`（README.md と隠しディレクトリを除く）
+//! - ディレクトリ: 配下のすべての `.md`（README.md・フラグメント・隠しディレクトリを除く）
 //! - ファイル: そのファイルとリンクで辿れる閉包
 //! - ファイル参照はシーンファイルからの相対パスのみ（SPEC 2章）
+//! - `<!-- include: -->` のフラグメントはパース直後に展開する（SPEC 4.8）
+//! - chapters.yaml があれば、章に並べたシーンもリンクと同じく読み込む（SPEC 2.5）
 
-use super::check::{CheckOptions, CheckResult, check_path};
+use super::chapters::{find_chapters_file, is_chapter_scene, load_chapters};
+use super::characters::{Characters, find_characters_file, load_characters};
+use super::check::{CheckOptions, CheckResult, check_paths};
 use super::diagnostic::{Diagnostic, Severity};
+use super::include::{expand_includes, is_fragment};
 use super::parse::{Parsed, parse_file};
 use super::{Block, LinkTarget, Scene};
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
 
 /// 読み込んだ 1 シーン
@@ -20,6 +25,18 @@ pub(super) struct LoadedScene {
     /// 同一ファイル判定用の正規化パス
     pub(super) canon: PathBuf,
     pub(super) parsed: Parsed,
+    /// 話者名 → このシーンでの表示名（characters.yaml の `display_name` に
+    /// front matter の `display_names` を重ねたもの、SPEC 2.1 / 3.1）
+    pub(super) display_names: BTreeMap<String, String>,
+}
+
+impl LoadedScene {
+    /// 話者のこのシーンでの表示名。宣言がなければ話者名そのもの
+    pub(super) fn display_name<'a>(&'a self, speaker: &'a str) -> &'a str {
+        self.display_names
+            .get(speaker)
+            .map_or(speaker, String::as_str)
+    }
 }
 
 /// 実行系コマンドが使う、検査済みのプロジェクト。
@@ -35,27 +52,36 @@ pub(super) struct CheckedProject {
 /// check と同じ規則で検査したあと、実行系コマンド用にシーン閉包を読み込む。
 ///
 /// `check_path` はディレクトリも検査対象にできるが、実行系コマンドは開始する
-/// シーンファイルを 1 つ必要とするため、ディレクトリだけはコマンド名入りの
-/// `io-error` にする。
+/// シーンファイルを必要とするため、ディレクトリだけはコマンド名入りの
+/// `io-error` にする。`extra_entries` は routes / compile の `--entry` で足した
+/// 開始シーンで、`path` と合わせて 1 つのプロジェクトとして検査・読み込みする
+/// （読み込んだシーンの先頭は常に `path`）
 pub(super) fn load_checked_project(
     path: &Path,
+    extra_entries: &[PathBuf],
     command: &str,
     check_assets: bool,
 ) -> Result<CheckedProject, CheckResult> {
-    if path.is_dir() {
-        let diag = file_level(
-            "io-error",
-            Severity::Error,
-            path,
-            format!(
-                "{} はディレクトリです。{} は開始するシーンファイル（.md）を 1 つ指定してください",
-                path.display(),
-                command
-            ),
-        );
+    let directories: Vec<Diagnostic> = std::iter::once(path)
+        .chain(extra_entries.iter().map(PathBuf::as_path))
+        .filter(|p| p.is_dir())
+        .map(|p| {
+            file_level(
+                "io-error",
+                Severity::Error,
+                p,
+                format!(
+                    "{} はディレクトリです。{} は開始するシーンファイル（.md）を指定してください",
+                    p.display(),
+                    command
+                ),
+            )
+        })
+        .collect();
+    if !directories.is_empty() {
         return Err(CheckResult {
             files: Vec::new(),
-            diagnostics: vec![diag],
+            diagnostics: directories,
         });
     }
 
@@ -63,13 +89,16 @@ pub(super) fn load_checked_project(
         check_assets,
         ..CheckOptions::default()
     };
-    let mut check = check_path(path, &check_options);
+    let entries: Vec<PathBuf> = std::iter::once(path.to_path_buf())
+        .chain(extra_entries.iter().cloned())
+        .collect();
+    let mut check = check_paths(&entries, &check_options);
     if check.has_errors() {
         return Err(check);
     }
 
     let mut load_diagnostics = Vec::new();
-    let scenes = load_project(vec![path.to_path_buf()], &mut load_diagnostics);
+    let scenes = load_project(entries, &mut load_diagnostics);
     check.diagnostics.extend(load_diagnostics);
     if check.has_errors() || scenes.is_empty() {
         return Err(check);
@@ -78,9 +107,43 @@ pub(super) fn load_checked_project(
     Ok(CheckedProject { check, scenes })
 }
 
+/// 読み込みの起点にするファイル。ディレクトリなら配下のすべての `.md`、
+/// ファイルならそれ自体。見つからなければ `io-error` を積んで空を返す
+pub(super) fn seed_files(path: &Path, diagnostics: &mut Vec<Diagnostic>) -> Vec<PathBuf> {
+    if path.is_dir() {
+        let mut files = Vec::new();
+        collect_md_files(path, &mut files);
+        if files.is_empty() {
+            diagnostics.push(file_level(
+                "io-error",
+                Severity::Error,
+                path,
+                format!(
+                    "{} に .md ファイルがありません。シナリオファイルのあるディレクトリか、ファイルそのものを指定してください",
+                    path.display()
+                ),
+            ));
+        }
+        files
+    } else if path.is_file() {
+        vec![path.to_path_buf()]
+    } else {
+        diagnostics.push(file_level(
+            "io-error",
+            Severity::Error,
+            path,
+            format!(
+                "{} が見つかりません。パスを確認してください",
+                path.display()
+            ),
+        ));
+        Vec::new()
+    }
+}
+
 /// ディレクトリ配下の `.md` を再帰的に集める（名前順）。
-/// 隠しディレクトリと、シーンではない README.md は除く（SPEC 6章）
-pub(super) fn collect_md_files(dir: &Path, out: &mut Vec<PathBuf>) {
+/// 隠しディレクトリと、シーンではない README.md・フラグメント（SPEC 4.8）は除く
+fn collect_md_files(dir: &Path, out: &mut Vec<PathBuf>) {
     let Ok(entries) = std::fs::read_dir(dir) else {
         return;
     };
@@ -94,17 +157,23 @@ pub(super) fn collect_md_files(dir: &Path, out: &mut Vec<PathBuf>) {
         let path = entry.path();
         if path.is_dir() {
             collect_md_files(&path, out);
-        } else if path.extension().and_then(|e| e.to_str()) == Some("md") {
+        } else if path.extension().and_then(|e| e.to_str()) == Some("md") && !is_fragment(&path) {
             out.push(path);
         }
     }
 }
 
-/// seeds とそこからリンクで辿れる `.md` をすべてパースする
+/// seeds とそこからリンクで辿れる `.md` をすべてパースする。
+///
+/// 話者の別名はここで characters.yaml の話者名に揃える（SPEC 2.1）。
+/// check / trace / routes / compile はどれも揃えた後の Scene だけを見る
 pub(super) fn load_project(
     seeds: Vec<PathBuf>,
     diagnostics: &mut Vec<Diagnostic>,
 ) -> Vec<LoadedScene> {
+    // 読み込みに失敗した characters.yaml は None（check が invalid-characters-file で報告する）
+    let mut characters: HashMap<PathBuf, Option<Characters>> = HashMap::new();
+    let mut chapter_files: HashSet<PathBuf> = HashSet::new();
     let mut scenes = Vec::new();
     let mut seen: HashSet<PathBuf> = HashSet::new();
     let mut queue: VecDeque<PathBuf> = seeds.into();
@@ -125,33 +194,99 @@ pub(super) fn load_project(
         if !seen.insert(canon.clone()) {
             continue;
         }
-        let parsed = match parse_file(&display) {
+        let mut parsed = match parse_file(&display) {
             Ok(p) => p,
             Err(e) => {
                 diagnostics.push(file_level("io-error", Severity::Error, &display, e));
                 continue;
             }
         };
+        expand_includes(&mut parsed);
         // リンク先の .md も検査対象に加える（閉包）。実在しない・絶対パスの
-        // ファイルは check_links が broken-link として報告する
+        // ファイルは check_links が broken-link として報告する。大文字小文字や
+        // 区切り文字の食い違い（path-case-mismatch / non-portable-path）は
+        // 実際の綴りで辿り、リンク先の検査を続けられるようにする
         for (_, target, _) in scene_links(&parsed.scene) {
             if let Some(file) = &target.file
-                && let Some(resolved) = resolve_sibling(&display, file)
+                && let Some(resolved) = resolve_sibling(
+                    &display,
+                    &actual_spelling(&display, file).unwrap_or_else(|| file.clone()),
+                )
                 && resolved.is_file()
                 && resolved.extension().and_then(|e| e.to_str()) == Some("md")
             {
                 queue.push_back(resolved);
             }
         }
+        // 章のシーンはリンクで辿れなくても読み込む。読み込めない chapters.yaml と
+        // 実在しないシーンは check が invalid-chapters-file / broken-chapter-ref で報告する
+        if let Some(path) = find_chapters_file(&display)
+            && chapter_files.insert(path.clone())
+            && let Ok(file) = load_chapters(&path)
+        {
+            for chapter in &file.chapters {
+                queue.extend(
+                    chapter
+                        .scenes
+                        .iter()
+                        .filter_map(|s| file.resolve_path(s))
+                        .filter(|p| is_chapter_scene(p)),
+                );
+            }
+        }
+        let chars = find_characters_file(&display).and_then(|path| {
+            characters
+                .entry(path)
+                .or_insert_with_key(|p| load_characters(p).ok())
+                .as_ref()
+        });
+        let display_names = match chars {
+            Some(chars) => resolve_characters(&mut parsed.scene, chars),
+            None => parsed.scene.display_names.clone(),
+        };
         scenes.push(LoadedScene {
             path: display,
             canon,
             parsed,
+            display_names,
         });
     }
     scenes
 }
 
+/// 別名で書かれた話者・立ち絵のキャラクター名を話者名に置き換え、
+/// シーンでの表示名の対応表を返す
+fn resolve_characters(scene: &mut Scene, chars: &Characters) -> BTreeMap<String, String> {
+    let canonical = |name: &mut String| {
+        if let Some(c) = chars.canonical(name)
+            && c != name
+        {
+            *name = c.to_string();
+        }
+    };
+    let blocks = scene
+        .lead
+        .iter_mut()
+        .chain(scene.sections.iter_mut().flat_map(|s| s.blocks.iter_mut()));
+    for block in blocks {
+        match block {
+            Block::Dialogue { speaker, .. } => canonical(speaker),
+            Block::Show { character, .. } | Block::Hide { character, .. } => canonical(character),
+            _ => {}
+        }
+    }
+    let mut display_names: BTreeMap<String, String> = chars
+        .entries
+        .keys()
+        .map(|name| (name.clone(), chars.display_name(name).to_string()))
+        .collect();
+    for (name, display) in &scene.display_names {
+        let name = chars.canonical(name).unwrap_or(name);
+        display_names.insert(name.to_string(), display.clone());
+    }
+    display_names
+}
+
 /// シーンファイルからの相対パスを解決する。
 /// tsumugai が読むのは相対パスで参照されるファイルだけ（SPEC 2章）なので、
 /// 絶対パスは解決せず None を返す（呼び出し側が broken-link / missing-asset に倒す）
@@ -167,8 +302,75 @@ pub(super) fn resolve_sibling(scene_path: &Path, relative: &str) -> Option<PathB
     )
 }
 
-/// シーン内のすべてのリンク（ジャンプ + 選択肢項目）を (ラベル, 飛び先, 行) で列挙する
-pub(super) fn scene_links(scene: &Scene) -> Vec<(&str, &LinkTarget, usize)> {
+/// シナリオファイルと同階層 → 祖先ディレクトリの順に `file_name` を探し、
+/// 最も近いものを返す（characters.yaml・endings.yaml 等のプロジェクト設定）
+pub(super) fn find_nearest(scene_path: &Path, file_name: &str) -> Option<PathBuf> {
+    scene_path
+        .parent()?
+        .ancestors()
+        .map(|dir| dir.join(file_name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// ディレクトリ群の共通の祖先（プロジェクトのルート）。compile の `file` と
+/// check の `unused-asset` が、同じ基準でアセットの置き場所を決めるために使う
+pub(super) fn common_root<'a>(dirs: impl IntoIterator<Item = &'a Path>) -> Option<PathBuf> {
+    dirs.into_iter()
+        .fold(None, |root: Option<PathBuf>, dir| match root {
+            None => Some(dir.to_path_buf()),
+            Some(root) => Some(
+                root.ancestors()
+                    .find(|a| dir.starts_with(a))
+                    .unwrap_or(&root)
+                    .to_path_buf(),
+            ),
+        })
+}
+
+/// `relative` を実際のディレクトリ一覧と 1 区切りずつ照合し、実在するファイル名の
+/// 綴りに直した `/` 区切りのパスを返す。大文字小文字を区別しないファイルシステム
+/// （macOS・Windows）で見逃される食い違いと `\` 区切りの検出に使う。
+/// 大文字小文字を無視しても一致しない区切りがあれば None
+pub(super) fn actual_spelling(scene_path: &Path, relative: &str) -> Option<String> {
+    let mut dir = scene_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .to_path_buf();
+    let mut segments = Vec::new();
+    for segment in relative.split(['/', '\\']) {
+        if matches!(segment, "" | "." | "..") {
+            dir.push(segment);
+            segments.push(segment.to_string());
+            continue;
+        }
+        let listing = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir.as_path()
+        };
+        let names: Vec<String> = std::fs::read_dir(listing)
+            .ok()?
+            .flatten()
+            .filter_map(|e| e.file_name().to_str().map(String::from))
+            .collect();
+        let actual = if names.iter().any(|n| n == segment) {
+            segment.to_string()
+        } else {
+            let lower = segment.to_lowercase();
+            names
+                .into_iter()
+                .filter(|n| n.to_lowercase() == lower)
+                .min()?
+        };
+        dir.push(&actual);
+        segments.push(actual);
+    }
+    Some(segments.join("/"))
+}
+
+/// シーン内のすべてのリンク（ジャンプ + 選択肢項目 + `<!-- call: -->`）を
+/// (ラベル, 飛び先, 行) で列挙する。call にはラベルがないので None
+pub(super) fn scene_links(scene: &Scene) -> Vec<(Option<&str>, &LinkTarget, usize)> {
     let mut out = Vec::new();
     let blocks = scene
         .lead
@@ -180,18 +382,102 @@ pub(super) fn scene_links(scene: &Scene) -> Vec<(&str, &LinkTarget, usize)> {
                 label,
                 target,
                 line,
-            } => out.push((label.as_str(), target, *line)),
+            } => out.push((Some(label.as_str()), target, *line)),
             Block::Choices { items, .. } => {
                 for item in items {
-                    out.push((item.label.as_str(), &item.target, item.line));
+                    out.push((Some(item.label.as_str()), &item.target, item.line));
                 }
             }
+            Block::Random { items, .. } => {
+                for item in items {
+                    out.push((Some(item.label.as_str()), &item.target, item.line));
+                }
+            }
+            Block::Call { target, line } => out.push((None, target, *line)),
             _ => {}
         }
     }
     out
 }
 
+/// シーンに登場するキャラクター（セリフの話者と show / hide の名前）
+pub(super) fn scene_characters(scene: &Scene) -> BTreeSet<&str> {
+    scene
+        .lead
+        .iter()
+        .chain(scene.sections.iter().flat_map(|s| s.blocks.iter()))
+        .filter_map(|block| match block {
+            Block::Dialogue { speaker, .. } => Some(speaker.as_str()),
+            Block::Show { character, .. } | Block::Hide { character, .. } => {
+                Some(character.as_str())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// シーンが参照する 1 つのアセット（front matter または制御コメント）
+pub(super) struct AssetRef<'a> {
+    /// 参照しているキー（`background` / `bgm` / `se`）
+    pub(super) key: &'static str,
+    /// シーンファイルからの相対パス（書かれたまま）
+    pub(super) path: &'a str,
+    pub(super) line: usize,
+    /// front matter に書かれたか（false なら `<!-- key: path -->`）
+    pub(super) in_front_matter: bool,
+}
+
+impl AssetRef<'_> {
+    /// 参照元と同じ書き方で `path` を差し替えた書き換え例
+    pub(super) fn rewrite(&self, path: &str) -> String {
+        if self.in_front_matter {
+            format!("{}: {path}", self.key)
+        } else {
+            format!("<!-- {}: {path} -->", self.key)
+        }
+    }
+}
+
+/// シーン内のすべてのアセット参照を、front matter → 本文の出現順で列挙する
+pub(super) fn scene_assets(parsed: &Parsed) -> Vec<AssetRef<'_>> {
+    let md = &parsed.scene;
+    let spans = &parsed.front_matter_spans;
+    let mut out = Vec::new();
+    let front_matter = [
+        ("background", &md.background, spans.background),
+        ("bgm", &md.bgm, spans.bgm),
+    ];
+    for (key, value, span) in front_matter {
+        if let Some(value) = value {
+            out.push(AssetRef {
+                key,
+                path: value,
+                line: span.unwrap_or(1),
+                in_front_matter: true,
+            });
+        }
+    }
+    let blocks = md
+        .lead
+        .iter()
+        .chain(md.sections.iter().flat_map(|s| s.blocks.iter()));
+    for block in blocks {
+        let (key, path, line) = match block {
+            Block::Background { path, line } => ("background", path, *line),
+            Block::Bgm { path, line } => ("bgm", path, *line),
+            Block::Se { path, line } => ("se", path, *line),
+            _ => continue,
+        };
+        out.push(AssetRef {
+            key,
+            path,
+            line,
+            in_front_matter: false,
+        });
+    }
+    out
+}
+
 /// span を持たないファイルレベルの Diagnostic
 pub(super) fn file_level(
     rule_id: &'static str,
@@ -12,6 +12,7 @@ use super::diagnostic::{Diagnostic, Severity};
 use super::exec::format_choices;
 use super::fmt::FmtResult;
 use super::routes::{RouteEnd, RoutesResult};
+use super::stats::{StatsCounts, StatsResult};
 use super::trace::{TraceEnd, TraceResult, TraceStep};
 use serde_json::{Value, json};
 use std::collections::HashMap;
@@ -190,10 +191,21 @@ fn render_trace_step(out: &mut String, step: &TraceStep) {
         TraceStep::Dialogue {
             line,
             speaker,
+            display_name,
+            expression,
             text,
             ..
         } => {
-            let _ = writeln!(out, "  {line:>4}| {speaker}: {text}");
+            // 表示名が話者名と違うときは両方を出す（例: `???（葵）`）
+            let mut name = if display_name == speaker {
+                speaker.clone()
+            } else {
+                format!("{display_name}（{speaker}）")
+            };
+            if let Some(expression) = expression {
+                name.push_str(&format!("({expression})"));
+            }
+            let _ = writeln!(out, "  {line:>4}| {name}: {text}");
         }
         TraceStep::Choice {
             line,
@@ -221,6 +233,35 @@ fn render_trace_step(out: &mut String, step: &TraceStep) {
                 }
             }
         }
+        TraceStep::Random {
+            line,
+            options,
+            selected,
+            forced,
+            ..
+        } => {
+            let total: u32 = options.iter().map(|o| o.weight).sum();
+            let _ = writeln!(out, "  {line:>4}| ランダム分岐:");
+            for (i, option) in options.iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "          {}. [{}]({})（重み {}/{total}）",
+                    i + 1,
+                    option.label,
+                    option.target,
+                    option.weight
+                );
+            }
+            if let Some(n) = selected {
+                let label = &options[n - 1].label;
+                let by = if *forced {
+                    "--random で指定"
+                } else {
+                    "抽選"
+                };
+                let _ = writeln!(out, "        → {n} に進む「{label}」（{by}）");
+            }
+        }
         TraceStep::Jump {
             line,
             label,
@@ -229,9 +270,45 @@ fn render_trace_step(out: &mut String, step: &TraceStep) {
         } => {
             let _ = writeln!(out, "  {line:>4}| ジャンプ → [{label}]({target})");
         }
+        TraceStep::Call { line, target, .. } => {
+            let _ = writeln!(out, "  {line:>4}| 呼び出し → {target}");
+        }
+        TraceStep::Return { line, .. } => {
+            let _ = writeln!(out, "  {line:>4}| 呼び出し元へ戻る");
+        }
         TraceStep::Ending { line, id, .. } => {
             let _ = writeln!(out, "  {line:>4}| エンディング: {id}");
         }
+        TraceStep::Background { line, path, .. } => {
+            let _ = writeln!(out, "  {line:>4}| background → {path}");
+        }
+        TraceStep::Bgm { line, path, .. } => {
+            let _ = writeln!(out, "  {line:>4}| bgm → {path}");
+        }
+        TraceStep::BgmStop { line, .. } => {
+            let _ = writeln!(out, "  {line:>4}| bgm 停止");
+        }
+        TraceStep::Se { line, path, .. } => {
+            let _ = writeln!(out, "  {line:>4}| se: {path}");
+        }
+        TraceStep::Show {
+            line,
+            character,
+            expression,
+            ..
+        } => match expression {
+            Some(expression) => {
+                let _ = writeln!(out, "  {line:>4}| show → {character}({expression})");
+            }
+            None => {
+                let _ = writeln!(out, "  {line:>4}| show → {character}");
+            }
+        },
+        TraceStep::Hide {
+            line, character, ..
+        } => {
+            let _ = writeln!(out, "  {line:>4}| hide: {character}");
+        }
     }
 }
 
@@ -265,12 +342,30 @@ fn render_trace_end(out: &mut String, trace: &super::trace::Trace) {
                 "結果: エラー: 選択番号 {given} はこの選択肢にありません。1〜{available} から選んでください"
             );
         }
+        TraceEnd::InvalidRandom { given, available } => {
+            let _ = writeln!(
+                out,
+                "結果: エラー: --random の番号 {given} はこのランダム分岐にありません。1〜{available} から選んでください"
+            );
+        }
         TraceEnd::Truncated { max_steps } => {
             let _ = writeln!(
                 out,
                 "結果: エラー: {max_steps} ステップを超えたため打ち切りました。ジャンプが同じ場所を回り続けていないか確認してください"
             );
         }
+        TraceEnd::ReturnWithoutCall => {
+            let _ = writeln!(
+                out,
+                "結果: エラー: 呼び出し中でないのに `<!-- return -->` に到達しました。ジャンプやフォールスルーで呼び出し先に入っていないか確認してください"
+            );
+        }
+        TraceEnd::UnboundedRecursion => {
+            let _ = writeln!(
+                out,
+                "結果: エラー: 戻る前に同じ `<!-- call: -->` にもう一度到達しました。このままでは呼び出しが止まりません"
+            );
+        }
     }
     if trace.choices_used > 0 {
         let used: Vec<String> = trace.choices_requested[..trace.choices_used]
@@ -290,6 +385,30 @@ fn render_trace_end(out: &mut String, trace: &super::trace::Trace) {
             unused.join(",")
         );
     }
+    let drawn = trace.steps.iter().any(|step| {
+        matches!(
+            step,
+            TraceStep::Random {
+                forced: false,
+                selected: Some(_),
+                ..
+            }
+        )
+    });
+    if drawn {
+        let _ = writeln!(out, "ランダム分岐のシード: --seed {}", trace.seed);
+    }
+    if trace.random_used < trace.random_requested.len() {
+        let unused: Vec<String> = trace.random_requested[trace.random_used..]
+            .iter()
+            .map(usize::to_string)
+            .collect();
+        let _ = writeln!(
+            out,
+            "未使用の --random の番号: {}（実行が終了したため使われませんでした）",
+            unused.join(",")
+        );
+    }
 }
 
 // ------------------------------------------------------------ trace JSON
@@ -336,18 +455,39 @@ pub fn render_routes_human(result: &RoutesResult) -> String {
     let _ = writeln!(out, "=== Routes: {} ===", result.file.display());
     for (i, route) in report.routes.iter().enumerate() {
         let choices = format_choices(&route.choices);
-        let choices_display = if choices.is_empty() {
+        let mut choices_display = if choices.is_empty() {
             "(選択なし)".to_string()
         } else {
             format!("--choices {choices}")
         };
+        if !route.random.is_empty() {
+            let random = format!("--random {}", format_choices(&route.random));
+            choices_display = if choices.is_empty() {
+                random
+            } else {
+                format!("{choices_display} {random}")
+            };
+        }
+        if let Some(entry) = &route.entry {
+            choices_display = format!("[{}] {choices_display}", entry.display());
+        }
         let end = match &route.end {
             RouteEnd::Ending { id } => format!("エンディング「{id}」"),
             RouteEnd::EndOfFile => "ファイル末尾（暗黙の終了）".to_string(),
             RouteEnd::Circular => "循環".to_string(),
             RouteEnd::MaxDepthExceeded { max_depth } => format!("深度超過（上限 {max_depth}）"),
+            RouteEnd::ReturnWithoutCall { .. } => "呼び出しのない return".to_string(),
+            RouteEnd::UnboundedRecursion { .. } => "止まらない再帰".to_string(),
+            RouteEnd::NextChapter { chapter } => format!("次の章「{chapter}」"),
         };
-        let _ = writeln!(out, "Route {}: {choices_display} → {end}", i + 1);
+        let _ = writeln!(
+            out,
+            "Route {}: {choices_display} → {end}（{} 文字・約 {:.1} 分・{} シーン）",
+            i + 1,
+            route.chars,
+            route.reading_minutes,
+            route.scenes
+        );
     }
     out.push('\n');
     let _ = writeln!(out, "発見した経路数: {}", report.routes.len());
@@ -383,6 +523,99 @@ pub fn render_routes_human(result: &RoutesResult) -> String {
             .collect();
         let _ = writeln!(out, "到達不能シーン: {}", list.join("、"));
     }
+    if !report.ending_lengths.is_empty() {
+        out.push('\n');
+        let _ = writeln!(out, "エンディング別の長さ:");
+        for lengths in &report.ending_lengths {
+            if lengths.routes == 1 {
+                let route = &lengths.shortest;
+                let _ = writeln!(
+                    out,
+                    "  {}（1 経路）: Route {}（{} 文字・約 {:.1} 分）",
+                    lengths.ending, route.route, route.chars, route.reading_minutes
+                );
+                continue;
+            }
+            let _ = writeln!(out, "  {}（{} 経路）", lengths.ending, lengths.routes);
+            for (label, route) in [
+                ("最短", &lengths.shortest),
+                ("中央", &lengths.median),
+                ("最長", &lengths.longest),
+            ] {
+                let _ = writeln!(
+                    out,
+                    "    {label}: Route {}（{} 文字・約 {:.1} 分）",
+                    route.route, route.chars, route.reading_minutes
+                );
+            }
+        }
+    }
+    if !report.achievements.is_empty() {
+        out.push('\n');
+        let _ = writeln!(out, "実績:");
+        for achievement in &report.achievements {
+            if !achievement.attainable {
+                let _ = writeln!(out, "  {}: 達成不能", achievement.id);
+                continue;
+            }
+            let list: Vec<String> = achievement
+                .choices
+                .iter()
+                .map(|choices| {
+                    let choices = format_choices(choices);
+                    if choices.is_empty() {
+                        "(選択なし)".to_string()
+                    } else {
+                        format!("--choices {choices}")
+                    }
+                })
+                .collect();
+            let _ = writeln!(out, "  {}: {}", achievement.id, list.join(" / "));
+        }
+    }
+    if !report.chapters.is_empty() {
+        out.push('\n');
+        let _ = writeln!(out, "章:");
+        for (i, chapter) in report.chapters.iter().enumerate() {
+            let name = match &chapter.title {
+                Some(title) => format!("{}「{title}」", chapter.id),
+                None => chapter.id.clone(),
+            };
+            let join = |list: &[String]| {
+                if list.is_empty() {
+                    "(なし)".to_string()
+                } else {
+                    list.join("、")
+                }
+            };
+            let _ = writeln!(
+                out,
+                "  {}. {name}（入口 {}・{} 経路{}）",
+                i + 1,
+                chapter.entry.display(),
+                chapter.routes,
+                if chapter.truncated {
+                    "・打ち切り"
+                } else {
+                    ""
+                }
+            );
+            let _ = writeln!(out, "    エンディング: {}", join(&chapter.endings));
+            let _ = writeln!(out, "    次の章: {}", join(&chapter.next_chapters));
+            if !chapter.unvisited_scenes.is_empty() {
+                let list: Vec<String> = chapter
+                    .unvisited_scenes
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect();
+                let _ = writeln!(
+                    out,
+                    "    章の入口から実行されないシーン: {}",
+                    list.join("、")
+                );
+            }
+        }
+    }
     if report.truncated {
         let _ = writeln!(
             out,
@@ -492,6 +725,96 @@ pub fn render_fmt_json(result: &FmtResult) -> String {
     serde_json::to_string_pretty(&value).expect("JSON のシリアライズは失敗しない")
 }
 
+// ---------------------------------------------------------------- stats
+
+/// stats の人間向け出力。シーンごとの集計のあとに合計を出す。
+/// 読み込めないファイルがあれば check と同じ形式で先に表示する
+pub fn render_stats_human(result: &StatsResult) -> String {
+    let mut out = String::new();
+    if !result.diagnostics.is_empty() {
+        out.push_str(&render_human(&CheckResult {
+            files: result.files.clone(),
+            diagnostics: result.diagnostics.clone(),
+        }));
+        out.push('\n');
+    }
+    let Some(report) = &result.report else {
+        return out;
+    };
+    let _ = writeln!(out, "=== Stats: {} ===", result.path.display());
+    for scene in &report.scenes {
+        out.push('\n');
+        match &scene.title {
+            Some(title) => {
+                let _ = writeln!(out, "{}「{}」", scene.path.display(), title);
+            }
+            None => {
+                let _ = writeln!(out, "{}", scene.path.display());
+            }
+        }
+        stats_counts_human(&mut out, &scene.counts);
+    }
+    for (i, chapter) in report.chapters.iter().enumerate() {
+        out.push('\n');
+        let name = match &chapter.title {
+            Some(title) => format!("{}「{title}」", chapter.id),
+            None => chapter.id.clone(),
+        };
+        let _ = writeln!(
+            out,
+            "章 {}: {name}（{} シーン）",
+            i + 1,
+            chapter.scenes.len()
+        );
+        stats_counts_human(&mut out, &chapter.counts);
+    }
+    out.push('\n');
+    let _ = writeln!(out, "合計（{} シーン）", report.scenes.len());
+    stats_counts_human(&mut out, &report.total);
+    let _ = writeln!(
+        out,
+        "  （読了目安は 1 分あたり {} 文字で計算）",
+        report.chars_per_minute
+    );
+    out
+}
+
+fn stats_counts_human(out: &mut String, counts: &StatsCounts) {
+    let _ = writeln!(
+        out,
+        "  文字数: {}（ナレーション {} / セリフ {}）",
+        counts.total_chars, counts.narration_chars, counts.dialogue_chars
+    );
+    if !counts.speakers.is_empty() {
+        let speakers: Vec<String> = counts
+            .speakers
+            .iter()
+            .map(|(name, s)| format!("{} {} 回（{} 文字）", name, s.lines, s.chars))
+            .collect();
+        let _ = writeln!(out, "  セリフ: {}", speakers.join(" / "));
+    }
+    let _ = writeln!(
+        out,
+        "  選択肢: {} か所（{} 項目）",
+        counts.choice_blocks, counts.choice_items
+    );
+    let _ = writeln!(out, "  エンディング: {}", counts.endings);
+    let _ = writeln!(out, "  読了目安: {:.1} 分", counts.reading_minutes);
+}
+
+/// stats の機械向け JSON 出力。check の JSON と同じ `status` / `files` /
+/// `diagnostics` に、集計の `report` が加わる
+pub fn render_stats_json(result: &StatsResult) -> String {
+    let value = json!({
+        "status": if result.has_errors() { "error" } else { "ok" },
+        "path": result.path,
+        "files": result.files,
+        "diagnostics": result.diagnostics,
+        "report": result.report,
+    });
+    serde_json::to_string_pretty(&value).expect("JSON のシリアライズは失敗しない")
+}
+
 // ------------------------------------------------------------------- JSON
 
 /// 機械向け JSON 出力（docs/CLI_OUTPUT.md のスキーマ）
@@ -579,11 +902,30 @@ fn rule_summary(rule_id: &str) -> &'static str {
         "broken-link" => "選択肢・ジャンプのリンク先が解決できない",
         "invalid-choice-item" => "選択肢リストにリンク以外の項目が混在している",
         "empty-choice-label" => "選択肢のリンクテキストが空",
-        "missing-asset" => "background / bgm のパスが実在しない",
+        "missing-asset" => "background / bgm / se・立ち絵のパスが実在しない",
         "legacy-command" => "旧記法（v0）のコマンドが使われている",
         "invalid-characters-file" => "characters.yaml が読み込めない、または形式が正しくない",
         "undefined-character" => "characters.yaml に宣言されていない話者",
-        "implicit-fallthrough" => "セクション末尾が ending・ジャンプ・選択肢のいずれでもない",
+        "undefined-expression" => "characters.yaml に宣言されていない表情",
+        "invalid-endings-file" => "endings.yaml が読み込めない、または形式が正しくない",
+        "broken-include" => "`<!-- include: -->` の取り込み先が見つからない",
+        "circular-include" => "フラグメントの取り込みが循環している",
+        "invalid-fragment" => {
+            "フラグメントの名前が `_` で始まらない、または front matter・見出しを含む"
+        }
+        "invalid-achievements-file" => "achievements.yaml が読み込めない、または形式が正しくない",
+        "broken-achievement-ref" => {
+            "実績の条件が存在しないエンディング・セクション・選択肢を参照している"
+        }
+        "invalid-chapters-file" => "chapters.yaml が読み込めない、または形式が正しくない",
+        "broken-chapter-ref" => "chapters.yaml の章のシーンファイルが見つからない",
+        "undefined-ending" => "endings.yaml に宣言されていないエンディング",
+        "empty-asset" => "参照しているアセットが 0 バイトの空ファイル",
+        "path-case-mismatch" => "パスが実際のファイル名と大文字小文字だけ違う",
+        "asset-kind-mismatch" => "参照するキーとアセットの中身の種類（画像 / 音声）が違う",
+        "implicit-fallthrough" => {
+            "セクション末尾が ending・ジャンプ・選択肢・return のいずれでもない"
+        }
         "missing-title" => "H1 タイトルがない",
         "linkless-list" => "リンクを 1 つも含まないリスト",
         "inline-link" => "本文中のインラインリンク",
@@ -591,10 +933,20 @@ fn rule_summary(rule_id: &str) -> &'static str {
         "unsupported-element" => "v1 で意味を定義していない Markdown 要素",
         "missing-characters-file" => "characters.yaml が見つからない",
         "unreachable-section" => "どこからも到達しないセクション",
+        "unreachable-chapter-scene" => "章の入口からリンクで辿れない章のシーン",
         "deep-heading" => "H3 以深の見出し",
         "unknown-frontmatter-key" => "front matter の未知キー",
         "unknown-directive" => "HTML コメントの未知の制御キー",
         "too-many-choices" => "選択肢ブロック 1 つの項目数が多すぎる",
+        "stray-return" => "呼び出しを経ずに到達する経路がある return",
+        "recursive-call" => "戻りも終わりもせず同じ call に再び到達する呼び出し",
+        "unused-character" => "どのシーンにも登場しない宣言済みキャラクター",
+        "unused-ending" => "どのシーンからも到達しない宣言済みエンディング",
+        "unused-asset" => "どこからも参照されていないアセットファイル",
+        "asset-format-mismatch" => "アセットの拡張子と中身の形式が違う",
+        "unsupported-asset-format" => "判別できない、または Web で扱えないアセット形式",
+        "non-portable-path" => "\\ 区切り、または ASCII 以外の文字を含むパス",
+        "canvas-size-mismatch" => "背景画像のサイズが画面サイズと違う",
         "io-error" => "ファイルの読み込みに失敗した（記法ではなく環境の問題）",
         _ => "tsumugai check の診断",
     }
@@ -604,9 +956,9 @@ fn rule_summary(rule_id: &str) -> &'static str {
 mod tests {
     use super::rule_summary;
 
-    /// SPEC 6章のルール表（error 12種 + warning 13種）+ CLI レベルの io-error。
+    /// SPEC 6章のルール表（error 23種 + warning 22種）+ CLI レベルの io-error。
     /// ルールを追加したら SPEC → この一覧 → rule_summary の順に更新する
-    const ALL_RULE_IDS: [&str; 26] = [
+    const ALL_RULE_IDS: [&str; 49] = [
         "missing-scene-id",
         "invalid-frontmatter",
         "duplicate-scene-id",
@@ -614,12 +966,25 @@ mod tests {
         "empty-anchor",
         "invalid-h1",
         "broken-link",
+        "broken-include",
+        "circular-include",
+        "invalid-fragment",
         "invalid-choice-item",
         "empty-choice-label",
         "missing-asset",
         "legacy-command",
         "invalid-characters-file",
         "undefined-character",
+        "undefined-ending",
+        "undefined-expression",
+        "invalid-endings-file",
+        "invalid-achievements-file",
+        "broken-achievement-ref",
+        "invalid-chapters-file",
+        "broken-chapter-ref",
+        "empty-asset",
+        "path-case-mismatch",
+        "asset-kind-mismatch",
         "implicit-fallthrough",
         "missing-title",
         "linkless-list",
@@ -628,10 +993,20 @@ mod tests {
         "unsupported-element",
         "missing-characters-file",
         "unreachable-section",
+        "unreachable-chapter-scene",
         "deep-heading",
         "unknown-frontmatter-key",
         "unknown-directive",
         "too-many-choices",
+        "stray-return",
+        "recursive-call",
+        "unused-character",
+        "unused-ending",
+        "unused-asset",
+        "asset-format-mismatch",
+        "unsupported-asset-format",
+        "non-portable-path",
+        "canvas-size-mismatch",
         "io-error",
     ];
 
@@ -10,14 +10,36 @@
 //!   として表現する
 //! - 循環（同一経路内で同じ地点に再到達）は error、それ以外（到達不能
 //!   ending / シーン・深度超過・経路数上限による打ち切り）は warning
+//! - `<!-- call: -->`（SPEC 4.9）の呼び出しスタックも地点の一部として扱う。
+//!   呼び出し中でない `<!-- return -->` と止まらない再帰は、その行を指す error
+//! - 経路ごとに通ったナレーション・セリフの数と文字数（数え方は
+//!   [`stats`](super::stats) と同じ）を数え、ending ごとに最短・中央・最長の
+//!   経路をまとめる。ルートごとの長さのバランスを見るため
+//! - achievements.yaml（SPEC 2.4）があれば、各実績を達成できる経路を探す。
+//!   どの経路でも達成できない実績は warning
+//! - chapters.yaml（SPEC 2.5）があれば、章ごとに章の入口からも探索する。
+//!   他の章のシーンに入ったところでその章の経路は終わり、章の並びの
+//!   つながり（どの章からどの章へ進むか）をまとめる。チャプターセレクトで
+//!   章の入口から遊べるため、到達不能の判定は開始シーンと章の入口からの
+//!   探索を合わせて行う
+//! - ランダム分岐（SPEC 4.10）も選択肢と同じくすべての項目へ分岐する。
+//!   抽選で選ばれた項目は選択番号列とは別に `random` に記録し、
+//!   `tsumugai trace --random` で同じ経路を再現できるようにする
+//! - `--entry` で開始シーンを足すと（DLC やおまけシナリオのように本編から
+//!   リンクされない入口）、それぞれの開始シーンからも探索する。到達可能な
+//!   ending・シーンはすべての開始シーンからの探索の和集合で判定する。
+//!   実績の判定は最初の開始シーンの経路だけを使う
 
 use super::Block;
+use super::achievements::{self, Resolved, find_achievements_file, load_achievements};
+use super::chapters::{ResolvedChapter, project_chapters};
 use super::check::CheckResult;
 use super::diagnostic::{Diagnostic, Severity};
-use super::exec::{Cursor, format_choices, goto, segment_blocks};
+use super::exec::{CallResult, Cursor, call, format_choices, goto, ret, segment_blocks};
 use super::project::{LoadedScene, file_level, load_checked_project};
+use super::stats::{DEFAULT_CHARS_PER_MINUTE, char_count, minutes};
 use serde::Serialize;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::path::{Path, PathBuf};
 
 /// 既定の経路数上限（SPEC 5.2: 探索が無限に分岐してもハングしない）
@@ -28,12 +50,16 @@ const DEFAULT_MAX_DEPTH: usize = 1000;
 /// routes の動作オプション
 #[derive(Debug, Clone)]
 pub struct RoutesOptions {
-    /// background / bgm の実在チェック（`--no-assets` で false）
+    /// background / bgm / se の実在チェック（`--no-assets` で false）
     pub check_assets: bool,
     /// 探索する経路の総数の上限
     pub max_routes: usize,
     /// 1 経路あたりのステップ数の上限
     pub max_depth: usize,
+    /// 読了時間の目安の計算に使う 1 分あたりの文字数（`--cpm`）
+    pub chars_per_minute: u32,
+    /// 追加の開始シーン（`--entry`、複数可）
+    pub entries: Vec<PathBuf>,
 }
 
 impl Default for RoutesOptions {
@@ -42,6 +68,8 @@ impl Default for RoutesOptions {
             check_assets: true,
             max_routes: DEFAULT_MAX_ROUTES,
             max_depth: DEFAULT_MAX_DEPTH,
+            chars_per_minute: DEFAULT_CHARS_PER_MINUTE,
+            entries: Vec::new(),
         }
     }
 }
@@ -58,6 +86,12 @@ pub enum RouteEnd {
     Circular,
     /// 1 経路のステップ数が上限に達した（warning）
     MaxDepthExceeded { max_depth: usize },
+    /// 呼び出し中でないのに `<!-- return -->` に到達した（error）
+    ReturnWithoutCall { file: PathBuf, line: usize },
+    /// 同じ `<!-- call: -->` に戻る前にもう一度到達した（error）
+    UnboundedRecursion { file: PathBuf, line: usize },
+    /// 他の章のシーンに入った。章ごとの探索（[`ChapterReport`]）でだけ現れる
+    NextChapter { chapter: String },
 }
 
 /// 探索で見つかった 1 経路
@@ -66,7 +100,81 @@ pub struct RouteRecord {
     /// 選択肢ブロックで選んだ項目の並び順（1 始まり）。
     /// `tsumugai trace --choices` にそのまま渡せる
     pub choices: Vec<usize>,
+    /// ランダム分岐で進んだ項目の番号（1 始まり、出会った順）。
+    /// `tsumugai trace --random` にそのまま渡せる。ランダム分岐を通らなければ省く
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub random: Vec<usize>,
+    /// 経路の開始シーン。最初の開始シーンから始まる経路では省く（`--entry` で
+    /// 足した開始シーンの経路だけに付く）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<PathBuf>,
     pub end: RouteEnd,
+    /// 通ったナレーションの数
+    pub narration_blocks: usize,
+    /// 通ったセリフの数
+    pub dialogue_blocks: usize,
+    /// 通ったナレーション・セリフの文字数（空白を除く、stats と同じ数え方）
+    pub chars: usize,
+    /// 通ったシーン（ファイル）の数
+    pub scenes: usize,
+    /// chars から計算した読了時間の目安（分、小数 1 桁）
+    pub reading_minutes: f64,
+}
+
+/// ending ごとの経路の長さのまとめ
+#[derive(Debug, Clone, Serialize)]
+pub struct EndingLengths {
+    pub ending: String,
+    /// この ending に到達する経路の数
+    pub routes: usize,
+    pub shortest: RouteLength,
+    /// 文字数で並べた中央の経路（偶数個なら短い方）
+    pub median: RouteLength,
+    pub longest: RouteLength,
+}
+
+/// まとめに載せる 1 経路
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteLength {
+    /// `routes` の何番目か（1 始まり、人間向け出力の `Route N`）
+    pub route: usize,
+    pub choices: Vec<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub random: Vec<usize>,
+    pub chars: usize,
+    pub reading_minutes: f64,
+}
+
+/// 1 つの実績の達成可否
+#[derive(Debug, Clone, Serialize)]
+pub struct AchievementReport {
+    pub id: String,
+    /// 探索した経路で達成できるか
+    pub attainable: bool,
+    /// 達成する経路の選択番号列。`when` の実績なら 1 つ、`endings` の実績なら
+    /// ending ごとに 1 つ（列挙した順）。達成できなければ空
+    pub choices: Vec<Vec<usize>>,
+}
+
+/// 1 つの章を章の入口から探索したまとめ（chapters.yaml の章ごと）
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterReport {
+    pub id: String,
+    /// chapters.yaml の `title`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// 章の入口のシーン
+    pub entry: PathBuf,
+    /// 章の入口から、エンディング・他の章への移動などで終わるまでの経路の数
+    pub routes: usize,
+    /// 章の中で到達する ending id（重複なし、ソート済み）
+    pub endings: Vec<String>,
+    /// 章の経路が進む先の章 id（chapters.yaml の順）
+    pub next_chapters: Vec<String>,
+    /// 章のシーンのうち、章の入口からのどの経路でも実行されないもの（ソート済み）
+    pub unvisited_scenes: Vec<PathBuf>,
+    /// 経路数の上限に達し、この章の探索を打ち切ったか
+    pub truncated: bool,
 }
 
 /// 全分岐探索の結果
@@ -77,15 +185,22 @@ pub struct RoutesReport {
     pub reached_endings: Vec<String>,
     /// プロジェクトに宣言されているが、どの経路からも到達できない ending id
     pub unreached_endings: Vec<String>,
-    /// entry から到達可能なシーン（ソート済み）。`unreachable_scenes` と
-    /// 合わせるとプロジェクト全シーンになる（#149）
+    /// 開始シーン（と章の入口）から到達可能なシーン（ソート済み）。
+    /// `unreachable_scenes` と合わせるとプロジェクト全シーンになる（#149）
     pub reachable_scenes: Vec<PathBuf>,
     /// プロジェクトに読み込まれているが、どの経路からも実行されないシーン
     pub unreachable_scenes: Vec<PathBuf>,
     /// 経路数の上限に達し、探索を打ち切ったか
     pub truncated: bool,
-    /// circular-route / unreachable-ending / unreachable-scene /
-    /// route-limit-exceeded / route-max-depth-exceeded の Diagnostic
+    /// ending ごとの最短・中央・最長の経路（ending id の順）
+    pub ending_lengths: Vec<EndingLengths>,
+    /// achievements.yaml の実績ごとの達成可否（実績 id の順）。ファイルがなければ空
+    pub achievements: Vec<AchievementReport>,
+    /// chapters.yaml の章ごとの探索のまとめ（章の順）。ファイルがなければ空
+    pub chapters: Vec<ChapterReport>,
+    /// circular-route / return-without-call / unbounded-recursion /
+    /// unreachable-ending / unreachable-scene / route-limit-exceeded /
+    /// route-max-depth-exceeded / unattainable-achievement の Diagnostic
     #[serde(skip)]
     pub diagnostics: Vec<Diagnostic>,
 }
@@ -117,7 +232,8 @@ impl RoutesResult {
 /// パスが存在しない・ディレクトリ・検査 error の場合も panic や Err にせず、
 /// Diagnostic 入りの [`RoutesResult`] を返す（report は None になる）。
 pub fn routes_path(path: &Path, options: &RoutesOptions) -> RoutesResult {
-    let project = match load_checked_project(path, "routes", options.check_assets) {
+    let project = match load_checked_project(path, &options.entries, "routes", options.check_assets)
+    {
         Ok(project) => project,
         Err(check) => {
             return RoutesResult {
@@ -128,7 +244,9 @@ pub fn routes_path(path: &Path, options: &RoutesOptions) -> RoutesResult {
         }
     };
 
-    let report = explore(&project.scenes, path, options);
+    let mut entries = vec![path.to_path_buf()];
+    entries.extend(options.entries.iter().cloned());
+    let report = explore(&project.scenes, &entries, options);
     RoutesResult {
         file: path.to_path_buf(),
         check: project.check,
@@ -140,39 +258,106 @@ pub fn routes_path(path: &Path, options: &RoutesOptions) -> RoutesResult {
 
 struct Explorer<'a> {
     scenes: &'a [LoadedScene],
+    chapters: &'a [ResolvedChapter],
+    /// シーン → 入っている章（`chapters` の添字）
+    chapter_of: &'a [Option<usize>],
+    /// 章ごとの探索なら、その章。他の章のシーンに入ったら経路を終える
+    chapter: Option<usize>,
+    /// `--entry` で足した開始シーンからの探索なら、そのパス（経路に記録する）
+    entry: Option<&'a Path>,
     max_routes: usize,
     max_depth: usize,
+    chars_per_minute: u32,
     routes: Vec<RouteRecord>,
+    /// `routes` と同じ並びの、経路ごとの通過記録（実績の判定用）
+    trails: Vec<Trail<'a>>,
     visited_scenes: HashSet<usize>,
     truncated: bool,
 }
 
-impl Explorer<'_> {
+/// 経路を進みながら数える分量
+#[derive(Clone, Default)]
+struct Progress<'a> {
+    /// ランダム分岐で進んだ項目の番号
+    random: Vec<usize>,
+    narration_blocks: usize,
+    dialogue_blocks: usize,
+    chars: usize,
+    scenes: BTreeSet<usize>,
+    trail: Trail<'a>,
+}
+
+/// 経路が通ったセクションと選んだ選択肢
+#[derive(Clone, Default)]
+struct Trail<'a> {
+    /// (シーン, セクション) の scenes・sections 内インデックス
+    sections: HashSet<(usize, usize)>,
+    /// (選択肢のあるシーン, 選んだ項目のラベル)
+    picks: Vec<(usize, &'a str)>,
+}
+
+impl<'a> Explorer<'a> {
+    fn finish(&mut self, choices: Vec<usize>, end: RouteEnd, progress: &Progress<'a>) {
+        self.trails.push(progress.trail.clone());
+        self.routes.push(RouteRecord {
+            choices,
+            random: progress.random.clone(),
+            entry: self.entry.map(Path::to_path_buf),
+            end,
+            narration_blocks: progress.narration_blocks,
+            dialogue_blocks: progress.dialogue_blocks,
+            chars: progress.chars,
+            scenes: progress.scenes.len(),
+            reading_minutes: minutes(progress.chars, self.chars_per_minute),
+        });
+    }
+
     /// 1 つの実行位置から、経路が終わる（または上限に達する）まで進む。
-    /// 選択肢ブロックに到達したら、そこですべての項目へ再帰的に分岐する
-    fn walk(&mut self, mut cursor: Cursor, choices: Vec<usize>, mut visited: HashSet<Cursor>) {
+    /// 選択肢ブロックに到達したら、そこですべての項目へ再帰的に分岐する。
+    /// `stack` は `<!-- call: -->` の戻り先。同じ地点でも呼び出し元が違えば
+    /// 先の進み方が違うので、`visited` には呼び出しスタックごと記録する
+    fn walk(
+        &mut self,
+        mut cursor: Cursor,
+        mut stack: Vec<Cursor>,
+        choices: Vec<usize>,
+        mut visited: HashSet<(Cursor, Vec<Cursor>)>,
+        mut progress: Progress<'a>,
+    ) {
         loop {
             if self.routes.len() >= self.max_routes {
                 self.truncated = true;
                 return;
             }
             if visited.len() >= self.max_depth {
-                self.routes.push(RouteRecord {
-                    choices,
-                    end: RouteEnd::MaxDepthExceeded {
-                        max_depth: self.max_depth,
-                    },
-                });
+                let end = RouteEnd::MaxDepthExceeded {
+                    max_depth: self.max_depth,
+                };
+                self.finish(choices, end, &progress);
                 return;
             }
-            if !visited.insert(cursor) {
-                self.routes.push(RouteRecord {
-                    choices,
-                    end: RouteEnd::Circular,
-                });
+            if let Some(current) = self.chapter
+                && let Some(next) = self.chapter_of[cursor.scene]
+                && next != current
+            {
+                let end = RouteEnd::NextChapter {
+                    chapter: self.chapters[next].id.clone(),
+                };
+                self.finish(choices, end, &progress);
+                return;
+            }
+            if !visited.insert((cursor, stack.clone())) {
+                self.finish(choices, RouteEnd::Circular, &progress);
                 return;
             }
             self.visited_scenes.insert(cursor.scene);
+            progress.scenes.insert(cursor.scene);
+            if cursor.seg > 0 {
+                progress
+                    .trail
+                    .sections
+                    .insert((cursor.scene, cursor.seg - 1));
+            }
 
             let loaded = &self.scenes[cursor.scene];
             let scene = &loaded.parsed.scene;
@@ -184,27 +369,60 @@ impl Explorer<'_> {
                     cursor.block = 0;
                     continue;
                 }
-                self.routes.push(RouteRecord {
-                    choices,
-                    end: RouteEnd::EndOfFile,
-                });
+                self.finish(choices, RouteEnd::EndOfFile, &progress);
                 return;
             }
 
             match &blocks[cursor.block] {
-                Block::Narration { .. } | Block::Dialogue { .. } => {
+                Block::Narration { text, .. } => {
+                    progress.narration_blocks += 1;
+                    progress.chars += char_count(text);
+                    cursor.block += 1;
+                }
+                Block::Dialogue { text, .. } => {
+                    progress.dialogue_blocks += 1;
+                    progress.chars += char_count(text);
+                    cursor.block += 1;
+                }
+                Block::Background { .. }
+                | Block::Bgm { .. }
+                | Block::BgmStop { .. }
+                | Block::Se { .. }
+                | Block::Show { .. }
+                | Block::Hide { .. } => {
                     cursor.block += 1;
                 }
                 Block::Ending { id, .. } => {
-                    self.routes.push(RouteRecord {
-                        choices,
-                        end: RouteEnd::Ending { id: id.clone() },
-                    });
+                    self.finish(choices, RouteEnd::Ending { id: id.clone() }, &progress);
                     return;
                 }
                 Block::Jump { target, .. } => {
                     goto(&mut cursor, self.scenes, target);
                 }
+                Block::Call { target, line } => {
+                    if let CallResult::Recursion =
+                        call(&mut cursor, &mut stack, self.scenes, target)
+                    {
+                        let (file, line) = loaded.parsed.locate(*line);
+                        let end = RouteEnd::UnboundedRecursion {
+                            file: file.to_path_buf(),
+                            line,
+                        };
+                        self.finish(choices, end, &progress);
+                        return;
+                    }
+                }
+                Block::Return { line } => {
+                    if !ret(&mut cursor, &mut stack) {
+                        let (file, line) = loaded.parsed.locate(*line);
+                        let end = RouteEnd::ReturnWithoutCall {
+                            file: file.to_path_buf(),
+                            line,
+                        };
+                        self.finish(choices, end, &progress);
+                        return;
+                    }
+                }
                 Block::Choices { items, .. } => {
                     for (i, item) in items.iter().enumerate() {
                         if self.routes.len() >= self.max_routes {
@@ -215,7 +433,38 @@ impl Explorer<'_> {
                         goto(&mut branch_cursor, self.scenes, &item.target);
                         let mut branch_choices = choices.clone();
                         branch_choices.push(i + 1);
-                        self.walk(branch_cursor, branch_choices, visited.clone());
+                        let mut branch_progress = progress.clone();
+                        branch_progress
+                            .trail
+                            .picks
+                            .push((cursor.scene, item.label.as_str()));
+                        self.walk(
+                            branch_cursor,
+                            stack.clone(),
+                            branch_choices,
+                            visited.clone(),
+                            branch_progress,
+                        );
+                    }
+                    return;
+                }
+                Block::Random { items, .. } => {
+                    for (i, item) in items.iter().enumerate() {
+                        if self.routes.len() >= self.max_routes {
+                            self.truncated = true;
+                            return;
+                        }
+                        let mut branch_cursor = cursor;
+                        goto(&mut branch_cursor, self.scenes, &item.target);
+                        let mut branch_progress = progress.clone();
+                        branch_progress.random.push(i + 1);
+                        self.walk(
+                            branch_cursor,
+                            stack.clone(),
+                            choices.clone(),
+                            visited.clone(),
+                            branch_progress,
+                        );
                     }
                     return;
                 }
@@ -224,25 +473,56 @@ impl Explorer<'_> {
     }
 }
 
-fn explore(scenes: &[LoadedScene], entry: &Path, options: &RoutesOptions) -> RoutesReport {
-    let mut explorer = Explorer {
-        scenes,
-        max_routes: options.max_routes,
-        max_depth: options.max_depth,
-        routes: Vec::new(),
-        visited_scenes: HashSet::new(),
-        truncated: false,
-    };
-    let start = Cursor {
+fn explore(scenes: &[LoadedScene], entries: &[PathBuf], options: &RoutesOptions) -> RoutesReport {
+    let chapters = project_chapters(scenes);
+    let mut chapter_of = vec![None; scenes.len()];
+    for (c, chapter) in chapters.iter().enumerate() {
+        for &i in &chapter.scenes {
+            chapter_of[i] = Some(c);
+        }
+    }
+    let start = Start {
+        chapter: None,
+        entry: None,
         scene: 0,
-        seg: 0,
-        block: 0,
     };
-    explorer.walk(start, Vec::new(), HashSet::new());
+    let mut explorer = run(scenes, &chapters, &chapter_of, start, options);
+    // 追加の開始シーン（読み込んだシーンに引けないものは check が報告済み）
+    let mut starts = vec![0];
+    let entry_runs: Vec<Explorer> = entries
+        .iter()
+        .skip(1)
+        .filter_map(|entry| {
+            let canon = entry.canonicalize().ok()?;
+            let scene = scenes.iter().position(|s| s.canon == canon)?;
+            if starts.contains(&scene) {
+                return None;
+            }
+            starts.push(scene);
+            let start = Start {
+                chapter: None,
+                entry: Some(&scenes[scene].path),
+                scene,
+            };
+            Some(run(scenes, &chapters, &chapter_of, start, options))
+        })
+        .collect();
+    let chapter_runs: Vec<Explorer> = (0..chapters.len())
+        .map(|c| {
+            let start = Start {
+                chapter: Some(c),
+                entry: None,
+                scene: chapters[c].entry,
+            };
+            run(scenes, &chapters, &chapter_of, start, options)
+        })
+        .collect();
 
     let mut reached_endings: Vec<String> = explorer
         .routes
         .iter()
+        .chain(entry_runs.iter().flat_map(|run| &run.routes))
+        .chain(chapter_runs.iter().flat_map(|run| &run.routes))
         .filter_map(|r| match &r.end {
             RouteEnd::Ending { id } => Some(id.clone()),
             _ => None,
@@ -250,6 +530,13 @@ fn explore(scenes: &[LoadedScene], entry: &Path, options: &RoutesOptions) -> Rou
         .collect();
     reached_endings.sort();
     reached_endings.dedup();
+    let visited_scenes: HashSet<usize> = explorer
+        .visited_scenes
+        .iter()
+        .chain(entry_runs.iter().flat_map(|run| &run.visited_scenes))
+        .chain(chapter_runs.iter().flat_map(|run| &run.visited_scenes))
+        .copied()
+        .collect();
 
     let mut declared_endings: Vec<String> = scenes
         .iter()
@@ -269,7 +556,7 @@ fn explore(scenes: &[LoadedScene], entry: &Path, options: &RoutesOptions) -> Rou
     let mut reachable_scenes: Vec<PathBuf> = scenes
         .iter()
         .enumerate()
-        .filter(|(i, _)| explorer.visited_scenes.contains(i))
+        .filter(|(i, _)| visited_scenes.contains(i))
         .map(|(_, s)| s.path.clone())
         .collect();
     reachable_scenes.sort();
@@ -277,21 +564,62 @@ fn explore(scenes: &[LoadedScene], entry: &Path, options: &RoutesOptions) -> Rou
     let mut unreachable_scenes: Vec<PathBuf> = scenes
         .iter()
         .enumerate()
-        .filter(|(i, _)| !explorer.visited_scenes.contains(i))
+        .filter(|(i, _)| !visited_scenes.contains(i))
         .map(|(_, s)| s.path.clone())
         .collect();
     unreachable_scenes.sort();
 
-    let diagnostics = build_diagnostics(
-        entry,
+    let start_paths: Vec<&Path> = starts.iter().map(|&i| scenes[i].path.as_path()).collect();
+    let mut diagnostics = build_diagnostics(
+        &start_paths,
         &explorer.routes,
         &unreached_endings,
         &unreachable_scenes,
         explorer.truncated,
         options,
     );
+    for (&scene, run) in starts.iter().skip(1).zip(&entry_runs) {
+        let entry_diagnostics = build_diagnostics(
+            &[scenes[scene].path.as_path()],
+            &run.routes,
+            &[],
+            &[],
+            run.truncated,
+            options,
+        );
+        merge_diagnostics(&mut diagnostics, entry_diagnostics);
+    }
+    // 開始シーンが入口の章は、開始シーンからの探索と同じ経路を辿るので重ねて報告しない
+    for (chapter, run) in chapters.iter().zip(&chapter_runs) {
+        if starts.contains(&chapter.entry) {
+            continue;
+        }
+        let chapter_diagnostics = build_diagnostics(
+            &[scenes[chapter.entry].path.as_path()],
+            &run.routes,
+            &[],
+            &[],
+            run.truncated,
+            options,
+        );
+        merge_diagnostics(&mut diagnostics, chapter_diagnostics);
+    }
+    let achievements = check_achievements(scenes, &explorer, &mut diagnostics);
 
+    let chapters = chapters
+        .iter()
+        .zip(&chapter_runs)
+        .map(|(chapter, run)| chapter_report(scenes, &chapters, chapter, run))
+        .collect();
+    for run in entry_runs {
+        explorer.routes.extend(run.routes);
+        explorer.truncated |= run.truncated;
+    }
+    let ending_lengths = ending_lengths(&explorer.routes);
     RoutesReport {
+        ending_lengths,
+        achievements,
+        chapters,
         routes: explorer.routes,
         reached_endings,
         unreached_endings,
@@ -302,6 +630,211 @@ fn explore(scenes: &[LoadedScene], entry: &Path, options: &RoutesOptions) -> Rou
     }
 }
 
+/// 開始シーンと章の入口からの探索で重なる Diagnostic を除いて足す
+fn merge_diagnostics(diagnostics: &mut Vec<Diagnostic>, more: Vec<Diagnostic>) {
+    for diag in more {
+        if !diagnostics.contains(&diag) {
+            diagnostics.push(diag);
+        }
+    }
+}
+
+/// 1 回の探索の始め方
+struct Start<'a> {
+    /// 章ごとの探索なら、その章
+    chapter: Option<usize>,
+    /// `--entry` で足した開始シーンからの探索なら、そのパス
+    entry: Option<&'a Path>,
+    /// 開始するシーンの scenes 内インデックス
+    scene: usize,
+}
+
+/// `start` のシーンの先頭から全分岐を探索する
+fn run<'a>(
+    scenes: &'a [LoadedScene],
+    chapters: &'a [ResolvedChapter],
+    chapter_of: &'a [Option<usize>],
+    start: Start<'a>,
+    options: &RoutesOptions,
+) -> Explorer<'a> {
+    let mut explorer = Explorer {
+        scenes,
+        chapters,
+        chapter_of,
+        chapter: start.chapter,
+        entry: start.entry,
+        max_routes: options.max_routes,
+        max_depth: options.max_depth,
+        chars_per_minute: options.chars_per_minute.max(1),
+        routes: Vec::new(),
+        trails: Vec::new(),
+        visited_scenes: HashSet::new(),
+        truncated: false,
+    };
+    let start = Cursor {
+        scene: start.scene,
+        seg: 0,
+        block: 0,
+    };
+    explorer.walk(
+        start,
+        Vec::new(),
+        Vec::new(),
+        HashSet::new(),
+        Progress::default(),
+    );
+    explorer
+}
+
+/// 章の探索結果をまとめる
+fn chapter_report(
+    scenes: &[LoadedScene],
+    chapters: &[ResolvedChapter],
+    chapter: &ResolvedChapter,
+    run: &Explorer,
+) -> ChapterReport {
+    let mut endings: Vec<String> = Vec::new();
+    let mut next: BTreeSet<usize> = BTreeSet::new();
+    for route in &run.routes {
+        match &route.end {
+            RouteEnd::Ending { id } => endings.push(id.clone()),
+            RouteEnd::NextChapter { chapter } => {
+                next.extend(chapters.iter().position(|c| c.id == *chapter));
+            }
+            _ => {}
+        }
+    }
+    endings.sort();
+    endings.dedup();
+    let mut unvisited_scenes: Vec<PathBuf> = chapter
+        .scenes
+        .iter()
+        .filter(|i| !run.visited_scenes.contains(i))
+        .map(|&i| scenes[i].path.clone())
+        .collect();
+    unvisited_scenes.sort();
+    ChapterReport {
+        id: chapter.id.clone(),
+        title: chapter.title.clone(),
+        entry: scenes[chapter.entry].path.clone(),
+        routes: run.routes.len(),
+        endings,
+        next_chapters: next.into_iter().map(|c| chapters[c].id.clone()).collect(),
+        unvisited_scenes,
+        truncated: run.truncated,
+    }
+}
+
+/// ending ごとに、到達する経路を文字数で並べて最短・中央・最長を取る
+fn ending_lengths(routes: &[RouteRecord]) -> Vec<EndingLengths> {
+    let mut by_ending: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, route) in routes.iter().enumerate() {
+        if let RouteEnd::Ending { id } = &route.end {
+            by_ending.entry(id).or_default().push(i);
+        }
+    }
+    by_ending
+        .into_iter()
+        .map(|(ending, mut indices)| {
+            // 同じ長さなら先に見つかった経路を前にする
+            indices.sort_by_key(|&i| (routes[i].chars, i));
+            let length = |i: usize| RouteLength {
+                route: i + 1,
+                choices: routes[i].choices.clone(),
+                random: routes[i].random.clone(),
+                chars: routes[i].chars,
+                reading_minutes: routes[i].reading_minutes,
+            };
+            EndingLengths {
+                ending: ending.to_string(),
+                routes: indices.len(),
+                shortest: length(indices[0]),
+                median: length(indices[(indices.len() - 1) / 2]),
+                longest: length(indices[indices.len() - 1]),
+            }
+        })
+        .collect()
+}
+
+/// 開始シーンに適用される achievements.yaml の各実績を、探索した経路で
+/// 達成できるか判定する。達成できない実績は `unattainable-achievement`
+fn check_achievements(
+    scenes: &[LoadedScene],
+    explorer: &Explorer,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<AchievementReport> {
+    // 読み込めない・参照が壊れている achievements.yaml は check が報告済み
+    let Some(file) =
+        find_achievements_file(&scenes[0].path).and_then(|path| load_achievements(&path).ok())
+    else {
+        return Vec::new();
+    };
+    let routes = explorer.routes.iter().zip(&explorer.trails);
+    let mut reports = Vec::new();
+    for (id, achievement) in &file.entries {
+        let choices: Option<Vec<Vec<usize>>> = if achievement.endings.is_empty() {
+            let conditions: Option<Vec<Resolved>> = achievement
+                .when
+                .iter()
+                .map(|c| achievements::resolve(scenes, c).ok())
+                .collect();
+            conditions.and_then(|conditions| {
+                routes
+                    .clone()
+                    .find(|(route, trail)| conditions.iter().all(|c| satisfies(route, trail, c)))
+                    .map(|(route, _)| vec![route.choices.clone()])
+            })
+        } else {
+            achievement
+                .endings
+                .iter()
+                .map(|ending| {
+                    routes
+                        .clone()
+                        .find(|(route, _)| {
+                            matches!(&route.end, RouteEnd::Ending { id } if id == ending)
+                        })
+                        .map(|(route, _)| route.choices.clone())
+                })
+                .collect()
+        };
+        if choices.is_none() {
+            let message = format!(
+                "実績「{id}」はどの経路でも達成できません。条件の組み合わせが同じ経路で満たせるか、条件に書いたエンディング・セクション・選択肢に到達できるかを確認してください"
+            );
+            diagnostics.push(match file.lines.get(id) {
+                Some(&line) => {
+                    Diagnostic::warning("unattainable-achievement", &file.path, line, message)
+                }
+                None => file_level(
+                    "unattainable-achievement",
+                    Severity::Warning,
+                    &file.path,
+                    message,
+                ),
+            });
+        }
+        reports.push(AchievementReport {
+            id: id.clone(),
+            attainable: choices.is_some(),
+            choices: choices.unwrap_or_default(),
+        });
+    }
+    reports
+}
+
+/// 経路が 1 つの条件を満たすか
+fn satisfies(route: &RouteRecord, trail: &Trail, condition: &Resolved) -> bool {
+    match condition {
+        Resolved::Ending(ending) => matches!(&route.end, RouteEnd::Ending { id } if id == ending),
+        Resolved::Section(scene, section) => trail.sections.contains(&(*scene, *section)),
+        Resolved::Choice(label, scene) => trail
+            .picks
+            .iter()
+            .any(|(s, l)| l == label && scene.is_none_or(|scene| scene == *s)),
+    }
+}
+
 fn all_blocks(scene: &super::Scene) -> impl Iterator<Item = &Block> {
     scene
         .lead
@@ -309,18 +842,47 @@ fn all_blocks(scene: &super::Scene) -> impl Iterator<Item = &Block> {
         .chain(scene.sections.iter().flat_map(|s| s.blocks.iter()))
 }
 
+/// 探索した経路から Diagnostic を組み立てる。`entries` は探索の開始シーンで、
+/// 先頭を trace コマンドと file 単位の Diagnostic の位置に使う
 fn build_diagnostics(
-    entry: &Path,
+    entries: &[&Path],
     routes: &[RouteRecord],
     unreached_endings: &[String],
     unreachable_scenes: &[PathBuf],
     truncated: bool,
     options: &RoutesOptions,
 ) -> Vec<Diagnostic> {
+    let entry = entries[0];
     let mut diagnostics = Vec::new();
+    // 同じ return / call には多くの経路が行き着くので、1 か所につき最初の経路だけ報告する
+    let mut located: HashSet<(&Path, usize)> = HashSet::new();
     for route in routes {
-        let (route_desc, trace_cmd) = describe_route(entry, &route.choices);
+        let (route_desc, trace_cmd) = describe_route(entry, route);
         match &route.end {
+            RouteEnd::ReturnWithoutCall { file, line } => {
+                if located.insert((file, *line)) {
+                    diagnostics.push(Diagnostic::error(
+                        "return-without-call",
+                        file,
+                        *line,
+                        format!(
+                            "{route_desc}は `<!-- call: -->` を経由せずにこの `<!-- return -->` に到達し、戻り先がありません。ジャンプやフォールスルーで呼び出し先に入っていないか、`{trace_cmd}` で確認してください"
+                        ),
+                    ));
+                }
+            }
+            RouteEnd::UnboundedRecursion { file, line } => {
+                if located.insert((file, *line)) {
+                    diagnostics.push(Diagnostic::error(
+                        "unbounded-recursion",
+                        file,
+                        *line,
+                        format!(
+                            "{route_desc}はこの `<!-- call: -->` から戻る前に、同じ呼び出しにもう一度到達します。変数を持たない v1 では呼び出しが止まらないため、呼び出し先から `<!-- return -->` に戻れるようにしてください。`{trace_cmd}` で確認できます"
+                        ),
+                    ));
+                }
+            }
             RouteEnd::Circular => {
                 diagnostics.push(file_level(
                     "circular-route",
@@ -351,7 +913,7 @@ fn build_diagnostics(
                     ),
                 ));
             }
-            RouteEnd::Ending { .. } => {}
+            RouteEnd::Ending { .. } | RouteEnd::NextChapter { .. } => {}
         }
     }
     for id in unreached_endings {
@@ -372,7 +934,11 @@ fn build_diagnostics(
             format!(
                 "{} はプロジェクトに読み込まれていますが、{} からのどの経路からも実行されません。リンクを含むセクション自体が到達不能になっていないか確認してください",
                 scene.display(),
-                entry.display()
+                entries
+                    .iter()
+                    .map(|e| e.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join("・")
             ),
         ));
     }
@@ -392,18 +958,23 @@ fn build_diagnostics(
 
 /// 経路の説明文（`route_desc`）と、その経路を再現する trace コマンド
 /// （`trace_cmd`）を組み立てる。選択肢を 1 つも経由しない経路
-/// （純粋なジャンプの循環など）では `--choices` を省いた自然な文にする
-fn describe_route(entry: &Path, choices: &[usize]) -> (String, String) {
-    if choices.is_empty() {
-        (
-            "経路（選択肢を経由しない）".to_string(),
-            format!("tsumugai trace {}", entry.display()),
-        )
-    } else {
-        let list = format_choices(choices);
-        (
-            format!("経路 --choices {list} "),
-            format!("tsumugai trace {} --choices {list}", entry.display()),
-        )
+/// （純粋なジャンプの循環など）では `--choices` を省いた自然な文にする。
+/// ランダム分岐を通った経路には `--random` を足す
+fn describe_route(entry: &Path, route: &RouteRecord) -> (String, String) {
+    let mut args = String::new();
+    if !route.choices.is_empty() {
+        args.push_str(&format!(" --choices {}", format_choices(&route.choices)));
+    }
+    if !route.random.is_empty() {
+        args.push_str(&format!(" --random {}", format_choices(&route.random)));
     }
+    let route_desc = if args.is_empty() {
+        "経路（選択肢を経由しない）".to_string()
+    } else {
+        format!("経路{args} ")
+    };
+    (
+        route_desc,
+        format!("tsumugai trace {}{args}", entry.display()),
+    )
 }
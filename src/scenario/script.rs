@@ -0,0 +1,664 @@
+//! `tsumugai export script`: 校正用の台本（テキスト / HTML）
+//!
+//! 紙で読み合わせ・校正するための、プロジェクト全体を 1 本に並べた文書を作る。
+//! 分岐はそのまま読めないので、シーンのリード部と H2 セクションを 1 つずつの
+//! 「まとまり」にして番号（`2` / `2-3`）を振り、選択肢・ジャンプはその番号で
+//! 参照する（`→ 2-3 after-school`）。
+//!
+//! 並び順は開始シーンからの深さ優先（選択肢は上から順、フォールスルーは
+//! そのまま次）で、読み進めた順に近くなる。`--entry` で足した開始シーンから
+//! 辿れる分はその後ろに続ける。どこからも辿れないまとまりは
+//! 付録に回す。番号はシーンの読み込み順とセクションの並び順で決まり、
+//! 並び順が変わっても同じまとまりは同じ番号のまま。
+//!
+//! 検査は compile と同じ（check の error があれば作らない）。
+
+use super::Block;
+use super::check::CheckResult;
+use super::compile::{entry_indices, load_compilable, resolve_link};
+use super::export::ExportOptions;
+use super::project::LoadedScene;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// 台本全体
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptDocument {
+    /// 開始シーンのタイトル（なければ scene_id）
+    pub title: String,
+    /// 開始シーンのファイル名
+    pub entry: String,
+    /// 開始シーンから辿った順のまとまり
+    pub units: Vec<ScriptUnit>,
+    /// どこからも辿れないまとまり（ファイル・セクションの並び順）
+    pub appendix: Vec<ScriptUnit>,
+}
+
+/// シーンのリード部、または H2 セクション 1 つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptUnit {
+    /// `2`（リード部）/ `2-3`（2 番目のシーンの 3 番目のセクション）
+    pub number: String,
+    /// シーンのタイトル（なければ scene_id）
+    pub scene_title: String,
+    /// シーンのファイル名
+    pub file: String,
+    /// セクションの見出し。リード部なら None
+    pub heading: Option<String>,
+    pub lines: Vec<ScriptLine>,
+}
+
+impl ScriptUnit {
+    /// 参照に使う `番号 見出し`
+    pub fn label(&self) -> String {
+        format!(
+            "{} {}",
+            self.number,
+            self.heading.as_deref().unwrap_or(&self.scene_title)
+        )
+    }
+}
+
+/// 台本の 1 行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptLine {
+    Narration(String),
+    /// `speaker` は表示名（characters.yaml / front matter の `display_names`）
+    Dialogue {
+        speaker: String,
+        expression: Option<String>,
+        text: String,
+    },
+    /// 背景・BGM 等の演出（SPEC 4.6）
+    Direction(String),
+    Choice {
+        label: String,
+        to: ScriptRef,
+    },
+    Jump {
+        label: String,
+        to: ScriptRef,
+    },
+    /// `<!-- random -->`（SPEC 4.10）の 1 項目。`weight` は重み、`total` は重みの合計
+    Random {
+        label: String,
+        weight: u32,
+        total: u32,
+        to: ScriptRef,
+    },
+    /// `<!-- call: -->`（SPEC 4.9）。呼び出し先を読んでから次の行へ戻る
+    Call {
+        to: ScriptRef,
+    },
+    /// `<!-- return -->`。呼び出し元へ戻る
+    Return,
+    /// フォールスルー（SPEC 5章）。次に並ぶまとまりへ続くなら書かない
+    Continue {
+        to: ScriptRef,
+    },
+    Ending(String),
+}
+
+/// 別のまとまりへの参照（`2-3 after-school`）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptRef {
+    pub number: String,
+    pub label: String,
+}
+
+/// `export script` の結果。実行前検査（compile と同じ規則）の結果を必ず含む
+#[derive(Debug)]
+pub struct ScriptResult {
+    pub file: PathBuf,
+    pub check: CheckResult,
+    /// check が error のときは None
+    pub document: Option<ScriptDocument>,
+}
+
+impl ScriptResult {
+    /// exit code を 1 にすべきか（check エラー）
+    pub fn has_errors(&self) -> bool {
+        self.check.has_errors()
+    }
+}
+
+/// 開始シーンからプロジェクト全体の台本を作る
+pub fn script_path(path: &Path, options: &ExportOptions) -> ScriptResult {
+    match load_compilable(path, &options.entries, options.check_assets) {
+        Ok(project) => ScriptResult {
+            file: path.to_path_buf(),
+            document: Some(build(&project.scenes, &entries(path, options))),
+            check: project.check,
+        },
+        Err(check) => ScriptResult {
+            file: path.to_path_buf(),
+            check,
+            document: None,
+        },
+    }
+}
+
+/// 開始シーン。`path` に `--entry` で足したものを続ける
+fn entries(path: &Path, options: &ExportOptions) -> Vec<PathBuf> {
+    std::iter::once(path.to_path_buf())
+        .chain(options.entries.iter().cloned())
+        .collect()
+}
+
+/// まとまりの位置（シーン, セクション）。セクションが None ならリード部
+type Key = (usize, Option<usize>);
+
+fn build(scenes: &[LoadedScene], entries: &[PathBuf]) -> ScriptDocument {
+    let keys: Vec<Key> = scenes
+        .iter()
+        .enumerate()
+        .flat_map(|(i, loaded)| {
+            std::iter::once((i, None))
+                .chain((0..loaded.parsed.scene.sections.len()).map(move |j| (i, Some(j))))
+        })
+        .collect();
+
+    // 開始シーンのリード部から深さ優先で辿る。足した開始シーンは、それまでに
+    // 辿れなかった分をその後ろに続ける
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<Key> = entry_indices(scenes, entries)
+        .into_iter()
+        .rev()
+        .map(|i| (i, None))
+        .collect();
+    while let Some(key) = stack.pop() {
+        if !seen.insert(key) {
+            continue;
+        }
+        order.push(key);
+        // 先に読むものが上に来るよう逆順に積む
+        for next in successors(scenes, key).into_iter().rev() {
+            if !seen.contains(&next) {
+                stack.push(next);
+            }
+        }
+    }
+    let appendix: Vec<Key> = keys.into_iter().filter(|k| !seen.contains(k)).collect();
+
+    let scene = &scenes[0];
+    ScriptDocument {
+        title: scene_title(scene),
+        entry: file_name(scene),
+        units: units(scenes, &order),
+        appendix: units(scenes, &appendix),
+    }
+}
+
+/// 並べたまとまりを作る。直後に並ぶまとまりへのフォールスルーは省く
+fn units(scenes: &[LoadedScene], order: &[Key]) -> Vec<ScriptUnit> {
+    order
+        .iter()
+        .enumerate()
+        .map(|(n, &key)| {
+            let mut unit = unit(scenes, key);
+            if let Some(ScriptLine::Continue { to }) = unit.lines.last()
+                && order.get(n + 1).map(|&next| number(next)) == Some(to.number.clone())
+            {
+                unit.lines.pop();
+            }
+            unit
+        })
+        .collect()
+}
+
+fn unit(scenes: &[LoadedScene], (i, section): Key) -> ScriptUnit {
+    let loaded = &scenes[i];
+    let scene = &loaded.parsed.scene;
+    let mut lines = Vec::new();
+    let (heading, blocks) = match section {
+        Some(j) => (
+            Some(scene.sections[j].heading.clone()),
+            &scene.sections[j].blocks,
+        ),
+        None => {
+            if let Some(path) = &scene.background {
+                lines.push(ScriptLine::Direction(format!("背景: {}", path)));
+            }
+            if let Some(bgm) = &scene.bgm {
+                lines.push(ScriptLine::Direction(format!("BGM: {}", bgm)));
+            }
+            (None, &scene.lead)
+        }
+    };
+
+    let link_ref = |target: &super::LinkTarget| {
+        let key = resolve_link(scenes, i, target);
+        reference(scenes, key)
+    };
+    let mut terminated = false;
+    for block in blocks {
+        let line = match block {
+            Block::Narration { text, .. } => ScriptLine::Narration(text.clone()),
+            Block::Dialogue {
+                speaker,
+                expression,
+                text,
+                ..
+            } => ScriptLine::Dialogue {
+                speaker: loaded.display_name(speaker).to_string(),
+                expression: expression.clone(),
+                text: text.clone(),
+            },
+            Block::Choices { items, .. } => {
+                lines.extend(items.iter().map(|item| ScriptLine::Choice {
+                    label: item.label.clone(),
+                    to: link_ref(&item.target),
+                }));
+                terminated = true;
+                break;
+            }
+            Block::Random { items, .. } => {
+                let total = items.iter().map(|item| item.weight).sum();
+                lines.extend(items.iter().map(|item| ScriptLine::Random {
+                    label: item.label.clone(),
+                    weight: item.weight,
+                    total,
+                    to: link_ref(&item.target),
+                }));
+                terminated = true;
+                break;
+            }
+            Block::Jump { label, target, .. } => {
+                lines.push(ScriptLine::Jump {
+                    label: label.clone(),
+                    to: link_ref(target),
+                });
+                terminated = true;
+                break;
+            }
+            Block::Call { target, .. } => ScriptLine::Call {
+                to: link_ref(target),
+            },
+            Block::Return { .. } => {
+                lines.push(ScriptLine::Return);
+                terminated = true;
+                break;
+            }
+            Block::Ending { id, .. } => {
+                lines.push(ScriptLine::Ending(id.clone()));
+                terminated = true;
+                break;
+            }
+            Block::Background { path, .. } => ScriptLine::Direction(format!("背景: {}", path)),
+            Block::Bgm { path, .. } => ScriptLine::Direction(format!("BGM: {}", path)),
+            Block::BgmStop { .. } => ScriptLine::Direction("BGM 停止".to_string()),
+            Block::Se { path, .. } => ScriptLine::Direction(format!("効果音: {}", path)),
+            Block::Show {
+                character,
+                expression,
+                ..
+            } => match expression {
+                Some(expression) => {
+                    ScriptLine::Direction(format!("立ち絵: {}（{}）", character, expression))
+                }
+                None => ScriptLine::Direction(format!("立ち絵: {}", character)),
+            },
+            Block::Hide { character, .. } => {
+                ScriptLine::Direction(format!("立ち絵を消す: {}", character))
+            }
+        };
+        lines.push(line);
+    }
+    if !terminated && let Some(next) = fallthrough(scenes, (i, section)) {
+        lines.push(ScriptLine::Continue {
+            to: reference(scenes, next),
+        });
+    }
+
+    ScriptUnit {
+        number: number((i, section)),
+        scene_title: scene_title(loaded),
+        file: file_name(loaded),
+        heading,
+        lines,
+    }
+}
+
+/// 次に進みうるまとまり（読む順）
+fn successors(scenes: &[LoadedScene], (i, section): Key) -> Vec<Key> {
+    let scene = &scenes[i].parsed.scene;
+    let blocks = match section {
+        Some(j) => &scene.sections[j].blocks,
+        None => &scene.lead,
+    };
+    // 呼び出し先は、呼び出した行の続きより先に読む
+    let mut called = Vec::new();
+    for block in blocks {
+        match block {
+            Block::Choices { items, .. } => {
+                called.extend(
+                    items
+                        .iter()
+                        .map(|item| resolve_link(scenes, i, &item.target)),
+                );
+                return called;
+            }
+            Block::Random { items, .. } => {
+                called.extend(
+                    items
+                        .iter()
+                        .map(|item| resolve_link(scenes, i, &item.target)),
+                );
+                return called;
+            }
+            Block::Jump { target, .. } => {
+                called.push(resolve_link(scenes, i, target));
+                return called;
+            }
+            Block::Call { target, .. } => called.push(resolve_link(scenes, i, target)),
+            Block::Ending { .. } | Block::Return { .. } => return called,
+            _ => {}
+        }
+    }
+    called.extend(fallthrough(scenes, (i, section)));
+    called
+}
+
+/// 終端のないまとまりの末尾から進む先（同じシーンの次のセクション）
+fn fallthrough(scenes: &[LoadedScene], (i, section): Key) -> Option<Key> {
+    let next = section.map_or(0, |j| j + 1);
+    (next < scenes[i].parsed.scene.sections.len()).then_some((i, Some(next)))
+}
+
+fn reference(scenes: &[LoadedScene], (i, section): Key) -> ScriptRef {
+    let scene = &scenes[i];
+    let label = match section {
+        Some(j) => scene.parsed.scene.sections[j].heading.clone(),
+        None => scene_title(scene),
+    };
+    ScriptRef {
+        number: number((i, section)),
+        label,
+    }
+}
+
+fn number((i, section): Key) -> String {
+    match section {
+        Some(j) => format!("{}-{}", i + 1, j + 1),
+        None => (i + 1).to_string(),
+    }
+}
+
+fn scene_title(loaded: &LoadedScene) -> String {
+    let scene = &loaded.parsed.scene;
+    scene
+        .title
+        .clone()
+        .or_else(|| scene.id.clone())
+        .unwrap_or_else(|| file_name(loaded))
+}
+
+fn file_name(loaded: &LoadedScene) -> String {
+    loaded
+        .path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+// ---------------------------------------------------------------- 出力
+
+/// 等幅でそのまま印刷できるプレーンテキスト
+pub fn render_script_text(document: &ScriptDocument) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{}　台本", document.title);
+    let _ = writeln!(
+        out,
+        "（tsumugai export script が {} から生成）",
+        document.entry
+    );
+    text_units(&mut out, &document.units);
+    if !document.appendix.is_empty() {
+        out.push_str("\n\n==== 付録: 開始シーンから辿れない部分 ====\n");
+        text_units(&mut out, &document.appendix);
+    }
+    out
+}
+
+fn text_units(out: &mut String, units: &[ScriptUnit]) {
+    let mut previous: Option<&str> = None;
+    for unit in units {
+        out.push('\n');
+        match &unit.heading {
+            None => {
+                let _ = writeln!(out, "\n■ {}（{}）", unit.label(), unit.file);
+            }
+            // 別のシーンのセクションに移ったときはファイル名を添える
+            Some(_) if previous != Some(unit.file.as_str()) => {
+                let _ = writeln!(out, "□ {}（{}）", unit.label(), unit.file);
+            }
+            Some(_) => {
+                let _ = writeln!(out, "□ {}", unit.label());
+            }
+        }
+        previous = Some(&unit.file);
+        for line in &unit.lines {
+            match line {
+                ScriptLine::Narration(text) => {
+                    let _ = writeln!(out, "{}", text);
+                }
+                ScriptLine::Dialogue {
+                    speaker,
+                    expression,
+                    text,
+                } => match expression {
+                    Some(expression) => {
+                        let _ = writeln!(out, "{}（{}）「{}」", speaker, expression, text);
+                    }
+                    None => {
+                        let _ = writeln!(out, "{}「{}」", speaker, text);
+                    }
+                },
+                ScriptLine::Direction(text) => {
+                    let _ = writeln!(out, "〔{}〕", text);
+                }
+                ScriptLine::Choice { label, to } => {
+                    let _ = writeln!(out, "  ・{} → {} {}", label, to.number, to.label);
+                }
+                ScriptLine::Jump { label, to } => {
+                    let _ = writeln!(out, "  ［{}］→ {} {}", label, to.number, to.label);
+                }
+                ScriptLine::Random {
+                    label,
+                    weight,
+                    total,
+                    to,
+                } => {
+                    let _ = writeln!(
+                        out,
+                        "  ？{}（重み {}/{}）→ {} {}",
+                        label, weight, total, to.number, to.label
+                    );
+                }
+                ScriptLine::Call { to } => {
+                    let _ = writeln!(out, "  （→ {} {} を読んで戻る）", to.number, to.label);
+                }
+                ScriptLine::Return => {
+                    let _ = writeln!(out, "  （呼び出し元へ戻る）");
+                }
+                ScriptLine::Continue { to } => {
+                    let _ = writeln!(out, "  （→ {} {} へ続く）", to.number, to.label);
+                }
+                ScriptLine::Ending(id) => {
+                    let _ = writeln!(out, "【エンディング: {}】", id);
+                }
+            }
+        }
+    }
+}
+
+/// 印刷用の 1 ファイルの HTML。参照はページ内リンクになる
+pub fn render_script_html(document: &ScriptDocument) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(out, "<title>{}　台本</title>", escape(&document.title));
+    out.push_str(concat!(
+        "<style>\n",
+        "body { font-family: serif; max-width: 40em; margin: 2em auto; line-height: 1.8; }\n",
+        "h2 { border-bottom: 1px solid #888; margin-top: 2.5em; }\n",
+        "h3 { margin-top: 1.8em; }\n",
+        "p { margin: 0.3em 0; }\n",
+        ".direction { color: #666; }\n",
+        ".speaker { font-weight: bold; }\n",
+        ".links { margin: 0.5em 0 0.5em 1.5em; padding: 0; }\n",
+        ".ending { font-weight: bold; }\n",
+        ".appendix { page-break-before: always; }\n",
+        "a { color: inherit; }\n",
+        "</style>\n</head>\n<body>\n",
+    ));
+    let _ = writeln!(out, "<h1>{}　台本</h1>", escape(&document.title));
+    let _ = writeln!(
+        out,
+        "<p class=\"direction\">tsumugai export script が {} から生成</p>",
+        escape(&document.entry)
+    );
+    html_units(&mut out, &document.units);
+    if !document.appendix.is_empty() {
+        out.push_str("<div class=\"appendix\">\n<h2>付録: 開始シーンから辿れない部分</h2>\n");
+        html_units(&mut out, &document.appendix);
+        out.push_str("</div>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn html_units(out: &mut String, units: &[ScriptUnit]) {
+    let mut previous: Option<&str> = None;
+    for unit in units {
+        let id = format!("u{}", unit.number);
+        match &unit.heading {
+            None => {
+                let _ = writeln!(
+                    out,
+                    "<h2 id=\"{}\">{} <small>（{}）</small></h2>",
+                    id,
+                    escape(&unit.label()),
+                    escape(&unit.file)
+                );
+            }
+            Some(_) if previous != Some(unit.file.as_str()) => {
+                let _ = writeln!(
+                    out,
+                    "<h3 id=\"{}\">{} <small>（{}）</small></h3>",
+                    id,
+                    escape(&unit.label()),
+                    escape(&unit.file)
+                );
+            }
+            Some(_) => {
+                let _ = writeln!(out, "<h3 id=\"{}\">{}</h3>", id, escape(&unit.label()));
+            }
+        }
+        previous = Some(&unit.file);
+        let mut in_list = false;
+        for line in &unit.lines {
+            let is_link = matches!(
+                line,
+                ScriptLine::Choice { .. } | ScriptLine::Jump { .. } | ScriptLine::Random { .. }
+            );
+            if is_link && !in_list {
+                out.push_str("<ul class=\"links\">\n");
+            }
+            if !is_link && in_list {
+                out.push_str("</ul>\n");
+            }
+            in_list = is_link;
+            match line {
+                ScriptLine::Narration(text) => {
+                    let _ = writeln!(out, "<p>{}</p>", escape(text));
+                }
+                ScriptLine::Dialogue {
+                    speaker,
+                    expression,
+                    text,
+                } => {
+                    let expression = expression
+                        .as_ref()
+                        .map(|e| format!("（{}）", escape(e)))
+                        .unwrap_or_default();
+                    let _ = writeln!(
+                        out,
+                        "<p><span class=\"speaker\">{}{}</span>「{}」</p>",
+                        escape(speaker),
+                        expression,
+                        escape(text)
+                    );
+                }
+                ScriptLine::Direction(text) => {
+                    let _ = writeln!(out, "<p class=\"direction\">〔{}〕</p>", escape(text));
+                }
+                ScriptLine::Choice { label, to } => {
+                    let _ = writeln!(out, "<li>{} → {}</li>", escape(label), html_ref(to));
+                }
+                ScriptLine::Jump { label, to } => {
+                    let _ = writeln!(out, "<li>［{}］→ {}</li>", escape(label), html_ref(to));
+                }
+                ScriptLine::Random {
+                    label,
+                    weight,
+                    total,
+                    to,
+                } => {
+                    let _ = writeln!(
+                        out,
+                        "<li>？{}（重み {}/{}）→ {}</li>",
+                        escape(label),
+                        weight,
+                        total,
+                        html_ref(to)
+                    );
+                }
+                ScriptLine::Call { to } => {
+                    let _ = writeln!(
+                        out,
+                        "<p class=\"direction\">（→ {} を読んで戻る）</p>",
+                        html_ref(to)
+                    );
+                }
+                ScriptLine::Return => {
+                    out.push_str("<p class=\"direction\">（呼び出し元へ戻る）</p>\n");
+                }
+                ScriptLine::Continue { to } => {
+                    let _ = writeln!(
+                        out,
+                        "<p class=\"direction\">（→ {} へ続く）</p>",
+                        html_ref(to)
+                    );
+                }
+                ScriptLine::Ending(id) => {
+                    let _ = writeln!(
+                        out,
+                        "<p class=\"ending\">【エンディング: {}】</p>",
+                        escape(id)
+                    );
+                }
+            }
+        }
+        if in_list {
+            out.push_str("</ul>\n");
+        }
+    }
+}
+
+fn html_ref(to: &ScriptRef) -> String {
+    format!(
+        "<a href=\"#u{}\">{} {}</a>",
+        to.number,
+        to.number,
+        escape(&to.label)
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,271 @@
+//! `tsumugai stats`: プロジェクトの分量の集計
+//!
+//! 「このゲームはどのくらいの長さか」に答えるため、シーンごとと合計で
+//! 本文の文字数・話者ごとのセリフ数・選択肢・エンディングの数と、
+//! 読了時間の目安を数える。
+//!
+//! 読み込みは check と同じ [`load_project`] で、ディレクトリなら配下の
+//! すべてのシーン、ファイルならそこからリンクで辿れるシーンが対象になる。
+//! chapters.yaml（SPEC 2.5）があれば、章ごとの合計も出す。
+//! 分量を見るためのコマンドなので、検査の error があっても集計はする
+//! （読み込めなかったファイルだけ `io-error` で報告する）。
+//!
+//! 文字数は空白・改行を除いた Unicode の文字（コードポイント）の数で、
+//! UTF-8 のバイト数ではない。日本語の原稿用紙換算と同じく、かな・漢字・
+//! 句読点・記号を 1 文字と数える。
+
+use super::Block;
+use super::chapters::project_chapters;
+use super::diagnostic::{Diagnostic, Severity};
+use super::project::{load_project, seed_files};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// 読了時間の目安の既定値（1 分あたりの文字数）。
+/// ノベルゲームの日本語テキストを黙読する速さのおおよその値
+pub const DEFAULT_CHARS_PER_MINUTE: u32 = 500;
+
+/// stats の動作オプション
+#[derive(Debug, Clone)]
+pub struct StatsOptions {
+    /// 読了時間の計算に使う 1 分あたりの文字数（`--cpm`）
+    pub chars_per_minute: u32,
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        Self {
+            chars_per_minute: DEFAULT_CHARS_PER_MINUTE,
+        }
+    }
+}
+
+/// 分量の集計値（シーン 1 つ分、または合計）
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StatsCounts {
+    /// ナレーションの文字数
+    pub narration_chars: usize,
+    /// セリフ本文の文字数（話者名は含まない）
+    pub dialogue_chars: usize,
+    /// narration_chars + dialogue_chars
+    pub total_chars: usize,
+    /// 話者名 → セリフの数と文字数
+    pub speakers: BTreeMap<String, SpeakerStats>,
+    /// 選択肢ブロックの数
+    pub choice_blocks: usize,
+    /// 選択肢の項目数の合計
+    pub choice_items: usize,
+    /// エンディングの数
+    pub endings: usize,
+    /// total_chars を chars_per_minute で割った読了時間の目安（分、小数 1 桁）
+    pub reading_minutes: f64,
+}
+
+/// 話者 1 人分のセリフ
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SpeakerStats {
+    /// セリフの数（段落の数）
+    pub lines: usize,
+    pub chars: usize,
+}
+
+/// シーン 1 つ分の集計
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SceneStats {
+    pub path: PathBuf,
+    pub id: Option<String>,
+    pub title: Option<String>,
+    #[serde(flatten)]
+    pub counts: StatsCounts,
+}
+
+/// 章 1 つ分の集計（chapters.yaml の章ごと）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChapterStats {
+    pub id: String,
+    pub title: Option<String>,
+    /// 章のシーン（chapters.yaml に書いた順）
+    pub scenes: Vec<PathBuf>,
+    /// 章のシーンの合計
+    #[serde(flatten)]
+    pub counts: StatsCounts,
+}
+
+/// 集計の結果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsReport {
+    /// 読み込んだ順のシーン
+    pub scenes: Vec<SceneStats>,
+    /// chapters.yaml の章の順。ファイルがなければ空
+    pub chapters: Vec<ChapterStats>,
+    pub total: StatsCounts,
+    /// 読了時間の計算に使った 1 分あたりの文字数
+    pub chars_per_minute: u32,
+}
+
+/// stats の結果
+#[derive(Debug)]
+pub struct StatsResult {
+    /// 指定されたパス（ファイルまたはディレクトリ）
+    pub path: PathBuf,
+    /// 読み込んだファイル
+    pub files: Vec<PathBuf>,
+    /// 読み込めなかったファイルの `io-error`
+    pub diagnostics: Vec<Diagnostic>,
+    /// 1 つもシーンを読み込めなければ None
+    pub report: Option<StatsReport>,
+}
+
+impl StatsResult {
+    /// exit code を 1 にすべきか
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// ファイルまたはディレクトリの分量を集計する。
+///
+/// [`check_path`](super::check_path) と同じく infallible で、読み込めない
+/// ときは `io-error` の Diagnostic を返す
+pub fn stats_path(path: &Path, options: &StatsOptions) -> StatsResult {
+    let mut diagnostics = Vec::new();
+    let seeds = seed_files(path, &mut diagnostics);
+    let loaded_scenes = load_project(seeds, &mut diagnostics);
+    // 構文・参照の問題は check の担当。ここでは読み込みの失敗だけ報告する
+    diagnostics.retain(|d| d.rule_id == "io-error");
+
+    let cpm = options.chars_per_minute.max(1);
+    let report = (!loaded_scenes.is_empty()).then(|| {
+        let scenes: Vec<SceneStats> = loaded_scenes
+            .iter()
+            .map(|loaded| {
+                let scene = &loaded.parsed.scene;
+                let mut counts = StatsCounts::default();
+                let blocks = scene
+                    .lead
+                    .iter()
+                    .chain(scene.sections.iter().flat_map(|s| &s.blocks));
+                for block in blocks {
+                    count_block(&mut counts, block);
+                }
+                counts.reading_minutes = minutes(counts.total_chars, cpm);
+                SceneStats {
+                    path: loaded.path.clone(),
+                    id: scene.id.clone(),
+                    title: scene.title.clone(),
+                    counts,
+                }
+            })
+            .collect();
+        let mut total = StatsCounts::default();
+        for scene in &scenes {
+            add(&mut total, &scene.counts);
+        }
+        total.reading_minutes = minutes(total.total_chars, cpm);
+        let chapters = project_chapters(&loaded_scenes)
+            .into_iter()
+            .map(|chapter| {
+                let mut counts = StatsCounts::default();
+                for &i in &chapter.scenes {
+                    add(&mut counts, &scenes[i].counts);
+                }
+                counts.reading_minutes = minutes(counts.total_chars, cpm);
+                ChapterStats {
+                    id: chapter.id,
+                    title: chapter.title,
+                    scenes: chapter
+                        .scenes
+                        .iter()
+                        .map(|&i| scenes[i].path.clone())
+                        .collect(),
+                    counts,
+                }
+            })
+            .collect();
+        StatsReport {
+            scenes,
+            chapters,
+            total,
+            chars_per_minute: cpm,
+        }
+    });
+
+    StatsResult {
+        path: path.to_path_buf(),
+        files: loaded_scenes.iter().map(|s| s.path.clone()).collect(),
+        diagnostics,
+        report,
+    }
+}
+
+fn count_block(counts: &mut StatsCounts, block: &Block) {
+    match block {
+        Block::Narration { text, .. } => {
+            let n = char_count(text);
+            counts.narration_chars += n;
+            counts.total_chars += n;
+        }
+        Block::Dialogue { speaker, text, .. } => {
+            let n = char_count(text);
+            counts.dialogue_chars += n;
+            counts.total_chars += n;
+            let speaker = counts.speakers.entry(speaker.clone()).or_default();
+            speaker.lines += 1;
+            speaker.chars += n;
+        }
+        Block::Choices { items, .. } => {
+            counts.choice_blocks += 1;
+            counts.choice_items += items.len();
+        }
+        Block::Ending { .. } => counts.endings += 1,
+        _ => {}
+    }
+}
+
+fn add(total: &mut StatsCounts, counts: &StatsCounts) {
+    total.narration_chars += counts.narration_chars;
+    total.dialogue_chars += counts.dialogue_chars;
+    total.total_chars += counts.total_chars;
+    for (name, speaker) in &counts.speakers {
+        let entry = total.speakers.entry(name.clone()).or_default();
+        entry.lines += speaker.lines;
+        entry.chars += speaker.chars;
+    }
+    total.choice_blocks += counts.choice_blocks;
+    total.choice_items += counts.choice_items;
+    total.endings += counts.endings;
+}
+
+/// 空白・改行を除いた文字（コードポイント）の数
+pub(super) fn char_count(text: &str) -> usize {
+    text.chars().filter(|c| !c.is_whitespace()).count()
+}
+
+/// 読了時間（分）。小数 1 桁に丸める
+pub(super) fn minutes(chars: usize, cpm: u32) -> f64 {
+    (chars as f64 / f64::from(cpm) * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn 文字数はバイト数ではなく空白を除いた文字の数() {
+        assert_eq!(char_count("おはよう。"), 5);
+        assert_eq!(char_count("桜が 舞う\n通学路"), 7);
+        assert_eq!(char_count("Hello, world"), 11);
+        // 全角空白も空白として除く
+        assert_eq!(char_count("あ　い"), 2);
+    }
+
+    #[test]
+    fn 読了時間は小数1桁に丸める() {
+        assert_eq!(minutes(0, 500), 0.0);
+        assert_eq!(minutes(480, 500), 1.0);
+        assert_eq!(minutes(1234, 400), 3.1);
+    }
+}
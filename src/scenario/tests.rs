@@ -134,6 +134,20 @@ fn 未知のfront_matterキーはwarningになる() {
     assert!(parsed.diagnostics[0].message.contains("bgv"));
 }
 
+#[test]
+fn display_namesは話者名と表示名の対応になる() {
+    let parsed = parse("---\nid: t\ndisplay_names:\n  葵: \"???\"\n---\n\n# t\n");
+    assert_eq!(parsed.diagnostics, vec![]);
+    assert_eq!(parsed.scene.display_names["葵"], "???");
+    assert_eq!(parsed.front_matter_spans.display_names, Some(3));
+}
+
+#[test]
+fn マッピングでないdisplay_namesはinvalid_frontmatterになる() {
+    let parsed = parse("---\nid: t\ndisplay_names: 葵\n---\n\n# t\n");
+    assert_eq!(rule_ids(&parsed), vec!["invalid-frontmatter"]);
+}
+
 #[test]
 fn 壊れたyamlはinvalid_frontmatterになる() {
     let parsed = parse("---\nid: [\n---\n\n# t\n\n本文。\n");
@@ -167,6 +181,21 @@ fn 半角コロンと全角コロンの両方がセリフになる() {
     );
 }
 
+#[test]
+fn 話者名の後の括弧は表情になる() {
+    let parsed = parse_body("幼なじみ(笑顔): おはよう。\n\n幼なじみ（怒り）：遅刻するよ。");
+    assert_eq!(parsed.diagnostics, vec![]);
+    let blocks = all_blocks(&parsed);
+    assert!(matches!(
+        blocks[0],
+        Block::Dialogue { speaker, expression: Some(e), .. } if speaker == "幼なじみ" && e == "笑顔"
+    ));
+    assert!(matches!(
+        blocks[1],
+        Block::Dialogue { speaker, expression: Some(e), .. } if speaker == "幼なじみ" && e == "怒り"
+    ));
+}
+
 #[test]
 fn コロン前に空白があればナレーションになる() {
     let parsed = parse_body("これは 昼下がり: の物語。");
@@ -334,8 +363,131 @@ fn 不正なending_idはunknown_directiveになる() {
 
 #[test]
 fn 未知の制御キーはunknown_directiveになる() {
-    let parsed = parse_body("<!-- bgm: change.ogg -->");
+    let parsed = parse_body("<!-- voice: hello.ogg -->");
+    assert_eq!(rule_ids(&parsed), vec!["unknown-directive"]);
+}
+
+// -------------------------------------------------------------- 演出の切り替え
+
+#[test]
+fn 演出コメントはシーン途中の背景_bgm_効果音になる() {
+    let parsed = parse_body(
+        "<!-- background: bg/classroom.png -->\n\n<!-- bgm: bgm/rain.ogg -->\n\n<!-- se: se/door.ogg -->\n\n<!-- bgm: stop -->",
+    );
+    assert_eq!(parsed.diagnostics, vec![]);
+    let blocks = all_blocks(&parsed);
+    assert!(matches!(blocks[0], Block::Background { path, .. } if path == "bg/classroom.png"));
+    assert!(matches!(blocks[1], Block::Bgm { path, .. } if path == "bgm/rain.ogg"));
+    assert!(matches!(blocks[2], Block::Se { path, .. } if path == "se/door.ogg"));
+    assert!(matches!(blocks[3], Block::BgmStop { .. }));
+}
+
+#[test]
+fn showとhideは立ち絵の表示と消去になる() {
+    let parsed = parse_body(
+        "<!-- show: 幼なじみ(笑顔) -->\n\n<!-- show: 主人公 -->\n\n<!-- hide: 幼なじみ -->",
+    );
+    assert_eq!(parsed.diagnostics, vec![]);
+    let blocks = all_blocks(&parsed);
+    assert!(matches!(
+        blocks[0],
+        Block::Show { character, expression: Some(e), .. } if character == "幼なじみ" && e == "笑顔"
+    ));
+    assert!(matches!(
+        blocks[1],
+        Block::Show { character, expression: None, .. } if character == "主人公"
+    ));
+    assert!(matches!(blocks[2], Block::Hide { character, .. } if character == "幼なじみ"));
+}
+
+#[test]
+fn 表情つきのhideはunknown_directiveになる() {
+    let parsed = parse_body("<!-- hide: 幼なじみ(笑顔) -->");
+    assert_eq!(rule_ids(&parsed), vec!["unknown-directive"]);
+    assert_eq!(
+        parsed.diagnostics[0].suggestion.as_deref(),
+        Some("<!-- hide: 幼なじみ -->")
+    );
+}
+
+#[test]
+fn 値のない演出コメントはunknown_directiveになる() {
+    let parsed = parse_body("<!-- background: -->");
+    assert_eq!(rule_ids(&parsed), vec!["unknown-directive"]);
+    assert!(all_blocks(&parsed).is_empty());
+}
+
+// ---------------------------------------------------------- 呼び出しと戻り
+
+#[test]
+fn callとreturnのコメントは呼び出しと戻りになる() {
+    let parsed = parse_body("<!-- call: common.md#rest -->\n\n本文。\n\n<!-- return -->");
+    assert_eq!(parsed.diagnostics, vec![]);
+    let blocks = all_blocks(&parsed);
+    assert!(matches!(
+        blocks[0],
+        Block::Call { target: LinkTarget { file: Some(f), anchor: Some(a) }, .. }
+            if f == "common.md" && a == "rest"
+    ));
+    assert!(matches!(blocks[2], Block::Return { .. }));
+}
+
+#[test]
+fn 値のあるreturnと値のないcallはunknown_directiveになる() {
+    let parsed = parse_body("<!-- return: #top -->\n\n<!-- call: -->");
+    assert_eq!(
+        rule_ids(&parsed),
+        vec!["unknown-directive", "unknown-directive"]
+    );
+    assert_eq!(
+        parsed.diagnostics[0].suggestion.as_deref(),
+        Some("<!-- return -->")
+    );
+    assert!(all_blocks(&parsed).is_empty());
+}
+
+// ------------------------------------------------------------ ランダム分岐
+
+#[test]
+fn randomの直後のリストは重みつきのランダム分岐になる() {
+    let parsed = parse_body(
+        "<!-- random -->\n- [先輩](#a)\n- [後輩](#b) <!-- weight: 3 -->\n- [ひとり](#c)",
+    );
+    assert_eq!(parsed.diagnostics, vec![]);
+    let [Block::Random { items, line }] = all_blocks(&parsed)[..] else {
+        panic!("ランダム分岐になっていない: {:?}", all_blocks(&parsed));
+    };
+    assert_eq!(*line, 8);
+    let summary: Vec<(&str, u32)> = items
+        .iter()
+        .map(|item| (item.label.as_str(), item.weight))
+        .collect();
+    assert_eq!(summary, vec![("先輩", 1), ("後輩", 3), ("ひとり", 1)]);
+    assert_eq!(items[1].target.anchor.as_deref(), Some("b"));
+}
+
+#[test]
+fn リストの続かないrandomはunknown_directiveになる() {
+    let parsed = parse_body("<!-- random -->\n\n本文。");
     assert_eq!(rule_ids(&parsed), vec!["unknown-directive"]);
+    assert!(matches!(all_blocks(&parsed)[..], [Block::Narration { .. }]));
+}
+
+#[test]
+fn 選択肢や不正な値のweightはunknown_directiveになり重み1で扱う() {
+    let parsed = parse_body(
+        "- [走る](#a) <!-- weight: 2 -->\n- [歩く](#b)\n\n<!-- random -->\n- [表](#a) <!-- weight: 0 -->\n- [裏](#b)",
+    );
+    assert_eq!(
+        rule_ids(&parsed),
+        vec!["unknown-directive", "unknown-directive"]
+    );
+    let blocks = all_blocks(&parsed);
+    assert!(matches!(blocks[0], Block::Choices { .. }));
+    let Block::Random { items, .. } = blocks[1] else {
+        panic!("ランダム分岐になっていない: {:?}", blocks[1]);
+    };
+    assert!(items.iter().all(|item| item.weight == 1));
 }
 
 #[test]
@@ -389,3 +541,32 @@ fn characters_yamlを祖先ディレクトリから発見して読み込める()
     assert!(characters.contains("主人公"));
     assert!(!characters.contains("先生"));
 }
+
+// ------------------------------------------------------------ ソース上の範囲
+
+#[test]
+fn ブロックごとにソース上の範囲を記録する() {
+    // parse_body の本文は 7 行目から始まる
+    let parsed = parse_body(
+        "桜が舞う。\n続きの行。\n\n幼なじみ: おはよう。\n\n<!-- bgm: a.ogg --> <!-- se: b.ogg -->\n\n## 選択肢\n\n- [走る](#選択肢)\n- [歩く](#選択肢)",
+    );
+    assert_eq!(parsed.block_ranges.len(), all_blocks(&parsed).len());
+    let range = |line, column, end_line, end_column| SourceRange {
+        line,
+        column,
+        end_line,
+        end_column,
+    };
+    assert_eq!(
+        parsed.block_ranges,
+        vec![
+            range(7, 1, 8, 5),
+            range(10, 1, 10, 11),
+            // 同じ行の制御コメントはそれぞれのコメントの範囲
+            range(12, 1, 12, 19),
+            range(12, 21, 12, 38),
+            range(16, 1, 17, 12),
+        ]
+    );
+    assert_eq!(parsed.item_ranges.get(&17), Some(&range(17, 1, 17, 12)));
+}
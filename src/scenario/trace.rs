@@ -6,12 +6,21 @@
 //! - 実行前に check と同じ検査を行い、error があれば実行しない（SPEC 6.1）
 //! - 選択肢は `--choices` の選択番号（ブロック内の並び順、1 始まり）を
 //!   先頭から消費して進む。尽きたら入力待ちとして停止する
+//! - ランダム分岐（SPEC 4.10）は `--random` の番号を先頭から消費し、尽きたら
+//!   `--seed` のシードで重みに比例して抽選する。同じシードなら同じ経路になる
+//! - `<!-- call: -->`（SPEC 4.9）の戻り先は呼び出しスタックに積む。呼び出し中
+//!   でない `<!-- return -->` と、戻る前の同じ call への再到達（止まらない
+//!   再帰）は error として停止する
+//! - フラグメント（SPEC 4.8）から取り込んだステップは、フラグメントの
+//!   ファイルと行で示す
 //! - [`check_path`] と同じく infallible。入出力エラーも Diagnostic として
 //!   [`TraceResult`] に含め、JSON 出力の形式を崩さない
 
 use super::Block;
 use super::check::CheckResult;
-use super::exec::{Cursor, GotoResult, goto, segment_blocks, target_string};
+use super::exec::{
+    CallResult, Cursor, GotoResult, Rng, call, goto, ret, segment_blocks, target_string,
+};
 use super::project::{LoadedScene, load_checked_project};
 use serde::Serialize;
 use std::path::{Path, PathBuf};
@@ -24,7 +33,12 @@ const MAX_STEPS: usize = 10_000;
 pub struct TraceOptions {
     /// 選択肢ブロックで消費する選択番号（1 始まり、`--choices`）
     pub choices: Vec<usize>,
-    /// background / bgm の実在チェック（`--no-assets` で false）
+    /// ランダム分岐で抽選せずに選ぶ項目の番号（1 始まり、`--random`）。
+    /// routes が報告した経路をそのまま再現するのに使う
+    pub random: Vec<usize>,
+    /// `random` が尽きた後のランダム分岐の抽選に使うシード（`--seed`）
+    pub seed: u64,
+    /// background / bgm / se の実在チェック（`--no-assets` で false）
     pub check_assets: bool,
 }
 
@@ -32,6 +46,8 @@ impl Default for TraceOptions {
     fn default() -> Self {
         Self {
             choices: Vec::new(),
+            random: Vec::new(),
+            seed: 0,
             check_assets: true,
         }
     }
@@ -64,7 +80,13 @@ pub enum TraceStep {
     Dialogue {
         file: PathBuf,
         line: usize,
+        /// characters.yaml の話者名（別名は話者名に揃える）
         speaker: String,
+        /// このシーンでの表示名（`display_name` / front matter の `display_names`）
+        display_name: String,
+        /// `名前(表情): 本文` の表情名
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expression: Option<String>,
         text: String,
     },
     /// 選択肢ブロックに到達した。`selected` は消費した選択番号
@@ -75,17 +97,68 @@ pub enum TraceStep {
         options: Vec<TraceChoice>,
         selected: Option<usize>,
     },
+    /// ランダム分岐に到達した（SPEC 4.10）。`selected` は選ばれた項目の番号
+    /// （1 始まり）。None は `--random` の番号が範囲外で停止したことを表す
+    Random {
+        file: PathBuf,
+        line: usize,
+        options: Vec<TraceRandom>,
+        selected: Option<usize>,
+        /// `--random` で指定した番号で選んだか（false ならシードで抽選した）
+        forced: bool,
+    },
     Jump {
         file: PathBuf,
         line: usize,
         label: String,
         target: String,
     },
+    /// `<!-- call: -->` で呼び出した（SPEC 4.9）
+    Call {
+        file: PathBuf,
+        line: usize,
+        target: String,
+    },
+    /// `<!-- return -->` で呼び出し元へ戻った（SPEC 4.9）
+    Return { file: PathBuf, line: usize },
     Ending {
         file: PathBuf,
         line: usize,
         id: String,
     },
+    /// `<!-- background: path -->` で背景を切り替えた（SPEC 4.6）
+    Background {
+        file: PathBuf,
+        line: usize,
+        path: String,
+    },
+    /// `<!-- bgm: path -->` で BGM を切り替えた
+    Bgm {
+        file: PathBuf,
+        line: usize,
+        path: String,
+    },
+    /// `<!-- bgm: stop -->` で BGM を止めた
+    BgmStop { file: PathBuf, line: usize },
+    /// `<!-- se: path -->` で効果音を鳴らした
+    Se {
+        file: PathBuf,
+        line: usize,
+        path: String,
+    },
+    /// `<!-- show: 名前(表情) -->` で立ち絵を表示した
+    Show {
+        file: PathBuf,
+        line: usize,
+        character: String,
+        expression: Option<String>,
+    },
+    /// `<!-- hide: 名前 -->` で立ち絵を消した
+    Hide {
+        file: PathBuf,
+        line: usize,
+        character: String,
+    },
 }
 
 /// 選択肢ブロックの 1 項目（表示用）
@@ -95,6 +168,14 @@ pub struct TraceChoice {
     pub target: String,
 }
 
+/// ランダム分岐の 1 項目（表示用）
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceRandom {
+    pub label: String,
+    pub target: String,
+    pub weight: u32,
+}
+
 /// トレースの終わり方
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "reason", rename_all = "snake_case")]
@@ -107,8 +188,14 @@ pub enum TraceEnd {
     AwaitingChoice,
     /// 選択番号がブロックの項目数を超えている（error）
     InvalidChoice { given: usize, available: usize },
+    /// `--random` の番号がランダム分岐の項目数を超えている（error）
+    InvalidRandom { given: usize, available: usize },
     /// ステップ数が上限に達して打ち切った（error）
     Truncated { max_steps: usize },
+    /// 呼び出し中でないのに `<!-- return -->` に到達した（error）
+    ReturnWithoutCall,
+    /// 同じ `<!-- call: -->` に、戻る前にもう一度到達した（error）
+    UnboundedRecursion,
 }
 
 /// 1 経路の実行記録
@@ -120,6 +207,12 @@ pub struct Trace {
     pub choices_requested: Vec<usize>,
     /// 実際に消費した個数（残りは未使用）
     pub choices_used: usize,
+    /// `--random` に与えられた番号
+    pub random_requested: Vec<usize>,
+    /// 実際に消費した個数（残りは未使用）
+    pub random_used: usize,
+    /// ランダム分岐の抽選に使ったシード
+    pub seed: u64,
 }
 
 /// trace の結果。実行前検査の結果（check）を必ず含む
@@ -139,7 +232,13 @@ impl TraceResult {
         self.check.has_errors()
             || matches!(
                 self.trace.as_ref().map(|t| &t.end),
-                Some(TraceEnd::InvalidChoice { .. } | TraceEnd::Truncated { .. })
+                Some(
+                    TraceEnd::InvalidChoice { .. }
+                        | TraceEnd::InvalidRandom { .. }
+                        | TraceEnd::Truncated { .. }
+                        | TraceEnd::ReturnWithoutCall
+                        | TraceEnd::UnboundedRecursion
+                )
             )
     }
 }
@@ -149,7 +248,7 @@ impl TraceResult {
 /// パスが存在しない・ディレクトリ・検査 error の場合も panic や Err にせず、
 /// Diagnostic 入りの [`TraceResult`] を返す（trace は None になる）。
 pub fn trace_path(path: &Path, options: &TraceOptions) -> TraceResult {
-    let project = match load_checked_project(path, "trace", options.check_assets) {
+    let project = match load_checked_project(path, &[], "trace", options.check_assets) {
         Ok(project) => project,
         Err(check) => {
             return TraceResult {
@@ -173,6 +272,9 @@ pub fn trace_path(path: &Path, options: &TraceOptions) -> TraceResult {
 fn run(scenes: &[LoadedScene], options: &TraceOptions) -> Trace {
     let mut steps: Vec<TraceStep> = Vec::new();
     let mut next_choice = 0usize;
+    let mut next_random = 0usize;
+    let mut rng = Rng::new(options.seed);
+    let mut stack: Vec<Cursor> = Vec::new();
     let mut cursor = Cursor {
         scene: 0,
         seg: 0,
@@ -212,6 +314,7 @@ fn run(scenes: &[LoadedScene], options: &TraceOptions) -> Trace {
             }
             Block::Dialogue {
                 speaker,
+                expression,
                 text,
                 line,
             } => {
@@ -219,10 +322,64 @@ fn run(scenes: &[LoadedScene], options: &TraceOptions) -> Trace {
                     file: loaded.path.clone(),
                     line: *line,
                     speaker: speaker.clone(),
+                    display_name: loaded.display_name(speaker).to_string(),
+                    expression: expression.clone(),
                     text: text.clone(),
                 });
                 cursor.block += 1;
             }
+            Block::Show {
+                character,
+                expression,
+                line,
+            } => {
+                steps.push(TraceStep::Show {
+                    file: loaded.path.clone(),
+                    line: *line,
+                    character: character.clone(),
+                    expression: expression.clone(),
+                });
+                cursor.block += 1;
+            }
+            Block::Hide { character, line } => {
+                steps.push(TraceStep::Hide {
+                    file: loaded.path.clone(),
+                    line: *line,
+                    character: character.clone(),
+                });
+                cursor.block += 1;
+            }
+            Block::Background { path, line } => {
+                steps.push(TraceStep::Background {
+                    file: loaded.path.clone(),
+                    line: *line,
+                    path: path.clone(),
+                });
+                cursor.block += 1;
+            }
+            Block::Bgm { path, line } => {
+                steps.push(TraceStep::Bgm {
+                    file: loaded.path.clone(),
+                    line: *line,
+                    path: path.clone(),
+                });
+                cursor.block += 1;
+            }
+            Block::BgmStop { line } => {
+                steps.push(TraceStep::BgmStop {
+                    file: loaded.path.clone(),
+                    line: *line,
+                });
+                cursor.block += 1;
+            }
+            Block::Se { path, line } => {
+                steps.push(TraceStep::Se {
+                    file: loaded.path.clone(),
+                    line: *line,
+                    path: path.clone(),
+                });
+                cursor.block += 1;
+            }
             Block::Ending { id, line } => {
                 steps.push(TraceStep::Ending {
                     file: loaded.path.clone(),
@@ -245,6 +402,30 @@ fn run(scenes: &[LoadedScene], options: &TraceOptions) -> Trace {
                 let result = goto(&mut cursor, scenes, target);
                 push_goto(&mut steps, scenes, &cursor, result);
             }
+            Block::Call { target, line } => {
+                steps.push(TraceStep::Call {
+                    file: loaded.path.clone(),
+                    line: *line,
+                    target: target_string(target),
+                });
+                match call(&mut cursor, &mut stack, scenes, target) {
+                    CallResult::Entered(result) => push_goto(&mut steps, scenes, &cursor, result),
+                    CallResult::Recursion => break TraceEnd::UnboundedRecursion,
+                }
+            }
+            Block::Return { line } => {
+                steps.push(TraceStep::Return {
+                    file: loaded.path.clone(),
+                    line: *line,
+                });
+                let from = cursor.scene;
+                if !ret(&mut cursor, &mut stack) {
+                    break TraceEnd::ReturnWithoutCall;
+                }
+                if cursor.scene != from {
+                    push_scene_enter(&mut steps, &scenes[cursor.scene]);
+                }
+            }
             Block::Choices { items, line } => {
                 let shown: Vec<TraceChoice> = items
                     .iter()
@@ -285,14 +466,90 @@ fn run(scenes: &[LoadedScene], options: &TraceOptions) -> Trace {
                 let result = goto(&mut cursor, scenes, &target);
                 push_goto(&mut steps, scenes, &cursor, result);
             }
+            Block::Random { items, line } => {
+                let shown: Vec<TraceRandom> = items
+                    .iter()
+                    .map(|item| TraceRandom {
+                        label: item.label.clone(),
+                        target: target_string(&item.target),
+                        weight: item.weight,
+                    })
+                    .collect();
+                let (selected, forced) = match options.random.get(next_random) {
+                    Some(&given) if given == 0 || given > items.len() => {
+                        steps.push(TraceStep::Random {
+                            file: loaded.path.clone(),
+                            line: *line,
+                            options: shown,
+                            selected: None,
+                            forced: true,
+                        });
+                        break TraceEnd::InvalidRandom {
+                            given,
+                            available: items.len(),
+                        };
+                    }
+                    Some(&given) => {
+                        next_random += 1;
+                        (given, true)
+                    }
+                    None => {
+                        let weights: Vec<u32> = items.iter().map(|item| item.weight).collect();
+                        (rng.pick(&weights) + 1, false)
+                    }
+                };
+                let target = items[selected - 1].target.clone();
+                steps.push(TraceStep::Random {
+                    file: loaded.path.clone(),
+                    line: *line,
+                    options: shown,
+                    selected: Some(selected),
+                    forced,
+                });
+                let result = goto(&mut cursor, scenes, &target);
+                push_goto(&mut steps, scenes, &cursor, result);
+            }
         }
     };
+    for step in &mut steps {
+        relocate(step, scenes);
+    }
 
     Trace {
         steps,
         end,
         choices_requested: options.choices.clone(),
         choices_used: next_choice,
+        random_requested: options.random.clone(),
+        random_used: next_random,
+        seed: options.seed,
+    }
+}
+
+/// 取り込んだフラグメントの行のステップを、フラグメントのファイルと行に付け替える
+fn relocate(step: &mut TraceStep, scenes: &[LoadedScene]) {
+    let (file, line) = match step {
+        TraceStep::SceneEnter { .. } => return,
+        TraceStep::SectionEnter { file, line, .. }
+        | TraceStep::Narration { file, line, .. }
+        | TraceStep::Dialogue { file, line, .. }
+        | TraceStep::Choice { file, line, .. }
+        | TraceStep::Random { file, line, .. }
+        | TraceStep::Jump { file, line, .. }
+        | TraceStep::Call { file, line, .. }
+        | TraceStep::Return { file, line }
+        | TraceStep::Ending { file, line, .. }
+        | TraceStep::Background { file, line, .. }
+        | TraceStep::Bgm { file, line, .. }
+        | TraceStep::BgmStop { file, line }
+        | TraceStep::Se { file, line, .. }
+        | TraceStep::Show { file, line, .. }
+        | TraceStep::Hide { file, line, .. } => (file, line),
+    };
+    if let Some(scene) = scenes.iter().find(|s| s.path == *file) {
+        let (actual, actual_line) = scene.parsed.locate(*line);
+        *file = actual.to_path_buf();
+        *line = actual_line;
     }
 }
 
@@ -0,0 +1,815 @@
+//! Twine の Twee 3 との相互変換（`tsumugai import` / `compile --target twee`）
+//!
+//! 取り込み（[`import_twee_path`]）は Twine で書いた下書きを 1 つの v1 シーンに
+//! する。
+//! - 開始パッセージを先頭に、パッセージ → H2 セクション（見出しはパッセージ名）
+//! - `[[リンク]]` → パッセージ末尾に 1 つならジャンプ、2 つ以上なら選択肢
+//!   リスト。文中のリンクは本文にリンクテキストを残したうえで末尾に集める
+//! - リンクのないパッセージ → パッセージ名から作ったエンディング
+//! - front matter の `id` は fmt と同じく出力ファイル名から補う
+//! - 存在しないパッセージへのリンクは `twee-broken-link` として報告する
+//! - v1 記法で表せない Twine の機能（Harlowe / SugarCube のマクロ、変数、
+//!   特殊パッセージ・スクリプト）は取り除くか残したうえで `twee-unsupported`
+//!   として報告する。黙って落とさない（SPEC 7.2）
+//!
+//! 書き出し（`compile --target twee`）は検査済みのプロジェクトを Twee 3 にする。
+//! リード部とセクションを 1 パッセージずつにし、選択肢・ジャンプ・
+//! フォールスルーはリンク、エンディングと演出は HTML コメントで残す。
+//! リンク文とパッセージ名の `->` / `<-` / `|` / `]]` は区切りと読まれないよう
+//! 全角に置き換える（パッセージ名はリンクの飛び先にも書くため）。
+//! 呼び出し（SPEC 4.9）は呼び出し先のパッセージをその場に差し込む Harlowe の
+//! `(display:)` にし、`<!-- return -->` は HTML コメントで残す。
+
+use super::compile::{fnv1a64, resolve_link};
+use super::diagnostic::{Diagnostic, Severity};
+use super::fmt::derive_id;
+use super::project::{LoadedScene, file_level};
+use super::{Block, LinkTarget, slugify};
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Twine（SugarCube）が特別扱いするパッセージ。中身は v1 記法にできない
+const SPECIAL_PASSAGES: &[&str] = &[
+    "StoryInit",
+    "StoryCaption",
+    "StoryMenu",
+    "StoryBanner",
+    "StorySubtitle",
+    "StoryAuthor",
+    "StoryShare",
+    "StoryInterface",
+    "PassageReady",
+    "PassageDone",
+    "PassageHeader",
+    "PassageFooter",
+    "startup",
+    "header",
+    "footer",
+    "debug-startup",
+    "debug-header",
+    "debug-footer",
+];
+
+/// import の結果
+#[derive(Debug)]
+pub struct ImportResult {
+    /// 取り込んだ Twee ファイル
+    pub path: PathBuf,
+    /// 生成した v1 シーンの Markdown
+    pub markdown: String,
+    /// シーンのセクションにしたパッセージの数
+    pub passages: usize,
+    /// 表せなかった機能の報告（error なら markdown は空）
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ImportResult {
+    /// exit code を 1 にすべきか
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// Twee 3 ファイルを読み込んで v1 シーンにする。`output` は書き出す予定の
+/// `.md` のパスで、front matter の `id` をそのファイル名から補う。
+///
+/// 読めないときも panic や Err にせず、`io-error` の Diagnostic を返す
+pub fn import_twee_path(path: &Path, output: &Path) -> ImportResult {
+    match std::fs::read_to_string(path) {
+        Ok(source) => import_twee_str(&source, path, output),
+        Err(e) => ImportResult {
+            path: path.to_path_buf(),
+            markdown: String::new(),
+            passages: 0,
+            diagnostics: vec![file_level(
+                "io-error",
+                Severity::Error,
+                path,
+                format!("{} を読み込めません: {}", path.display(), e),
+            )],
+        },
+    }
+}
+
+/// Twee 3 の文字列を v1 シーンにする（[`import_twee_path`] の本体）
+pub fn import_twee_str(source: &str, path: &Path, output: &Path) -> ImportResult {
+    let mut diagnostics = Vec::new();
+    let passages = parse_passages(source);
+
+    let mut title = None;
+    let mut start = None;
+    let mut story = Vec::new();
+    for passage in &passages {
+        match passage.name.as_str() {
+            "StoryTitle" => title = Some(passage.text().trim().to_string()),
+            "StoryData" => {
+                start = serde_json::from_str::<serde_json::Value>(&passage.text())
+                    .ok()
+                    .and_then(|data| data.get("start")?.as_str().map(String::from));
+            }
+            name if SPECIAL_PASSAGES.contains(&name) => {
+                diagnostics.push(Diagnostic::warning(
+                    "twee-unsupported",
+                    path,
+                    passage.line,
+                    format!(
+                        "特殊パッセージ「{}」は v1 記法で表せないため取り込みませんでした。初期化や毎回の処理が必要なら、各セクションに書き直してください",
+                        name
+                    ),
+                ));
+            }
+            name if passage
+                .tags
+                .iter()
+                .any(|t| matches!(t.as_str(), "script" | "stylesheet" | "widget")) =>
+            {
+                diagnostics.push(Diagnostic::warning(
+                    "twee-unsupported",
+                    path,
+                    passage.line,
+                    format!(
+                        "スクリプト・スタイル用のパッセージ「{}」（タグ {}）は取り込みませんでした",
+                        name,
+                        passage.tags.join(" ")
+                    ),
+                ));
+            }
+            _ => story.push(passage),
+        }
+    }
+    if story.is_empty() {
+        return ImportResult {
+            path: path.to_path_buf(),
+            markdown: String::new(),
+            passages: 0,
+            diagnostics: vec![file_level(
+                "twee-invalid",
+                Severity::Error,
+                path,
+                format!(
+                    "{} に物語のパッセージ（`:: 名前` で始まる行）が 1 つもありません。Twine の「Twee 形式で書き出す」で保存したファイルを指定してください",
+                    path.display()
+                ),
+            )],
+        };
+    }
+
+    // 開始パッセージを先頭にする（v1 はファイルの先頭から始まる）
+    let start_idx = start
+        .as_deref()
+        .or(Some("Start"))
+        .and_then(|name| story.iter().position(|p| p.name == name))
+        .unwrap_or(0);
+    let entry = story.remove(start_idx);
+    story.insert(0, entry);
+
+    let title = title.filter(|t| !t.is_empty()).unwrap_or_else(|| {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    let mut out = format!("---\nid: {}\n---\n\n# {}\n", derive_id(output), title);
+    let mut endings = HashSet::new();
+    let names: HashSet<&str> = story.iter().map(|p| p.name.as_str()).collect();
+    for passage in &story {
+        let _ = write!(out, "\n## {}\n", passage.name);
+        let links = convert_body(passage, &names, path, &mut out, &mut diagnostics);
+        match links.as_slice() {
+            [] => {
+                // ending id は英数字・`-`・`_` だけ（日本語のパッセージ名は連番にする）
+                let mut id = slugify(&passage.name).replace('-', "_");
+                if id.is_empty() || !id.is_ascii() {
+                    id = format!("ending_{}", endings.len() + 1);
+                }
+                let base = id.clone();
+                let mut n = 2;
+                while !endings.insert(id.clone()) {
+                    id = format!("{}_{}", base, n);
+                    n += 1;
+                }
+                let _ = write!(out, "\n<!-- ending: {} -->\n", id);
+            }
+            [(label, target)] => {
+                let _ = write!(out, "\n[{}](#{})\n", md_label(label), slugify(target));
+            }
+            links => {
+                out.push('\n');
+                for (label, target) in links {
+                    let _ = writeln!(out, "- [{}](#{})", md_label(label), slugify(target));
+                }
+            }
+        }
+    }
+
+    diagnostics.sort_by_key(|d| d.span.as_ref().map(|s| s.line));
+    ImportResult {
+        path: path.to_path_buf(),
+        markdown: out,
+        passages: story.len(),
+        diagnostics,
+    }
+}
+
+/// `:: 名前 [タグ] {メタデータ}` で区切ったパッセージ
+struct Passage {
+    name: String,
+    tags: Vec<String>,
+    /// 見出し行（1-origin）
+    line: usize,
+    /// 本文の（行番号, 行）
+    body: Vec<(usize, String)>,
+}
+
+impl Passage {
+    fn text(&self) -> String {
+        self.body
+            .iter()
+            .map(|(_, l)| l.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn parse_passages(source: &str) -> Vec<Passage> {
+    let mut passages: Vec<Passage> = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        if let Some(header) = line.strip_prefix("::") {
+            let (name, tags) = parse_header(header);
+            passages.push(Passage {
+                name,
+                tags,
+                line: i + 1,
+                body: Vec::new(),
+            });
+        } else if let Some(passage) = passages.last_mut() {
+            passage.body.push((i + 1, line.to_string()));
+        }
+    }
+    passages
+}
+
+/// 見出し行から名前とタグを取り出す（`\[` 等のエスケープを解く）
+fn parse_header(header: &str) -> (String, Vec<String>) {
+    let mut name = String::new();
+    let mut chars = header.trim().chars();
+    let mut rest = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => name.extend(chars.next()),
+            '[' | '{' => {
+                rest.push(c);
+                rest.extend(chars.by_ref());
+            }
+            _ => name.push(c),
+        }
+    }
+    let tags = rest
+        .strip_prefix('[')
+        .and_then(|r| r.split_once(']'))
+        .map(|(tags, _)| tags.split_whitespace().map(String::from).collect())
+        .unwrap_or_default();
+    (name.trim().to_string(), tags)
+}
+
+/// 本文を 1 行 1 段落で書き、パッセージ内のリンク（ラベル, 飛び先）を返す。
+/// `names` は取り込む物語のパッセージ名で、ないパッセージへのリンクを報告する
+fn convert_body(
+    passage: &Passage,
+    names: &HashSet<&str>,
+    path: &Path,
+    out: &mut String,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<(String, String)> {
+    let mut links = Vec::new();
+    for (line_no, line) in &passage.body {
+        let mut removed = Vec::new();
+        let text = strip_macros(line, &mut removed);
+        for macro_text in removed {
+            diagnostics.push(Diagnostic::warning(
+                "twee-unsupported",
+                path,
+                *line_no,
+                format!(
+                    "Twine のマクロ `{}` は v1 記法で表せないため取り除きました。条件つきの文章は条件なしで残っています。分岐させたいなら選択肢で書き直してください",
+                    macro_text
+                ),
+            ));
+        }
+        if let Some(variable) = find_variable(&text) {
+            diagnostics.push(Diagnostic::warning(
+                "twee-unsupported",
+                path,
+                *line_no,
+                format!(
+                    "変数 `{}` は v1 記法にないため、文字どおりの本文として残しました",
+                    variable
+                ),
+            ));
+        }
+        let (text, line_links) = extract_links(&text);
+        for (_, target) in &line_links {
+            if !names.contains(target.as_str()) {
+                diagnostics.push(Diagnostic::warning(
+                    "twee-broken-link",
+                    path,
+                    *line_no,
+                    format!(
+                        "リンク先のパッセージ「{}」がありません。取り込んだシーンでは存在しないセクションへのリンクになるため、パッセージ名を確かめてください",
+                        target
+                    ),
+                ));
+            }
+        }
+        links.extend(line_links);
+        let text = text.trim();
+        if !text.is_empty() {
+            let _ = write!(out, "\n{}\n", md_paragraph(text));
+        }
+    }
+    links
+}
+
+/// Harlowe の `(名前: …)` と SugarCube の `<<…>>` を取り除き、取り除いた
+/// マクロを `removed` に積む。マクロ直後の Harlowe のフック `[…]` は中身を残す
+fn strip_macros(line: &str, removed: &mut Vec<String>) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '<' && chars.get(i + 1) == Some(&'<') {
+            let end = (i + 2..chars.len().saturating_sub(1))
+                .find(|&j| chars[j] == '>' && chars[j + 1] == '>')
+                .map_or(chars.len(), |j| j + 2);
+            removed.push(chars[i..end].iter().collect());
+            i = end;
+            continue;
+        }
+        if chars[i] == '(' && is_harlowe_macro(&chars[i + 1..]) {
+            let end = matching(&chars, i, '(', ')');
+            removed.push(chars[i..end].iter().collect());
+            i = end;
+            // フック: 括弧だけ外して中身を残す
+            if chars.get(i) == Some(&'[') && chars.get(i + 1) != Some(&'[') {
+                let close = matching(&chars, i, '[', ']');
+                let inner_end = if chars[close - 1] == ']' {
+                    close - 1
+                } else {
+                    close
+                };
+                out.extend(&chars[i + 1..inner_end]);
+                i = close;
+            }
+            continue;
+        }
+        out.push(chars[i]);
+        i += 1;
+    }
+    out
+}
+
+/// `名前:` で始まるか（Harlowe のマクロ名は英字で始まり、英数字と `-`）
+fn is_harlowe_macro(rest: &[char]) -> bool {
+    let name_len = rest
+        .iter()
+        .take_while(|c| c.is_ascii_alphanumeric() || **c == '-')
+        .count();
+    name_len > 0 && rest[0].is_ascii_alphabetic() && rest.get(name_len) == Some(&':')
+}
+
+/// `open` の位置から対応する `close` の次の位置。閉じていなければ行末
+fn matching(chars: &[char], open_at: usize, open: char, close: char) -> usize {
+    let mut depth = 0;
+    for (j, &c) in chars.iter().enumerate().skip(open_at) {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return j + 1;
+            }
+        }
+    }
+    chars.len()
+}
+
+/// `$名前` / `_名前` の変数参照（最初の 1 つ）
+fn find_variable(text: &str) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    (0..chars.len()).find_map(|i| {
+        let sigil = chars[i];
+        let starts_word = i == 0 || !chars[i - 1].is_alphanumeric();
+        let next = chars.get(i + 1)?;
+        if (sigil == '$' || (sigil == '_' && starts_word)) && next.is_ascii_alphabetic() {
+            let name: String = chars[i..]
+                .iter()
+                .enumerate()
+                .take_while(|(k, c)| *k == 0 || c.is_ascii_alphanumeric() || **c == '_')
+                .map(|(_, c)| *c)
+                .collect();
+            Some(name)
+        } else {
+            None
+        }
+    })
+}
+
+/// `[[…]]` を取り出す。行がリンクだけなら本文は空に、文中のリンクは
+/// リンクテキストを本文に残す
+fn extract_links(text: &str) -> (String, Vec<(String, String)>) {
+    let mut links = Vec::new();
+    let mut out = String::new();
+    // リンク以外の部分だけを集めたもの
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(open) = rest.find("[[") {
+        let Some(close) = rest[open..].find("]]") else {
+            break;
+        };
+        let inner = &rest[open + 2..open + close];
+        let (label, target) = if let Some((label, target)) = inner.rsplit_once("->") {
+            (label, target)
+        } else if let Some((target, label)) = inner.split_once("<-") {
+            (label, target)
+        } else if let Some((label, target)) = inner.split_once('|') {
+            (label, target)
+        } else {
+            (inner, inner)
+        };
+        out.push_str(&rest[..open]);
+        plain.push_str(&rest[..open]);
+        out.push_str(label.trim());
+        links.push((label.trim().to_string(), target.trim().to_string()));
+        rest = &rest[open + close + 2..];
+    }
+    out.push_str(rest);
+    plain.push_str(rest);
+    // リンクだけの行は本文を残さない
+    if plain.trim().is_empty() {
+        out.clear();
+    }
+    (out, links)
+}
+
+/// Markdown として別の要素に読まれないよう、行頭の記号をエスケープする
+fn md_paragraph(text: &str) -> String {
+    let first = text.chars().next();
+    if matches!(first, Some('#' | '-' | '*' | '+' | '>' | '|' | '`' | '=')) {
+        return format!("\\{}", text);
+    }
+    // `1. ` の番号付きリスト
+    let digits = text.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && matches!(text[digits..].chars().next(), Some('.' | ')')) {
+        return format!("{}\\{}", &text[..digits], &text[digits..]);
+    }
+    text.to_string()
+}
+
+fn md_label(label: &str) -> String {
+    label.replace('[', "\\[").replace(']', "\\]")
+}
+
+// -------------------------------------------------------------- 書き出し
+
+/// Twine の開くストーリー形式。書き出す本文はリンクと HTML コメントだけなので
+/// 形式に依存しないが、Twee 3 の StoryData には必要
+const STORY_FORMAT: (&str, &str) = ("Harlowe", "3.3.9");
+
+/// シーンを Twee 3 にする（先頭のシーンから開始する）
+pub(super) fn render(scenes: &[LoadedScene]) -> String {
+    let names = PassageNames::new(scenes);
+    let mut body = String::new();
+    let mut position = 0;
+    for (i, loaded) in scenes.iter().enumerate() {
+        let scene = &loaded.parsed.scene;
+        let writer = BlockWriter {
+            scenes,
+            names: &names,
+            current: i,
+        };
+        if let Some(lead) = &names.leads[i] {
+            let mut text = String::new();
+            if let Some(path) = &scene.background {
+                let _ = writeln!(text, "<!-- background: {} -->", path);
+            }
+            if let Some(bgm) = &scene.bgm {
+                let _ = writeln!(text, "<!-- bgm: {} -->", bgm);
+            }
+            let next = names.sections[i].first().map(String::as_str);
+            writer.blocks(&mut text, &scene.lead, next);
+            passage(&mut body, lead, &mut position, &text);
+        }
+        for (j, section) in scene.sections.iter().enumerate() {
+            let mut text = String::new();
+            // リード部がなければ、front matter の演出は最初のセクションに付ける
+            if j == 0 && names.leads[i].is_none() {
+                if let Some(path) = &scene.background {
+                    let _ = writeln!(text, "<!-- background: {} -->", path);
+                }
+                if let Some(bgm) = &scene.bgm {
+                    let _ = writeln!(text, "<!-- bgm: {} -->", bgm);
+                }
+            }
+            let next = names.sections[i].get(j + 1).map(String::as_str);
+            writer.blocks(&mut text, &section.blocks, next);
+            passage(&mut body, &names.sections[i][j], &mut position, &text);
+        }
+    }
+
+    let title = scenes[0]
+        .parsed
+        .scene
+        .title
+        .clone()
+        .or_else(|| scenes[0].parsed.scene.id.clone())
+        .unwrap_or_default();
+    // IFID は乱数ではなく内容から決める（同じ入力からは同じ出力）
+    let a = fnv1a64(body.as_bytes());
+    let b = fnv1a64(format!("{title}\n{body}").as_bytes());
+    let ifid = format!(
+        "{:08X}-{:04X}-4{:03X}-{:04X}-{:012X}",
+        a >> 32,
+        (a >> 16) & 0xffff,
+        a & 0xfff,
+        0x8000 | ((b >> 48) & 0x3fff),
+        b & 0xffff_ffff_ffff
+    );
+    let data = serde_json::json!({
+        "ifid": ifid,
+        "format": STORY_FORMAT.0,
+        "format-version": STORY_FORMAT.1,
+        "start": names.entry(0),
+    });
+    format!(
+        ":: StoryTitle\n{}\n\n:: StoryData\n{}\n\n{}",
+        title,
+        serde_json::to_string_pretty(&data).expect("StoryData は常に JSON にできる"),
+        body
+    )
+}
+
+/// パッセージを 1 つ書く。Twine で開いたときに重ならないよう格子状に並べる
+fn passage(out: &mut String, name: &str, position: &mut usize, text: &str) {
+    let (x, y) = (100 + (*position % 6) * 200, 100 + (*position / 6) * 150);
+    *position += 1;
+    let _ = write!(
+        out,
+        ":: {} {{\"position\":\"{},{}\"}}\n{}\n",
+        escape_name(name),
+        x,
+        y,
+        text
+    );
+}
+
+/// 見出し行で特別な意味を持つ文字をエスケープする
+fn escape_name(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        if matches!(c, '\\' | '[' | ']' | '{' | '}') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// リンク文。Twee のリンクにはエスケープがないため、区切りやリンクの終わりと
+/// 読まれる `->` / `<-` / `|` / `]]` を全角の記号に置き換える
+fn link_label(label: &str) -> String {
+    label
+        .replace("->", "→")
+        .replace("<-", "←")
+        .replace('|', "｜")
+        .replace("]]", "］］")
+}
+
+/// パッセージ名。リード部はシーン ID、セクションは見出し（重なれば
+/// `シーン ID 見出し`）。リンクの飛び先にそのまま書けるよう [`link_label`]
+/// と同じ置き換えをしてある
+struct PassageNames {
+    /// リード部のパッセージ。リード部が空でセクションがあれば None
+    leads: Vec<Option<String>>,
+    sections: Vec<Vec<String>>,
+}
+
+impl PassageNames {
+    fn new(scenes: &[LoadedScene]) -> Self {
+        let mut used = HashSet::new();
+        let mut leads = Vec::new();
+        for loaded in scenes {
+            let scene = &loaded.parsed.scene;
+            let id = link_label(scene.id.as_deref().expect("check済みなのでidがある"));
+            used.insert(id.clone());
+            leads.push((!scene.lead.is_empty() || scene.sections.is_empty()).then_some(id));
+        }
+        let mut sections = Vec::new();
+        for loaded in scenes {
+            let scene = &loaded.parsed.scene;
+            let id = link_label(scene.id.as_deref().unwrap_or_default());
+            sections.push(
+                scene
+                    .sections
+                    .iter()
+                    .map(|section| {
+                        let heading = link_label(&section.heading);
+                        let mut name = heading.clone();
+                        if used.contains(&name) {
+                            name = format!("{} {}", id, heading);
+                        }
+                        let base = name.clone();
+                        let mut n = 2;
+                        while !used.insert(name.clone()) {
+                            name = format!("{} {}", base, n);
+                            n += 1;
+                        }
+                        name
+                    })
+                    .collect(),
+            );
+        }
+        Self { leads, sections }
+    }
+
+    /// シーンの先頭（ファイルへのリンクの着地点）のパッセージ
+    fn entry(&self, scene: usize) -> &str {
+        self.leads[scene]
+            .as_deref()
+            .unwrap_or_else(|| &self.sections[scene][0])
+    }
+}
+
+struct BlockWriter<'a> {
+    scenes: &'a [LoadedScene],
+    names: &'a PassageNames,
+    current: usize,
+}
+
+impl BlockWriter<'_> {
+    /// ブロック列を 1 行ずつ書き、終端がなければ `next` へのリンクを置く
+    fn blocks(&self, out: &mut String, blocks: &[Block], next: Option<&str>) {
+        for block in blocks {
+            if self.block(out, block) {
+                return;
+            }
+        }
+        if let Some(next) = next {
+            let _ = writeln!(out, "[[次へ->{}]]", next);
+        }
+    }
+
+    /// 1 ブロックを書く。実行がここで終わる（先へ進まない）なら true
+    fn block(&self, out: &mut String, block: &Block) -> bool {
+        match block {
+            Block::Narration { text, .. } => {
+                let _ = writeln!(out, "{}", text);
+            }
+            Block::Dialogue {
+                speaker,
+                expression,
+                text,
+                ..
+            } => match expression {
+                Some(expression) => {
+                    let _ = writeln!(out, "{}({}): {}", speaker, expression, text);
+                }
+                None => {
+                    let _ = writeln!(out, "{}: {}", speaker, text);
+                }
+            },
+            Block::Choices { items, .. } => {
+                for item in items {
+                    let _ = writeln!(
+                        out,
+                        "[[{}->{}]]",
+                        link_label(&item.label),
+                        self.target(&item.target)
+                    );
+                }
+                return true;
+            }
+            Block::Random { items, .. } => {
+                // Harlowe の (either:) は重みを持たないため、重みの数だけ候補を並べる
+                let candidates: Vec<String> = items
+                    .iter()
+                    .flat_map(|item| {
+                        let name = format!("\"{}\"", self.target(&item.target));
+                        std::iter::repeat_n(name, item.weight as usize)
+                    })
+                    .collect();
+                let _ = writeln!(out, "(go-to: (either: {}))", candidates.join(", "));
+                return true;
+            }
+            Block::Jump { label, target, .. } => {
+                let _ = writeln!(out, "[[{}->{}]]", link_label(label), self.target(target));
+                return true;
+            }
+            Block::Call { target, .. } => {
+                let _ = writeln!(out, "(display: \"{}\")", self.target(target));
+            }
+            Block::Return { .. } => {
+                out.push_str("<!-- return -->\n");
+                return true;
+            }
+            Block::Ending { id, .. } => {
+                let _ = writeln!(out, "<!-- ending: {} -->", id);
+                return true;
+            }
+            Block::Background { path, .. } => {
+                let _ = writeln!(out, "<!-- background: {} -->", path);
+            }
+            Block::Bgm { path, .. } => {
+                let _ = writeln!(out, "<!-- bgm: {} -->", path);
+            }
+            Block::BgmStop { .. } => out.push_str("<!-- bgm: stop -->\n"),
+            Block::Se { path, .. } => {
+                let _ = writeln!(out, "<!-- se: {} -->", path);
+            }
+            Block::Show {
+                character,
+                expression,
+                ..
+            } => match expression {
+                Some(expression) => {
+                    let _ = writeln!(out, "<!-- show: {}({}) -->", character, expression);
+                }
+                None => {
+                    let _ = writeln!(out, "<!-- show: {} -->", character);
+                }
+            },
+            Block::Hide { character, .. } => {
+                let _ = writeln!(out, "<!-- hide: {} -->", character);
+            }
+        }
+        false
+    }
+
+    fn target(&self, target: &LinkTarget) -> &str {
+        match resolve_link(self.scenes, self.current, target) {
+            (scene, Some(section)) => &self.names.sections[scene][section],
+            (scene, None) => self.names.entry(scene),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn リンクの書き方を全部読める() {
+        let (text, links) = extract_links("[[走る]] [[歩く->walk]] [[back<-戻る]] [[休む|rest]]");
+        assert_eq!(text, "");
+        assert_eq!(
+            links,
+            [
+                ("走る".to_string(), "走る".to_string()),
+                ("歩く".to_string(), "walk".to_string()),
+                ("戻る".to_string(), "back".to_string()),
+                ("休む".to_string(), "rest".to_string()),
+            ]
+        );
+        // 文中のリンクはテキストを残す
+        let (text, links) = extract_links("扉を[[開ける->open]]か迷った");
+        assert_eq!(text, "扉を開けるか迷った");
+        assert_eq!(links.len(), 1);
+    }
+
+    #[test]
+    fn マクロを取り除きフックの中身は残す() {
+        let mut removed = Vec::new();
+        let text = strip_macros("(if: $met)[また会ったね]<<set $x to 1>>。", &mut removed);
+        assert_eq!(text, "また会ったね。");
+        assert_eq!(removed, ["(if: $met)", "<<set $x to 1>>"]);
+        // マクロではない丸括弧はそのまま
+        let text = strip_macros("（小声で）(笑)", &mut removed);
+        assert_eq!(text, "（小声で）(笑)");
+    }
+
+    #[test]
+    fn 見出し行からエスケープを解いて名前とタグを取り出す() {
+        assert_eq!(
+            parse_header(r#" A \[1\] [start intro] {"position":"1,1"}"#),
+            (
+                "A [1]".to_string(),
+                vec!["start".to_string(), "intro".to_string()]
+            )
+        );
+        assert_eq!(parse_header(" 始まり"), ("始まり".to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn リンク文の区切り記号は全角に置き換える() {
+        assert_eq!(link_label("普通の文"), "普通の文");
+        assert_eq!(link_label("A->B"), "A→B");
+        assert_eq!(link_label("B<-A"), "B←A");
+        assert_eq!(link_label("右|左"), "右｜左");
+        assert_eq!(link_label("[[x]]"), "[[x］］");
+        // 書き出したリンクを読み直すと、ラベルと飛び先が元のまま分かれる
+        let text = format!("[[{}->行き先]]", link_label("a->b|c]]d"));
+        let (_, links) = extract_links(&text);
+        assert_eq!(links, [("a→b｜c］］d".to_string(), "行き先".to_string())]);
+    }
+}
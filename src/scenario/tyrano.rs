@@ -0,0 +1,316 @@
+//! `compile --target tyrano`: TyranoScript（ティラノビルダー等）への書き出し
+//!
+//! シーン（1 ファイル）ごとに 1 つのシナリオファイル（`.ks`）を書く。
+//! ファイル名（`storage`）は元の Markdown のファイル名の拡張子を `.ks` に
+//! したもので、[`LinkTarget`](super::LinkTarget) のファイル参照はこの名前に
+//! 読み替える。対応は次のとおり。
+//! - H2 セクション → ラベル `*anchor`（識別子の作り方は ink.rs と共通）
+//! - ナレーション → 名前欄を消す `#` の後に本文と `[p]`、セリフ →
+//!   characters.yaml の表示名の `#表示名` の後に本文と `[p]`
+//! - 選択肢 → `[glink]` を並べて `[s]`、ジャンプ → `[jump storage=... target=*x]`
+//! - 呼び出し（SPEC 4.9）→ `[call storage=... target=*x]`、`<!-- return -->` →
+//!   `[return]`
+//! - エンディング → `; ending: id` のコメントと `[s]`
+//! - 演出（SPEC 4.6）・front matter → `[bg]` / `[playbgm]` / `[stopbgm]` /
+//!   `[playse]` / `[chara_show]` / `[chara_hide]`。TyranoScript は素材を
+//!   種類ごとのフォルダ（`data/bgimage` 等）から探すため、`storage` には
+//!   ファイル名だけを書く
+//!
+//! TyranoScript はラベルを越えて次の行へ進むので、フォールスルー（SPEC 5章）は
+//! そのまま次のラベルに続ける。ファイルの末尾だけ `[s]` で止める。
+
+use super::Block;
+use super::compile::resolve_link;
+use super::export::ExportFile;
+use super::ink::{ident, unique};
+use super::project::LoadedScene;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::Path;
+
+/// シーンごとの `.ks` を返す（先頭が開始シーン）
+pub(super) fn render(scenes: &[LoadedScene]) -> Vec<ExportFile> {
+    let names = Storages::new(scenes);
+    scenes
+        .iter()
+        .enumerate()
+        .map(|(i, loaded)| {
+            let scene = &loaded.parsed.scene;
+            let mut out = String::new();
+            let file = loaded
+                .path
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "; tsumugai compile --target tyrano が {} から生成したファイルです",
+                file
+            );
+            if let Some(title) = &scene.title {
+                let _ = writeln!(out, "; {}", title);
+            }
+            if let Some(path) = &scene.background {
+                let _ = writeln!(out, "[bg storage=\"{}\"]", storage(path));
+            }
+            if let Some(bgm) = &scene.bgm {
+                let _ = writeln!(out, "[playbgm storage=\"{}\"]", storage(bgm));
+            }
+
+            let writer = BlockWriter {
+                scenes,
+                names: &names,
+                current: i,
+            };
+            let mut stopped = writer.blocks(&mut out, &scene.lead);
+            for (j, section) in scene.sections.iter().enumerate() {
+                out.push('\n');
+                let _ = writeln!(out, "*{}", names.labels[i][j]);
+                let _ = writeln!(out, "; {}", section.heading);
+                stopped = writer.blocks(&mut out, &section.blocks);
+            }
+            if !stopped {
+                out.push_str("[s]\n");
+            }
+            ExportFile {
+                name: names.storages[i].clone(),
+                text: out,
+            }
+        })
+        .collect()
+}
+
+/// シナリオファイル名とラベル
+struct Storages {
+    storages: Vec<String>,
+    /// シーンごとの、セクションの並びと同じ順のラベル
+    labels: Vec<Vec<String>>,
+}
+
+impl Storages {
+    fn new(scenes: &[LoadedScene]) -> Self {
+        let mut used = HashSet::new();
+        let mut storages = Vec::new();
+        let mut labels = Vec::new();
+        for (i, loaded) in scenes.iter().enumerate() {
+            let stem = loaded
+                .path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| format!("scene_{}", i + 1));
+            // 別のディレクトリに同じファイル名があれば `_2` 等で区別する
+            storages.push(format!("{}.ks", unique(stem, &mut used)));
+
+            let mut local = HashSet::new();
+            labels.push(
+                loaded
+                    .parsed
+                    .scene
+                    .sections
+                    .iter()
+                    .enumerate()
+                    .map(|(j, section)| {
+                        let name =
+                            ident(&section.anchor).unwrap_or_else(|| format!("section_{}", j + 1));
+                        unique(name, &mut local)
+                    })
+                    .collect(),
+            );
+        }
+        Self { storages, labels }
+    }
+}
+
+struct BlockWriter<'a> {
+    scenes: &'a [LoadedScene],
+    names: &'a Storages,
+    current: usize,
+}
+
+impl BlockWriter<'_> {
+    /// ブロック列を書く。終端（`[s]` か `[jump]`）で終わったら true
+    fn blocks(&self, out: &mut String, blocks: &[Block]) -> bool {
+        for block in blocks {
+            if self.block(out, block) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// 1 ブロックを書く。実行がここで終わる（先へ進まない）なら true
+    fn block(&self, out: &mut String, block: &Block) -> bool {
+        match block {
+            Block::Narration { text, .. } => {
+                let _ = writeln!(out, "#\n{}[p]", escape(text));
+            }
+            Block::Dialogue {
+                speaker,
+                expression,
+                text,
+                ..
+            } => {
+                // 表情つきのセリフは立ち絵の差し替え
+                if let Some(expression) = expression {
+                    let _ = writeln!(
+                        out,
+                        "[chara_mod name=\"{}\" face=\"{}\"]",
+                        attr(speaker),
+                        attr(expression)
+                    );
+                }
+                let display = self.scenes[self.current].display_name(speaker);
+                let _ = writeln!(out, "#{}\n{}[p]", escape(display), escape(text));
+            }
+            Block::Choices { items, .. } => {
+                for item in items {
+                    let _ = writeln!(
+                        out,
+                        "[glink text=\"{}\"{}]",
+                        attr(&item.label),
+                        self.target(&item.target)
+                    );
+                }
+                out.push_str("[s]\n");
+                return true;
+            }
+            Block::Random { items, .. } => {
+                // 0 以上 重みの合計 未満の出目を、重みの累積値で振り分ける
+                let total: u32 = items.iter().map(|item| item.weight).sum();
+                let _ = writeln!(
+                    out,
+                    "[eval exp=\"tf.roll = Math.floor(Math.random() * {})\"]",
+                    total
+                );
+                let mut upper = 0;
+                for (i, item) in items.iter().enumerate() {
+                    upper += item.weight;
+                    if i + 1 == items.len() {
+                        let _ = writeln!(out, "[jump{}]", self.target(&item.target));
+                    } else {
+                        let _ = writeln!(
+                            out,
+                            "[jump{} cond=\"tf.roll < {}\"]",
+                            self.target(&item.target),
+                            upper
+                        );
+                    }
+                }
+                return true;
+            }
+            Block::Jump { target, .. } => {
+                let _ = writeln!(out, "[jump{}]", self.target(target));
+                return true;
+            }
+            Block::Call { target, .. } => {
+                let _ = writeln!(out, "[call{}]", self.target(target));
+            }
+            Block::Return { .. } => {
+                out.push_str("[return]\n");
+                return true;
+            }
+            Block::Ending { id, .. } => {
+                let _ = writeln!(out, "; ending: {}\n[s]", id);
+                return true;
+            }
+            Block::Background { path, .. } => {
+                let _ = writeln!(out, "[bg storage=\"{}\"]", storage(path));
+            }
+            Block::Bgm { path, .. } => {
+                let _ = writeln!(out, "[playbgm storage=\"{}\"]", storage(path));
+            }
+            Block::BgmStop { .. } => out.push_str("[stopbgm]\n"),
+            Block::Se { path, .. } => {
+                let _ = writeln!(out, "[playse storage=\"{}\"]", storage(path));
+            }
+            Block::Show {
+                character,
+                expression,
+                ..
+            } => match expression {
+                Some(expression) => {
+                    let _ = writeln!(
+                        out,
+                        "[chara_show name=\"{}\" face=\"{}\"]",
+                        attr(character),
+                        attr(expression)
+                    );
+                }
+                None => {
+                    let _ = writeln!(out, "[chara_show name=\"{}\"]", attr(character));
+                }
+            },
+            Block::Hide { character, .. } => {
+                let _ = writeln!(out, "[chara_hide name=\"{}\"]", attr(character));
+            }
+        }
+        false
+    }
+
+    /// リンク先の ` storage="x.ks" target=*label` 属性。同じファイル内なら
+    /// storage を、ファイルの先頭なら target を省く
+    fn target(&self, target: &super::LinkTarget) -> String {
+        let (scene, section) = resolve_link(self.scenes, self.current, target);
+        let mut attrs = String::new();
+        if scene != self.current || section.is_none() {
+            let _ = write!(attrs, " storage=\"{}\"", self.names.storages[scene]);
+        }
+        if let Some(section) = section {
+            let _ = write!(attrs, " target=*{}", self.names.labels[scene][section]);
+        }
+        attrs
+    }
+}
+
+/// 素材の `storage`。TyranoScript は種類ごとのフォルダから探すのでファイル名だけ
+fn storage(path: &str) -> String {
+    let name = Path::new(path)
+        .file_name()
+        .map_or_else(|| path.to_string(), |f| f.to_string_lossy().to_string());
+    attr(&name)
+}
+
+/// タグの属性値。`"` は値の終わりになるため全角にする
+fn attr(value: &str) -> String {
+    value.replace('"', "＂")
+}
+
+/// 本文を TyranoScript のテキストとして書けるようにする。`[` はタグの
+/// 始まりになるため `[[` にし、行頭で特別な意味を持つ文字（`@` タグ・
+/// `*` ラベル・`;` コメント・`#` 名前欄）は `[emb]` で出す
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    if let Some(first) = chars.clone().next()
+        && matches!(first, '@' | '*' | ';' | '#')
+    {
+        let _ = write!(out, "[emb exp=\"'{}'\"]", first);
+        chars.next();
+    }
+    for c in chars {
+        if c == '[' {
+            out.push('[');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn 記法として解釈される文字をエスケープする() {
+        assert_eq!(escape("普通の文"), "普通の文");
+        assert_eq!(escape("[注] 本文"), "[[注] 本文");
+        assert_eq!(escape("#1 の部屋"), "[emb exp=\"'#'\"]1 の部屋");
+        assert_eq!(escape("a; b"), "a; b");
+    }
+
+    #[test]
+    fn 素材はファイル名だけを指定する() {
+        assert_eq!(storage("../assets/bg/school_gate.png"), "school_gate.png");
+        assert_eq!(storage("bgm/\"a\".ogg"), "＂a＂.ogg");
+    }
+}
@@ -0,0 +1,343 @@
+//! `compile --target yarn`: Yarn Spinner（Unity 等）への書き出し
+//!
+//! 対応は次のとおり。
+//! - シーンのリード部とH2 セクション → 1 つずつのノード。Yarn のノード名は
+//!   プロジェクト全体で一意なので、セクションは `シーン_セクション` にする
+//!   （識別子の作り方は ink.rs と共通。日本語の見出しは `section_2` 等の連番）。
+//!   リード部が空でセクションがあるシーンはリード部のノードを作らない
+//! - 選択肢 → `-> ラベル` と、その下に字下げした `<<jump ノード>>`
+//! - ジャンプ → `<<jump ノード>>`、エンディング → `<<stop>>` と、ノードの
+//!   `tags:` ヘッダの `ending:id`
+//! - 呼び出し（SPEC 4.9）→ `<<detour ノード>>`、`<!-- return -->` → `<<return>>`
+//!   （Yarn Spinner 2.3 以降）
+//! - セリフ → characters.yaml の表示名を使った `表示名: 本文` の行に
+//!   `#speaker:` / `#expression:` のタグ
+//! - 演出（SPEC 4.6）→ `<<background path>>` 等のコマンド。Yarn 側で同名の
+//!   コマンドを登録して受け取る
+//! - Yarn はノードの末尾から次のノードへ進まないため、フォールスルー
+//!   （SPEC 5章）は明示の `<<jump>>` にする
+//!
+//! 終端より後ろのブロックは到達不能（check の warning）なので書き出さない。
+
+use super::Block;
+use super::compile::resolve_link;
+use super::ink::{ident, unique};
+use super::project::LoadedScene;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// シーンを Yarn のソースにする（先頭のシーンの最初のノードから開始する）
+pub(super) fn render(scenes: &[LoadedScene]) -> String {
+    let names = Nodes::new(scenes);
+    let mut out = String::new();
+    out.push_str("// tsumugai compile --target yarn が生成したファイルです\n");
+    let _ = writeln!(out, "// 開始ノード: {}", names.entry(0));
+
+    for (i, loaded) in scenes.iter().enumerate() {
+        let scene = &loaded.parsed.scene;
+        let writer = BlockWriter {
+            scenes,
+            names: &names,
+            current: i,
+        };
+        let mut front = String::new();
+        if let Some(path) = &scene.background {
+            let _ = writeln!(front, "<<background {}>>", arg(path));
+        }
+        if let Some(bgm) = &scene.bgm {
+            let _ = writeln!(front, "<<bgm {}>>", arg(bgm));
+        }
+
+        if let Some(lead) = &names.leads[i] {
+            let next = names.sections[i].first().map(String::as_str);
+            let (body, ending) = writer.body(&front, &scene.lead, next);
+            node(&mut out, lead, scene.title.as_deref(), ending, &body);
+            front.clear();
+        }
+        for (j, section) in scene.sections.iter().enumerate() {
+            let next = names.sections[i].get(j + 1).map(String::as_str);
+            // リード部のノードがなければ、front matter の演出は最初のセクションに付ける
+            let (body, ending) = writer.body(&front, &section.blocks, next);
+            node(
+                &mut out,
+                &names.sections[i][j],
+                Some(&section.heading),
+                ending,
+                &body,
+            );
+            front.clear();
+        }
+    }
+    out
+}
+
+/// ノードを 1 つ書く。見出しはコメントで残す
+fn node(out: &mut String, title: &str, heading: Option<&str>, ending: Option<&str>, body: &str) {
+    out.push('\n');
+    let _ = writeln!(out, "title: {}", title);
+    if let Some(ending) = ending {
+        let _ = writeln!(out, "tags: ending:{}", tag_value(ending));
+    }
+    out.push_str("---\n");
+    if let Some(heading) = heading {
+        let _ = writeln!(out, "// {}", heading);
+    }
+    out.push_str(body);
+    out.push_str("===\n");
+}
+
+/// ノード名
+struct Nodes {
+    /// リード部のノード。リード部が空でセクションがあれば None
+    leads: Vec<Option<String>>,
+    sections: Vec<Vec<String>>,
+}
+
+impl Nodes {
+    fn new(scenes: &[LoadedScene]) -> Self {
+        let mut used = HashSet::new();
+        let mut leads = Vec::new();
+        let mut sections = Vec::new();
+        for (i, loaded) in scenes.iter().enumerate() {
+            let scene = &loaded.parsed.scene;
+            let base = scene
+                .id
+                .as_deref()
+                .and_then(ident)
+                .unwrap_or_else(|| format!("scene_{}", i + 1));
+            let lead = (!scene.lead.is_empty() || scene.sections.is_empty())
+                .then(|| unique(base.clone(), &mut used));
+            leads.push(lead);
+            sections.push(
+                scene
+                    .sections
+                    .iter()
+                    .enumerate()
+                    .map(|(j, section)| {
+                        let name =
+                            ident(&section.anchor).unwrap_or_else(|| format!("section_{}", j + 1));
+                        unique(format!("{}_{}", base, name), &mut used)
+                    })
+                    .collect(),
+            );
+        }
+        Self { leads, sections }
+    }
+
+    /// シーンの先頭（ファイルへのリンクの着地点）のノード
+    fn entry(&self, scene: usize) -> &str {
+        self.leads[scene]
+            .as_deref()
+            .unwrap_or_else(|| &self.sections[scene][0])
+    }
+}
+
+struct BlockWriter<'a> {
+    scenes: &'a [LoadedScene],
+    names: &'a Nodes,
+    current: usize,
+}
+
+impl BlockWriter<'_> {
+    /// ノードの本文を書く。終端がなければ `next` へ進む。
+    /// 返り値は (本文, エンディングで終わるならその id)
+    fn body<'b>(
+        &self,
+        front: &str,
+        blocks: &'b [Block],
+        next: Option<&str>,
+    ) -> (String, Option<&'b str>) {
+        let mut out = front.to_string();
+        for block in blocks {
+            if self.block(&mut out, block) {
+                let ending = match block {
+                    Block::Ending { id, .. } => Some(id.as_str()),
+                    _ => None,
+                };
+                return (out, ending);
+            }
+        }
+        if let Some(next) = next {
+            let _ = writeln!(out, "<<jump {}>>", next);
+        }
+        (out, None)
+    }
+
+    /// 1 ブロックを書く。実行がここで終わる（先へ進まない）なら true
+    fn block(&self, out: &mut String, block: &Block) -> bool {
+        match block {
+            Block::Narration { text, .. } => {
+                let _ = writeln!(out, "{}", escape(text));
+            }
+            Block::Dialogue {
+                speaker,
+                expression,
+                text,
+                ..
+            } => {
+                let display = self.scenes[self.current].display_name(speaker);
+                let _ = write!(
+                    out,
+                    "{}: {} #speaker:{}",
+                    escape(display),
+                    escape(text),
+                    tag_value(speaker)
+                );
+                if let Some(expression) = expression {
+                    let _ = write!(out, " #expression:{}", tag_value(expression));
+                }
+                out.push('\n');
+            }
+            Block::Choices { items, .. } => {
+                for item in items {
+                    let _ = writeln!(
+                        out,
+                        "-> {}\n    <<jump {}>>",
+                        escape(&item.label),
+                        self.node(&item.target)
+                    );
+                }
+                return true;
+            }
+            Block::Random { items, .. } => {
+                // 1 以上 重みの合計 以下の出目を、重みの累積値で振り分ける
+                let total: u32 = items.iter().map(|item| item.weight).sum();
+                let _ = writeln!(out, "<<set $tsumugai_roll to dice({})>>", total);
+                let mut upper = 0;
+                for (i, item) in items.iter().enumerate() {
+                    upper += item.weight;
+                    if i + 1 == items.len() {
+                        let _ = writeln!(out, "<<jump {}>>", self.node(&item.target));
+                    } else {
+                        let _ = writeln!(
+                            out,
+                            "<<if $tsumugai_roll <= {}>>\n    <<jump {}>>\n<<endif>>",
+                            upper,
+                            self.node(&item.target)
+                        );
+                    }
+                }
+                return true;
+            }
+            Block::Jump { target, .. } => {
+                let _ = writeln!(out, "<<jump {}>>", self.node(target));
+                return true;
+            }
+            Block::Call { target, .. } => {
+                let _ = writeln!(out, "<<detour {}>>", self.node(target));
+            }
+            Block::Return { .. } => {
+                out.push_str("<<return>>\n");
+                return true;
+            }
+            Block::Ending { .. } => {
+                out.push_str("<<stop>>\n");
+                return true;
+            }
+            Block::Background { path, .. } => {
+                let _ = writeln!(out, "<<background {}>>", arg(path));
+            }
+            Block::Bgm { path, .. } => {
+                let _ = writeln!(out, "<<bgm {}>>", arg(path));
+            }
+            Block::BgmStop { .. } => out.push_str("<<bgm stop>>\n"),
+            Block::Se { path, .. } => {
+                let _ = writeln!(out, "<<se {}>>", arg(path));
+            }
+            Block::Show {
+                character,
+                expression,
+                ..
+            } => match expression {
+                Some(expression) => {
+                    let _ = writeln!(out, "<<show {} {}>>", arg(character), arg(expression));
+                }
+                None => {
+                    let _ = writeln!(out, "<<show {}>>", arg(character));
+                }
+            },
+            Block::Hide { character, .. } => {
+                let _ = writeln!(out, "<<hide {}>>", arg(character));
+            }
+        }
+        false
+    }
+
+    /// リンク先のノード
+    fn node(&self, target: &super::LinkTarget) -> &str {
+        let (scene, section) = resolve_link(self.scenes, self.current, target);
+        match section {
+            Some(section) => &self.names.sections[scene][section],
+            None => self.names.entry(scene),
+        }
+    }
+}
+
+/// 本文を Yarn の行として書けるようにする。記法として解釈される文字
+/// （タグ・式・マークアップ・コマンド・コメント・行頭の選択肢記号等）を
+/// `\` でエスケープする
+fn escape(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let next = chars.get(i + 1).copied();
+        let special = match c {
+            '\\' | '#' | '{' | '}' | '[' | ']' => true,
+            '<' => next == Some('<'),
+            '/' => next == Some('/'),
+            '-' => i == 0 && next == Some('>'),
+            '=' => i == 0,
+            _ => false,
+        };
+        if special {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// コマンドの引数。空白を含むなら `"` で囲む
+fn arg(value: &str) -> String {
+    if value.contains(char::is_whitespace) {
+        format!("\"{}\"", value.replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// タグの値。空白と `#` はタグの区切りになるため `_` にする
+fn tag_value(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_whitespace() || c == '#' {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn 記法として解釈される文字をエスケープする() {
+        assert_eq!(escape("普通の文"), "普通の文");
+        assert_eq!(escape("-> 行頭"), "\\-> 行頭");
+        assert_eq!(escape("a -> b"), "a -> b");
+        assert_eq!(escape("#1 {x} [b]"), "\\#1 \\{x\\} \\[b\\]");
+        assert_eq!(escape("<<stop>>"), "\\<<stop>>");
+        assert_eq!(escape("http://example.com"), "http:\\//example.com");
+    }
+
+    #[test]
+    fn 空白を含む引数とタグを書ける形にする() {
+        assert_eq!(arg("bg/school.png"), "bg/school.png");
+        assert_eq!(arg("bg/my school.png"), "\"bg/my school.png\"");
+        assert_eq!(tag_value("笑顔 2#b"), "笑顔_2_b");
+    }
+}
@@ -50,7 +50,11 @@ fn examples_springは単一ファイル起点でもdiagnostic0件() {
 #[test]
 fn シーンidの重複はduplicate_scene_idになる() {
     let result = check("duplicate_id");
-    assert_eq!(rule_ids(&result), vec!["duplicate-scene-id"]);
+    // characters.yaml の 2 人はどのシーンにも登場しない（ディレクトリ検査なので報告される）
+    assert_eq!(
+        rule_ids(&result),
+        vec!["duplicate-scene-id", "unused-character", "unused-character"]
+    );
     let diag = &result.diagnostics[0];
     assert!(
         diag.file.ends_with("b.md"),
@@ -115,7 +119,11 @@ fn h1タイトルへのリンクはbroken_linkでh2だけが分岐先だと案
 #[test]
 fn 別ファイルの存在しないアンカーはbroken_linkで使える見出しが案内される() {
     let result = check("cross_file");
-    assert_eq!(rule_ids(&result), vec!["broken-link"]);
+    // characters.yaml の 2 人はどのシーンにも登場しない（ディレクトリ検査なので報告される）
+    assert_eq!(
+        rule_ids(&result),
+        vec!["broken-link", "unused-character", "unused-character"]
+    );
     let diag = &result.diagnostics[0];
     assert!(diag.file.ends_with("a.md"));
     assert!(diag.message.contains("b.md"), "{}", diag.message);
@@ -138,6 +146,215 @@ fn 実在しないアセットはmissing_assetと類似ファイルの提案に
     );
 }
 
+#[test]
+fn 本文の演出コメントのアセットもmissing_assetになる() {
+    let result = check("missing_directive_asset/scene.md");
+    assert_eq!(rule_ids(&result), vec!["missing-asset"]);
+    let diag = &result.diagnostics[0];
+    assert_eq!(diag.span.as_ref().map(|s| s.line), Some(9));
+    assert_eq!(
+        diag.suggestion.as_deref(),
+        Some("<!-- se: assets/se/door.ogg -->")
+    );
+}
+
+#[test]
+fn アセットの中身が参照キーや拡張子と食い違えば報告する() {
+    let result = check("asset_content/scene.md");
+    assert_eq!(
+        rule_ids(&result),
+        vec![
+            "asset-format-mismatch",
+            "asset-kind-mismatch",
+            "empty-asset",
+            "unsupported-asset-format",
+            "asset-kind-mismatch",
+        ]
+    );
+    let lines: Vec<_> = result
+        .diagnostics
+        .iter()
+        .map(|d| d.span.as_ref().map(|s| s.line))
+        .collect();
+    assert_eq!(lines, vec![Some(3), Some(4), Some(11), Some(17), Some(4)]);
+    // 拡張子の食い違いは、中身に合わせた拡張子での参照を提案する
+    assert_eq!(
+        result.diagnostics[0].suggestion.as_deref(),
+        Some("background: assets/bg/gate.jpg")
+    );
+    assert_eq!(result.diagnostics[1].severity, Severity::Error);
+    assert!(result.diagnostics[1].message.contains("音声"));
+    // 立ち絵は characters.yaml の行に報告する
+    assert!(result.diagnostics[4].file.ends_with("characters.yaml"));
+}
+
+#[test]
+fn キャンバスサイズを指定するとbackgroundの画像サイズを照合する() {
+    let options = CheckOptions {
+        canvas: Some((1280, 720)),
+        ..CheckOptions::default()
+    };
+    let result = check_path(&fixture("asset_content/scene.md"), &options);
+    let canvas: Vec<_> = result
+        .diagnostics
+        .iter()
+        .filter(|d| d.rule_id == "canvas-size-mismatch")
+        .collect();
+    // 1280x720 の classroom.png は一致するので、800x600 の gate.png だけ
+    assert_eq!(canvas.len(), 1);
+    assert_eq!(canvas[0].span.as_ref().map(|s| s.line), Some(3));
+    assert!(
+        canvas[0].message.contains("800x600"),
+        "{}",
+        canvas[0].message
+    );
+}
+
+#[test]
+fn no_assetsでは中身の検査も省略する() {
+    let options = CheckOptions {
+        check_assets: false,
+        canvas: Some((1280, 720)),
+        ..CheckOptions::default()
+    };
+    let result = check_path(&fixture("asset_content/scene.md"), &options);
+    assert_eq!(result.diagnostics, vec![]);
+}
+
+#[test]
+fn 大文字小文字の違いと移植性の低いパスを報告する() {
+    let result = check("portable_path/scene.md");
+    assert_eq!(
+        rule_ids(&result),
+        vec![
+            "path-case-mismatch",
+            "non-portable-path",
+            "non-portable-path",
+            "path-case-mismatch",
+        ]
+    );
+    let suggestions: Vec<_> = result
+        .diagnostics
+        .iter()
+        .map(|d| d.suggestion.as_deref())
+        .collect();
+    assert_eq!(
+        suggestions,
+        vec![
+            Some("background: assets/bg/school_gate.png"),
+            // ASCII 以外の名前は機械的に直せないので提案しない
+            None,
+            Some("<!-- se: assets/se/door.ogg -->"),
+            Some("[次へ](next.md#教室)"),
+        ]
+    );
+    assert!(result.diagnostics[1].message.contains("春.ogg"));
+    // 実際の綴りで辿れるので、missing-asset や broken-link にはならず
+    // リンク先のファイルも検査対象に入る
+    assert_eq!(result.files.len(), 2);
+}
+
+#[test]
+fn display_namesの未宣言の話者はundefined_characterになる() {
+    let result = check("undefined_display_name/scene.md");
+    assert_eq!(rule_ids(&result), vec!["undefined-character"]);
+    let diag = &result.diagnostics[0];
+    assert_eq!(diag.span.as_ref().map(|s| s.line), Some(3));
+    assert!(diag.message.contains("幼なじみ"), "{}", diag.message);
+}
+
+#[test]
+fn 未宣言の表情はundefined_expressionになる() {
+    let result = check("undefined_expression/scene.md");
+    let expressions: Vec<_> = result
+        .diagnostics
+        .iter()
+        .filter(|d| d.rule_id == "undefined-expression")
+        .collect();
+    assert_eq!(expressions.len(), 3);
+    assert!(expressions.iter().all(|d| d.severity == Severity::Error));
+    // 似た表情がなければ宣言済みの一覧を示す
+    assert_eq!(expressions[0].span.as_ref().map(|s| s.line), Some(9));
+    assert!(expressions[0].message.contains("怒り、笑顔"));
+    // show の表情の書き間違いは宣言済みの表情を提案する
+    assert_eq!(
+        expressions[1].suggestion.as_deref(),
+        Some("<!-- show: 幼なじみ(怒り) -->")
+    );
+    // 表情を 1 つも宣言していない話者には expressions: の書き方を示す
+    assert!(
+        expressions[2]
+            .suggestion
+            .as_deref()
+            .unwrap()
+            .contains("expressions:")
+    );
+}
+
+#[test]
+fn 実在しない立ち絵はcharacters_yamlの行でmissing_assetになる() {
+    let result = check("undefined_expression/scene.md");
+    let diag = result
+        .diagnostics
+        .iter()
+        .find(|d| d.rule_id == "missing-asset")
+        .expect("立ち絵の missing-asset がある");
+    assert!(diag.file.ends_with("characters.yaml"));
+    assert_eq!(diag.span.as_ref().map(|s| s.line), Some(6));
+    assert_eq!(diag.suggestion.as_deref(), Some("怒り: sprites/angry.png"));
+}
+
+#[test]
+fn ディレクトリ検査では使われていないキャラクターとアセットを報告する() {
+    let result = check("unused");
+    assert_eq!(
+        rule_ids(&result),
+        vec!["unused-asset", "unused-asset", "unused-character"]
+    );
+    assert!(!result.has_errors(), "どれも warning");
+    assert!(
+        result.diagnostics[0]
+            .file
+            .ends_with("assets/bg/old_gate.png")
+    );
+    // 参照が 1 つもない assets/se/ も assets/ の配下として見る
+    assert!(result.diagnostics[1].file.ends_with("assets/se/chime.ogg"));
+    let character = &result.diagnostics[2];
+    assert!(character.file.ends_with("characters.yaml"));
+    assert_eq!(character.span.as_ref().map(|s| s.line), Some(5));
+    assert!(character.message.contains("先生"));
+}
+
+#[test]
+fn シーンと同じディレクトリのアセットでは素材以外のファイルを報告しない() {
+    // scene.md・characters.yaml・endings.yaml・フラグメントと画像が同じ階層にある
+    let result = check("unused_flat");
+    assert_eq!(rule_ids(&result), vec!["unused-asset"]);
+    assert!(
+        result.diagnostics[0]
+            .file
+            .ends_with("unused_flat/old_gate.png")
+    );
+}
+
+#[test]
+fn ファイル検査では使われていない宣言とアセットを報告しない() {
+    let result = check("unused/scenario/scene.md");
+    assert_eq!(result.diagnostics, vec![]);
+}
+
+#[test]
+fn no_assetsではunused_assetを報告しない() {
+    let result = check_path(
+        &fixture("unused"),
+        &CheckOptions {
+            check_assets: false,
+            ..CheckOptions::default()
+        },
+    );
+    assert_eq!(rule_ids(&result), vec!["unused-character"]);
+}
+
 #[test]
 fn no_assetsオプションでアセット検査を省略できる() {
     let result = check_path(
@@ -184,6 +401,170 @@ fn 壊れたcharacters_yamlはinvalid_characters_fileになる() {
     assert!(diag.file.ends_with("characters.yaml"), "{:?}", diag.file);
 }
 
+#[test]
+fn endings_yamlに宣言のないending_idはundefined_endingになる() {
+    let result = check("endings/scenario/scene.md");
+    // unused-ending はディレクトリを検査したときだけ
+    assert_eq!(rule_ids(&result), vec!["undefined-ending"]);
+    let diag = &result.diagnostics[0];
+    assert_eq!(diag.severity, Severity::Warning);
+    assert_eq!(diag.span.as_ref().map(|s| s.line), Some(23));
+    // 「god_end」→ 宣言済みの「good_end」を提案する
+    assert!(diag.message.contains("good_end"), "{}", diag.message);
+    assert_eq!(
+        diag.suggestion.as_deref(),
+        Some("<!-- ending: good_end -->")
+    );
+}
+
+#[test]
+fn ディレクトリ検査では使われていないエンディングを報告する() {
+    let result = check("endings");
+    assert_eq!(rule_ids(&result), vec!["undefined-ending", "unused-ending"]);
+    let unused = &result.diagnostics[1];
+    assert!(unused.file.ends_with("endings.yaml"));
+    assert_eq!(unused.span.as_ref().map(|s| s.line), Some(10));
+    assert!(unused.message.contains("secret_end"));
+}
+
+#[test]
+fn 壊れたendings_yamlはinvalid_endings_fileになる() {
+    let result = check("invalid_endings/scene.md");
+    // undefined-ending は報告しない（SPEC 2.3）
+    assert_eq!(rule_ids(&result), vec!["invalid-endings-file"]);
+    let diag = &result.diagnostics[0];
+    assert_eq!(diag.severity, Severity::Error);
+    assert!(diag.file.ends_with("endings.yaml"), "{:?}", diag.file);
+    assert!(diag.message.contains("type"), "{}", diag.message);
+}
+
+#[test]
+fn 実績の条件が存在しない参照を含むとbroken_achievement_refになる() {
+    let result = check("achievements/scene.md");
+    assert_eq!(
+        rule_ids(&result),
+        vec!["broken-achievement-ref", "broken-achievement-ref"]
+    );
+    let ending = &result.diagnostics[0];
+    assert_eq!(ending.severity, Severity::Error);
+    assert!(
+        ending.file.ends_with("achievements.yaml"),
+        "{:?}",
+        ending.file
+    );
+    assert_eq!(ending.span.as_ref().map(|s| s.line), Some(2));
+    assert!(ending.message.contains("god_end"), "{}", ending.message);
+    let section = &result.diagnostics[1];
+    assert_eq!(section.span.as_ref().map(|s| s.line), Some(6));
+    assert!(section.message.contains("#nowhere"), "{}", section.message);
+}
+
+#[test]
+fn whenとendingsを両方書いた実績はinvalid_achievements_fileになる() {
+    let result = check("invalid_achievements/scene.md");
+    assert_eq!(rule_ids(&result), vec!["invalid-achievements-file"]);
+    let diag = &result.diagnostics[0];
+    assert_eq!(diag.severity, Severity::Error);
+    assert!(diag.message.contains("「good」"), "{}", diag.message);
+}
+
+#[test]
+fn 章のシーンが見つからない_辿れないとbroken_chapter_refとunreachable_chapter_sceneになる() {
+    // 章のシーンはリンクされていなくても読み込まれる
+    let result = check("chapters/scene.md");
+    assert_eq!(result.files.len(), 2);
+    assert_eq!(
+        rule_ids(&result),
+        vec!["unreachable-chapter-scene", "broken-chapter-ref"]
+    );
+    let orphan = &result.diagnostics[0];
+    assert_eq!(orphan.severity, Severity::Warning);
+    assert!(orphan.file.ends_with("chapters.yaml"), "{:?}", orphan.file);
+    assert_eq!(orphan.span.as_ref().map(|s| s.line), Some(5));
+    assert!(orphan.message.contains("orphan.md"), "{}", orphan.message);
+    let missing = &result.diagnostics[1];
+    assert_eq!(missing.severity, Severity::Error);
+    assert_eq!(missing.span.as_ref().map(|s| s.line), Some(6));
+    assert!(
+        missing.message.contains("missing.md"),
+        "{}",
+        missing.message
+    );
+}
+
+#[test]
+fn 同じシーンを2つの章に入れるとinvalid_chapters_fileになる() {
+    let result = check("invalid_chapters");
+    assert_eq!(rule_ids(&result), vec!["invalid-chapters-file"]);
+    assert!(
+        result.diagnostics[0].message.contains("「one」と「two」"),
+        "{}",
+        result.diagnostics[0].message
+    );
+}
+
+#[test]
+fn 取り込んだフラグメントへの指摘はフラグメントの行を指す() {
+    let result = check("include/scene.md");
+    assert_eq!(rule_ids(&result), vec!["undefined-character"]);
+    let diag = &result.diagnostics[0];
+    assert!(diag.file.ends_with("_morning.md"), "{:?}", diag.file);
+    assert_eq!(diag.span.as_ref().map(|s| s.line), Some(5));
+    assert!(diag.message.contains("父"), "{}", diag.message);
+    // フラグメントも検査したファイルに数える（書かれた順）
+    let names: Vec<String> = result
+        .files
+        .iter()
+        .map(|f| f.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(
+        names,
+        vec!["scene.md", "_morning.md", "_epilogue.md", "_credits.md"]
+    );
+}
+
+#[test]
+fn ディレクトリ検査ではフラグメントをシーンとして扱わない() {
+    let result = check("include");
+    // 同じフラグメントを 2 か所で取り込んでも指摘は 1 つ、missing-scene-id も出ない
+    assert_eq!(rule_ids(&result), vec!["undefined-character"]);
+}
+
+#[test]
+fn 取り込みの循環はcircular_includeになる() {
+    let result = check("include_cycle/scene.md");
+    assert_eq!(rule_ids(&result), vec!["circular-include"]);
+    let diag = &result.diagnostics[0];
+    assert_eq!(diag.severity, Severity::Error);
+    assert!(diag.file.ends_with("_b.md"), "{:?}", diag.file);
+    assert_eq!(diag.span.as_ref().map(|s| s.line), Some(3));
+    assert!(
+        diag.message.contains("_a.md → _b.md → _a.md"),
+        "{}",
+        diag.message
+    );
+}
+
+#[test]
+fn 取り込めないファイルはbroken_includeとinvalid_fragmentになる() {
+    let result = check("include_broken/scene.md");
+    assert_eq!(
+        rule_ids(&result),
+        vec!["broken-include", "invalid-fragment", "invalid-fragment"]
+    );
+    assert_eq!(result.diagnostics[0].span.as_ref().map(|s| s.line), Some(7));
+    // `_` で始まらない名前は取り込む側の directive に、見出しはフラグメント側に
+    let name = &result.diagnostics[1];
+    assert!(name.file.ends_with("scene.md"), "{:?}", name.file);
+    assert_eq!(
+        name.suggestion.as_deref(),
+        Some("<!-- include: _common.md -->")
+    );
+    let heading = &result.diagnostics[2];
+    assert!(heading.file.ends_with("common.md"), "{:?}", heading.file);
+    assert_eq!(heading.span.as_ref().map(|s| s.line), Some(1));
+}
+
 #[test]
 fn 終端のないセクションはimplicit_fallthroughになる() {
     let result = check("fallthrough/scene.md");
@@ -203,6 +584,39 @@ fn どこからも到達しないセクションはunreachable_sectionになる(
     assert!(diag.message.contains("orphan"), "{}", diag.message);
 }
 
+#[test]
+fn 呼び出しを経ずに到達するreturnはstray_returnになる() {
+    let result = check("stray_return/scene.md");
+    assert_eq!(rule_ids(&result), vec!["stray-return"]);
+    let diag = &result.diagnostics[0];
+    assert_eq!(diag.severity, Severity::Warning);
+    assert_eq!(diag.span.as_ref().map(|s| s.line), Some(17));
+
+    // ディレクトリでも、call でだけ入る幕間の return は報告しない
+    let result = check("stray_return");
+    let stray: Vec<&Diagnostic> = result
+        .diagnostics
+        .iter()
+        .filter(|d| d.rule_id == "stray-return")
+        .collect();
+    assert_eq!(stray.len(), 1);
+    assert!(stray[0].file.ends_with("scene.md"));
+}
+
+#[test]
+fn 戻りも終わりもせず同じcallに着く呼び出しはrecursive_callになる() {
+    let result = check("recursive_call/scene.md");
+    assert_eq!(rule_ids(&result), vec!["recursive-call", "recursive-call"]);
+    let lines: Vec<Option<usize>> = result
+        .diagnostics
+        .iter()
+        .map(|d| d.span.as_ref().map(|s| s.line))
+        .collect();
+    // 選択肢で return に進める #think の再帰は報告しない
+    assert_eq!(lines, [Some(34), Some(42)]);
+    assert!(result.diagnostics[0].message.contains("#pong"));
+}
+
 #[test]
 fn 選択肢の項目数が既定の目安を超えるとtoo_many_choicesになる() {
     let result = check("too_many_choices/scene.md");
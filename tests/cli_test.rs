@@ -113,6 +113,23 @@ fn trace_範囲外の選択番号はexit1になる() {
     assert!(stdout(&out).contains("選択番号 99"));
 }
 
+#[test]
+fn trace_seedとrandomでランダム分岐の経路を再現できる() {
+    let scene = "tests/fixtures/routes/random/scenario.md";
+    let first = run(&["trace", scene, "--seed", "42"]);
+    let second = run(&["trace", scene, "--seed", "42"]);
+    assert!(first.status.success());
+    assert_eq!(stdout(&first), stdout(&second));
+    assert!(stdout(&first).contains("ランダム分岐のシード: --seed 42"));
+
+    let forced = run(&["trace", scene, "--random", "2"]);
+    assert!(forced.status.success());
+    assert!(stdout(&forced).contains("kouhai_end"));
+
+    let bad = run(&["trace", scene, "--seed", "abc"]);
+    assert!(!bad.status.success());
+}
+
 // --------------------------------------------------------------------- routes
 
 #[test]
@@ -135,6 +152,21 @@ fn routes_jsonは有効なjsonを返す() {
     assert_eq!(json["status"], "ok");
 }
 
+#[test]
+fn routesのentryは繰り返し指定できる() {
+    let out = run(&[
+        "routes",
+        "tests/fixtures/routes/entries/main.md",
+        "--entry",
+        "tests/fixtures/routes/entries/dlc.md",
+        "--entry",
+        "tests/fixtures/routes/entries/bonus.md",
+    ]);
+    assert!(out.status.success(), "stdout: {}", stdout(&out));
+    assert!(stdout(&out).contains("発見した経路数: 3"));
+    assert!(!stdout(&out).contains("unreachable-scene"));
+}
+
 #[test]
 fn routesで循環があるとexit1になる() {
     let out = run(&["routes", "tests/fixtures/trace/loop/scenario.md"]);
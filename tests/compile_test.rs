@@ -12,12 +12,20 @@
 
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use tsumugai::scenario::{BundleAsset, BundleStep, CompileOptions, StepTarget, compile_path};
+use tsumugai::scenario::{
+    BundleAssetKind, BundleStep, CompileOptions, ExportOptions, ExportTarget, StepTarget,
+    StoryBundle, compile_path, copy_assets, export_path, import_twee_str, render_html,
+    split_bundle, write_split_bundle,
+};
 
 fn spring() -> &'static Path {
     Path::new("examples/spring/scenario/spring_001.md")
 }
 
+fn has_asset(bundle: &StoryBundle, kind: BundleAssetKind) -> bool {
+    bundle.assets.iter().any(|a| a.kind == kind)
+}
+
 // -------------------------------------------------------------- 正常系
 
 #[test]
@@ -146,21 +154,473 @@ fn 同一ファイル内の選択肢は自身のscene_idとstep_indexに解決
     assert_eq!(leave_first.target.step_index, 0);
 }
 
+#[test]
+fn stepのsourceには列範囲とセクションのアンカーが載る() {
+    let result = compile_path(spring(), &CompileOptions::default());
+    let bundle = result.bundle.as_ref().unwrap();
+    let spring_001 = bundle.scenes.iter().find(|s| s.id == "spring_001").unwrap();
+
+    // リード部のナレーション: 1 行全体、アンカーなし
+    let BundleStep::Narration { source, .. } = &spring_001.steps[0] else {
+        panic!("先頭はナレーション");
+    };
+    let source = source.as_ref().unwrap();
+    assert_eq!(source.line, 9);
+    assert_eq!(source.column, Some(1));
+    assert_eq!(source.end_line, Some(9));
+    assert_eq!(source.end_column, Some(26));
+    assert_eq!(source.anchor, None);
+
+    // 選択肢: ブロックは 19〜21 行、各項目は自分の行
+    let (items, source) = spring_001
+        .steps
+        .iter()
+        .find_map(|s| match s {
+            BundleStep::Choice { items, source } => Some((items, source.as_ref().unwrap())),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!((source.line, source.end_line), (19, Some(21)));
+    assert_eq!(source.anchor.as_deref(), Some("選択肢"));
+    let lines: Vec<usize> = items
+        .iter()
+        .map(|i| i.source.as_ref().unwrap().line)
+        .collect();
+    assert_eq!(lines, [19, 20, 21]);
+}
+
+#[test]
+fn strip_sourceではbundleからsourceを省きソースマップに残す() {
+    let options = CompileOptions {
+        strip_source: true,
+        ..CompileOptions::default()
+    };
+    let result = compile_path(spring(), &options);
+    let bundle = result.bundle.as_ref().unwrap();
+    let json = serde_json::to_string(bundle).unwrap();
+    assert!(!json.contains("\"source\""), "source が残っている: {json}");
+
+    // ソースマップはシーンごとに steps と同じ並び
+    let map = result.source_map.as_ref().unwrap();
+    assert_eq!(map.story_build_id, bundle.story_build_id);
+    assert_eq!(map.scenes.len(), bundle.scenes.len());
+    for scene in &bundle.scenes {
+        assert_eq!(map.scenes[&scene.id].len(), scene.steps.len());
+    }
+    let spring_001 = &map.scenes["spring_001"];
+    assert_eq!(spring_001[0].location.line, 9);
+    assert_eq!(spring_001[0].heading, None);
+    let choice = spring_001.iter().find(|s| !s.items.is_empty()).unwrap();
+    assert_eq!(choice.heading.as_deref(), Some("選択肢"));
+    assert_eq!(choice.location.anchor.as_deref(), Some("選択肢"));
+    assert_eq!(choice.items.len(), 3);
+
+    // 位置を省いてもステップの並びは変わらない
+    let full = compile_path(spring(), &CompileOptions::default());
+    let full = full.bundle.as_ref().unwrap();
+    for (a, b) in full.scenes.iter().zip(&bundle.scenes) {
+        assert_eq!(a.steps.len(), b.steps.len());
+    }
+}
+
 #[test]
 fn assetはファイル横断で重複排除して収集される() {
     let result = compile_path(spring(), &CompileOptions::default());
     let bundle = result.bundle.as_ref().unwrap();
 
     assert_eq!(bundle.assets.len(), 3);
-    assert!(bundle.assets.contains(&BundleAsset::Background {
-        path: "../assets/bg/school_gate.png".to_string()
-    }));
-    assert!(bundle.assets.contains(&BundleAsset::Background {
-        path: "../assets/bg/classroom.png".to_string()
-    }));
-    assert!(bundle.assets.contains(&BundleAsset::Bgm {
-        path: "../assets/bgm/spring.ogg".to_string()
-    }));
+    assert!(has_asset(
+        bundle,
+        BundleAssetKind::Background {
+            path: "../assets/bg/school_gate.png".to_string()
+        }
+    ));
+    assert!(has_asset(
+        bundle,
+        BundleAssetKind::Background {
+            path: "../assets/bg/classroom.png".to_string()
+        }
+    ));
+    assert!(has_asset(
+        bundle,
+        BundleAssetKind::Bgm {
+            path: "../assets/bgm/spring.ogg".to_string()
+        }
+    ));
+}
+
+#[test]
+fn assetにはルートからの相対パスとサイズとハッシュが載る() {
+    let result = compile_path(spring(), &CompileOptions::default());
+    let bundle = result.bundle.as_ref().unwrap();
+    let bgm = bundle
+        .assets
+        .iter()
+        .find(|a| matches!(a.kind, BundleAssetKind::Bgm { .. }))
+        .unwrap();
+    // ルートはシーン（scenario/）とアセット（assets/）の共通の祖先 examples/spring
+    assert_eq!(bgm.file.as_deref(), Some("assets/bgm/spring.ogg"));
+    let bytes = std::fs::read("examples/spring/assets/bgm/spring.ogg").unwrap();
+    assert_eq!(bgm.size, Some(bytes.len() as u64));
+    assert_eq!(bgm.hash.as_ref().map(String::len), Some(16));
+}
+
+#[test]
+fn アセットだけを差し替えてもstory_build_idが変わる() {
+    let dir = unique_output_path("rebuild").with_extension("");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("assets/bg")).unwrap();
+    std::fs::write(
+        dir.join("scene.md"),
+        "---\nid: rebuild\nbackground: assets/bg/gate.png\n---\n\n# 校門\n\n<!-- ending: gate -->\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("characters.yaml"), "characters: {}\n").unwrap();
+    let gate = dir.join("assets/bg/gate.png");
+    let options = CompileOptions {
+        check_assets: false,
+        ..CompileOptions::default()
+    };
+    let build_id = || {
+        let result = compile_path(&dir.join("scene.md"), &options);
+        let bundle = result.bundle.expect("check を通れば bundle がある");
+        (bundle.story_build_id, bundle.assets[0].hash.clone())
+    };
+
+    std::fs::write(&gate, b"first").unwrap();
+    let (first_id, first_hash) = build_id();
+    std::fs::write(&gate, b"second").unwrap();
+    let (second_id, second_hash) = build_id();
+    assert_ne!(first_hash, second_hash);
+    assert_ne!(first_id, second_id);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn copy_assetsはfileの配置でアセットを書き出す() {
+    let result = compile_path(
+        Path::new("tests/fixtures/trace/sprites/scenario.md"),
+        &CompileOptions::default(),
+    );
+    let bundle = result.bundle.as_ref().unwrap();
+    let out = unique_output_path("assets-out").with_extension("");
+    let _ = std::fs::remove_dir_all(&out);
+
+    let copied = copy_assets(bundle, &out).expect("書き出せる");
+    assert_eq!(copied, 2);
+    for asset in &bundle.assets {
+        let file = asset.file.as_deref().unwrap();
+        let written = std::fs::read(out.join(file)).expect("file の位置にコピーされる");
+        assert_eq!(Some(written.len() as u64), asset.size);
+    }
+    assert!(out.join("sprites/smile.png").is_file());
+
+    let _ = std::fs::remove_dir_all(&out);
+}
+
+#[test]
+fn cliのassets_outがプロジェクトの根元でも素材を壊さない() {
+    // examples/spring を一時ディレクトリに写し、その根元を --assets-out にする
+    let dir = unique_output_path("assets-out-root").with_extension("");
+    let _ = std::fs::remove_dir_all(&dir);
+    for file in [
+        "characters.yaml",
+        "endings.yaml",
+        "achievements.yaml",
+        "scenario/spring_001.md",
+        "scenario/spring_002.md",
+        "assets/bg/school_gate.png",
+        "assets/bg/classroom.png",
+        "assets/bgm/spring.ogg",
+    ] {
+        let dest = dir.join(file);
+        std::fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        std::fs::copy(Path::new("examples/spring").join(file), dest).unwrap();
+    }
+
+    let result = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+        .args([
+            "compile",
+            "scenario/spring_001.md",
+            "--target",
+            "web",
+            "--output",
+            "out.json",
+            "--assets-out",
+            ".",
+        ])
+        .current_dir(&dir)
+        .output()
+        .expect("tsumugai バイナリを起動できる");
+    assert!(
+        result.status.success(),
+        "stdout: {}",
+        String::from_utf8_lossy(&result.stdout)
+    );
+    for file in [
+        "assets/bg/school_gate.png",
+        "assets/bg/classroom.png",
+        "assets/bgm/spring.ogg",
+    ] {
+        assert_eq!(
+            std::fs::read(dir.join(file)).unwrap(),
+            std::fs::read(Path::new("examples/spring").join(file)).unwrap(),
+            "{file} が壊れている"
+        );
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn 演出コメントはstepとassetの両方に出力される() {
+    let result = compile_path(
+        Path::new("tests/fixtures/trace/staging/scenario.md"),
+        &CompileOptions::default(),
+    );
+    let bundle = result
+        .bundle
+        .as_ref()
+        .expect("check を通れば bundle がある");
+    let steps = &bundle.scenes[0].steps;
+    assert!(
+        matches!(&steps[1], BundleStep::Background { path, .. } if path == "assets/bg/classroom.png")
+    );
+    assert!(matches!(&steps[2], BundleStep::Bgm { path, .. } if path == "assets/bgm/rain.ogg"));
+    assert!(matches!(&steps[3], BundleStep::Se { path, .. } if path == "assets/se/door.ogg"));
+    assert!(matches!(&steps[4], BundleStep::BgmStop { .. }));
+    assert!(has_asset(
+        bundle,
+        BundleAssetKind::Se {
+            path: "assets/se/door.ogg".to_string()
+        }
+    ));
+    assert_eq!(bundle.assets.len(), 3);
+}
+
+#[test]
+fn 立ち絵は表情名つきのstepとsprite_assetに出力される() {
+    let result = compile_path(
+        Path::new("tests/fixtures/trace/sprites/scenario.md"),
+        &CompileOptions::default(),
+    );
+    let bundle = result
+        .bundle
+        .as_ref()
+        .expect("check を通れば bundle がある");
+    let steps = &bundle.scenes[0].steps;
+    assert!(matches!(
+        &steps[0],
+        BundleStep::Show { character, expression: Some(e), .. } if character == "幼なじみ" && e == "笑顔"
+    ));
+    assert!(matches!(
+        &steps[1],
+        BundleStep::Dialogue { expression: Some(e), .. } if e == "怒り"
+    ));
+    assert!(matches!(
+        &steps[2],
+        BundleStep::Dialogue {
+            expression: None,
+            ..
+        }
+    ));
+    assert!(matches!(&steps[3], BundleStep::Hide { character, .. } if character == "幼なじみ"));
+    // 登場するキャラクターの表情はすべて載る（characters.yaml からの相対パス）
+    assert!(has_asset(
+        bundle,
+        BundleAssetKind::Sprite {
+            character: "幼なじみ".to_string(),
+            expression: "笑顔".to_string(),
+            path: "sprites/smile.png".to_string(),
+        }
+    ));
+    assert!(has_asset(
+        bundle,
+        BundleAssetKind::Sprite {
+            character: "幼なじみ".to_string(),
+            expression: "怒り".to_string(),
+            path: "sprites/angry.png".to_string(),
+        }
+    ));
+    assert_eq!(bundle.assets.len(), 2);
+}
+
+#[test]
+fn charactersには登場するキャラクターだけが載る() {
+    let result = compile_path(
+        Path::new("tests/fixtures/trace/sprites/scenario.md"),
+        &CompileOptions::default(),
+    );
+    let bundle = result.bundle.as_ref().unwrap();
+    let names: Vec<&str> = bundle.characters.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["主人公", "幼なじみ"]);
+    let osananajimi = &bundle.characters[1];
+    assert_eq!(osananajimi.display_name, "幼なじみ");
+    assert_eq!(osananajimi.color.as_deref(), Some("#ff9999"));
+    // expressions は assets の sprite で渡すため metadata には含めない
+    assert!(osananajimi.metadata.is_empty());
+}
+
+#[test]
+fn all_charactersで宣言済みの全員とメタデータが載る() {
+    let options = CompileOptions {
+        all_characters: true,
+        ..CompileOptions::default()
+    };
+    let result = compile_path(
+        Path::new("tests/fixtures/trace/sprites/scenario.md"),
+        &options,
+    );
+    let bundle = result.bundle.as_ref().unwrap();
+    let teacher = bundle
+        .characters
+        .iter()
+        .find(|c| c.name == "先生")
+        .expect("登場しない先生も載る");
+    assert_eq!(teacher.display_name, "佐藤先生");
+    assert_eq!(teacher.color.as_deref(), Some("#336699"));
+    assert_eq!(
+        teacher.metadata.get("voice"),
+        Some(&serde_json::json!("sato"))
+    );
+
+    // characters の違いは story_build_id にも反映される
+    let default = compile_path(
+        Path::new("tests/fixtures/trace/sprites/scenario.md"),
+        &CompileOptions::default(),
+    );
+    assert_ne!(
+        bundle.story_build_id,
+        default.bundle.as_ref().unwrap().story_build_id
+    );
+}
+
+#[test]
+fn 別名の話者は話者名と表示名の両方でstepに出力される() {
+    let result = compile_path(
+        Path::new("tests/fixtures/trace/aliases/scenario.md"),
+        &CompileOptions::default(),
+    );
+    let bundle = result.bundle.as_ref().unwrap();
+    let json = serde_json::to_value(&bundle.scenes[0].steps[0]).unwrap();
+    assert_eq!(json["speaker"], "葵");
+    assert_eq!(json["displayName"], "???");
+    let aoi = bundle.characters.iter().find(|c| c.name == "葵").unwrap();
+    assert_eq!(aoi.display_name, "春日葵");
+    assert_eq!(aoi.aliases, vec!["あおい".to_string()]);
+}
+
+#[test]
+fn endingsには宣言と未宣言のエンディングが解放順に載る() {
+    let result = compile_path(
+        Path::new("tests/fixtures/check/endings/scenario/scene.md"),
+        &CompileOptions::default(),
+    );
+    let bundle = result.bundle.as_ref().unwrap();
+    let ids: Vec<&str> = bundle.endings.iter().map(|e| e.id.as_str()).collect();
+    // order の順、order のないものは id 順で最後
+    assert_eq!(ids, vec!["good_end", "bad_end", "god_end", "secret_end"]);
+    let json = serde_json::to_value(&bundle.endings).unwrap();
+    assert_eq!(
+        json[0],
+        serde_json::json!({
+            "id": "good_end",
+            "title": "晴れの日",
+            "type": "good",
+            "order": 1,
+            "declared": true
+        })
+    );
+    // 未宣言の ending は id をタイトルにして載せる
+    assert_eq!(
+        json[2],
+        serde_json::json!({ "id": "god_end", "title": "god_end", "declared": false })
+    );
+    assert_eq!(json[3]["type"], "true");
+}
+
+#[test]
+fn achievementsには参照を解決した実績が載る() {
+    let result = compile_path(spring(), &CompileOptions::default());
+    let bundle = result.bundle.as_ref().unwrap();
+    let json = serde_json::to_value(&bundle.achievements).unwrap();
+    let ids: Vec<&str> = bundle.achievements.iter().map(|a| a.id.as_str()).collect();
+    assert_eq!(ids, vec!["after_school", "all_endings", "morning_run"]);
+    // セクション条件はリンクと同じ step の位置に解決する
+    assert_eq!(
+        json[0]["conditions"][0],
+        serde_json::json!({
+            "kind": "section",
+            "target": { "sceneId": "spring_002", "stepIndex": 8 }
+        })
+    );
+    assert!(json[1]["conditions"].is_null());
+    assert_eq!(
+        json[1]["endings"],
+        serde_json::json!(["childhood_route", "sprint_route", "calm_route"])
+    );
+    assert_eq!(
+        json[2]["conditions"][0],
+        serde_json::json!({ "kind": "choice", "label": "一緒に走る", "sceneId": "spring_001" })
+    );
+}
+
+#[test]
+fn chaptersには章の入口とシーンが章の順に載る() {
+    let result = compile_path(
+        Path::new("tests/fixtures/routes/chapters/one.md"),
+        &CompileOptions::default(),
+    );
+    assert!(!result.has_errors(), "{:?}", result.check.diagnostics);
+    let bundle = result.bundle.as_ref().unwrap();
+    // リンクされていない章のシーンも bundle に入る
+    assert!(bundle.scenes.iter().any(|s| s.id == "epilogue"));
+    let json = serde_json::to_value(&bundle.chapters).unwrap();
+    assert_eq!(
+        json[0],
+        serde_json::json!({
+            "id": "one",
+            "title": "第1章",
+            "entry": { "sceneId": "one", "stepIndex": 0 },
+            "sceneIds": ["one"]
+        })
+    );
+    // title がなければ id と同じ
+    assert_eq!(json[2]["title"], "epilogue");
+    assert_eq!(split_bundle(bundle).index.chapters, bundle.chapters);
+
+    let spring = compile_path(spring(), &CompileOptions::default());
+    assert!(spring.bundle.unwrap().chapters.is_empty());
+}
+
+#[test]
+fn entryで足した開始シーンがentry_scene_idsに載る() {
+    let dir = Path::new("tests/fixtures/routes/entries");
+    let options = CompileOptions {
+        entries: vec![dir.join("dlc.md"), dir.join("main.md")],
+        ..CompileOptions::default()
+    };
+    let result = compile_path(&dir.join("main.md"), &options);
+    assert!(!result.has_errors(), "{:?}", result.check.diagnostics);
+    // DLC の入口から到達できるので unreachable-scene にならない
+    assert!(
+        result
+            .check
+            .diagnostics
+            .iter()
+            .all(|d| d.rule_id != "unreachable-scene"),
+        "{:?}",
+        result.check.diagnostics
+    );
+    let bundle = result.bundle.as_ref().unwrap();
+    assert_eq!(bundle.entry_scene_id, "main");
+    // 重複した開始シーンは 1 つにまとめる
+    assert_eq!(bundle.entry_scene_ids, vec!["main", "dlc"]);
+    assert!(bundle.scenes.iter().any(|s| s.id == "dlc"));
+    assert_eq!(
+        split_bundle(bundle).index.entry_scene_ids,
+        bundle.entry_scene_ids
+    );
 }
 
 #[test]
@@ -173,6 +633,24 @@ fn 同じ入力からは同じstory_build_idが生成される() {
     );
 }
 
+#[test]
+fn 取り込んだフラグメントのステップはソースマップでフラグメントを指す() {
+    let result = compile_path(
+        Path::new("tests/fixtures/check/include/scene.md"),
+        &CompileOptions::default(),
+    );
+    let map = result.source_map.as_ref().unwrap();
+    let steps = &map.scenes["include"];
+    assert!(steps[0].location.file.ends_with("_morning.md"));
+    assert_eq!(steps[0].location.line, 1);
+    assert_eq!(steps[0].location.end_line, Some(1));
+    let own = steps.iter().find(|s| s.location.file.ends_with("scene.md"));
+    assert_eq!(own.map(|s| s.location.line), Some(9));
+    // ソースマップと steps の並びは揃う
+    let bundle = result.bundle.as_ref().unwrap();
+    assert_eq!(bundle.scenes[0].steps.len(), steps.len());
+}
+
 // -------------------------------------------------------------- 異常系
 
 #[test]
@@ -339,6 +817,7 @@ fn walk_to_ending(bundle: &tsumugai::scenario::StoryBundle, choose: &[&str]) ->
     let mut scene_id = bundle.entry_scene_id.clone();
     let mut step_index = 0usize;
     let mut choose = choose.iter();
+    let mut calls: Vec<(String, usize)> = Vec::new();
     loop {
         let scene = bundle
             .scenes
@@ -346,12 +825,27 @@ fn walk_to_ending(bundle: &tsumugai::scenario::StoryBundle, choose: &[&str]) ->
             .find(|s| s.id == scene_id)
             .unwrap_or_else(|| panic!("scene {scene_id} が bundle にある"));
         match &scene.steps[step_index] {
-            BundleStep::Narration { .. } | BundleStep::Dialogue { .. } => step_index += 1,
+            BundleStep::Narration { .. }
+            | BundleStep::Dialogue { .. }
+            | BundleStep::Background { .. }
+            | BundleStep::Bgm { .. }
+            | BundleStep::BgmStop { .. }
+            | BundleStep::Se { .. }
+            | BundleStep::Show { .. }
+            | BundleStep::Hide { .. } => step_index += 1,
             BundleStep::Ending { id, .. } => return id.clone(),
             BundleStep::Jump { target, .. } => {
                 scene_id = target.scene_id.clone();
                 step_index = target.step_index;
             }
+            BundleStep::Call { target, .. } => {
+                calls.push((scene_id.clone(), step_index + 1));
+                scene_id = target.scene_id.clone();
+                step_index = target.step_index;
+            }
+            BundleStep::Return { .. } => {
+                (scene_id, step_index) = calls.pop().expect("call から戻る");
+            }
             BundleStep::Choice { items, .. } => {
                 let label = choose.next().expect("choose に選択肢の数だけラベルを渡す");
                 let item = items
@@ -361,6 +855,16 @@ fn walk_to_ending(bundle: &tsumugai::scenario::StoryBundle, choose: &[&str]) ->
                 scene_id = item.target.scene_id.clone();
                 step_index = item.target.step_index;
             }
+            // ランダム分岐も抽選せずラベルで選ぶ
+            BundleStep::Random { items, .. } => {
+                let label = choose.next().expect("choose に分岐の数だけラベルを渡す");
+                let item = items
+                    .iter()
+                    .find(|i| i.label == *label)
+                    .unwrap_or_else(|| panic!("ランダム分岐の項目「{label}」が見つかる"));
+                scene_id = item.target.scene_id.clone();
+                step_index = item.target.step_index;
+            }
         }
     }
 }
@@ -381,27 +885,844 @@ fn choiceからendingまでの経路をbundle単体で再現できる() {
     );
 }
 
-// -------------------------------------------------------------- Golden JSON
+#[test]
+fn callとreturnは解決済みの飛び先と戻りのステップになる() {
+    let result = compile_path(
+        Path::new("tests/fixtures/routes/call/main.md"),
+        &CompileOptions::default(),
+    );
+    let bundle = result
+        .bundle
+        .as_ref()
+        .expect("error がなければ bundle がある");
 
-fn golden_fixture() -> PathBuf {
-    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/compile/golden/spring_001.json")
+    let main = bundle.scenes.iter().find(|s| s.id == "main").unwrap();
+    let BundleStep::Call { target, .. } = &main.steps[1] else {
+        panic!("2 ステップ目が call ではない: {:?}", main.steps[1]);
+    };
+    assert_eq!(target.scene_id, "interlude");
+    assert_eq!(target.step_index, 0);
+    let interlude = bundle.scenes.iter().find(|s| s.id == "interlude").unwrap();
+    assert!(matches!(interlude.steps[1], BundleStep::Return { .. }));
+
+    let json: serde_json::Value = serde_json::to_value(bundle).unwrap();
+    assert_eq!(json["scenes"][0]["steps"][1]["type"], "call");
+    assert_eq!(walk_to_ending(bundle, &["寄り道する"]), "detour_end");
 }
 
 #[test]
-fn spring例のstorybundleはgolden_jsonと一致する() {
+fn ランダム分岐は重みと解決済みの飛び先を持つrandomステップになる() {
+    let result = compile_path(
+        Path::new("tests/fixtures/routes/random/scenario.md"),
+        &CompileOptions::default(),
+    );
+    let bundle = result
+        .bundle
+        .as_ref()
+        .expect("error がなければ bundle がある");
+
+    let lunch = &bundle.scenes[0];
+    let BundleStep::Random { items, .. } = &lunch.steps[1] else {
+        panic!("2 ステップ目が random ではない: {:?}", lunch.steps[1]);
+    };
+    let weights: Vec<(&str, u32)> = items.iter().map(|i| (i.label.as_str(), i.weight)).collect();
+    assert_eq!(
+        weights,
+        vec![("先輩に会う", 1), ("後輩に会う", 2), ("誰にも会わない", 1)]
+    );
+    for (item, ending) in items.iter().zip(["senpai_end", "kouhai_end", "alone_end"]) {
+        assert_eq!(item.target.scene_id, "lunch");
+        assert!(matches!(
+            &lunch.steps[item.target.step_index + 1],
+            BundleStep::Ending { id, .. } if id == ending
+        ));
+    }
+
+    let json: serde_json::Value = serde_json::to_value(bundle).unwrap();
+    let step = &json["scenes"][0]["steps"][1];
+    assert_eq!(step["type"], "random");
+    assert_eq!(step["items"][1]["weight"], 2);
+    assert_eq!(step["items"][1]["target"]["sceneId"], "lunch");
+    assert_eq!(walk_to_ending(bundle, &["後輩に会う"]), "kouhai_end");
+}
+
+// -------------------------------------------------------------- 分割出力（--split）
+
+#[test]
+fn split_bundleは目次とシーンごとのファイルに分ける() {
     let result = compile_path(spring(), &CompileOptions::default());
     let bundle = result.bundle.as_ref().unwrap();
-    let actual: serde_json::Value = serde_json::to_value(bundle).unwrap();
+    let split = split_bundle(bundle);
 
-    let golden_text = std::fs::read_to_string(golden_fixture()).expect(
-        "tests/fixtures/compile/golden/spring_001.json が読める（先に生成してコミットする）",
+    assert_eq!(split.index.story_build_id, bundle.story_build_id);
+    assert_eq!(split.index.entry_scene_id, "spring_001");
+    assert_eq!(split.index.assets, bundle.assets);
+    assert_eq!(split.chunks.len(), bundle.scenes.len());
+    for (entry, chunk) in split.index.scenes.iter().zip(&split.chunks) {
+        assert_eq!(entry.id, chunk.scene.id);
+        assert_eq!(entry.file, chunk.file);
+        // scenes/<シーン ID>-<内容のハッシュ 8 桁>.json
+        let name = entry.file.strip_prefix("scenes/").unwrap();
+        let hash = name
+            .strip_prefix(&format!("{}-", entry.id))
+            .and_then(|n| n.strip_suffix(".json"))
+            .unwrap();
+        assert_eq!(hash.len(), 8);
+    }
+    // 飛び先の scene_id から読むべきファイルが引ける
+    for chunk in &split.chunks {
+        for step in &chunk.scene.steps {
+            if let BundleStep::Jump { target, .. } = step {
+                assert!(split.index.scenes.iter().any(|s| s.id == target.scene_id));
+            }
+        }
+    }
+    // 同じ入力からは同じファイル名になる
+    let again = split_bundle(
+        compile_path(spring(), &CompileOptions::default())
+            .bundle
+            .as_ref()
+            .unwrap(),
     );
-    let expected: serde_json::Value = serde_json::from_str(&golden_text).unwrap();
+    assert_eq!(again.index.scenes, split.index.scenes);
+}
 
-    assert_eq!(
-        actual, expected,
-        "StoryBundle の出力が変化した。意図した変更なら tests/fixtures/compile/golden/spring_001.json を更新すること"
-    );
+#[test]
+fn write_split_bundleは前回の古いシーンファイルを消してから書き出す() {
+    let dir = unique_output_path("split_stale").with_extension("");
+    let _ = std::fs::remove_dir_all(&dir);
+    let result = compile_path(spring(), &CompileOptions::default());
+    let mut bundle = result.bundle.unwrap();
+    let before = write_split_bundle(&bundle, &dir).unwrap();
+    // 目次にないファイルは残す
+    std::fs::write(dir.join("scenes/keep.json"), "{}").unwrap();
+
+    bundle.scenes[0].steps.pop();
+    let after = write_split_bundle(&bundle, &dir).unwrap();
+    let (old, new) = (&before.chunks[0].file, &after.chunks[0].file);
+    assert_ne!(old, new);
+    assert!(!dir.join(old).exists());
+    assert!(dir.join(new).exists());
+    // 中身が変わらないシーンは同じファイルのまま残る
+    assert_eq!(before.chunks[1].file, after.chunks[1].file);
+    assert!(dir.join(&after.chunks[1].file).exists());
+    assert!(dir.join("scenes/keep.json").exists());
+
+    let mut files: Vec<String> = std::fs::read_dir(dir.join("scenes"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    assert_eq!(files.len(), 3);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn cliのsplitはindex_jsonとシーンごとのjsonを書き出す() {
+    let dir = unique_output_path("split").with_extension("");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let result = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+        .args([
+            "compile",
+            "examples/spring/scenario/spring_001.md",
+            "--target",
+            "web",
+            "--output",
+            dir.to_str().unwrap(),
+            "--split",
+        ])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("tsumugai バイナリを起動できる");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    let index: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("index.json")).unwrap()).unwrap();
+    let golden: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(golden_fixture()).unwrap()).unwrap();
+    assert_eq!(index["storyBuildId"], golden["storyBuildId"]);
+    let scenes = index["scenes"].as_array().unwrap();
+    assert_eq!(scenes.len(), 2);
+    for entry in scenes {
+        let chunk: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(dir.join(entry["file"].as_str().unwrap())).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(chunk["id"], entry["id"]);
+        // 1 ファイル出力と同じ中身
+        let whole = golden["scenes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["id"] == entry["id"])
+            .unwrap();
+        assert_eq!(&chunk, whole);
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn cliのsource_mapはステップの位置を別ファイルに書き出す() {
+    let output = unique_output_path("stripped");
+    let map_path = unique_output_path("source_map");
+
+    let result = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+        .args([
+            "compile",
+            "examples/spring/scenario/spring_001.md",
+            "--target",
+            "web",
+            "--output",
+            output.to_str().unwrap(),
+            "--strip-source",
+            "--source-map",
+            map_path.to_str().unwrap(),
+        ])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("tsumugai バイナリを起動できる");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    let bundle: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+    let map: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&map_path).unwrap()).unwrap();
+    assert_eq!(map["storyBuildId"], bundle["storyBuildId"]);
+    assert!(bundle["scenes"][0]["steps"][0].get("source").is_none());
+    let first = &map["scenes"]["spring_001"][0];
+    assert_eq!(first["file"], "examples/spring/scenario/spring_001.md");
+    assert_eq!(first["line"], 9);
+    assert_eq!(first["column"], 1);
+
+    let _ = std::fs::remove_file(&output);
+    let _ = std::fs::remove_file(&map_path);
+}
+
+/// 生成した HTML から `<script id="..." type="application/json">` の中身を取り出す
+fn embedded_json(html: &str, id: &str) -> serde_json::Value {
+    let open = format!("<script id=\"{id}\" type=\"application/json\">");
+    let start = html.find(&open).unwrap() + open.len();
+    let len = html[start..].find("</script>").unwrap();
+    serde_json::from_str(&html[start..start + len]).unwrap()
+}
+
+#[test]
+fn render_htmlはbundleを埋め込みアセットをhtmlからの相対urlで参照する() {
+    let result = compile_path(spring(), &CompileOptions::default());
+    let bundle = result.bundle.as_ref().unwrap();
+    let html = render_html(bundle, Path::new("examples/spring"), None);
+
+    assert!(html.contains("<title>春・出会い</title>"));
+    assert_eq!(
+        embedded_json(&html, "bundle"),
+        serde_json::to_value(bundle).unwrap()
+    );
+    let urls = embedded_json(&html, "asset-urls");
+    assert_eq!(
+        asset_url(
+            &urls,
+            "spring_001",
+            "background:../assets/bg/school_gate.png"
+        ),
+        "assets/bg/school_gate.png"
+    );
+    assert_eq!(
+        asset_url(&urls, "spring_001", "bgm:../assets/bgm/spring.ogg"),
+        "assets/bgm/spring.ogg"
+    );
+
+    // --assets-out のコピー先を参照する
+    let dir = unique_output_path("html_assets").with_extension("");
+    let _ = std::fs::remove_dir_all(&dir);
+    copy_assets(bundle, &dir.join("assets_out")).unwrap();
+    let html = render_html(bundle, &dir, Some(&dir.join("assets_out")));
+    let urls = embedded_json(&html, "asset-urls");
+    assert_eq!(
+        asset_url(&urls, "spring_001", "bgm:../assets/bgm/spring.ogg"),
+        "assets_out/assets/bgm/spring.ogg"
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// 埋め込んだ asset-urls で、シーンに書かれたままの参照から URL を引く
+/// （プレイヤーと同じく、参照 → 解決先のアセット → URL の順）
+fn asset_url<'a>(urls: &'a serde_json::Value, scene: &str, key: &str) -> &'a str {
+    let file = urls["refs"][scene][key].as_str().unwrap();
+    urls["urls"][file].as_str().unwrap()
+}
+
+#[test]
+fn render_htmlは別ディレクトリの同じ名前のアセットを取り違えない() {
+    let dir = unique_output_path("html_same_name").with_extension("");
+    let _ = std::fs::remove_dir_all(&dir);
+    for (scene, next) in [("a", "\n[次へ](../b/scene.md)\n"), ("b", "")] {
+        std::fs::create_dir_all(dir.join(scene)).unwrap();
+        std::fs::write(
+            dir.join(scene).join("scene.md"),
+            format!("---\nid: {scene}\nbackground: bg.png\n---\n\n# {scene}\n\n本文。\n{next}"),
+        )
+        .unwrap();
+        std::fs::write(dir.join(scene).join("bg.png"), scene).unwrap();
+    }
+
+    let result = compile_path(&dir.join("a/scene.md"), &CompileOptions::default());
+    let bundle = result.bundle.as_ref().expect("compile できる");
+    let files: Vec<_> = bundle
+        .assets
+        .iter()
+        .filter_map(|a| a.file.as_deref())
+        .collect();
+    assert_eq!(files, ["a/bg.png", "b/bg.png"]);
+
+    let html = render_html(bundle, &dir, None);
+    let urls = embedded_json(&html, "asset-urls");
+    assert_eq!(asset_url(&urls, "a", "background:bg.png"), "a/bg.png");
+    assert_eq!(asset_url(&urls, "b", "background:bg.png"), "b/bg.png");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn render_htmlは本文中のscript終了タグで埋め込みが途切れない() {
+    let result = compile_path(spring(), &CompileOptions::default());
+    let mut bundle = result.bundle.unwrap();
+    bundle.title = "<b>春</b>".to_string();
+    bundle.scenes[0].steps[0] = BundleStep::Narration {
+        text: "</script><script>alert(1)</script>".to_string(),
+        source: None,
+    };
+    let html = render_html(&bundle, Path::new("."), None);
+
+    assert!(html.contains("<title>&lt;b&gt;春&lt;/b&gt;</title>"));
+    assert!(!html.contains("alert(1)</script>"));
+    let embedded = embedded_json(&html, "bundle");
+    assert_eq!(
+        embedded["scenes"][0]["steps"][0]["text"],
+        "</script><script>alert(1)</script>"
+    );
+}
+
+#[test]
+fn render_htmlは値の中のプレースホルダを置き換えない() {
+    let result = compile_path(spring(), &CompileOptions::default());
+    let mut bundle = result.bundle.unwrap();
+    bundle.title = "{{ASSET_URLS}}{{BUNDLE}}".to_string();
+    bundle.scenes[0].steps[0] = BundleStep::Narration {
+        text: "{{TITLE}}{{ASSET_URLS}}".to_string(),
+        source: None,
+    };
+    let html = render_html(&bundle, Path::new("."), None);
+
+    assert!(html.contains("<title>{{ASSET_URLS}}{{BUNDLE}}</title>"));
+    let embedded = embedded_json(&html, "bundle");
+    assert_eq!(embedded["title"], "{{ASSET_URLS}}{{BUNDLE}}");
+    assert_eq!(
+        embedded["scenes"][0]["steps"][0]["text"],
+        "{{TITLE}}{{ASSET_URLS}}"
+    );
+    assert!(embedded_json(&html, "asset-urls").is_object());
+}
+
+#[test]
+fn cliのhtml_targetは単体で開けるhtmlを書き出す() {
+    let output = unique_output_path("preview").with_extension("html");
+
+    let result = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+        .args([
+            "compile",
+            "examples/spring/scenario/spring_001.md",
+            "--target",
+            "html",
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("tsumugai バイナリを起動できる");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    let html = std::fs::read_to_string(&output).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    let golden: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(golden_fixture()).unwrap()).unwrap();
+    assert_eq!(embedded_json(&html, "bundle"), golden);
+    // 一時ディレクトリからでもシナリオの横のアセットを参照できる
+    let urls = embedded_json(&html, "asset-urls");
+    let url = asset_url(
+        &urls,
+        "spring_001",
+        "background:../assets/bg/school_gate.png",
+    );
+    assert!(
+        output
+            .parent()
+            .unwrap()
+            .join(url)
+            .ends_with("examples/spring/assets/bg/school_gate.png")
+    );
+    assert!(output.parent().unwrap().join(url).exists());
+
+    let _ = std::fs::remove_file(&output);
+}
+
+#[test]
+fn cliのsplitはhtml_targetでは使えない() {
+    let result = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+        .args([
+            "compile",
+            "examples/spring/scenario/spring_001.md",
+            "--target",
+            "html",
+            "--output",
+            "unused.html",
+            "--split",
+        ])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("tsumugai バイナリを起動できる");
+    assert!(!result.status.success());
+    assert!(
+        !Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("unused.html")
+            .exists()
+    );
+}
+
+#[test]
+fn cliの他エンジンへの書き出しではweb専用のオプションを使えない() {
+    for flag in ["--all-characters", "--strip-source"] {
+        for target in ["ink", "twee", "yarn", "tyrano"] {
+            let output = unique_output_path("web_only").with_extension(target);
+            let result = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+                .args([
+                    "compile",
+                    "examples/spring/scenario/spring_001.md",
+                    "--target",
+                    target,
+                    "--output",
+                    output.to_str().unwrap(),
+                    flag,
+                ])
+                .current_dir(env!("CARGO_MANIFEST_DIR"))
+                .output()
+                .expect("tsumugai バイナリを起動できる");
+            assert!(!result.status.success(), "{target} {flag}");
+            assert!(String::from_utf8_lossy(&result.stderr).contains(flag));
+            assert!(!output.exists());
+        }
+    }
+}
+
+#[test]
+fn 他エンジンへの書き出しはentryで足した開始シーンも含める() {
+    let main = Path::new("tests/fixtures/routes/entries/main.md");
+    let result = export_path(main, ExportTarget::Twee, &ExportOptions::default());
+    assert!(!result.output.unwrap().contains(":: dlc "));
+
+    let options = ExportOptions {
+        entries: vec!["tests/fixtures/routes/entries/dlc.md".into()],
+        ..ExportOptions::default()
+    };
+    let result = export_path(main, ExportTarget::Twee, &options);
+    assert!(!result.has_errors(), "{:?}", result.check.diagnostics);
+    let twee = result.output.unwrap();
+    assert!(twee.contains(":: dlc "));
+    assert!(twee.contains("\"start\": \"main\""));
+    // 足した開始シーンから辿れるので unreachable-scene にならない
+    assert!(
+        result
+            .check
+            .diagnostics
+            .iter()
+            .all(|d| d.rule_id != "unreachable-scene")
+    );
+}
+
+#[test]
+fn cliのink_targetはgoldenと同じinkを書き出す() {
+    let output = unique_output_path("story").with_extension("ink");
+
+    let result = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+        .args([
+            "compile",
+            "examples/spring/scenario/spring_001.md",
+            "--target",
+            "ink",
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("tsumugai バイナリを起動できる");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        std::fs::read_to_string(ink_golden_fixture()).unwrap()
+    );
+
+    let _ = std::fs::remove_file(&output);
+}
+
+#[test]
+fn cliのtyrano_targetはoutputのディレクトリにシーンごとのksを書き出す() {
+    let dir = unique_output_path("tyrano").with_extension("");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let result = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+        .args([
+            "compile",
+            "examples/spring/scenario/spring_001.md",
+            "--target",
+            "tyrano",
+            "--output",
+            dir.to_str().unwrap(),
+        ])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("tsumugai バイナリを起動できる");
+    assert!(
+        result.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    for name in ["spring_001.ks", "spring_002.ks"] {
+        assert_eq!(
+            std::fs::read_to_string(dir.join(name)).unwrap(),
+            std::fs::read_to_string(tyrano_golden_dir().join(name)).unwrap()
+        );
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// -------------------------------------------------------------- Golden JSON
+
+fn golden_fixture() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/compile/golden/spring_001.json")
+}
+
+#[test]
+fn spring例のstorybundleはgolden_jsonと一致する() {
+    let result = compile_path(spring(), &CompileOptions::default());
+    let bundle = result.bundle.as_ref().unwrap();
+    let actual: serde_json::Value = serde_json::to_value(bundle).unwrap();
+
+    let golden_text = std::fs::read_to_string(golden_fixture()).expect(
+        "tests/fixtures/compile/golden/spring_001.json が読める（先に生成してコミットする）",
+    );
+    let expected: serde_json::Value = serde_json::from_str(&golden_text).unwrap();
+
+    assert_eq!(
+        actual, expected,
+        "StoryBundle の出力が変化した。意図した変更なら tests/fixtures/compile/golden/spring_001.json を更新すること"
+    );
+}
+
+fn ink_golden_fixture() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/compile/golden/spring_001.ink")
+}
+
+#[test]
+fn spring例のink書き出しはgoldenと一致する() {
+    let result = export_path(spring(), ExportTarget::Ink, &ExportOptions::default());
+    assert!(!result.has_errors(), "{:?}", result.check.diagnostics);
+    let actual = result.output.unwrap();
+
+    let expected = std::fs::read_to_string(ink_golden_fixture()).expect(
+        "tests/fixtures/compile/golden/spring_001.ink が読める（先に生成してコミットする）",
+    );
+    assert_eq!(
+        actual, expected,
+        "Ink の出力が変化した。意図した変更なら tests/fixtures/compile/golden/spring_001.ink を更新すること"
+    );
+}
+
+#[test]
+fn inkではシーンがknotにセクションがstitchになり分岐はdivertになる() {
+    let result = export_path(spring(), ExportTarget::Ink, &ExportOptions::default());
+    let ink = result.output.unwrap();
+
+    assert!(ink.starts_with("// tsumugai"));
+    assert!(ink.contains("\n-> spring_001\n"));
+    assert!(ink.contains("=== spring_001 ===\n"));
+    assert!(ink.contains("=== spring_002 ===\n"));
+    // Ink の識別子にできない見出しは連番にして、見出しをコメントに残す
+    assert!(ink.contains("= section_1  // 選択肢\n"));
+    assert!(ink.contains("= run_together\n"));
+    // リード部からセクションへのフォールスルーは明示の divert
+    assert!(ink.contains("あと三分。\n-> spring_001.section_1\n"));
+    assert!(ink.contains("+ [一緒に走る] -> spring_001.run_together\n"));
+    assert!(ink.contains("+ [先に行ってもらう] -> spring_002\n"));
+    assert!(ink.contains("# ending: childhood_route\n-> END\n"));
+    assert!(ink.contains("幼なじみ: ほら、急ぐよ！ # speaker: 幼なじみ\n"));
+}
+
+#[test]
+fn inkでは表情と演出がタグになる() {
+    let result = export_path(
+        Path::new("tests/fixtures/trace/sprites/scenario.md"),
+        ExportTarget::Ink,
+        &ExportOptions::default(),
+    );
+    let ink = result.output.unwrap();
+    assert!(ink.contains("# show: 幼なじみ(笑顔)\n"));
+    assert!(ink.contains("幼なじみ: 遅刻するよ。 # speaker: 幼なじみ # expression: 怒り\n"));
+    assert!(ink.contains("# hide: 幼なじみ\n"));
+
+    let result = export_path(
+        Path::new("tests/fixtures/trace/staging/scenario.md"),
+        ExportTarget::Ink,
+        &ExportOptions::default(),
+    );
+    let ink = result.output.unwrap();
+    assert!(ink.contains("# background: assets/bg/classroom.png\n"));
+    assert!(ink.contains("# bgm: stop\n"));
+    assert!(ink.contains("# se: assets/se/door.ogg\n"));
+}
+
+#[test]
+fn checkエラーがあるとinkを書き出さない() {
+    let result = export_path(
+        Path::new("tests/fixtures/trace/broken/scenario.md"),
+        ExportTarget::Ink,
+        &ExportOptions::default(),
+    );
+    assert!(result.has_errors());
+    assert!(result.output.is_none());
+}
+
+fn yarn_golden_fixture() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/compile/golden/spring_001.yarn")
+}
+
+#[test]
+fn spring例のyarn書き出しはgoldenと一致する() {
+    let result = export_path(spring(), ExportTarget::Yarn, &ExportOptions::default());
+    assert!(!result.has_errors(), "{:?}", result.check.diagnostics);
+    let actual = result.output.unwrap();
+
+    let expected = std::fs::read_to_string(yarn_golden_fixture()).expect(
+        "tests/fixtures/compile/golden/spring_001.yarn が読める（先に生成してコミットする）",
+    );
+    assert_eq!(
+        actual, expected,
+        "Yarn の出力が変化した。意図した変更なら tests/fixtures/compile/golden/spring_001.yarn を更新すること"
+    );
+}
+
+#[test]
+fn yarnではセクションがノードになり分岐はoptionとjumpになる() {
+    let result = export_path(spring(), ExportTarget::Yarn, &ExportOptions::default());
+    let yarn = result.output.unwrap();
+    assert!(yarn.contains("title: spring_001\n---\n"));
+    // フォールスルーは明示の jump になる
+    assert!(yarn.contains("あと三分。\n<<jump spring_001_section_1>>\n===\n"));
+    assert!(yarn.contains("-> 一緒に走る\n    <<jump spring_001_run_together>>\n"));
+    assert!(yarn.contains("-> 先に行ってもらう\n    <<jump spring_002>>\n"));
+    assert!(yarn.contains("title: spring_001_run_together\ntags: ending:childhood_route\n"));
+    assert!(yarn.contains("<<stop>>\n===\n"));
+}
+
+#[test]
+fn yarnのセリフはcharactersの表示名で書き演出はコマンドになる() {
+    let result = export_path(
+        Path::new("tests/fixtures/trace/aliases/scenario.md"),
+        ExportTarget::Yarn,
+        &ExportOptions::default(),
+    );
+    let yarn = result.output.unwrap();
+    assert!(yarn.contains("春日葵: 私は葵。 #speaker:葵\n"));
+
+    let result = export_path(
+        Path::new("tests/fixtures/trace/sprites/scenario.md"),
+        ExportTarget::Yarn,
+        &ExportOptions::default(),
+    );
+    let yarn = result.output.unwrap();
+    assert!(yarn.contains("<<show 幼なじみ 笑顔>>\n"));
+    assert!(yarn.contains("幼なじみ: 遅刻するよ。 #speaker:幼なじみ #expression:怒り\n"));
+    assert!(yarn.contains("<<hide 幼なじみ>>\n"));
+
+    let result = export_path(
+        Path::new("tests/fixtures/trace/staging/scenario.md"),
+        ExportTarget::Yarn,
+        &ExportOptions::default(),
+    );
+    let yarn = result.output.unwrap();
+    assert!(yarn.contains("<<background assets/bg/classroom.png>>\n"));
+    assert!(yarn.contains("<<bgm stop>>\n"));
+}
+
+#[test]
+fn checkエラーがあるとyarnを書き出さない() {
+    let result = export_path(
+        Path::new("tests/fixtures/trace/broken/scenario.md"),
+        ExportTarget::Yarn,
+        &ExportOptions::default(),
+    );
+    assert!(result.has_errors());
+    assert!(result.output.is_none());
+}
+
+fn tyrano_golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/compile/golden/tyrano")
+}
+
+#[test]
+fn spring例のtyrano書き出しはgoldenと一致する() {
+    let result = export_path(spring(), ExportTarget::Tyrano, &ExportOptions::default());
+    assert!(!result.has_errors(), "{:?}", result.check.diagnostics);
+    assert!(result.output.is_none());
+    let names: Vec<&str> = result.files.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["spring_001.ks", "spring_002.ks"]);
+    for file in &result.files {
+        let expected = std::fs::read_to_string(tyrano_golden_dir().join(&file.name))
+            .expect("tests/fixtures/compile/golden/tyrano/ が読める（先に生成してコミットする）");
+        assert_eq!(
+            file.text, expected,
+            "TyranoScript の出力が変化した。意図した変更なら tests/fixtures/compile/golden/tyrano/ を更新すること"
+        );
+    }
+}
+
+#[test]
+fn tyranoではセクションがラベルになり別ファイルへの分岐はstorageになる() {
+    let result = export_path(spring(), ExportTarget::Tyrano, &ExportOptions::default());
+    let first = &result.files[0].text;
+    assert!(first.contains("[bg storage=\"school_gate.png\"]\n"));
+    assert!(first.contains("[playbgm storage=\"spring.ogg\"]\n"));
+    assert!(first.contains("\n*run_together\n"));
+    assert!(first.contains("[glink text=\"一緒に走る\" target=*run_together]\n"));
+    // ファイル先頭へのリンクは storage だけ
+    assert!(first.contains("[glink text=\"先に行ってもらう\" storage=\"spring_002.ks\"]\n[s]\n"));
+    assert!(first.contains("[jump storage=\"spring_002.ks\" target=*after_school]\n"));
+    assert!(first.contains("#幼なじみ\nほら、急ぐよ！[p]\n"));
+    assert!(first.contains("; ending: childhood_route\n[s]\n"));
+    // 同じファイル内のジャンプは target だけ
+    assert!(
+        result.files[1]
+            .text
+            .contains("[jump target=*after_school]\n")
+    );
+}
+
+#[test]
+fn tyranoのセリフはcharactersの表示名で書き演出はタグになる() {
+    let result = export_path(
+        Path::new("tests/fixtures/trace/aliases/scenario.md"),
+        ExportTarget::Tyrano,
+        &ExportOptions::default(),
+    );
+    assert!(result.files[1].text.contains("#春日葵\n私は葵。[p]\n"));
+
+    let result = export_path(
+        Path::new("tests/fixtures/trace/sprites/scenario.md"),
+        ExportTarget::Tyrano,
+        &ExportOptions::default(),
+    );
+    let ks = &result.files[0].text;
+    assert!(ks.contains("[chara_show name=\"幼なじみ\" face=\"笑顔\"]\n"));
+    assert!(
+        ks.contains("[chara_mod name=\"幼なじみ\" face=\"怒り\"]\n#幼なじみ\n遅刻するよ。[p]\n")
+    );
+    assert!(ks.contains("[chara_hide name=\"幼なじみ\"]\n"));
+}
+
+#[test]
+fn tyranoの名前欄の表示名もエスケープする() {
+    let result = export_path(
+        Path::new("tests/fixtures/compile/tyrano_name/scenario.md"),
+        ExportTarget::Tyrano,
+        &ExportOptions::default(),
+    );
+    assert!(!result.has_errors());
+    assert!(
+        result.files[0]
+            .text
+            .contains("#[[謎の少女]\nはじめまして。[p]\n")
+    );
+}
+
+#[test]
+fn checkエラーがあるとtyranoを書き出さない() {
+    let result = export_path(
+        Path::new("tests/fixtures/trace/broken/scenario.md"),
+        ExportTarget::Tyrano,
+        &ExportOptions::default(),
+    );
+    assert!(result.has_errors());
+    assert!(result.files.is_empty());
+}
+
+#[test]
+fn tweeではセクションがパッセージになり分岐はリンクになる() {
+    let result = export_path(spring(), ExportTarget::Twee, &ExportOptions::default());
+    let twee = result.output.unwrap();
+    assert!(twee.starts_with(":: StoryTitle\n春・出会い\n"));
+    assert!(twee.contains("\"start\": \"spring_001\""));
+    assert!(twee.contains("\n:: 選択肢 {"));
+    // フォールスルーは明示のリンクになる
+    assert!(twee.contains("あと三分。\n[[次へ->選択肢]]\n"));
+    assert!(twee.contains("[[一緒に走る->run-together]]\n"));
+    assert!(twee.contains("[[先に行ってもらう->spring_002]]\n"));
+    assert!(twee.contains("<!-- ending: childhood_route -->"));
+    assert!(twee.contains("<!-- background: ../assets/bg/school_gate.png -->"));
+}
+
+#[test]
+fn tweeのパッセージ名はリンクの区切り記号を全角にする() {
+    let result = export_path(
+        Path::new("tests/fixtures/compile/twee_name/scenario.md"),
+        ExportTarget::Twee,
+        &ExportOptions::default(),
+    );
+    assert!(!result.has_errors());
+    let twee = result.output.unwrap();
+    assert!(twee.contains("\n:: 右｜左 → 奥］］ {"));
+    assert!(twee.contains("[[次へ->右｜左 → 奥］］]]\n"));
+    // 読み直してもフォールスルーのリンクが同じパッセージに着く
+    let back = import_twee_str(
+        &twee,
+        Path::new("twee_name.twee"),
+        Path::new("twee_name.md"),
+    );
+    assert!(
+        back.diagnostics
+            .iter()
+            .all(|d| d.rule_id != "twee-broken-link"),
+        "{:?}",
+        back.diagnostics
+    );
+}
+
+#[test]
+fn tweeのifidは同じ入力なら毎回同じになる() {
+    let ifid = |twee: String| {
+        twee.lines()
+            .find(|l| l.contains("\"ifid\""))
+            .unwrap()
+            .to_string()
+    };
+    let a = export_path(spring(), ExportTarget::Twee, &ExportOptions::default());
+    let b = export_path(spring(), ExportTarget::Twee, &ExportOptions::default());
+    assert_eq!(ifid(a.output.unwrap()), ifid(b.output.unwrap()));
 }
 
 // -------------------------------------------------------------- CLIプロセス経由の確認
@@ -0,0 +1,134 @@
+//! `tsumugai import`（Twee 3 の取り込み）の統合テスト
+//!
+//! SPEC 7.2 の対応表を検証する。tests/fixtures/import/twee/story.twee は
+//! 開始パッセージの指定・特殊パッセージ・スクリプト・マクロ・変数・
+//! 3 種類のリンク記法をひととおり含む。取り込んだ結果がそのまま
+//! `check` を通ることも確かめる（書き出し側のテストは compile_test.rs）。
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tsumugai::scenario::{CheckOptions, check_path, import_twee_path, import_twee_str};
+
+fn story() -> &'static Path {
+    Path::new("tests/fixtures/import/twee/story.twee")
+}
+
+fn unique_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "tsumugai-import-test-{}-{}",
+        name,
+        std::process::id()
+    ))
+}
+
+#[test]
+fn パッセージはstart順にセクションになりリンクは選択肢とジャンプになる() {
+    let result = import_twee_path(story(), Path::new("after_school.md"));
+    assert!(!result.has_errors());
+    assert_eq!(result.passages, 4);
+    let md = &result.markdown;
+    assert!(md.starts_with("---\nid: after_school\n---\n\n# 放課後の約束\n"));
+    // StoryData の start が先頭になる
+    let start = md.find("## はじまり").unwrap();
+    assert!(start < md.find("## 図書室").unwrap());
+    assert!(md.contains("- [図書室](#図書室)\n- [上へ](#屋上)\n"));
+    assert!(md.contains("\n[帰る](#帰り道)\n"));
+    assert!(md.contains("\n[降りる](#帰り道)\n"));
+    // リンクのないパッセージはエンディング（id は ASCII の連番）
+    assert!(md.contains("<!-- ending: ending_1 -->"));
+    // 行頭の `- ` は選択肢にならないようエスケープされる
+    assert!(md.contains("\\- 放課後の教室には誰もいない。"));
+}
+
+#[test]
+fn 表せないマクロと特殊パッセージはtwee_unsupportedで報告する() {
+    let result = import_twee_path(story(), Path::new("after_school.md"));
+    let rules: Vec<&str> = result.diagnostics.iter().map(|d| d.rule_id).collect();
+    assert_eq!(rules, vec!["twee-unsupported"; 5]);
+    let md = &result.markdown;
+    assert!(!md.contains("StoryInit"));
+    assert!(!md.contains("window.debug"));
+    assert!(!md.contains("<<set"));
+    // フックの中身は条件なしの本文として残る
+    assert!(md.contains("\nまた会ったね。\n"));
+    assert!(!md.contains("(if:"));
+}
+
+#[test]
+fn 取り込んだシーンはcheckでエラーにならない() {
+    let dir = unique_dir("check");
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("after_school.md");
+    let result = import_twee_path(story(), &output);
+    std::fs::write(&output, &result.markdown).unwrap();
+
+    let check = check_path(&output, &CheckOptions::default());
+    let _ = std::fs::remove_dir_all(&dir);
+    let rules: Vec<&str> = check.diagnostics.iter().map(|d| d.rule_id).collect();
+    assert_eq!(rules, vec!["missing-characters-file"]);
+}
+
+#[test]
+fn ないパッセージへのリンクはtwee_broken_linkで報告する() {
+    let result = import_twee_str(
+        ":: Start\n[[行く->図書室]]\n[[戻る->教室]]\n\n:: 教室\nおしまい\n",
+        Path::new("broken.twee"),
+        Path::new("broken.md"),
+    );
+    assert!(!result.has_errors());
+    let found: Vec<(&str, usize)> = result
+        .diagnostics
+        .iter()
+        .map(|d| (d.rule_id, d.span.as_ref().unwrap().line))
+        .collect();
+    assert_eq!(found, [("twee-broken-link", 2)]);
+    assert!(result.diagnostics[0].message.contains("「図書室」"));
+}
+
+#[test]
+fn パッセージがないとtwee_invalidのエラーになる() {
+    let result = import_twee_str(
+        "ただの文章\n",
+        Path::new("empty.twee"),
+        Path::new("empty.md"),
+    );
+    assert!(result.has_errors());
+    assert_eq!(result.diagnostics[0].rule_id, "twee-invalid");
+    assert!(result.markdown.is_empty());
+}
+
+#[test]
+fn cli_importはoutputなしならmarkdownをstdoutに出す() {
+    let out = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+        .args(["import", "tests/fixtures/import/twee/story.twee"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("tsumugai バイナリを起動できる");
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.starts_with("---\nid: story\n---\n"));
+    // 報告は stdout の Markdown に混ぜない
+    assert!(!stdout.contains("twee-unsupported"));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("twee-unsupported"));
+}
+
+#[test]
+fn cli_importは出力ファイルを書き出す() {
+    let dir = unique_dir("cli");
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("after_school.md");
+    let out = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+        .args([
+            "import",
+            "tests/fixtures/import/twee/story.twee",
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("tsumugai バイナリを起動できる");
+    let written = std::fs::read_to_string(&output);
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(out.status.success());
+    assert!(written.unwrap().contains("id: after_school"));
+}
@@ -3,7 +3,7 @@
 //! SPEC 5.2「全分岐探索」の仕様を検証する。
 //! examples/spring を全分岐が既知の正常系サンプルとして使い、
 //! tests/fixtures/routes/ と tests/fixtures/trace/ を異常系
-//! （到達不能・経路数上限・循環・深度超過・check エラー）の入力例に使う。
+//! （到達不能・経路数上限・循環・深度超過・呼び出し・check エラー）の入力例に使う。
 
 use std::path::Path;
 use tsumugai::scenario::{
@@ -168,6 +168,78 @@ fn 深度上限に達するとmax_depth_exceededになりwarning扱いになる(
     assert!(!result.has_errors());
 }
 
+// ------------------------------------------------------------ 呼び出しと戻り
+
+#[test]
+fn 同じ呼び出し先を2回呼んでも循環にならず呼び出し元へ戻る() {
+    let result = routes_path(
+        Path::new("tests/fixtures/routes/call/main.md"),
+        &RoutesOptions::default(),
+    );
+    let report = result.report.as_ref().unwrap();
+
+    assert!(
+        !result.has_errors(),
+        "diagnostics: {:?}",
+        report.diagnostics
+    );
+    let ends: Vec<&RouteEnd> = report.routes.iter().map(|r| &r.end).collect();
+    assert_eq!(
+        ends,
+        vec![
+            &RouteEnd::Ending {
+                id: "home_end".to_string()
+            },
+            &RouteEnd::Ending {
+                id: "detour_end".to_string()
+            },
+        ]
+    );
+    assert!(report.unreachable_scenes.is_empty());
+}
+
+#[test]
+fn 呼び出しなしで到達するreturnはreturn_without_callのerrorになる() {
+    // リード部からのフォールスルーで、呼び出し先のつもりのセクションに入ってしまう
+    let result = routes_path(
+        Path::new("tests/fixtures/routes/call_errors/stray.md"),
+        &RoutesOptions::default(),
+    );
+    let report = result.report.as_ref().unwrap();
+
+    assert!(matches!(
+        report.routes[0].end,
+        RouteEnd::ReturnWithoutCall { line: 13, .. }
+    ));
+    assert!(result.has_errors());
+    let diag = report
+        .diagnostics
+        .iter()
+        .find(|d| d.rule_id == "return-without-call")
+        .expect("return-without-call が報告される");
+    assert_eq!(diag.span.as_ref().map(|s| s.line), Some(13));
+}
+
+#[test]
+fn 戻る前に同じcallへ再到達するとunbounded_recursionのerrorになる() {
+    let result = routes_path(
+        Path::new("tests/fixtures/routes/call_errors/recursion.md"),
+        &RoutesOptions::default(),
+    );
+    let report = result.report.as_ref().unwrap();
+
+    assert_eq!(report.routes.len(), 1);
+    assert!(result.has_errors());
+    let diag = report
+        .diagnostics
+        .iter()
+        .find(|d| d.rule_id == "unbounded-recursion")
+        .expect("unbounded-recursion が報告される");
+    // 2 度目に実行される、セクション内の call を指す
+    assert_eq!(diag.span.as_ref().map(|s| s.line), Some(15));
+    assert!(diag.file.ends_with("recursion.md"));
+}
+
 // ------------------------------------------------------------ 経路数の上限
 
 #[test]
@@ -212,6 +284,213 @@ fn ディレクトリを渡すとio_errorになる() {
     );
 }
 
+// ------------------------------------------------------------ 経路の長さ
+
+#[test]
+fn 経路ごとに通ったブロックの数と文字数とシーン数を数える() {
+    let result = routes_path(spring(), &RoutesOptions::default());
+    let report = result.report.as_ref().unwrap();
+    // Route 1（--choices 1）は spring_001.md の中だけで終わる
+    let first = &report.routes[0];
+    assert_eq!(first.choices, vec![1]);
+    assert_eq!(first.scenes, 1);
+    assert!(first.narration_blocks > 0);
+    assert!(first.dialogue_blocks > 0);
+    assert_eq!(first.chars, 94);
+    assert_eq!(first.reading_minutes, 0.2);
+    // spring_002.md へ進む経路は 2 シーンを通り、分岐の前の本文も数える
+    for route in &report.routes[1..] {
+        assert_eq!(route.scenes, 2);
+        assert!(route.chars > first.chars);
+    }
+}
+
+#[test]
+fn 読了目安はcpmの指定で変わる() {
+    let options = RoutesOptions {
+        chars_per_minute: 100,
+        ..RoutesOptions::default()
+    };
+    let result = routes_path(spring(), &options);
+    let report = result.report.as_ref().unwrap();
+    assert_eq!(report.routes[0].reading_minutes, 0.9);
+}
+
+#[test]
+fn endingごとに最短中央最長の経路をまとめる() {
+    let result = routes_path(spring(), &RoutesOptions::default());
+    let report = result.report.as_ref().unwrap();
+    let endings: Vec<&str> = report
+        .ending_lengths
+        .iter()
+        .map(|e| e.ending.as_str())
+        .collect();
+    assert_eq!(
+        endings,
+        vec!["calm_route", "childhood_route", "sprint_route"]
+    );
+
+    // calm_route には --choices 2 と --choices 3,2 の 2 経路が着く
+    let calm = &report.ending_lengths[0];
+    assert_eq!(calm.routes, 2);
+    assert_eq!(calm.shortest.choices, vec![2]);
+    assert_eq!(calm.longest.choices, vec![3, 2]);
+    assert!(calm.shortest.chars < calm.longest.chars);
+    // 偶数個なら中央は短い方
+    assert_eq!(calm.median.route, calm.shortest.route);
+    assert_eq!(report.routes[calm.longest.route - 1].choices, vec![3, 2]);
+}
+
+#[test]
+fn endingに着かない経路はまとめに含めない() {
+    let result = routes_path(
+        Path::new("tests/fixtures/trace/eof/scenario.md"),
+        &RoutesOptions::default(),
+    );
+    let report = result.report.as_ref().unwrap();
+    assert!(!report.routes.is_empty());
+    assert!(report.ending_lengths.is_empty());
+}
+
+#[test]
+fn 実績ごとに達成する経路を示す() {
+    let result = routes_path(spring(), &RoutesOptions::default());
+    let report = result.report.as_ref().unwrap();
+    assert!(report.diagnostics.is_empty(), "{:?}", report.diagnostics);
+    let ids: Vec<&str> = report.achievements.iter().map(|a| a.id.as_str()).collect();
+    assert_eq!(ids, vec!["after_school", "all_endings", "morning_run"]);
+    assert!(report.achievements.iter().all(|a| a.attainable));
+    // endings の実績は列挙した ending ごとに 1 経路
+    assert_eq!(
+        report.achievements[1].choices,
+        vec![vec![1], vec![3, 1], vec![2]]
+    );
+    assert_eq!(report.achievements[2].choices, vec![vec![1]]);
+}
+
+#[test]
+fn 同じ経路で満たせない実績はunattainable_achievementになる() {
+    let result = routes_path(
+        Path::new("tests/fixtures/routes/achievements/entry.md"),
+        &RoutesOptions::default(),
+    );
+    let report = result.report.as_ref().unwrap();
+    let rules: Vec<&str> = report.diagnostics.iter().map(|d| d.rule_id).collect();
+    assert_eq!(rules, vec!["unattainable-achievement"]);
+    let diag = &report.diagnostics[0];
+    assert!(diag.file.ends_with("achievements.yaml"), "{:?}", diag.file);
+    assert_eq!(diag.span.as_ref().map(|s| s.line), Some(6));
+    assert!(diag.message.contains("b_then_x"), "{}", diag.message);
+
+    let b_then_x = &report.achievements[0];
+    assert!(!b_then_x.attainable);
+    assert!(b_then_x.choices.is_empty());
+    // セクション条件は選択肢で a を通った経路だけが満たす
+    assert_eq!(report.achievements[2].choices, vec![vec![1, 2]]);
+
+    let human = render_routes_human(&result);
+    assert!(human.contains("  b_then_x: 達成不能"), "出力: {human}");
+    assert!(
+        human.contains("  collector: --choices 2 / --choices 1,1"),
+        "出力: {human}"
+    );
+}
+
+#[test]
+fn 章ごとに章の入口から探索して次の章をまとめる() {
+    let result = routes_path(
+        Path::new("tests/fixtures/routes/chapters/one.md"),
+        &RoutesOptions::default(),
+    );
+    let report = result.report.as_ref().unwrap();
+    assert!(report.diagnostics.is_empty(), "{:?}", report.diagnostics);
+    // 後日談はリンクされていないが、章の入口から遊べるので到達可能に数える
+    assert!(report.unreachable_scenes.is_empty());
+    assert_eq!(
+        report.reached_endings,
+        vec!["early_end", "epilogue_end", "two_end"]
+    );
+    // 開始シーンからの経路は章をまたいで進む
+    assert_eq!(report.routes.len(), 2);
+
+    let ids: Vec<&str> = report.chapters.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, vec!["one", "two", "epilogue"]);
+    let one = &report.chapters[0];
+    assert_eq!(one.title.as_deref(), Some("第1章"));
+    assert_eq!(one.routes, 2);
+    assert_eq!(one.endings, vec!["early_end"]);
+    assert_eq!(one.next_chapters, vec!["two"]);
+    assert!(one.unvisited_scenes.is_empty());
+    assert_eq!(report.chapters[2].endings, vec!["epilogue_end"]);
+    assert!(report.chapters[2].next_chapters.is_empty());
+
+    let human = render_routes_human(&result);
+    assert!(
+        human.contains("  1. one「第1章」（入口 tests/fixtures/routes/chapters/one.md・2 経路）"),
+        "出力: {human}"
+    );
+    assert!(human.contains("    次の章: two"), "出力: {human}");
+}
+
+#[test]
+fn entryで足した開始シーンからも探索し到達可能性は和集合で判定する() {
+    let dir = Path::new("tests/fixtures/routes/entries");
+    let main = dir.join("main.md");
+    let result = routes_path(&main, &RoutesOptions::default());
+    let report = result.report.as_ref().unwrap();
+    assert_eq!(report.unreachable_scenes, vec![dir.join("bonus.md")]);
+    assert_eq!(report.unreached_endings, vec!["bonus_end"]);
+
+    let options = RoutesOptions {
+        entries: vec![dir.join("dlc.md")],
+        ..RoutesOptions::default()
+    };
+    let result = routes_path(&main, &options);
+    let report = result.report.as_ref().unwrap();
+    assert!(report.diagnostics.is_empty(), "{:?}", report.diagnostics);
+    assert!(report.unreachable_scenes.is_empty());
+    assert_eq!(report.reached_endings, vec!["bonus_end", "main_end"]);
+    assert_eq!(report.routes.len(), 2);
+    assert_eq!(report.routes[0].entry, None);
+    assert_eq!(report.routes[1].entry, Some(dir.join("dlc.md")));
+    assert_eq!(report.ending_lengths[0].ending, "bonus_end");
+    assert_eq!(report.ending_lengths[0].shortest.route, 2);
+
+    let human = render_routes_human(&result);
+    assert!(
+        human.contains(
+            "Route 2: [tests/fixtures/routes/entries/dlc.md] (選択なし) → エンディング「bonus_end」"
+        ),
+        "出力: {human}"
+    );
+    let json: serde_json::Value = serde_json::from_str(&render_routes_json(&result)).unwrap();
+    assert!(json["report"]["routes"][0].get("entry").is_none());
+    assert_eq!(
+        json["report"]["routes"][1]["entry"],
+        "tests/fixtures/routes/entries/dlc.md"
+    );
+}
+
+#[test]
+fn 存在しないentryはio_errorになり探索しない() {
+    let dir = Path::new("tests/fixtures/routes/entries");
+    let options = RoutesOptions {
+        entries: vec![dir.join("missing.md")],
+        ..RoutesOptions::default()
+    };
+    let result = routes_path(&dir.join("main.md"), &options);
+    assert!(result.report.is_none());
+    assert!(
+        result
+            .check
+            .diagnostics
+            .iter()
+            .any(|d| d.rule_id == "io-error"),
+        "{:?}",
+        result.check.diagnostics
+    );
+}
+
 // ------------------------------------------------------------ 出力形式
 
 #[test]
@@ -226,6 +505,58 @@ fn json出力は安定形式で経路と到達可能性を保持する() {
     assert_eq!(json["report"]["truncated"], false);
     let reachable_scenes = json["report"]["reachable_scenes"].as_array().unwrap();
     assert_eq!(reachable_scenes.len(), 2);
+
+    let first = &json["report"]["routes"][0];
+    assert_eq!(first["chars"], 94);
+    assert_eq!(first["scenes"], 1);
+    assert!(first["dialogue_blocks"].is_u64());
+    let calm = &json["report"]["ending_lengths"][0];
+    assert_eq!(calm["ending"], "calm_route");
+    assert_eq!(calm["longest"]["choices"], serde_json::json!([3, 2]));
+}
+
+#[test]
+fn ランダム分岐は全項目を探索し経路にrandomの番号を記録する() {
+    let result = routes_path(
+        Path::new("tests/fixtures/routes/random/scenario.md"),
+        &RoutesOptions::default(),
+    );
+    assert!(!result.has_errors());
+    let report = result.report.as_ref().unwrap();
+    assert_eq!(report.reached_endings.len(), 3);
+    assert!(report.unreached_endings.is_empty());
+    let picks: Vec<(&[usize], &[usize])> = report
+        .routes
+        .iter()
+        .map(|r| (r.choices.as_slice(), r.random.as_slice()))
+        .collect();
+    assert_eq!(
+        picks,
+        vec![
+            (&[][..], &[1][..]),
+            (&[][..], &[2][..]),
+            (&[][..], &[3][..])
+        ]
+    );
+
+    let human = render_routes_human(&result);
+    assert!(
+        human.contains("Route 2: --random 2 → エンディング「kouhai_end」"),
+        "出力: {human}"
+    );
+
+    // ランダム分岐を通らない経路の JSON には random が出ない
+    let json: serde_json::Value = serde_json::from_str(&render_routes_json(&result)).unwrap();
+    assert_eq!(
+        json["report"]["routes"][0]["random"],
+        serde_json::json!([1])
+    );
+    let spring: serde_json::Value = serde_json::from_str(&render_routes_json(&routes_path(
+        spring(),
+        &RoutesOptions::default(),
+    )))
+    .unwrap();
+    assert!(spring["report"]["routes"][0].get("random").is_none());
 }
 
 #[test]
@@ -248,6 +579,14 @@ fn 人間向け出力に経路一覧とendingの到達可否が含まれる() {
     assert!(human.contains("--choices 3,1"), "出力: {human}");
     assert!(human.contains("sprint_route"), "出力: {human}");
     assert!(human.contains("発見した経路数: 4"), "出力: {human}");
+    assert!(
+        human.contains(
+            "Route 1: --choices 1 → エンディング「childhood_route」（94 文字・約 0.2 分・1 シーン）"
+        ),
+        "出力: {human}"
+    );
+    assert!(human.contains("エンディング別の長さ:"), "出力: {human}");
+    assert!(human.contains("    最長: Route 4"), "出力: {human}");
 }
 
 #[test]
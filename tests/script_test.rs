@@ -0,0 +1,119 @@
+//! `tsumugai export script`（校正用の台本）の統合テスト
+//!
+//! examples/spring を分岐とシーンをまたぐジャンプの正常系として、
+//! tests/fixtures/routes/unreachable を付録に回るまとまりの例として使う。
+//! 並び順（開始シーンからの深さ優先）と番号による参照を検証する。
+
+use std::path::Path;
+use std::process::Command;
+use tsumugai::scenario::{
+    ExportOptions, ScriptDocument, ScriptLine, render_script_html, render_script_text, script_path,
+};
+
+fn document(path: &str) -> ScriptDocument {
+    let result = script_path(Path::new(path), &ExportOptions::default());
+    assert!(!result.has_errors(), "{:?}", result.check.diagnostics);
+    result.document.unwrap()
+}
+
+fn numbers(units: &[tsumugai::scenario::ScriptUnit]) -> Vec<&str> {
+    units.iter().map(|u| u.number.as_str()).collect()
+}
+
+#[test]
+fn 開始シーンから深さ優先で選択肢の上から順に並べる() {
+    let doc = document("examples/spring/scenario/spring_001.md");
+    assert_eq!(doc.title, "春・出会い");
+    assert_eq!(
+        numbers(&doc.units),
+        ["1", "1-1", "1-2", "1-3", "2-3", "2", "2-1", "2-2"]
+    );
+    assert!(doc.appendix.is_empty());
+}
+
+#[test]
+fn 選択肢とジャンプは番号と見出しで参照する() {
+    let text = render_script_text(&document("examples/spring/scenario/spring_001.md"));
+    assert!(text.contains("□ 1-1 選択肢\n  ・一緒に走る → 1-2 run-together\n"));
+    assert!(text.contains("  ・先に行ってもらう → 2 翌朝\n"));
+    assert!(text.contains("  ［翌朝の放課後へ］→ 2-3 after-school\n"));
+    // 別のシーンのセクションに移ったらファイル名を添える
+    assert!(text.contains("□ 2-3 after-school（spring_002.md）\n"));
+    assert!(text.contains("幼なじみ「ほら、急ぐよ！」\n"));
+    assert!(text.contains("〔背景: ../assets/bg/school_gate.png〕\n"));
+    assert!(text.contains("【エンディング: childhood_route】\n"));
+}
+
+#[test]
+fn 直後に並ぶまとまりへのフォールスルーは書かない() {
+    let doc = document("examples/spring/scenario/spring_001.md");
+    assert!(
+        doc.units
+            .iter()
+            .flat_map(|u| &u.lines)
+            .all(|l| !matches!(l, ScriptLine::Continue { .. }))
+    );
+}
+
+#[test]
+fn 開始シーンから辿れないまとまりは付録に回す() {
+    let doc = document("tests/fixtures/routes/unreachable/entry.md");
+    assert_eq!(numbers(&doc.units), ["1", "1-1"]);
+    assert_eq!(numbers(&doc.appendix), ["1-2", "2"]);
+    let text = render_script_text(&doc);
+    let appendix = text.find("付録").unwrap();
+    assert!(text.find("1-2 orphan").unwrap() > appendix);
+}
+
+#[test]
+fn entryで足した開始シーンから辿れる分は本文に続ける() {
+    let options = ExportOptions {
+        entries: vec!["tests/fixtures/routes/entries/dlc.md".into()],
+        ..ExportOptions::default()
+    };
+    let result = script_path(Path::new("tests/fixtures/routes/entries/main.md"), &options);
+    assert!(!result.has_errors(), "{:?}", result.check.diagnostics);
+    let doc = result.document.unwrap();
+    assert_eq!(doc.entry, "main.md");
+    assert_eq!(numbers(&doc.units), ["1", "2", "3"]);
+    // 本編のどこからも辿れないセクションは付録のまま
+    assert_eq!(numbers(&doc.appendix), ["1-1"]);
+    assert!(render_script_text(&doc).contains("■ 2 追加シナリオ（dlc.md）\n"));
+}
+
+#[test]
+fn htmlでは参照がページ内リンクになる() {
+    let html = render_script_html(&document("examples/spring/scenario/spring_001.md"));
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<h3 id=\"u1-2\">1-2 run-together</h3>"));
+    assert!(html.contains("<li>一緒に走る → <a href=\"#u1-2\">1-2 run-together</a></li>"));
+}
+
+#[test]
+fn checkエラーがあると台本を作らない() {
+    let result = script_path(
+        Path::new("tests/fixtures/trace/broken/scenario.md"),
+        &ExportOptions::default(),
+    );
+    assert!(result.has_errors());
+    assert!(result.document.is_none());
+}
+
+#[test]
+fn cli_export_scriptはoutputなしなら台本をstdoutに出す() {
+    let out = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+        .args([
+            "export",
+            "script",
+            "tests/fixtures/routes/unreachable/entry.md",
+        ])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("tsumugai バイナリを起動できる");
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.starts_with("到達不能を含むプロジェクト　台本\n"));
+    // 警告は stdout の台本に混ぜない
+    assert!(!stdout.contains("unreachable-section"));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("unreachable-section"));
+}
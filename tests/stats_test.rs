@@ -0,0 +1,132 @@
+//! `tsumugai stats`（分量の集計）の統合テスト
+//!
+//! examples/spring の文字数・セリフ数・選択肢・エンディングを既知の値として
+//! 検証する。ディレクトリとファイルのどちらを渡しても check と同じ読み込み
+//! 規則で対象が決まることも確かめる。
+
+use std::path::Path;
+use std::process::Command;
+use tsumugai::scenario::{StatsOptions, render_stats_human, render_stats_json, stats_path};
+
+#[test]
+fn ディレクトリを渡すと全シーンと合計を集計する() {
+    let result = stats_path(Path::new("examples/spring"), &StatsOptions::default());
+    assert!(!result.has_errors());
+    let report = result.report.unwrap();
+    assert_eq!(report.scenes.len(), 2);
+
+    let first = &report.scenes[0].counts;
+    assert_eq!(first.narration_chars, 56);
+    assert_eq!(first.dialogue_chars, 70);
+    assert_eq!(first.total_chars, 126);
+    assert_eq!(first.choice_blocks, 1);
+    assert_eq!(first.choice_items, 3);
+    assert_eq!(first.endings, 1);
+
+    let total = &report.total;
+    assert_eq!(total.total_chars, 273);
+    assert_eq!(total.choice_items, 5);
+    assert_eq!(total.endings, 3);
+    assert_eq!(total.speakers["幼なじみ"].lines, 5);
+    assert_eq!(total.speakers["主人公"].lines, 6);
+    assert_eq!(total.speakers["主人公"].chars, 71);
+}
+
+#[test]
+fn ファイルを渡すとリンクで辿れるシーンだけを集計する() {
+    let result = stats_path(
+        Path::new("examples/spring/scenario/spring_002.md"),
+        &StatsOptions::default(),
+    );
+    let report = result.report.unwrap();
+    assert_eq!(report.scenes.len(), 1);
+    assert_eq!(report.scenes[0].id.as_deref(), Some("spring_002"));
+    assert_eq!(report.total.total_chars, 147);
+}
+
+#[test]
+fn chapters_yamlがあれば章ごとに合計する() {
+    let result = stats_path(
+        Path::new("tests/fixtures/routes/chapters/two.md"),
+        &StatsOptions::default(),
+    );
+    let report = result.report.as_ref().unwrap();
+    // 章のシーンはリンクで辿れなくても集計の対象になる
+    assert_eq!(report.scenes.len(), 3);
+    let ids: Vec<&str> = report.chapters.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, vec!["one", "two", "epilogue"]);
+    assert_eq!(report.chapters[1].counts.total_chars, 4);
+    assert_eq!(report.chapters[2].counts.endings, 1);
+    assert_eq!(report.total.endings, 3);
+
+    let human = render_stats_human(&result);
+    assert!(
+        human.contains("章 1: one「第1章」（1 シーン）"),
+        "出力: {human}"
+    );
+    assert!(
+        human.contains("章 3: epilogue（1 シーン）"),
+        "出力: {human}"
+    );
+}
+
+#[test]
+fn 読了目安は1分あたりの文字数から計算する() {
+    let options = StatsOptions {
+        chars_per_minute: 100,
+    };
+    let result = stats_path(Path::new("examples/spring"), &options);
+    let report = result.report.unwrap();
+    assert_eq!(report.chars_per_minute, 100);
+    assert_eq!(report.total.reading_minutes, 2.7);
+    assert_eq!(report.scenes[0].counts.reading_minutes, 1.3);
+}
+
+#[test]
+fn 存在しないパスはio_errorになる() {
+    let result = stats_path(Path::new("no/such/dir"), &StatsOptions::default());
+    assert!(result.has_errors());
+    assert_eq!(result.diagnostics[0].rule_id, "io-error");
+    assert!(result.report.is_none());
+}
+
+#[test]
+fn 人間向けとjsonの出力() {
+    let result = stats_path(Path::new("examples/spring"), &StatsOptions::default());
+    let human = render_stats_human(&result);
+    assert!(human.contains("合計（2 シーン）\n  文字数: 273（ナレーション 126 / セリフ 147）\n"));
+    assert!(human.contains("  読了目安: 0.5 分\n"));
+
+    let json: serde_json::Value = serde_json::from_str(&render_stats_json(&result)).unwrap();
+    assert_eq!(json["status"], "ok");
+    assert_eq!(json["report"]["total"]["total_chars"], 273);
+    assert_eq!(json["report"]["scenes"][1]["id"], "spring_002");
+    assert_eq!(json["report"]["total"]["speakers"]["幼なじみ"]["lines"], 5);
+}
+
+#[test]
+fn cli_statsのcpm指定() {
+    let out = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+        .args([
+            "stats",
+            "examples/spring",
+            "--format",
+            "json",
+            "--cpm",
+            "100",
+        ])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("tsumugai バイナリを起動できる");
+    assert!(out.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(json["report"]["chars_per_minute"], 100);
+    assert_eq!(json["report"]["total"]["reading_minutes"], 2.7);
+
+    let out = Command::new(env!("CARGO_BIN_EXE_tsumugai"))
+        .args(["stats", "examples/spring", "--cpm", "0"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("tsumugai バイナリを起動できる");
+    assert!(!out.status.success());
+}
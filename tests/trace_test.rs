@@ -6,7 +6,8 @@
 
 use std::path::Path;
 use tsumugai::scenario::{
-    TraceEnd, TraceOptions, TraceStep, render_trace_human, render_trace_json, trace_path,
+    TraceEnd, TraceOptions, TraceResult, TraceStep, render_trace_human, render_trace_json,
+    trace_path,
 };
 
 fn spring() -> &'static Path {
@@ -148,6 +149,82 @@ fn ジャンプ段落で同一ファイル内を移動する() {
     assert!(matches!(&trace.end, TraceEnd::Ending { id } if id == "calm_route"));
 }
 
+#[test]
+fn 取り込んだフラグメントを通りステップはフラグメントの位置を示す() {
+    let result = trace_path(
+        Path::new("tests/fixtures/check/include/scene.md"),
+        &options(&[2]),
+    );
+    let trace = result.trace.as_ref().unwrap();
+    // フラグメントの中の ending（入れ子の取り込み）で終わる
+    assert!(matches!(&trace.end, TraceEnd::Ending { id } if id == "monday_end"));
+
+    let Some(TraceStep::Dialogue { file, line, .. }) = trace.steps.get(1) else {
+        panic!("2 ステップ目がセリフではない: {:?}", trace.steps.get(1));
+    };
+    assert!(file.ends_with("_morning.md"), "{file:?}");
+    assert_eq!(*line, 1);
+    let Some(TraceStep::Ending { file, line, .. }) = trace.steps.last() else {
+        panic!("最後のステップが ending ではない");
+    };
+    assert!(file.ends_with("_credits.md"), "{file:?}");
+    assert_eq!(*line, 1);
+    // 取り込む側のシーンに書いたブロックの行はそのまま
+    assert!(trace.steps.iter().any(|s| matches!(
+        s,
+        TraceStep::Dialogue { file, line: 9, .. } if file.ends_with("scene.md")
+    )));
+}
+
+#[test]
+fn callで呼び出した先からreturnで呼び出しの次へ戻る() {
+    let result = trace_path(
+        Path::new("tests/fixtures/routes/call/main.md"),
+        &options(&[1]),
+    );
+    let trace = result.trace.as_ref().unwrap();
+    assert!(matches!(&trace.end, TraceEnd::Ending { id } if id == "home_end"));
+
+    let texts: Vec<&str> = trace
+        .steps
+        .iter()
+        .filter_map(|s| match s {
+            TraceStep::Narration { text, .. } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        texts,
+        vec![
+            "朝になった。",
+            "少し休むことにした。",
+            "昼になった。",
+            "少し休むことにした。",
+            "家に着いた。"
+        ]
+    );
+    let returns = trace
+        .steps
+        .iter()
+        .filter(|s| matches!(s, TraceStep::Return { line: 11, .. }))
+        .count();
+    assert_eq!(returns, 2);
+    assert!(trace.steps.iter().any(
+        |s| matches!(s, TraceStep::Call { line: 9, target, .. } if target == "interlude.md#rest")
+    ));
+}
+
+#[test]
+fn 呼び出し中でないreturnはエラーで停止する() {
+    let result = trace_path(
+        Path::new("tests/fixtures/routes/call_errors/stray.md"),
+        &options(&[]),
+    );
+    let trace = result.trace.as_ref().unwrap();
+    assert!(matches!(trace.end, TraceEnd::ReturnWithoutCall));
+    assert!(result.has_errors());
+}
+
 // ------------------------------------------------------------ choices の消費
 
 #[test]
@@ -181,6 +258,97 @@ fn 範囲外の選択番号はエラーになる() {
     assert!(result.has_errors());
 }
 
+// ------------------------------------------------------------ ランダム分岐
+
+fn random_scene() -> &'static Path {
+    Path::new("tests/fixtures/routes/random/scenario.md")
+}
+
+fn reached(result: &TraceResult) -> Option<String> {
+    match &result.trace.as_ref()?.end {
+        TraceEnd::Ending { id } => Some(id.clone()),
+        _ => None,
+    }
+}
+
+#[test]
+fn 同じシードならランダム分岐は同じ項目に進む() {
+    let seeded = |seed| TraceOptions {
+        seed,
+        ..TraceOptions::default()
+    };
+    let first = trace_path(random_scene(), &seeded(42));
+    let second = trace_path(random_scene(), &seeded(42));
+    assert!(!first.has_errors());
+    assert!(reached(&first).is_some());
+    assert_eq!(reached(&first), reached(&second));
+
+    let trace = first.trace.as_ref().unwrap();
+    assert_eq!(trace.seed, 42);
+    let Some(TraceStep::Random {
+        options,
+        selected: Some(_),
+        forced: false,
+        ..
+    }) = trace
+        .steps
+        .iter()
+        .find(|s| matches!(s, TraceStep::Random { .. }))
+    else {
+        panic!("抽選したランダム分岐が記録されていない: {:?}", trace.steps);
+    };
+    let weights: Vec<u32> = options.iter().map(|o| o.weight).collect();
+    assert_eq!(weights, vec![1, 2, 1]);
+    assert!(render_trace_human(&first).contains("--seed 42"));
+
+    // シードを変えればいずれ別の項目に進む
+    let endings: std::collections::HashSet<_> = (0..32)
+        .filter_map(|seed| reached(&trace_path(random_scene(), &seeded(seed))))
+        .collect();
+    assert_eq!(endings.len(), 3);
+}
+
+#[test]
+fn randomで指定した項目には抽選せずに進む() {
+    let options = TraceOptions {
+        random: vec![3],
+        ..TraceOptions::default()
+    };
+    let result = trace_path(random_scene(), &options);
+    assert!(!result.has_errors());
+    assert_eq!(reached(&result).as_deref(), Some("alone_end"));
+    let trace = result.trace.as_ref().unwrap();
+    assert_eq!(trace.random_used, 1);
+    assert!(trace.steps.iter().any(|s| matches!(
+        s,
+        TraceStep::Random {
+            selected: Some(3),
+            forced: true,
+            ..
+        }
+    )));
+}
+
+#[test]
+fn 範囲外のrandomの番号はエラーで停止する() {
+    let options = TraceOptions {
+        random: vec![5],
+        ..TraceOptions::default()
+    };
+    let result = trace_path(random_scene(), &options);
+    let trace = result.trace.as_ref().unwrap();
+    assert!(matches!(
+        trace.end,
+        TraceEnd::InvalidRandom {
+            given: 5,
+            available: 3
+        }
+    ));
+    assert!(result.has_errors());
+    let json: serde_json::Value = serde_json::from_str(&render_trace_json(&result)).unwrap();
+    assert_eq!(json["trace"]["end"]["reason"], "invalid_random");
+}
+
 // ------------------------------------------------------------ 終了と保護
 
 #[test]
@@ -194,6 +362,58 @@ fn ファイル末尾で暗黙に終了する() {
     assert!(!result.has_errors());
 }
 
+#[test]
+fn 演出コメントは出現位置のステップとして記録される() {
+    let result = trace_path(
+        Path::new("tests/fixtures/trace/staging/scenario.md"),
+        &TraceOptions::default(),
+    );
+    assert!(!result.has_errors());
+    let trace = result.trace.as_ref().unwrap();
+    assert!(matches!(trace.end, TraceEnd::Ending { .. }));
+    assert!(matches!(
+        &trace.steps[2],
+        TraceStep::Background { path, line: 9, .. } if path == "assets/bg/classroom.png"
+    ));
+    assert!(
+        matches!(&trace.steps[3], TraceStep::Bgm { path, .. } if path == "assets/bgm/rain.ogg")
+    );
+    assert!(matches!(&trace.steps[4], TraceStep::Se { path, .. } if path == "assets/se/door.ogg"));
+    assert!(matches!(
+        &trace.steps[5],
+        TraceStep::BgmStop { line: 15, .. }
+    ));
+    assert!(render_trace_human(&result).contains("background → assets/bg/classroom.png"));
+}
+
+#[test]
+fn 別名の話者は話者名に揃い表示名も記録される() {
+    let result = trace_path(
+        Path::new("tests/fixtures/trace/aliases/scenario.md"),
+        &TraceOptions::default(),
+    );
+    assert_eq!(result.check.diagnostics, vec![], "別名は宣言済みとして扱う");
+    let trace = result.trace.as_ref().unwrap();
+    let dialogues: Vec<(&str, &str)> = trace
+        .steps
+        .iter()
+        .filter_map(|step| match step {
+            TraceStep::Dialogue {
+                speaker,
+                display_name,
+                ..
+            } => Some((speaker.as_str(), display_name.as_str())),
+            _ => None,
+        })
+        .collect();
+    // front matter の display_names はそのシーンだけ、display_name は全シーンに効く
+    assert_eq!(
+        dialogues,
+        vec![("葵", "???"), ("主人公", "主人公"), ("葵", "春日葵")]
+    );
+    assert!(render_trace_human(&result).contains("???（葵）: はじめまして。"));
+}
+
 #[test]
 fn ジャンプループは上限で打ち切られエラーになる() {
     let result = trace_path(